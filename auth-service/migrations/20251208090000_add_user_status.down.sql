@@ -0,0 +1,6 @@
+-- Add down migration script here
+ALTER TABLE users ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE users SET disabled = TRUE WHERE status <> 'active';
+
+ALTER TABLE users DROP COLUMN IF EXISTS status;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS audit_log(
+   id BIGSERIAL PRIMARY KEY,
+   occurred_at TIMESTAMPTZ NOT NULL,
+   actor TEXT,
+   action TEXT NOT NULL,
+   outcome TEXT NOT NULL,
+   detail TEXT,
+   ip TEXT,
+   user_agent TEXT,
+   request_id TEXT,
+   prev_hash TEXT NOT NULL,
+   hash TEXT NOT NULL UNIQUE
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_actor_occurred_at_idx ON audit_log (actor, occurred_at);
+CREATE INDEX IF NOT EXISTS audit_log_action_occurred_at_idx ON audit_log (action, occurred_at);
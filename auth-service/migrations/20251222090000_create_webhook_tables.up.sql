@@ -0,0 +1,32 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS webhook_subscriptions(
+   id UUID NOT NULL PRIMARY KEY,
+   url TEXT NOT NULL,
+   secret TEXT NOT NULL,
+   event_types TEXT[] NOT NULL DEFAULT '{}',
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries(
+   id UUID NOT NULL PRIMARY KEY,
+   subscription_id UUID NOT NULL REFERENCES webhook_subscriptions(id) ON DELETE CASCADE,
+   event_type TEXT NOT NULL,
+   payload JSONB NOT NULL,
+   attempts INTEGER NOT NULL DEFAULT 0,
+   next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   last_error TEXT,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_next_attempt_at_idx
+   ON webhook_deliveries (next_attempt_at);
+
+CREATE TABLE IF NOT EXISTS webhook_dead_letters(
+   id UUID NOT NULL PRIMARY KEY,
+   subscription_id UUID NOT NULL,
+   event_type TEXT NOT NULL,
+   payload JSONB NOT NULL,
+   attempts INTEGER NOT NULL,
+   last_error TEXT NOT NULL,
+   failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,8 @@
+-- Add down migration script here
+ALTER TABLE users
+   DROP CONSTRAINT IF EXISTS users_sms_requires_verified_phone,
+   DROP COLUMN IF EXISTS phone_verification_expires_at,
+   DROP COLUMN IF EXISTS phone_verification_code,
+   DROP COLUMN IF EXISTS two_fa_channel,
+   DROP COLUMN IF EXISTS phone_verified,
+   DROP COLUMN IF EXISTS phone_number;
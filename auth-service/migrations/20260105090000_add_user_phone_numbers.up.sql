@@ -0,0 +1,10 @@
+-- Add up migration script here
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS phone_number TEXT,
+   ADD COLUMN IF NOT EXISTS phone_verified BOOLEAN NOT NULL DEFAULT FALSE,
+   ADD COLUMN IF NOT EXISTS two_fa_channel TEXT NOT NULL DEFAULT 'email'
+      CHECK (two_fa_channel IN ('email', 'sms')),
+   ADD COLUMN IF NOT EXISTS phone_verification_code TEXT,
+   ADD COLUMN IF NOT EXISTS phone_verification_expires_at TIMESTAMPTZ,
+   ADD CONSTRAINT users_sms_requires_verified_phone
+      CHECK (two_fa_channel = 'email' OR phone_verified);
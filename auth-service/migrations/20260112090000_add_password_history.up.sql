@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- Existing passwords count as changed when the migration runs, so none expire straight away.
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS password_changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+CREATE TABLE IF NOT EXISTS password_history(
+   id BIGSERIAL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE,
+   password_hash TEXT NOT NULL,
+   replaced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS password_history_email_replaced_at_idx
+   ON password_history (email, replaced_at);
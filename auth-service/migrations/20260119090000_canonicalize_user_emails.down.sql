@@ -0,0 +1,3 @@
+-- Add down migration script here
+-- The original spelling of each email is not kept, so only the index is dropped.
+DROP INDEX IF EXISTS users_email_lower_idx;
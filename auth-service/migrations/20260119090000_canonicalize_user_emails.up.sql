@@ -0,0 +1,23 @@
+-- Add up migration script here
+-- Emails are now stored lowercased. Accounts that only differ in case are refused rather than
+-- merged, since only one of them can be kept; they must be resolved by hand first.
+DO $$
+DECLARE
+   duplicates TEXT;
+BEGIN
+   SELECT string_agg(canonical, ', ' ORDER BY canonical) INTO duplicates
+   FROM (
+      SELECT lower(btrim(email)) AS canonical
+      FROM users
+      GROUP BY 1
+      HAVING COUNT(*) > 1
+   ) AS duplicated;
+
+   IF duplicates IS NOT NULL THEN
+      RAISE EXCEPTION 'Users with emails that only differ in case or whitespace: %', duplicates;
+   END IF;
+END $$;
+
+UPDATE users SET email = lower(btrim(email)) WHERE email <> lower(btrim(email));
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users (lower(email));
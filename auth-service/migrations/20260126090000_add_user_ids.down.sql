@@ -0,0 +1,24 @@
+-- Add down migration script here
+ALTER TABLE password_history ADD COLUMN email TEXT;
+
+UPDATE password_history
+SET email = users.email
+FROM users
+WHERE users.id = password_history.user_id;
+
+ALTER TABLE password_history
+   DROP COLUMN user_id,
+   ALTER COLUMN email SET NOT NULL;
+
+ALTER TABLE users
+   DROP CONSTRAINT users_pkey,
+   DROP CONSTRAINT users_email_key,
+   ADD PRIMARY KEY (email),
+   DROP COLUMN id;
+
+ALTER TABLE password_history
+   ADD CONSTRAINT password_history_email_fkey
+      FOREIGN KEY (email) REFERENCES users(email) ON DELETE CASCADE ON UPDATE CASCADE;
+
+CREATE INDEX IF NOT EXISTS password_history_email_replaced_at_idx
+   ON password_history (email, replaced_at);
@@ -0,0 +1,29 @@
+-- Add up migration script here
+-- Users are identified by a UUID from now on, so that their email can change. Existing users
+-- get a random one.
+ALTER TABLE users
+   ADD COLUMN IF NOT EXISTS id UUID NOT NULL DEFAULT gen_random_uuid();
+
+ALTER TABLE password_history ADD COLUMN user_id UUID;
+
+UPDATE password_history
+SET user_id = users.id
+FROM users
+WHERE users.email = password_history.email;
+
+-- Also drops the foreign key to users(email) and the index on it.
+ALTER TABLE password_history
+   ALTER COLUMN user_id SET NOT NULL,
+   DROP COLUMN email;
+
+ALTER TABLE users
+   DROP CONSTRAINT users_pkey,
+   ADD PRIMARY KEY (id),
+   ADD CONSTRAINT users_email_key UNIQUE (email);
+
+ALTER TABLE password_history
+   ADD CONSTRAINT password_history_user_id_fkey
+      FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS password_history_user_id_replaced_at_idx
+   ON password_history (user_id, replaced_at);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+-- At most one pending change per user. Only a SHA-256 hash of the link token is stored, so
+-- the table does not hand out working links if it leaks.
+CREATE TABLE IF NOT EXISTS email_changes(
+   user_id UUID NOT NULL PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+   new_email TEXT NOT NULL,
+   token_hash TEXT NOT NULL UNIQUE,
+   expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Users without a row have an empty profile. app_metadata is only written by admins.
+CREATE TABLE IF NOT EXISTS user_profiles(
+   user_id UUID NOT NULL PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+   display_name TEXT,
+   locale TEXT,
+   timezone TEXT,
+   avatar_url TEXT,
+   user_metadata JSONB NOT NULL DEFAULT '{}',
+   app_metadata JSONB NOT NULL DEFAULT '{}'
+);
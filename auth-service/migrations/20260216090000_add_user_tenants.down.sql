@@ -0,0 +1,18 @@
+-- Add down migration script here
+-- Users of other tenants would collide with the default tenant's emails, so they must be
+-- removed by hand first.
+DO $$
+BEGIN
+   IF EXISTS (SELECT 1 FROM users WHERE tenant_id <> 'default') THEN
+      RAISE EXCEPTION 'Users outside the default tenant must be removed first';
+   END IF;
+END $$;
+
+DROP INDEX IF EXISTS users_tenant_id_email_lower_idx;
+
+ALTER TABLE users
+   DROP CONSTRAINT users_tenant_id_email_key,
+   ADD CONSTRAINT users_email_key UNIQUE (email),
+   DROP COLUMN tenant_id;
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower_idx ON users (lower(email));
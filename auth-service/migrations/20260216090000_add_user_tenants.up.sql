@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- Users belong to a tenant and their email is only unique within it. Existing users belong to
+-- the "default" tenant.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE users ALTER COLUMN tenant_id DROP DEFAULT;
+
+DROP INDEX IF EXISTS users_email_lower_idx;
+
+ALTER TABLE users
+   DROP CONSTRAINT users_email_key,
+   ADD CONSTRAINT users_tenant_id_email_key UNIQUE (tenant_id, email);
+
+CREATE UNIQUE INDEX IF NOT EXISTS users_tenant_id_email_lower_idx
+   ON users (tenant_id, lower(email));
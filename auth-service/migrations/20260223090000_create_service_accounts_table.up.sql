@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- Non-human clients of a tenant, authenticated by the Argon2 hash of their secret.
+CREATE TABLE IF NOT EXISTS service_accounts(
+   client_id UUID NOT NULL PRIMARY KEY,
+   tenant_id TEXT NOT NULL,
+   name TEXT NOT NULL,
+   secret_hash TEXT NOT NULL,
+   scopes TEXT[] NOT NULL DEFAULT '{}',
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS service_accounts_tenant_id_created_at_idx
+   ON service_accounts (tenant_id, created_at);
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- Only a SHA-256 hash of each token is stored, and the prefix users see it by.
+CREATE TABLE IF NOT EXISTS personal_access_tokens(
+   id UUID NOT NULL PRIMARY KEY,
+   tenant_id TEXT NOT NULL,
+   user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+   name TEXT NOT NULL,
+   token_hash TEXT NOT NULL UNIQUE,
+   prefix TEXT NOT NULL,
+   scopes TEXT[] NOT NULL DEFAULT '{}',
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   expires_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS personal_access_tokens_user_id_created_at_idx
+   ON personal_access_tokens (user_id, created_at);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+UPDATE webhook_subscriptions
+   SET event_types = array_replace(event_types, 'user.suspended', 'user.locked_out')
+   WHERE 'user.suspended' = ANY(event_types);
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- The event is published when an admin suspends a user, which "locked out" did not make clear.
+UPDATE webhook_subscriptions
+   SET event_types = array_replace(event_types, 'user.locked_out', 'user.suspended')
+   WHERE 'user.locked_out' = ANY(event_types);
@@ -0,0 +1,2 @@
+-- Add down migration script here
+-- The blanked bodies cannot be restored.
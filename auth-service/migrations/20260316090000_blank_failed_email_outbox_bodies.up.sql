@@ -0,0 +1,2 @@
+-- Add up migration script here
+UPDATE email_outbox SET html_body = '', text_body = '' WHERE status = 'failed';
@@ -0,0 +1,8 @@
+-- Add up migration script here
+-- At most one pending reset per user, stored as a SHA-256 hash of the link token like
+-- email_changes.
+CREATE TABLE IF NOT EXISTS password_resets(
+   user_id UUID NOT NULL PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+   token_hash TEXT NOT NULL UNIQUE,
+   expires_at TIMESTAMPTZ NOT NULL
+);
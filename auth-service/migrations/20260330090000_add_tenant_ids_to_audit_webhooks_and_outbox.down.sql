@@ -0,0 +1,29 @@
+-- Add down migration script here
+-- Rows of other tenants would show up for the default tenant, so they must be removed by hand
+-- first. The audit log can only be emptied as a whole without breaking its hash chain.
+DO $$
+BEGIN
+   IF EXISTS (SELECT 1 FROM audit_log WHERE tenant_id <> 'default')
+      OR EXISTS (SELECT 1 FROM webhook_subscriptions WHERE tenant_id <> 'default')
+      OR EXISTS (SELECT 1 FROM webhook_dead_letters WHERE tenant_id <> 'default')
+      OR EXISTS (SELECT 1 FROM email_outbox WHERE tenant_id <> 'default') THEN
+      RAISE EXCEPTION 'Rows outside the default tenant must be removed first';
+   END IF;
+END $$;
+
+DROP INDEX IF EXISTS email_outbox_tenant_id_failed_at_idx;
+ALTER TABLE email_outbox DROP COLUMN IF EXISTS tenant_id;
+
+DROP INDEX IF EXISTS webhook_dead_letters_tenant_id_failed_at_idx;
+ALTER TABLE webhook_dead_letters DROP COLUMN IF EXISTS tenant_id;
+
+ALTER TABLE webhook_deliveries DROP COLUMN IF EXISTS tenant_id;
+
+DROP INDEX IF EXISTS webhook_subscriptions_tenant_id_idx;
+ALTER TABLE webhook_subscriptions DROP COLUMN IF EXISTS tenant_id;
+
+DROP INDEX IF EXISTS audit_log_tenant_id_action_occurred_at_idx;
+DROP INDEX IF EXISTS audit_log_tenant_id_actor_occurred_at_idx;
+ALTER TABLE audit_log DROP COLUMN IF EXISTS tenant_id;
+CREATE INDEX IF NOT EXISTS audit_log_actor_occurred_at_idx ON audit_log (actor, occurred_at);
+CREATE INDEX IF NOT EXISTS audit_log_action_occurred_at_idx ON audit_log (action, occurred_at);
@@ -0,0 +1,30 @@
+-- Add up migration script here
+-- Audit records, webhooks and queued emails belong to the tenant they were created for.
+-- Existing rows belong to the "default" tenant.
+ALTER TABLE audit_log ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE audit_log ALTER COLUMN tenant_id DROP DEFAULT;
+
+DROP INDEX IF EXISTS audit_log_actor_occurred_at_idx;
+DROP INDEX IF EXISTS audit_log_action_occurred_at_idx;
+CREATE INDEX IF NOT EXISTS audit_log_tenant_id_actor_occurred_at_idx
+   ON audit_log (tenant_id, actor, occurred_at);
+CREATE INDEX IF NOT EXISTS audit_log_tenant_id_action_occurred_at_idx
+   ON audit_log (tenant_id, action, occurred_at);
+
+ALTER TABLE webhook_subscriptions ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE webhook_subscriptions ALTER COLUMN tenant_id DROP DEFAULT;
+CREATE INDEX IF NOT EXISTS webhook_subscriptions_tenant_id_idx
+   ON webhook_subscriptions (tenant_id);
+
+ALTER TABLE webhook_deliveries ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE webhook_deliveries ALTER COLUMN tenant_id DROP DEFAULT;
+
+ALTER TABLE webhook_dead_letters ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE webhook_dead_letters ALTER COLUMN tenant_id DROP DEFAULT;
+CREATE INDEX IF NOT EXISTS webhook_dead_letters_tenant_id_failed_at_idx
+   ON webhook_dead_letters (tenant_id, failed_at);
+
+ALTER TABLE email_outbox ADD COLUMN IF NOT EXISTS tenant_id TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE email_outbox ALTER COLUMN tenant_id DROP DEFAULT;
+CREATE INDEX IF NOT EXISTS email_outbox_tenant_id_failed_at_idx
+   ON email_outbox (tenant_id, failed_at) WHERE status = 'failed';
@@ -1,36 +1,94 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
-use crate::domain::{
-    data_stores::{BannedTokenStore, TwoFACodeStore, UserStore},
-    EmailClient,
+use crate::{
+    domain::{
+        audit_log::AuditLog,
+        data_stores::{BannedTokenStore, SmsRateLimiter, TwoFACodeStore, UserStore},
+        email_outbox::EmailOutbox,
+        health::HealthCheck,
+        password::PasswordPolicy,
+        personal_access_token::PersonalAccessTokenStore,
+        service_account::ServiceAccountStore,
+        tenant::Tenant,
+        webhooks::WebhookStore,
+        EmailClient, SmsClient,
+    },
+    services::email_templates::EmailTemplates,
 };
 
-pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
-pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
-pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
-pub type EmailClientType = Arc<RwLock<dyn EmailClient + Send + Sync>>;
+pub type UserStoreType = Arc<dyn UserStore>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore>;
+pub type ServiceAccountStoreType = Arc<dyn ServiceAccountStore>;
+pub type PersonalAccessTokenStoreType = Arc<dyn PersonalAccessTokenStore>;
+pub type EmailClientType = Arc<dyn EmailClient>;
+pub type SmsClientType = Arc<dyn SmsClient>;
+pub type SmsRateLimiterType = Arc<dyn SmsRateLimiter>;
+pub type AuditLogType = Arc<dyn AuditLog>;
+pub type WebhookStoreType = Arc<dyn WebhookStore>;
+pub type EmailOutboxType = Arc<dyn EmailOutbox>;
+pub type HealthCheckType = Arc<dyn HealthCheck>;
+pub type EmailTemplatesType = Arc<EmailTemplates>;
+pub type PasswordPolicyType = Arc<PasswordPolicy>;
+pub type TenantType = Arc<Tenant>;
 
+// The state of one tenant. Every store only sees that tenant's data; only the workers that
+// deliver webhooks and queued emails are shared by every tenant.
 #[derive(Clone)]
 pub struct AppState {
+    pub tenant: TenantType,
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
-    pub email_client: EmailClientType,
+    pub service_account_store: ServiceAccountStoreType,
+    pub personal_access_token_store: PersonalAccessTokenStoreType,
+    pub email_outbox: EmailOutboxType,
+    pub email_templates: EmailTemplatesType,
+    pub sms_client: SmsClientType,
+    pub sms_rate_limiter: SmsRateLimiterType,
+    pub audit_log: AuditLogType,
+    pub webhook_store: WebhookStoreType,
+    pub health_checks: Vec<HealthCheckType>,
+    pub password_policy: PasswordPolicyType,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
-        email_client: EmailClientType,
+        service_account_store: ServiceAccountStoreType,
+        personal_access_token_store: PersonalAccessTokenStoreType,
+        email_outbox: EmailOutboxType,
+        email_templates: EmailTemplatesType,
+        sms_client: SmsClientType,
+        sms_rate_limiter: SmsRateLimiterType,
+        audit_log: AuditLogType,
+        webhook_store: WebhookStoreType,
+        health_checks: Vec<HealthCheckType>,
+        password_policy: PasswordPolicyType,
     ) -> Self {
         Self {
+            tenant: Arc::new(Tenant::from_env()),
             user_store,
             banned_token_store,
             two_fa_code_store,
-            email_client,
+            service_account_store,
+            personal_access_token_store,
+            email_outbox,
+            email_templates,
+            sms_client,
+            sms_rate_limiter,
+            audit_log,
+            webhook_store,
+            health_checks,
+            password_policy,
         }
     }
+
+    pub fn with_tenant(mut self, tenant: Tenant) -> Self {
+        self.tenant = Arc::new(tenant);
+        self
+    }
 }
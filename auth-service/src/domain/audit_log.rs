@@ -0,0 +1,286 @@
+use chrono::{DateTime, SubsecRound, Utc};
+use color_eyre::eyre::{eyre, Report, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::domain::tenant::TenantId;
+
+// prev_hash of the very first record in the chain.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[async_trait::async_trait]
+pub trait AuditLog: Send + Sync {
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditLogError>;
+    // Only returns records of the store's tenant.
+    async fn query(&self, query: AuditQuery) -> Result<Vec<AuditRecord>, AuditLogError>;
+    // Walks the whole chain, which spans every tenant, and returns the id of the first record
+    // whose hash does not match.
+    async fn verify_chain(&self) -> Result<Option<i64>, AuditLogError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuditLogError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Signup,
+    Login,
+    #[serde(rename = "verify_2fa")]
+    Verify2FA,
+    Logout,
+    ChangePassword,
+    ResetPassword,
+    ChangeEmail,
+    ConfirmEmailChange,
+    ClientCredentials,
+}
+
+impl AuditAction {
+    pub fn parse(action: &str) -> Result<Self> {
+        match action {
+            "signup" => Ok(Self::Signup),
+            "login" => Ok(Self::Login),
+            "verify_2fa" => Ok(Self::Verify2FA),
+            "logout" => Ok(Self::Logout),
+            "change_password" => Ok(Self::ChangePassword),
+            "reset_password" => Ok(Self::ResetPassword),
+            "change_email" => Ok(Self::ChangeEmail),
+            "confirm_email_change" => Ok(Self::ConfirmEmailChange),
+            "client_credentials" => Ok(Self::ClientCredentials),
+            other => Err(eyre!("{} is not a valid audit action.", other)),
+        }
+    }
+}
+
+impl AsRef<str> for AuditAction {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Signup => "signup",
+            Self::Login => "login",
+            Self::Verify2FA => "verify_2fa",
+            Self::Logout => "logout",
+            Self::ChangePassword => "change_password",
+            Self::ResetPassword => "reset_password",
+            Self::ChangeEmail => "change_email",
+            Self::ConfirmEmailChange => "confirm_email_change",
+            Self::ClientCredentials => "client_credentials",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn parse(outcome: &str) -> Result<Self> {
+        match outcome {
+            "success" => Ok(Self::Success),
+            "failure" => Ok(Self::Failure),
+            other => Err(eyre!("{} is not a valid audit outcome.", other)),
+        }
+    }
+}
+
+impl AsRef<str> for AuditOutcome {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Success => "success",
+            Self::Failure => "failure",
+        }
+    }
+}
+
+// Where a request came from, as extracted by `utils::audit::AuditContext`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestMetadata {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub occurred_at: DateTime<Utc>,
+    pub actor: Option<String>,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    pub detail: Option<String>,
+    #[serde(flatten)]
+    pub metadata: RequestMetadata,
+}
+
+impl AuditEvent {
+    pub fn new(
+        actor: Option<String>,
+        action: AuditAction,
+        outcome: AuditOutcome,
+        metadata: RequestMetadata,
+    ) -> Self {
+        Self {
+            // Postgres keeps microseconds, so truncate up front to hash what gets stored.
+            occurred_at: Utc::now().trunc_subsecs(6),
+            actor,
+            action,
+            outcome,
+            detail: None,
+            metadata,
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    // Hash of this event of `tenant_id` chained onto `prev_hash`, hex encoded. Records of the
+    // default tenant predate tenants, so their hashes leave the tenant out.
+    pub fn chain_hash(&self, tenant_id: &TenantId, prev_hash: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(prev_hash.as_bytes());
+        let tenant = (!tenant_id.is_default()).then(|| tenant_id.as_ref().to_owned());
+        for field in [
+            Some(self.occurred_at.to_rfc3339()),
+            self.actor.clone(),
+            Some(self.action.as_ref().to_owned()),
+            Some(self.outcome.as_ref().to_owned()),
+            self.detail.clone(),
+            self.metadata.ip.clone(),
+            self.metadata.user_agent.clone(),
+            self.metadata.request_id.clone(),
+        ]
+        .into_iter()
+        .chain(tenant.map(Some))
+        {
+            // Length-prefix every field so that no two different events serialize the same.
+            match field {
+                Some(value) => {
+                    hasher.update((value.len() as u64).to_be_bytes());
+                    hasher.update(value.as_bytes());
+                }
+                None => hasher.update(u64::MAX.to_be_bytes()),
+            }
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub id: i64,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: u32,
+}
+
+impl Default for AuditQuery {
+    fn default() -> Self {
+        Self {
+            actor: None,
+            action: None,
+            from: None,
+            to: None,
+            limit: 100,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> AuditEvent {
+        AuditEvent::new(
+            Some("foo@bar.com".to_owned()),
+            AuditAction::Login,
+            AuditOutcome::Success,
+            RequestMetadata {
+                ip: Some("127.0.0.1".to_owned()),
+                user_agent: None,
+                request_id: Some("abc".to_owned()),
+            },
+        )
+    }
+
+    #[test]
+    fn chain_hash_is_deterministic() {
+        let event = event();
+        let tenant_id = TenantId::default();
+        assert_eq!(
+            event.chain_hash(&tenant_id, GENESIS_HASH),
+            event.chain_hash(&tenant_id, GENESIS_HASH)
+        );
+        assert_eq!(event.chain_hash(&tenant_id, GENESIS_HASH).len(), 64);
+    }
+
+    #[test]
+    fn chain_hash_depends_on_previous_hash_and_fields() {
+        let event = event();
+        let tenant_id = TenantId::default();
+        let hash = event.chain_hash(&tenant_id, GENESIS_HASH);
+
+        assert_ne!(hash, event.chain_hash(&tenant_id, &hash));
+        assert_ne!(
+            hash,
+            event
+                .clone()
+                .with_detail("tampered")
+                .chain_hash(&tenant_id, GENESIS_HASH)
+        );
+
+        let mut moved = event.clone();
+        moved.actor = None;
+        moved.metadata.user_agent = Some("foo@bar.com".to_owned());
+        assert_ne!(hash, moved.chain_hash(&tenant_id, GENESIS_HASH));
+    }
+
+    #[test]
+    fn chain_hash_depends_on_the_tenant() {
+        let event = event();
+        let acme = TenantId::parse("acme").unwrap();
+        let globex = TenantId::parse("globex").unwrap();
+
+        let hash = event.chain_hash(&acme, GENESIS_HASH);
+        assert_ne!(hash, event.chain_hash(&globex, GENESIS_HASH));
+        assert_ne!(hash, event.chain_hash(&TenantId::default(), GENESIS_HASH));
+    }
+
+    #[test]
+    fn audit_action_round_trips() {
+        for action in [
+            AuditAction::Signup,
+            AuditAction::Login,
+            AuditAction::Verify2FA,
+            AuditAction::Logout,
+            AuditAction::ChangePassword,
+            AuditAction::ResetPassword,
+            AuditAction::ChangeEmail,
+            AuditAction::ConfirmEmailChange,
+            AuditAction::ClientCredentials,
+        ] {
+            assert_eq!(AuditAction::parse(action.as_ref()).unwrap(), action);
+        }
+        assert!(AuditAction::parse("delete").is_err());
+    }
+}
@@ -1,13 +1,186 @@
-use crate::domain::{email::Email, password::Password, user::User};
+use crate::domain::{
+    email::Email,
+    password::Password,
+    phone_number::PhoneNumber,
+    profile::{Metadata, UserProfile},
+    user::{TwoFAChannel, User, UserId, UserStatus},
+};
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{eyre, Context, Report, Result};
 use rand::{thread_rng, Rng};
 use thiserror::Error;
 
+// Stores are shared by every request without an outer lock, so implementations take `&self`
+// and synchronize internally where they need to.
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
     async fn get_user(&self, email: Email) -> Result<User, UserStoreError>;
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError>;
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
+    async fn list_users(&self, query: UserQuery) -> Result<UserPage, UserStoreError>;
+    async fn set_status(&self, email: &Email, status: UserStatus)
+        -> Result<(), UserStoreError>;
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError>;
+    // Replaces the password, clears any required reset and drops any pending reset link.
+    // Fails with `PasswordReused` if `password` is the current one or among the
+    // `reuse_limit - 1` before it, which are kept.
+    async fn change_password(
+        &self,
+        email: &Email,
+        password: Password,
+        reuse_limit: usize,
+    ) -> Result<(), UserStoreError>;
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError>;
+    // Replaces the user's phone number with an unverified one, moving 2FA back to email
+    // until the number is confirmed.
+    async fn start_phone_verification(
+        &self,
+        email: &Email,
+        verification: PhoneVerification,
+    ) -> Result<(), UserStoreError>;
+    // Marks the pending phone number as verified if `code` matches and has not expired,
+    // otherwise fails with `InvalidCredentials`.
+    async fn confirm_phone_number(
+        &self,
+        email: &Email,
+        code: &TwoFACode,
+    ) -> Result<PhoneNumber, UserStoreError>;
+    async fn remove_phone_number(&self, email: &Email) -> Result<(), UserStoreError>;
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError>;
+    // Records the reset link mailed to a user, replacing any earlier one. It stays usable
+    // until it expires or the password is changed.
+    async fn start_password_reset(
+        &self,
+        email: &Email,
+        reset: PasswordReset,
+    ) -> Result<(), UserStoreError>;
+    // The user a pending, unexpired reset link with this token was mailed to, failing with
+    // `InvalidCredentials` otherwise.
+    async fn find_password_reset(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, UserStoreError>;
+    // Records a pending move to `change.new_email`, replacing any earlier one. Fails with
+    // `UserAlreadyExists` if another account uses that address.
+    async fn start_email_change(
+        &self,
+        email: &Email,
+        change: EmailChange,
+    ) -> Result<(), UserStoreError>;
+    // Consumes the pending change with this token and swaps in its address, failing with
+    // `InvalidCredentials` if there is none or it has expired.
+    async fn confirm_email_change(
+        &self,
+        token: &EmailChangeToken,
+    ) -> Result<ChangedEmail, UserStoreError>;
+    // An empty profile for users who never set one.
+    async fn get_profile(&self, email: &Email) -> Result<UserProfile, UserStoreError>;
+    // Stores every field but `app_metadata`, which only `set_app_metadata` changes, so that
+    // user and admin edits cannot overwrite each other.
+    async fn set_profile(&self, email: &Email, profile: &UserProfile)
+        -> Result<(), UserStoreError>;
+    async fn set_app_metadata(
+        &self,
+        email: &Email,
+        app_metadata: &Metadata,
+    ) -> Result<(), UserStoreError>;
+}
+
+// A code texted to a phone number to prove the user owns it.
+#[derive(Debug, Clone)]
+pub struct PhoneVerification {
+    pub phone_number: PhoneNumber,
+    pub code: TwoFACode,
+    pub expires_at: DateTime<Utc>,
+}
+
+// How long the link for resetting a password stays valid.
+pub const PASSWORD_RESET_TTL_SECONDS: u64 = 60 * 60;
+
+// A link mailed to users who must choose a new password but cannot log in to do so.
+#[derive(Debug, Clone)]
+pub struct PasswordReset {
+    pub token: PasswordResetToken,
+    pub expires_at: DateTime<Utc>,
+}
+
+// How long the link confirming a new email address stays valid.
+pub const EMAIL_CHANGE_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+// A request to move an account to `new_email`, which only takes effect once the link carrying
+// `token` that was mailed there is followed.
+#[derive(Debug, Clone)]
+pub struct EmailChange {
+    pub new_email: Email,
+    pub token: EmailChangeToken,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangedEmail {
+    pub user_id: UserId,
+    pub old_email: Email,
+    pub new_email: Email,
+}
+
+// Filters and pagination used by the admin user listing.
+#[derive(Debug, Clone)]
+pub struct UserQuery {
+    pub email_contains: Option<String>,
+    pub requires_2fa: Option<bool>,
+    pub status: Option<UserStatus>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl UserQuery {
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page.saturating_sub(1)) * u64::from(self.per_page)
+    }
+
+    pub fn matches(&self, user: &User) -> bool {
+        let email_matches = self.email_contains.as_ref().is_none_or(|needle| {
+            user.email
+                .as_ref()
+                .to_lowercase()
+                .contains(&needle.to_lowercase())
+        });
+
+        email_matches
+            && self.requires_2fa.is_none_or(|v| user.requires_2fa == v)
+            && self.status.is_none_or(|v| user.status == v)
+    }
+}
+
+impl Default for UserQuery {
+    fn default() -> Self {
+        Self {
+            email_contains: None,
+            requires_2fa: None,
+            status: None,
+            page: 1,
+            per_page: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UserPage {
+    pub users: Vec<User>,
+    pub total: u64,
 }
 
 #[derive(Debug, Error)]
@@ -18,6 +191,8 @@ pub enum UserStoreError {
     UserNotFound,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Password was used recently")]
+    PasswordReused,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
@@ -29,15 +204,27 @@ impl PartialEq for UserStoreError {
             (Self::UserAlreadyExists, Self::UserAlreadyExists)
                 | (Self::UserNotFound, Self::UserNotFound)
                 | (Self::InvalidCredentials, Self::InvalidCredentials)
+                | (Self::PasswordReused, Self::PasswordReused)
                 | (Self::UnexpectedError(_), Self::UnexpectedError(_))
         )
     }
 }
 
 #[async_trait::async_trait]
-pub trait BannedTokenStore {
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
+pub trait BannedTokenStore: Send + Sync {
+    async fn add_token(&self, token: String) -> Result<(), BannedTokenStoreError>;
     async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    // Invalidates every token issued to `subject` before `issued_before` (a unix timestamp in
+    // milliseconds), without having to know the individual tokens.
+    async fn revoke_all_tokens(
+        &self,
+        subject: &str,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError>;
+    async fn tokens_revoked_before(
+        &self,
+        subject: &str,
+    ) -> Result<Option<i64>, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -46,15 +233,42 @@ pub enum BannedTokenStoreError {
     UnexpectedError(#[source] Report),
 }
 
+// A number gets at most one verification text per cooldown, and a user at most
+// `SMS_MAX_PER_WINDOW` texts per window, so the endpoint cannot be used to flood a phone or
+// run up the gateway bill.
+pub const SMS_COOLDOWN_SECONDS: u64 = 60;
+pub const SMS_MAX_PER_WINDOW: u64 = 5;
+pub const SMS_WINDOW_SECONDS: u64 = 3600;
+
+#[async_trait::async_trait]
+pub trait SmsRateLimiter: Send + Sync {
+    // Counts a text to `recipient` on behalf of `email`, or returns false if the number is
+    // cooling down or the user has used up the window.
+    async fn try_acquire(
+        &self,
+        email: &Email,
+        recipient: &PhoneNumber,
+    ) -> Result<bool, SmsRateLimiterError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SmsRateLimiterError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+// How long a 2FA code stays valid after it was issued.
+pub const TWO_FA_CODE_TTL_SECONDS: u64 = 600;
+
 #[async_trait::async_trait]
-pub trait TwoFACodeStore {
+pub trait TwoFACodeStore: Send + Sync {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
         email: &Email,
@@ -102,6 +316,37 @@ impl AsRef<str> for LoginAttemptId {
     }
 }
 
+// A random secret proving access to the mailbox a link carrying it was sent to.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LinkToken(String);
+
+pub type EmailChangeToken = LinkToken;
+pub type PasswordResetToken = LinkToken;
+
+impl LinkToken {
+    pub fn parse(token: String) -> Result<Self> {
+        let token = token.to_ascii_lowercase();
+        if token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid link token"))
+        }
+    }
+}
+
+impl Default for LinkToken {
+    fn default() -> Self {
+        let bytes: [u8; 32] = thread_rng().gen();
+        Self(hex::encode(bytes))
+    }
+}
+
+impl AsRef<str> for LinkToken {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct TwoFACode(String);
 
@@ -1,15 +1,49 @@
+use std::collections::HashMap;
+
 use color_eyre::eyre::{eyre, Result};
+use lazy_static::lazy_static;
 use validator::validate_email;
 
+use crate::utils::constants::EMAIL_FOLDING_DOMAINS;
+
+lazy_static! {
+    static ref CONFIGURED_FOLDING: EmailFolding =
+        EmailFolding::parse(&EMAIL_FOLDING_DOMAINS).expect("Invalid EMAIL_FOLDING_DOMAINS.");
+}
+
+// An address in canonical form, so that every spelling of the same mailbox is one identity:
+// trimmed, lowercased, with the domain IDNA-encoded and the local part folded as configured
+// for its domain.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Email(String);
 
 impl Email {
     pub fn parse(s: &str) -> Result<Email> {
-        if !validate_email(s) {
-            return Err(eyre!(format!("{} is not a valid email.", s)));
+        Self::parse_with_folding(s, EmailFolding::configured())
+    }
+
+    pub fn parse_with_folding(s: &str, folding: &EmailFolding) -> Result<Email> {
+        let invalid = || eyre!(format!("{} is not a valid email.", s));
+
+        let (local_part, domain) = s.trim().rsplit_once('@').ok_or_else(invalid)?;
+        let domain = idna::domain_to_ascii(domain).map_err(|_| invalid())?;
+        let mut local_part = local_part.to_lowercase();
+        if let Some(rule) = folding.0.get(&domain) {
+            if rule.plus_addressing {
+                if let Some((mailbox, _tag)) = local_part.split_once('+') {
+                    local_part = mailbox.to_owned();
+                }
+            }
+            if rule.dots {
+                local_part.retain(|c| c != '.');
+            }
+        }
+
+        let email = format!("{}@{}", local_part, domain);
+        if !validate_email(&email) {
+            return Err(invalid());
         }
-        Ok(Self(s.to_owned()))
+        Ok(Self(email))
     }
 }
 
@@ -25,6 +59,62 @@ impl From<String> for Email {
     }
 }
 
+// Which parts of the local part a domain ignores when delivering mail.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LocalPartFolding {
+    // "bob+news@" is delivered to "bob@".
+    pub plus_addressing: bool,
+    // "b.o.b@" is delivered to "bob@".
+    pub dots: bool,
+}
+
+// Per-domain folding rules. Addresses already stored are rewritten to match them at startup,
+// see `canonicalize_stored_emails`.
+#[derive(Debug, Clone, Default)]
+pub struct EmailFolding(HashMap<String, LocalPartFolding>);
+
+impl EmailFolding {
+    // Comma-separated domains, each followed by the parts it folds, e.g.
+    // `gmail.com:plus:dots,outlook.com:plus`.
+    pub fn parse(rules: &str) -> Result<Self> {
+        let mut folding = HashMap::new();
+        for rule in rules
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let mut parts = rule.split(':').map(str::trim);
+            let domain = parts.next().unwrap_or_default();
+            let domain = idna::domain_to_ascii(domain)
+                .ok()
+                .filter(|domain| !domain.is_empty())
+                .ok_or_else(|| eyre!("{} is not a valid domain.", domain))?;
+
+            let mut local_part_folding = LocalPartFolding::default();
+            for part in parts {
+                match part {
+                    "plus" => local_part_folding.plus_addressing = true,
+                    "dots" => local_part_folding.dots = true,
+                    other => {
+                        return Err(eyre!(
+                            "Unknown folding {:?} for {}, expected plus or dots.",
+                            other,
+                            domain
+                        ))
+                    }
+                }
+            }
+            folding.insert(domain, local_part_folding);
+        }
+        Ok(Self(folding))
+    }
+
+    // The rules from EMAIL_FOLDING_DOMAINS. Panics if they are invalid.
+    pub fn configured() -> &'static Self {
+        &CONFIGURED_FOLDING
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,4 +148,36 @@ mod tests {
             assert!(result.is_err(), "Should fail for: {}", email);
         }
     }
+
+    #[test]
+    fn email_parse_canonicalizes() {
+        let email = Email::parse("  Bob.Smith@Example.COM\n").unwrap();
+        assert_eq!(email.as_ref(), "bob.smith@example.com");
+        assert_eq!(email, Email::parse("bob.smith@example.com").unwrap());
+    }
+
+    #[test]
+    fn email_parse_encodes_internationalized_domains() {
+        let email = Email::parse("anna@Bücher.example").unwrap();
+        assert_eq!(email.as_ref(), "anna@xn--bcher-kva.example");
+        assert_eq!(email, Email::parse("anna@xn--bcher-kva.example").unwrap());
+    }
+
+    #[test]
+    fn email_parse_folds_only_configured_domains() {
+        let folding = EmailFolding::parse("Gmail.com:plus:dots, outlook.com:plus").unwrap();
+        let parse = |s| Email::parse_with_folding(s, &folding).unwrap();
+
+        assert_eq!(parse("B.o.b+news@gmail.com").as_ref(), "bob@gmail.com");
+        assert_eq!(parse("b.ob+news@outlook.com").as_ref(), "b.ob@outlook.com");
+        assert_eq!(parse("b.ob+news@example.com").as_ref(), "b.ob+news@example.com");
+        assert!(Email::parse_with_folding("+news@gmail.com", &folding).is_err());
+    }
+
+    #[test]
+    fn email_folding_parse_invalid() {
+        assert!(EmailFolding::parse("").unwrap().0.is_empty());
+        assert!(EmailFolding::parse("gmail.com:dashes").is_err());
+        assert!(EmailFolding::parse(":plus").is_err());
+    }
 }
@@ -1,12 +1,20 @@
 use crate::domain::email::Email;
 use color_eyre::eyre::Result;
 
+// A rendered email, sent as multipart/alternative so clients without HTML support still
+// get a readable message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
 #[async_trait::async_trait]
-pub trait EmailClient {
+pub trait EmailClient: Send + Sync {
     async fn send_email(
         &self,
         recipient: &Email,
-        subject: &str,
-        content: &str,
+        message: &EmailMessage,
     ) -> Result<()>;
 }
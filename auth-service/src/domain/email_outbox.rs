@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Report;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::domain::{email::Email, EmailMessage};
+
+// Emails are queued here by the routes and sent by `EmailOutboxWorker`, so a slow or
+// failing provider neither blocks nor fails the request that triggered the email. Queued and
+// failed emails belong to the store's tenant; the worker claims and settles them across tenants.
+#[async_trait::async_trait]
+pub trait EmailOutbox: Send + Sync {
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxError>;
+    // Leases up to `limit` pending emails that are due, so that concurrent workers do not
+    // pick the same ones until `lease_until`.
+    async fn claim_due(
+        &self,
+        limit: u32,
+        lease_until: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxError>;
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxError>;
+    async fn schedule_retry(
+        &self,
+        id: Uuid,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), EmailOutboxError>;
+    // Keeps the email's metadata for admins to inspect but stops retrying it. The bodies are
+    // blanked because they hold codes and links that must not outlive the failed delivery.
+    async fn mark_failed(&self, id: Uuid, error: &str) -> Result<(), EmailOutboxError>;
+    async fn list_failed(&self, limit: u32) -> Result<Vec<FailedEmail>, EmailOutboxError>;
+}
+
+#[derive(Debug, Error)]
+pub enum EmailOutboxError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+#[derive(Debug, Clone)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub recipient: Email,
+    pub message: EmailMessage,
+    // Number of delivery attempts so far, including the one in progress once claimed.
+    pub attempts: i32,
+    // Emails that are useless after a deadline (e.g. 2FA codes) are not sent after it.
+    pub send_before: Option<DateTime<Utc>>,
+}
+
+impl OutboxEmail {
+    pub fn new(recipient: Email, message: EmailMessage) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            recipient,
+            message,
+            attempts: 0,
+            send_before: None,
+        }
+    }
+
+    pub fn send_before(mut self, deadline: DateTime<Utc>) -> Self {
+        self.send_before = Some(deadline);
+        self
+    }
+}
+
+// What admins see of an email that could not be sent. The bodies are left out since they
+// may contain codes or links that grant access to the account.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedEmail {
+    pub id: Uuid,
+    pub recipient: String,
+    pub subject: String,
+    pub attempts: i32,
+    pub last_error: String,
+    pub created_at: DateTime<Utc>,
+    pub failed_at: DateTime<Utc>,
+}
@@ -1,18 +1,54 @@
 use color_eyre::eyre::Report;
 use thiserror::Error;
 
+use crate::domain::{
+    password::PasswordPolicyViolation, profile::ProfileViolation, user::UserStatus,
+};
+
 #[derive(Debug, Error)]
 pub enum AuthAPIError {
     #[error("User already exists")]
     UserAlreadyExists,
     #[error("Invalid credentials")]
     InvalidCredentials,
+    #[error("Password does not meet the password policy")]
+    WeakPassword(Vec<PasswordPolicyViolation>),
     #[error("Incorrect credentials")]
     IncorrectCredentials,
     #[error("Missing token")]
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Webhook subscription not found")]
+    WebhookSubscriptionNotFound,
+    #[error("Service account not found")]
+    ServiceAccountNotFound,
+    #[error("Invalid service account")]
+    InvalidServiceAccount(Vec<String>),
+    #[error("Personal access token not found")]
+    PersonalAccessTokenNotFound,
+    #[error("Invalid personal access token")]
+    InvalidPersonalAccessToken(Vec<String>),
+    #[error("Password reset required")]
+    PasswordResetRequired,
+    #[error("Password expired")]
+    PasswordExpired,
+    #[error("Invalid phone number")]
+    InvalidPhoneNumber,
+    #[error("Incorrect verification code")]
+    IncorrectVerificationCode,
+    #[error("Phone number not verified")]
+    PhoneNumberNotVerified,
+    #[error("Too many texts")]
+    TooManyTexts,
+    #[error("Invalid profile")]
+    InvalidProfile(Vec<ProfileViolation>),
+    #[error("Signup is disabled")]
+    SignupDisabled,
+    #[error("Account is not active")]
+    AccountNotActive(UserStatus),
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
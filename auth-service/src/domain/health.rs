@@ -0,0 +1,12 @@
+use color_eyre::eyre::Result;
+
+// A dependency probed by the readiness endpoint.
+#[async_trait::async_trait]
+pub trait HealthCheck: Send + Sync {
+    fn name(&self) -> &'static str;
+    // Whether the service can serve traffic without this dependency.
+    fn required(&self) -> bool {
+        true
+    }
+    async fn check(&self) -> Result<()>;
+}
@@ -3,5 +3,18 @@ pub mod error;
 pub mod data_stores;
 pub mod email;
 pub mod password;
+pub mod personal_access_token;
 pub mod email_client;
+pub mod audit_log;
+pub mod webhooks;
+pub mod health;
+pub mod email_outbox;
+pub mod name;
+pub mod phone_number;
+pub mod profile;
+pub mod scope;
+pub mod service_account;
+pub mod tenant;
+pub mod sms_client;
 pub use email_client::*;
+pub use sms_client::*;
@@ -0,0 +1,35 @@
+use color_eyre::eyre::{eyre, Result};
+
+pub const MAX_NAME_LENGTH: usize = 100;
+
+// The name a credential is given so that it can be told apart from others, such as
+// "CI deploys". Surrounding whitespace is dropped.
+pub fn parse_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > MAX_NAME_LENGTH
+        || name.chars().any(char::is_control)
+    {
+        return Err(eyre!(
+            "name must be 1 to {} characters without control characters",
+            MAX_NAME_LENGTH
+        ));
+    }
+    Ok(name.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_trimmed_and_checked() {
+        assert_eq!(parse_name("  Billing sync ").unwrap(), "Billing sync");
+        let longest = "é".repeat(MAX_NAME_LENGTH);
+        assert_eq!(parse_name(&longest).unwrap(), longest);
+
+        for name in ["", "   ", "tab\there", &"x".repeat(MAX_NAME_LENGTH + 1)] {
+            assert!(parse_name(name).is_err(), "{:?} should be invalid", name);
+        }
+    }
+}
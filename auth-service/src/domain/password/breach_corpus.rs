@@ -0,0 +1,298 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+};
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use sha1::{Digest, Sha1};
+
+// SHA-1 hashes of breached passwords, such as a download of Pwned Passwords. Lookups go by
+// the first five hex digits of a hash and compare the rest locally, like the k-anonymity range
+// API, so the same check can later be pointed at a remote corpus without sending out hashes.
+#[derive(Debug)]
+pub struct BreachCorpus {
+    source: Source,
+}
+
+#[derive(Debug)]
+enum Source {
+    // Sorted, so that a prefix selects a contiguous range.
+    Memory(Vec<[u8; 20]>),
+    // A file sorted by hash, searched in place since the full corpus runs to tens of gigabytes.
+    File(Mutex<SortedFile>),
+}
+
+impl Default for BreachCorpus {
+    fn default() -> Self {
+        Self {
+            source: Source::Memory(Vec::new()),
+        }
+    }
+}
+
+impl BreachCorpus {
+    // `path` must be sorted by hash, as the "ordered by hash" Pwned Passwords download is.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to read breach corpus {}", path.display()))?;
+        let mut file = SortedFile::new(file)
+            .wrap_err_with(|| format!("Failed to read breach corpus {}", path.display()))?;
+        file.check_first_line()
+            .wrap_err_with(|| format!("Invalid breach corpus {}", path.display()))?;
+        Ok(Self {
+            source: Source::File(Mutex::new(file)),
+        })
+    }
+
+    // One hex SHA-1 hash per line, optionally followed by `:<count>` as in Pwned Passwords.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut hashes = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let hash = line.split(':').next().unwrap_or_default();
+            let mut bytes = [0u8; 20];
+            hex::decode_to_slice(hash, &mut bytes)
+                .map_err(|_| eyre!("Line {} is not a SHA-1 hash", number + 1))?;
+            hashes.push(bytes);
+        }
+        hashes.sort_unstable();
+        hashes.dedup();
+        Ok(Self {
+            source: Source::Memory(hashes),
+        })
+    }
+
+    // Passwords are let through if the corpus cannot be read, rather than locking everyone
+    // out of changing their password.
+    pub fn contains(&self, password: &str) -> bool {
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+        match self.suffixes(prefix) {
+            Ok(suffixes) => suffixes.iter().any(|candidate| candidate == suffix),
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to search the password breach corpus");
+                false
+            }
+        }
+    }
+
+    // The remaining 35 hex digits of every breached hash starting with the 5 digit `prefix`.
+    pub fn suffixes(&self, prefix: &str) -> Result<Vec<String>> {
+        if prefix.len() != 5 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(Vec::new());
+        }
+        let prefix = prefix.to_ascii_uppercase();
+
+        match &self.source {
+            Source::Memory(hashes) => {
+                let prefix = u32::from_str_radix(&prefix, 16)?;
+                let start = hashes.partition_point(|hash| hash_prefix(hash) < prefix);
+                let end = hashes.partition_point(|hash| hash_prefix(hash) <= prefix);
+                Ok(hashes[start..end]
+                    .iter()
+                    .map(|hash| hex::encode_upper(hash)[5..].to_owned())
+                    .collect())
+            }
+            Source::File(file) => {
+                let mut file = file
+                    .lock()
+                    .map_err(|_| eyre!("Breach corpus lock was poisoned"))?;
+                Ok(file.suffixes(&prefix)?)
+            }
+        }
+    }
+}
+
+// The first 20 bits of `hash`, i.e. its first five hex digits.
+fn hash_prefix(hash: &[u8; 20]) -> u32 {
+    (u32::from(hash[0]) << 12) | (u32::from(hash[1]) << 4) | (u32::from(hash[2]) >> 4)
+}
+
+#[derive(Debug)]
+struct SortedFile {
+    reader: BufReader<File>,
+    len: u64,
+}
+
+impl SortedFile {
+    fn new(file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self {
+            reader: BufReader::new(file),
+            len,
+        })
+    }
+
+    fn check_first_line(&mut self) -> Result<()> {
+        match self.line_at(0)? {
+            Some((line, _)) if parse_line(&line).is_none() => {
+                Err(eyre!("Line 1 is not a SHA-1 hash"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn suffixes(&mut self, prefix: &str) -> io::Result<Vec<String>> {
+        // Binary search over byte offsets for the first line whose hash is not below `prefix`.
+        let (mut low, mut high) = (0, self.len);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let start = self.line_start_from(middle)?;
+            let below = match self.line_at(start)? {
+                Some((line, _)) => parse_line(&line).is_some_and(|hash| hash[..5] < *prefix),
+                None => false,
+            };
+            if below {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        let mut suffixes = Vec::new();
+        let mut position = self.line_start_from(low)?;
+        while let Some((line, next)) = self.line_at(position)? {
+            match parse_line(&line) {
+                Some(hash) if hash.starts_with(prefix) => suffixes.push(hash[5..].to_owned()),
+                _ => break,
+            }
+            position = next;
+        }
+        Ok(suffixes)
+    }
+
+    // The offset of the first line that starts at or after `offset`.
+    fn line_start_from(&mut self, offset: u64) -> io::Result<u64> {
+        if offset == 0 {
+            return Ok(0);
+        }
+        // If the byte before `offset` ends a line, `offset` itself starts one.
+        self.reader.seek(SeekFrom::Start(offset - 1))?;
+        let mut skipped = Vec::new();
+        let read = self.reader.read_until(b'\n', &mut skipped)?;
+        Ok(offset - 1 + read as u64)
+    }
+
+    // The line starting at `offset`, and the offset of the line after it.
+    fn line_at(&mut self, offset: u64) -> io::Result<Option<(String, u64)>> {
+        if offset >= self.len {
+            return Ok(None);
+        }
+        self.reader.seek(SeekFrom::Start(offset))?;
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line)?;
+        Ok(Some((line, offset + read as u64)))
+    }
+}
+
+// The upper-case hex hash at the start of a `<hash>[:<count>]` line.
+fn parse_line(line: &str) -> Option<String> {
+    let hash = line.trim().split(':').next()?;
+    let is_hash = hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit());
+    is_hash.then(|| hash.to_ascii_uppercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    // SHA-1 of "password", "correct horse battery staple" and "P@ssw0rd".
+    const CORPUS: &str = "\
+5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004
+5baa61e4c9b93f3f0682250b6cf8331b7ee68fd8
+ABF7AAD6438836DBE526AA231ABDE2D0EEF74D42:1
+21BD12DC183F740EE76F27B78EB39C8AD972A757:52579
+
+";
+
+    // The same hashes sorted, as in the downloaded corpus, with neighbours on either side of
+    // the "21BD1" prefix.
+    const SORTED_CORPUS: &str = "\
+21BD0FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF:3\r
+21BD12DC183F740EE76F27B78EB39C8AD972A757:52579\r
+21BD1AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA:7\r
+21BD200000000000000000000000000000000000:1\r
+5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:10434004\r
+ABF7AAD6438836DBE526AA231ABDE2D0EEF74D42:1\r
+";
+
+    fn load(contents: &str) -> BreachCorpus {
+        let path = std::env::temp_dir().join(format!("breach-corpus-{}.txt", uuid::Uuid::new_v4()));
+        File::create(&path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+        let corpus = BreachCorpus::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        corpus
+    }
+
+    #[test]
+    fn parses_hashes_with_and_without_counts() {
+        let corpus = BreachCorpus::parse(CORPUS).unwrap();
+        // The duplicate only differs in case and count.
+        assert_eq!(corpus.suffixes("5BAA6").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_lines_that_are_not_hashes() {
+        let error =
+            BreachCorpus::parse("21BD12DC183F740EE76F27B78EB39C8AD972A757\nhunter2\n").unwrap_err();
+        assert_eq!(error.to_string(), "Line 2 is not a SHA-1 hash");
+    }
+
+    #[test]
+    fn finds_breached_passwords() {
+        for corpus in [BreachCorpus::parse(CORPUS).unwrap(), load(SORTED_CORPUS)] {
+            assert!(corpus.contains("password"));
+            assert!(corpus.contains("P@ssw0rd"));
+            assert!(corpus.contains("correct horse battery staple"));
+            assert!(!corpus.contains("vivid-Otter-chimney-17"));
+        }
+    }
+
+    #[test]
+    fn looks_up_suffixes_by_prefix() {
+        let corpus = BreachCorpus::parse(CORPUS).unwrap();
+        assert_eq!(
+            corpus.suffixes("21BD1").unwrap(),
+            vec!["2DC183F740EE76F27B78EB39C8AD972A757".to_owned()]
+        );
+        assert_eq!(corpus.suffixes("21bd1").unwrap().len(), 1);
+        assert_eq!(corpus.suffixes("21BD2").unwrap().len(), 0);
+        assert_eq!(corpus.suffixes("nope!").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn searches_a_sorted_file_in_place() {
+        let corpus = load(SORTED_CORPUS);
+        assert_eq!(
+            corpus.suffixes("21BD1").unwrap(),
+            vec![
+                "2DC183F740EE76F27B78EB39C8AD972A757".to_owned(),
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_owned(),
+            ]
+        );
+        assert_eq!(corpus.suffixes("21BD0").unwrap().len(), 1);
+        assert_eq!(corpus.suffixes("ABF7A").unwrap().len(), 1);
+        assert_eq!(corpus.suffixes("00000").unwrap().len(), 0);
+        assert_eq!(corpus.suffixes("FFFFF").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_hashes() {
+        let path = std::env::temp_dir().join(format!("breach-corpus-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "hunter2\n").unwrap();
+        let error = BreachCorpus::load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(format!("{:?}", error).contains("Line 1 is not a SHA-1 hash"));
+    }
+}
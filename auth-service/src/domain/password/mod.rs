@@ -0,0 +1,348 @@
+use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::domain::email::Email;
+
+mod breach_corpus;
+pub mod strength;
+
+pub use breach_corpus::BreachCorpus;
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const DEFAULT_MAX_PASSWORD_LENGTH: usize = 128;
+// zxcvbn's "safely unguessable" score.
+pub const DEFAULT_MIN_PASSWORD_STRENGTH: u8 = 3;
+pub const DEFAULT_PASSWORD_HISTORY_SIZE: usize = 5;
+// Email local parts shorter than this are too likely to appear by chance to reject them.
+const MIN_EMAIL_LOCAL_PART_LENGTH: usize = 3;
+
+#[derive(Debug)]
+pub enum PasswordError {
+    InvalidPassword,
+}
+
+#[derive(Debug, Clone)]
+pub struct Password {
+    normalized: String,
+    // The password as typed, if NFKC changed it.
+    unnormalized: Option<String>,
+}
+
+impl Password{
+    // Normalizes to NFKC, so that the same password typed on different platforms matches.
+    pub fn parse(password: &str) -> Result<Self, PasswordError> {
+        let normalized: String = password.nfkc().collect();
+        if normalized.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(PasswordError::InvalidPassword);
+        }
+        let unnormalized = (normalized != password).then(|| password.to_owned());
+        Ok(Self {
+            normalized,
+            unnormalized,
+        })
+    }
+
+    // Hashes stored before passwords were normalized were made from this form.
+    pub fn unnormalized(&self) -> Option<&str> {
+        self.unnormalized.as_deref()
+    }
+}
+
+impl AsRef<str> for Password{
+    fn as_ref(&self) -> &str {
+        self.normalized.as_str()
+    }
+}
+
+impl PartialEq for Password {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized == other.normalized
+    }
+}
+
+impl Eq for Password {}
+
+impl From<String> for Password {
+    fn from(value: String) -> Self {
+        Self {
+            normalized: value,
+            unnormalized: None,
+        }
+    }
+}
+
+// One reason a new password was refused. The messages are shown to the user.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PasswordPolicyViolation {
+    #[error("Password must be at least {0} characters long")]
+    TooShort(usize),
+    #[error("Password must be at most {0} characters long")]
+    TooLong(usize),
+    #[error("Password is too easy to guess{}", .0.map(|hint| format!(": {}", hint)).unwrap_or_default())]
+    TooGuessable(Option<&'static str>),
+    #[error("Password must not contain the email address")]
+    ContainsEmail,
+    #[error("Password has appeared in a data breach")]
+    Breached,
+    #[error("Password must differ from the last {0} passwords")]
+    RecentlyUsed(usize),
+}
+
+// Requirements for new passwords. Logins are only held to the maximum length, so that
+// accounts created under an older policy keep working.
+#[derive(Debug)]
+pub struct PasswordPolicy {
+    max_length: usize,
+    min_strength: u8,
+    breach_corpus: Option<BreachCorpus>,
+    history_size: usize,
+    max_age: Option<Duration>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_PASSWORD_LENGTH, DEFAULT_MIN_PASSWORD_STRENGTH)
+    }
+}
+
+impl PasswordPolicy {
+    // `max_length` bounds the work a single Argon2 hash can be made to do; `min_strength` is a
+    // zxcvbn-style score from 0 to 4.
+    pub fn new(max_length: usize, min_strength: u8) -> Self {
+        Self {
+            max_length,
+            min_strength,
+            breach_corpus: None,
+            history_size: DEFAULT_PASSWORD_HISTORY_SIZE,
+            max_age: None,
+        }
+    }
+
+    pub fn with_breach_corpus(mut self, breach_corpus: BreachCorpus) -> Self {
+        self.breach_corpus = Some(breach_corpus);
+        self
+    }
+
+    // How many of the user's most recent passwords, including the current one, a new password
+    // must differ from.
+    pub fn with_history_size(mut self, history_size: usize) -> Self {
+        self.history_size = history_size;
+        self
+    }
+
+    // Passwords older than `max_age` must be changed before the user can log in again.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    pub fn history_size(&self) -> usize {
+        self.history_size
+    }
+
+    pub fn is_expired(&self, password_changed_at: DateTime<Utc>) -> bool {
+        self.max_age
+            .is_some_and(|max_age| password_changed_at + max_age <= Utc::now())
+    }
+
+    // Parses a new password for `email`, listing every requirement it misses.
+    pub fn check(
+        &self,
+        password: &str,
+        email: &Email,
+    ) -> Result<Password, Vec<PasswordPolicyViolation>> {
+        let password: String = password.nfkc().collect();
+        let length = password.chars().count();
+        if length > self.max_length {
+            // Too long to be worth estimating.
+            return Err(vec![PasswordPolicyViolation::TooLong(self.max_length)]);
+        }
+
+        let mut violations = Vec::new();
+        if length < MIN_PASSWORD_LENGTH {
+            violations.push(PasswordPolicyViolation::TooShort(MIN_PASSWORD_LENGTH));
+        }
+
+        let local_part = email
+            .as_ref()
+            .rsplit_once('@')
+            .map_or(email.as_ref(), |(local_part, _)| local_part)
+            .to_lowercase();
+        let contains_email = local_part.chars().count() >= MIN_EMAIL_LOCAL_PART_LENGTH
+            && password.to_lowercase().contains(&local_part);
+        if contains_email {
+            violations.push(PasswordPolicyViolation::ContainsEmail);
+        } else {
+            let strength = strength::estimate(&password, &[&local_part]);
+            if strength.score < self.min_strength {
+                let hint = strength.patterns.iter().find_map(|pattern| hint_for(*pattern));
+                violations.push(PasswordPolicyViolation::TooGuessable(hint));
+            }
+        }
+
+        if let Some(breach_corpus) = &self.breach_corpus {
+            if breach_corpus.contains(&password) {
+                violations.push(PasswordPolicyViolation::Breached);
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(Password::from(password))
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn hint_for(pattern: strength::Pattern) -> Option<&'static str> {
+    use strength::Pattern;
+
+    match pattern {
+        Pattern::Dictionary => Some("avoid common passwords and words"),
+        Pattern::Sequence => Some("avoid sequences like abc or 6543"),
+        Pattern::Repeat => Some("avoid repeated characters and words"),
+        Pattern::Keyboard => Some("avoid keyboard patterns like qwerty"),
+        Pattern::Year => Some("avoid years"),
+        Pattern::Bruteforce => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("jane.doe@example.com").unwrap()
+    }
+
+    #[test]
+    fn password_parse_valid() {
+        assert!(Password::parse("password1234").is_ok());
+    }
+
+    #[test]
+    fn emal_parse_invalid() {
+        let invalid_passwords = vec![
+            "",                     // empty
+            "5454",                 // too short
+        ];
+
+        for password in invalid_passwords {
+            assert!(Password::parse(password).is_err(), "Should fail for: {}", password);
+        }
+    }
+
+    #[test]
+    fn password_parse_normalizes_to_nfkc() {
+        // "é" as "e" and a combining accent, and a full-width "Ａ".
+        let password = Password::parse("caf\u{0065}\u{0301}\u{FF21}1234").unwrap();
+        assert_eq!(password.as_ref(), "caf\u{00E9}A1234");
+        assert_eq!(
+            password,
+            Password::parse("caf\u{00E9}A1234").unwrap()
+        );
+        assert_eq!(password.unnormalized(), Some("caf\u{0065}\u{0301}\u{FF21}1234"));
+        assert_eq!(Password::parse("caf\u{00E9}A1234").unwrap().unnormalized(), None);
+    }
+
+    #[test]
+    fn policy_accepts_strong_password() {
+        let password = PasswordPolicy::default()
+            .check("vivid-Otter-chimney-17", &email())
+            .unwrap();
+        assert_eq!(password.as_ref(), "vivid-Otter-chimney-17");
+    }
+
+    #[test]
+    fn policy_rejects_guessable_passwords() {
+        for password in ["password", "12345678", "P@ssw0rd1", "qwertyuiop", "aaaaaaaaaaaa"] {
+            let violations = PasswordPolicy::default()
+                .check(password, &email())
+                .unwrap_err();
+            assert!(
+                matches!(
+                    violations.as_slice(),
+                    [PasswordPolicyViolation::TooGuessable(Some(_))]
+                ),
+                "{}: {:?}",
+                password,
+                violations
+            );
+        }
+    }
+
+    #[test]
+    fn policy_itemizes_every_violation() {
+        let breach_corpus =
+            BreachCorpus::parse("40BD001563085FC35165329EA1FF5C5ECBDBBEEF\n").unwrap(); // "123"
+        let policy = PasswordPolicy::default().with_breach_corpus(breach_corpus);
+
+        assert_eq!(
+            policy.check("123", &email()).unwrap_err(),
+            vec![
+                PasswordPolicyViolation::TooShort(MIN_PASSWORD_LENGTH),
+                PasswordPolicyViolation::TooGuessable(Some("avoid sequences like abc or 6543")),
+                PasswordPolicyViolation::Breached,
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_rejects_password_containing_email_local_part() {
+        assert_eq!(
+            PasswordPolicy::default()
+                .check("Xq7!JANE.DOE-vault", &email())
+                .unwrap_err(),
+            vec![PasswordPolicyViolation::ContainsEmail]
+        );
+
+        // Too short a local part to count.
+        let email = Email::parse("jd@example.com").unwrap();
+        assert!(PasswordPolicy::default()
+            .check("vivid-Otter-chimney-17jd", &email)
+            .is_ok());
+    }
+
+    #[test]
+    fn policy_rejects_breached_password() {
+        let breach_corpus =
+            BreachCorpus::parse("ABF7AAD6438836DBE526AA231ABDE2D0EEF74D42:1\n").unwrap();
+        let policy = PasswordPolicy::default().with_breach_corpus(breach_corpus);
+
+        assert_eq!(
+            policy
+                .check("correct horse battery staple", &email())
+                .unwrap_err(),
+            vec![PasswordPolicyViolation::Breached]
+        );
+    }
+
+    #[test]
+    fn policy_expires_passwords_past_max_age() {
+        let changed_at = Utc::now() - Duration::days(31);
+        assert!(!PasswordPolicy::default().is_expired(changed_at));
+
+        let policy = PasswordPolicy::default().with_max_age(Duration::days(30));
+        assert!(policy.is_expired(changed_at));
+        assert!(!policy.is_expired(Utc::now() - Duration::days(29)));
+    }
+
+    #[test]
+    fn policy_enforces_max_length_after_normalization() {
+        let policy = PasswordPolicy::new(16, DEFAULT_MIN_PASSWORD_STRENGTH);
+        assert_eq!(
+            policy.check(&"x".repeat(17), &email()).unwrap_err(),
+            vec![PasswordPolicyViolation::TooLong(16)]
+        );
+        // Each ligature expands to two characters under NFKC.
+        assert_eq!(
+            policy.check(&"\u{FB00}".repeat(9), &email()).unwrap_err(),
+            vec![PasswordPolicyViolation::TooLong(16)]
+        );
+    }
+}
@@ -0,0 +1,485 @@
+use chrono::{Datelike, Utc};
+use lazy_static::lazy_static;
+
+// Characters that stand in for letters in l33t speak, e.g. "p@ssw0rd".
+fn l33t_letters(c: char) -> &'static [char] {
+    match c {
+        '4' | '@' => &['a'],
+        '8' => &['b'],
+        '(' | '{' | '[' | '<' => &['c'],
+        '3' => &['e'],
+        '6' | '9' => &['g'],
+        '1' | '|' => &['i', 'l'],
+        '!' => &['i'],
+        '7' => &['l', 't'],
+        '0' => &['o'],
+        '$' | '5' => &['s'],
+        '+' => &['t'],
+        '%' => &['x'],
+        '2' => &['z'],
+        _ => &[],
+    }
+}
+
+// Rows and columns of a US keyboard that people walk along, e.g. "asdf" or "1qaz2wsx".
+const KEYBOARD_PATHS: &[&str] = &[
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+    "1qaz2wsx3edc4rfv5tgb6yhn7ujm8ik,9ol.0p;/",
+];
+const KEYBOARD_KEYS: f64 = 47.0;
+const KEYBOARD_AVERAGE_NEIGHBOURS: f64 = 4.0;
+
+// Guesses per character that no pattern explains.
+const BRUTEFORCE_CARDINALITY_LOG10: f64 = 1.0;
+// Years are guessed outward from the current one, but never from fewer than this many.
+const MIN_YEAR_SPACE: i32 = 20;
+
+lazy_static! {
+    static ref COMMON_PASSWORDS: Vec<Vec<char>> = include_str!("common_passwords.txt")
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.chars().collect())
+        .collect();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    Dictionary,
+    Sequence,
+    Repeat,
+    Keyboard,
+    Year,
+    Bruteforce,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Strength {
+    // log10 of the number of guesses an attacker needs to find the password.
+    pub guesses_log10: f64,
+    // From 0 (guessed almost instantly) to 4 (out of reach of an offline attack), as in zxcvbn.
+    pub score: u8,
+    // The patterns of the cheapest way to guess the password, in order.
+    pub patterns: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone)]
+struct Match {
+    start: usize,
+    end: usize,
+    guesses_log10: f64,
+    pattern: Pattern,
+}
+
+// Estimates how many guesses it takes to find `password` the way zxcvbn does: by finding the
+// cheapest way to cover it with guessable patterns, such as common passwords (also reversed
+// or in l33t speak), sequences, repeats, keyboard walks and years, and brute force for the
+// rest. `user_inputs` are guessed first, like a targeted attacker would.
+pub fn estimate(password: &str, user_inputs: &[&str]) -> Strength {
+    let chars: Vec<char> = password.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+
+    let mut matches = Vec::new();
+    dictionary_matches(&chars, &lower, user_inputs, &mut matches);
+    sequence_matches(&chars, &mut matches);
+    repeat_matches(&chars, user_inputs, &mut matches);
+    keyboard_matches(&chars, &lower, &mut matches);
+    year_matches(&chars, &mut matches);
+
+    cheapest_guesses(chars.len(), matches)
+}
+
+fn dictionary_matches(
+    chars: &[char],
+    lower: &[char],
+    user_inputs: &[&str],
+    matches: &mut Vec<Match>,
+) {
+    let user_inputs: Vec<Vec<char>> = user_inputs
+        .iter()
+        .map(|input| input.to_lowercase().chars().collect())
+        .collect();
+    let words = user_inputs.iter().chain(COMMON_PASSWORDS.iter());
+
+    for (index, word) in words.enumerate() {
+        let rank = (index + 1) as f64;
+        if word.is_empty() || word.len() > lower.len() {
+            continue;
+        }
+        for start in 0..=lower.len() - word.len() {
+            let end = start + word.len();
+            for reversed in [false, true] {
+                let Some(substitutions) = match_word(&lower[start..end], word, reversed) else {
+                    continue;
+                };
+                let guesses_log10 = rank.log10()
+                    + uppercase_variations(&chars[start..end]).log10()
+                    + substitutions as f64 * 2f64.log10()
+                    + if reversed { 2f64.log10() } else { 0.0 };
+                matches.push(Match {
+                    start,
+                    end,
+                    guesses_log10,
+                    pattern: Pattern::Dictionary,
+                });
+            }
+        }
+    }
+}
+
+// The number of l33t substitutions needed to read `token` as `word`, if it can be read so.
+fn match_word(token: &[char], word: &[char], reversed: bool) -> Option<usize> {
+    let mut substitutions = 0;
+    for (i, &expected) in word.iter().enumerate() {
+        let c = if reversed {
+            token[token.len() - 1 - i]
+        } else {
+            token[i]
+        };
+        if c == expected {
+            continue;
+        }
+        if !l33t_letters(c).contains(&expected) {
+            return None;
+        }
+        substitutions += 1;
+    }
+    Some(substitutions)
+}
+
+// How many ways a word could have been capitalized to give `token`.
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let capitalized = upper == 1
+        && (token.first().is_some_and(|c| c.is_uppercase())
+            || token.last().is_some_and(|c| c.is_uppercase()));
+    if lower == 0 || capitalized {
+        return 2.0;
+    }
+    (1..=upper.min(lower))
+        .map(|i| binomial(upper + lower, i))
+        .sum()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k).fold(1.0, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+// Runs like "abcd", "9876" or "XYZ".
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let steps = |i: usize| {
+            chars[i + 1] as i64 - chars[i] as i64 == delta
+                && char_class(chars[i]) == char_class(chars[i + 1])
+                && char_class(chars[i]).is_some()
+        };
+        if delta.abs() != 1 || !steps(start) {
+            start += 1;
+            continue;
+        }
+
+        let mut end = start + 1;
+        while end + 1 < chars.len() && steps(end) {
+            end += 1;
+        }
+        let len = end - start + 1;
+        if len >= 3 {
+            let first = chars[start];
+            let mut base: f64 = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+            matches.push(Match {
+                start,
+                end: end + 1,
+                guesses_log10: (base * len as f64).log10(),
+                pattern: Pattern::Sequence,
+            });
+            start = end;
+        } else {
+            start += 1;
+        }
+    }
+}
+
+fn char_class(c: char) -> Option<u8> {
+    if c.is_ascii_digit() {
+        Some(0)
+    } else if c.is_ascii_lowercase() {
+        Some(1)
+    } else if c.is_ascii_uppercase() {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+// The same character or chunk several times over, e.g. "aaaa" or "abcabcabc". Costs about as
+// much as guessing the chunk once, times the number of repetitions.
+fn repeat_matches(chars: &[char], user_inputs: &[&str], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let mut best: Option<(usize, usize)> = None;
+        for unit in 1..=(chars.len() - start) / 2 {
+            let mut count = 1;
+            while start + unit * (count + 1) <= chars.len()
+                && chars[start..start + unit]
+                    == chars[start + unit * count..start + unit * (count + 1)]
+            {
+                count += 1;
+            }
+            let repeated = (unit == 1 && count >= 3) || (unit > 1 && count >= 2);
+            if repeated && best.is_none_or(|(u, c)| unit * count > u * c) {
+                best = Some((unit, count));
+            }
+        }
+
+        let Some((unit, count)) = best else {
+            start += 1;
+            continue;
+        };
+        let chunk: String = chars[start..start + unit].iter().collect();
+        let chunk_guesses_log10 = if unit == 1 {
+            cardinality(chars[start]).log10()
+        } else {
+            estimate(&chunk, user_inputs).guesses_log10
+        };
+        matches.push(Match {
+            start,
+            end: start + unit * count,
+            guesses_log10: chunk_guesses_log10 + (count as f64).log10(),
+            pattern: Pattern::Repeat,
+        });
+        start += unit * count;
+    }
+}
+
+fn cardinality(c: char) -> f64 {
+    if c.is_ascii_digit() {
+        10.0
+    } else if c.is_ascii_alphabetic() {
+        26.0
+    } else {
+        33.0
+    }
+}
+
+fn keyboard_matches(chars: &[char], lower: &[char], matches: &mut Vec<Match>) {
+    for path in KEYBOARD_PATHS {
+        let forward: Vec<char> = path.chars().collect();
+        let backward: Vec<char> = path.chars().rev().collect();
+        for walk in [forward, backward] {
+            for start in 0..lower.len() {
+                let mut end = start;
+                while end < lower.len()
+                    && walk
+                        .windows(end - start + 1)
+                        .any(|window| window == &lower[start..=end])
+                {
+                    end += 1;
+                }
+                let len = end - start;
+                if len < 4 {
+                    continue;
+                }
+                let shifted = chars[start..end].iter().any(|c| c.is_uppercase());
+                let guesses = KEYBOARD_KEYS
+                    * KEYBOARD_AVERAGE_NEIGHBOURS
+                    * len as f64
+                    * if shifted { 2.0 } else { 1.0 };
+                matches.push(Match {
+                    start,
+                    end,
+                    guesses_log10: guesses.log10(),
+                    pattern: Pattern::Keyboard,
+                });
+            }
+        }
+    }
+}
+
+fn year_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let this_year = Utc::now().year();
+    for start in 0..chars.len().saturating_sub(3) {
+        let token: String = chars[start..start + 4].iter().collect();
+        let Ok(year) = token.parse::<i32>() else {
+            continue;
+        };
+        if !(1900..=2099).contains(&year) || !token.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        matches.push(Match {
+            start,
+            end: start + 4,
+            guesses_log10: f64::from((year - this_year).abs().max(MIN_YEAR_SPACE)).log10(),
+            pattern: Pattern::Year,
+        });
+    }
+}
+
+// Covers the password with the sequence of matches that is cheapest to guess, counting
+// `k! * product(guesses)` for a sequence of `k` matches since an attacker does not know in
+// which order the patterns come. Spans no pattern explains are brute forced.
+fn cheapest_guesses(len: usize, mut matches: Vec<Match>) -> Strength {
+    if len == 0 {
+        return Strength {
+            guesses_log10: 0.0,
+            score: 0,
+            patterns: Vec::new(),
+        };
+    }
+
+    for start in 0..len {
+        for end in start + 1..=len {
+            matches.push(Match {
+                start,
+                end,
+                guesses_log10: (end - start) as f64 * BRUTEFORCE_CARDINALITY_LOG10,
+                pattern: Pattern::Bruteforce,
+            });
+        }
+    }
+
+    // cheapest[k][end]: the cheapest `k` matches covering the first `end` characters.
+    let mut cheapest = vec![vec![f64::INFINITY; len + 1]; len + 1];
+    let mut last_match = vec![vec![None; len + 1]; len + 1];
+    cheapest[0][0] = 0.0;
+    let mut by_end: Vec<Vec<usize>> = vec![Vec::new(); len + 1];
+    for (index, m) in matches.iter().enumerate() {
+        by_end[m.end].push(index);
+    }
+    for end in 1..=len {
+        for &index in &by_end[end] {
+            let m = &matches[index];
+            for k in 1..=m.start + 1 {
+                let guesses_log10 = cheapest[k - 1][m.start] + m.guesses_log10;
+                if guesses_log10 < cheapest[k][end] {
+                    cheapest[k][end] = guesses_log10;
+                    last_match[k][end] = Some(index);
+                }
+            }
+        }
+    }
+
+    let mut k_factorial_log10 = 0.0;
+    let mut best = (f64::INFINITY, 0);
+    for (k, row) in cheapest.iter().enumerate().skip(1) {
+        k_factorial_log10 += (k as f64).log10();
+        let guesses_log10 = k_factorial_log10 + row[len];
+        if guesses_log10 < best.0 {
+            best = (guesses_log10, k);
+        }
+    }
+
+    let (guesses_log10, mut k) = best;
+    let mut patterns = Vec::with_capacity(k);
+    let mut end = len;
+    while let Some(index) = last_match[k][end] {
+        patterns.push(matches[index].pattern);
+        end = matches[index].start;
+        k -= 1;
+    }
+    patterns.reverse();
+
+    Strength {
+        guesses_log10,
+        score: score(guesses_log10),
+        patterns,
+    }
+}
+
+fn score(guesses_log10: f64) -> u8 {
+    match guesses_log10 {
+        g if g < 3.0 => 0,
+        g if g < 6.0 => 1,
+        g if g < 8.0 => 2,
+        g if g < 10.0 => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_passwords_are_trivial() {
+        for password in [
+            "password",
+            "12345678",
+            "password123",
+            "qwerty123",
+            "iloveyou",
+        ] {
+            let strength = estimate(password, &[]);
+            assert_eq!(strength.score, 0, "{}: {:?}", password, strength);
+            assert_eq!(strength.patterns[0], Pattern::Dictionary, "{}", password);
+        }
+    }
+
+    #[test]
+    fn l33t_capitalized_and_reversed_words_are_weak() {
+        for password in ["P@ssw0rd", "Dragon", "drowssap", "M0nk3y!!"] {
+            let strength = estimate(password, &[]);
+            assert!(strength.score <= 1, "{}: {:?}", password, strength);
+            assert_eq!(strength.patterns[0], Pattern::Dictionary, "{}", password);
+        }
+    }
+
+    #[test]
+    fn finds_sequences_repeats_keyboard_walks_and_years() {
+        let cases = [
+            ("lmnopqrs", Pattern::Sequence),
+            ("zzzzzzzzzz", Pattern::Repeat),
+            ("xkcdxkcdxkcd", Pattern::Repeat),
+            ("sdfghjkl", Pattern::Keyboard),
+            ("1982", Pattern::Year),
+        ];
+        for (password, pattern) in cases {
+            let strength = estimate(password, &[]);
+            assert_eq!(strength.patterns, vec![pattern], "{}", password);
+            assert!(strength.score <= 1, "{}: {:?}", password, strength);
+        }
+    }
+
+    #[test]
+    fn user_inputs_are_guessed_first() {
+        let password = "jane.doe.smith";
+        assert!(estimate(password, &[]).score >= 3);
+        assert_eq!(estimate(password, &["jane.doe.smith"]).score, 0);
+    }
+
+    #[test]
+    fn random_and_long_passwords_are_strong() {
+        for password in [
+            "vivid-Otter-chimney-17",
+            "rG7#kP2v!Lq9",
+            "correct horse battery staple",
+        ] {
+            let strength = estimate(password, &[]);
+            assert!(strength.score >= 3, "{}: {:?}", password, strength);
+        }
+    }
+
+    #[test]
+    fn empty_password_scores_zero() {
+        assert_eq!(estimate("", &[]).score, 0);
+    }
+}
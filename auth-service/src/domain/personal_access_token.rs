@@ -0,0 +1,262 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Report, Result};
+use rand::RngCore;
+use serde::Serialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::domain::{name::parse_name, scope::Scope, user::UserId};
+
+// Tells personal access tokens apart from JWTs, and from other secrets in leak scanners.
+pub const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "pat_";
+const SECRET_BYTES: usize = 32;
+// How much of a token is kept in the clear, so users can tell their tokens apart.
+const DISPLAYED_PREFIX_LENGTH: usize = PERSONAL_ACCESS_TOKEN_PREFIX.len() + 8;
+
+// Tokens belong to the tenant of the store, like users.
+#[async_trait::async_trait]
+pub trait PersonalAccessTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        token: PersonalAccessToken,
+        secret: &PersonalAccessTokenSecret,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+    // The user's tokens, oldest first, including expired ones.
+    async fn list_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalAccessTokenStoreError>;
+    // Fails with `NotFound` unless the token belongs to `user_id`.
+    async fn revoke_token(
+        &self,
+        user_id: &UserId,
+        id: &PersonalAccessTokenId,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+    // Revokes every token of the user, for when all of their sessions are revoked.
+    async fn revoke_all_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+    // The token with this secret, whether or not it has expired.
+    async fn find_token(
+        &self,
+        secret: &PersonalAccessTokenSecret,
+    ) -> Result<PersonalAccessToken, PersonalAccessTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PersonalAccessTokenStoreError {
+    #[error("Personal access token not found")]
+    NotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+// A long-lived token users create to call APIs from scripts, acting as them within its scopes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalAccessToken {
+    pub id: PersonalAccessTokenId,
+    #[serde(skip)]
+    pub user_id: UserId,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    // The start of the secret, such as "pat_1a2b3c4d".
+    pub prefix: String,
+    pub created_at: DateTime<Utc>,
+    // Never expires if unset.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl PersonalAccessToken {
+    pub fn new(
+        user_id: UserId,
+        name: String,
+        scopes: Vec<Scope>,
+        expires_at: Option<DateTime<Utc>>,
+        secret: &PersonalAccessTokenSecret,
+    ) -> Result<Self> {
+        let name = parse_name(&name)?;
+        let created_at = Utc::now();
+        if expires_at.is_some_and(|expires_at| expires_at <= created_at) {
+            return Err(eyre!("expiresAt must be in the future"));
+        }
+
+        Ok(Self {
+            id: PersonalAccessTokenId::default(),
+            user_id,
+            name,
+            scopes: Scope::dedup(scopes),
+            prefix: secret.displayed_prefix().to_owned(),
+            created_at,
+            expires_at,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub struct PersonalAccessTokenId(Uuid);
+
+impl PersonalAccessTokenId {
+    pub fn parse(id: &str) -> Result<Self> {
+        Uuid::parse_str(id)
+            .map(Self)
+            .map_err(|_| eyre!("{} is not a valid personal access token id.", id))
+    }
+}
+
+impl Default for PersonalAccessTokenId {
+    fn default() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl From<Uuid> for PersonalAccessTokenId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+impl AsRef<Uuid> for PersonalAccessTokenId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for PersonalAccessTokenId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// The token itself, "pat_" followed by 256 random bits in hex. Shown once, when it is created;
+// only its hash is stored.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct PersonalAccessTokenSecret(String);
+
+impl PersonalAccessTokenSecret {
+    pub fn parse(secret: &str) -> Result<Self> {
+        let valid = secret
+            .strip_prefix(PERSONAL_ACCESS_TOKEN_PREFIX)
+            .is_some_and(|random| {
+                random.len() == SECRET_BYTES * 2
+                    && random
+                        .chars()
+                        .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+            });
+        if valid {
+            Ok(Self(secret.to_owned()))
+        } else {
+            Err(eyre!("Invalid personal access token."))
+        }
+    }
+
+    // Whether `token` looks like a personal access token rather than a JWT.
+    pub fn is_personal_access_token(token: &str) -> bool {
+        token.starts_with(PERSONAL_ACCESS_TOKEN_PREFIX)
+    }
+
+    fn displayed_prefix(&self) -> &str {
+        &self.0[..DISPLAYED_PREFIX_LENGTH]
+    }
+}
+
+impl Default for PersonalAccessTokenSecret {
+    fn default() -> Self {
+        let mut bytes = [0u8; SECRET_BYTES];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(format!(
+            "{}{}",
+            PERSONAL_ACCESS_TOKEN_PREFIX,
+            hex::encode(bytes)
+        ))
+    }
+}
+
+impl AsRef<str> for PersonalAccessTokenSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for PersonalAccessTokenSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "PersonalAccessTokenSecret({}..)",
+            self.displayed_prefix()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn secrets_round_trip_and_show_only_their_prefix() {
+        let secret = PersonalAccessTokenSecret::default();
+        assert_eq!(
+            PersonalAccessTokenSecret::parse(secret.as_ref()).unwrap(),
+            secret
+        );
+        assert!(PersonalAccessTokenSecret::is_personal_access_token(
+            secret.as_ref()
+        ));
+        assert_eq!(secret.displayed_prefix().len(), 12);
+        assert!(secret.as_ref().starts_with(secret.displayed_prefix()));
+        assert_eq!(
+            format!("{:?}", secret),
+            format!("PersonalAccessTokenSecret({}..)", secret.displayed_prefix())
+        );
+
+        for invalid in [
+            "",
+            "pat_",
+            "eyJhbGciOiJIUzI1NiJ9.e30.sig",
+            &secret.as_ref()[..20],
+            &secret.as_ref().to_ascii_uppercase(),
+            &secret.as_ref().replace("pat_", "tap_"),
+        ] {
+            assert!(PersonalAccessTokenSecret::parse(invalid).is_err());
+        }
+    }
+
+    #[test]
+    fn new_validates_name_and_expiry() {
+        let secret = PersonalAccessTokenSecret::default();
+        let scopes = Scope::parse_list("repo:read repo:read").unwrap();
+        let token = PersonalAccessToken::new(
+            UserId::default(),
+            " CI deploys ".to_owned(),
+            scopes,
+            Some(Utc::now() + Duration::days(30)),
+            &secret,
+        )
+        .unwrap();
+        assert_eq!(token.name, "CI deploys");
+        assert_eq!(token.scopes, Scope::parse_list("repo:read").unwrap());
+        assert_eq!(token.prefix, secret.displayed_prefix());
+        assert!(!token.is_expired());
+
+        let new = |name: &str, expires_at| {
+            PersonalAccessToken::new(
+                UserId::default(),
+                name.to_owned(),
+                Vec::new(),
+                expires_at,
+                &secret,
+            )
+        };
+        assert!(new("Laptop", None).is_ok());
+        assert!(new(" ", None).is_err());
+        assert!(new("Laptop", Some(Utc::now() - Duration::seconds(1))).is_err());
+    }
+}
@@ -0,0 +1,77 @@
+use color_eyre::eyre::{eyre, Result};
+
+// A phone number in E.164 form, e.g. +14155550123, which is what SMS gateways expect.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhoneNumber(String);
+
+impl PhoneNumber {
+    // Accepts the separators people commonly type (spaces, dashes, dots and parentheses).
+    pub fn parse(phone_number: &str) -> Result<Self> {
+        let normalized: String = phone_number
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+            .collect();
+
+        let digits = normalized
+            .strip_prefix('+')
+            .ok_or_else(|| eyre!("Phone number must start with a country code, e.g. +1."))?;
+        let valid = (8..=15).contains(&digits.len())
+            && digits.chars().all(|c| c.is_ascii_digit())
+            && !digits.starts_with('0');
+
+        if valid {
+            Ok(Self(normalized))
+        } else {
+            Err(eyre!("{} is not a valid phone number.", phone_number))
+        }
+    }
+}
+
+impl AsRef<str> for PhoneNumber {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl From<String> for PhoneNumber {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phone_number_parse_valid() {
+        let cases = [
+            ("+14155550123", "+14155550123"),
+            ("+1 (415) 555-0123", "+14155550123"),
+            (" +44 20.7946.0958 ", "+442079460958"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(PhoneNumber::parse(input).unwrap().as_ref(), expected);
+        }
+    }
+
+    #[test]
+    fn phone_number_parse_invalid() {
+        let invalid_numbers = vec![
+            "",                  // empty
+            "4155550123",        // missing country code
+            "+0155550123",       // country codes do not start with 0
+            "+1415",             // too short
+            "+1415555012345678", // too long
+            "+1415abc0123",      // letters
+            "++14155550123",     // double plus
+        ];
+
+        for phone_number in invalid_numbers {
+            let result = PhoneNumber::parse(phone_number);
+            assert!(result.is_err(), "Should fail for: {}", phone_number);
+        }
+    }
+}
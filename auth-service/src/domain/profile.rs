@@ -0,0 +1,406 @@
+use color_eyre::eyre::{eyre, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::utils::constants::JWT_PROFILE_CLAIMS;
+
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 100;
+pub const MAX_AVATAR_URL_LENGTH: usize = 2048;
+// Each metadata section, serialized. Kept small because it may be projected into tokens,
+// which travel in a cookie.
+pub const MAX_METADATA_BYTES: usize = 2048;
+// The `profile` claim, serialized. Base64 grows it by a third, which leaves room for the rest
+// of the token in a 4KB cookie.
+pub const MAX_PROFILE_CLAIM_BYTES: usize = 2048;
+
+lazy_static! {
+    static ref LOCALE: Regex = Regex::new(r"^[A-Za-z]{2,3}(-[A-Za-z0-9]{2,8})*$").unwrap();
+    static ref TIMEZONE: Regex = Regex::new(r"^[A-Za-z]+([/_+-][A-Za-z0-9]+)*$").unwrap();
+    static ref CONFIGURED_CLAIMS: Vec<ProfileField> =
+        ProfileField::parse_list(&JWT_PROFILE_CLAIMS).expect("Invalid JWT_PROFILE_CLAIMS.");
+}
+
+// Schemaless JSON attached to a user by the apps built on this service.
+pub type Metadata = Map<String, Value>;
+
+// What the user tells us about themselves, plus metadata. `user_metadata` is theirs to edit;
+// `app_metadata` is only readable by them and is set through the admin API.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct UserProfile {
+    pub display_name: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar_url: Option<String>,
+    #[serde(default)]
+    pub user_metadata: Metadata,
+    #[serde(default)]
+    pub app_metadata: Metadata,
+}
+
+impl UserProfile {
+    // Applies a JSON merge patch (RFC 7396) from the user: omitted fields are kept, nulls
+    // clear them, and metadata objects are merged key by key.
+    pub fn patched(&self, patch: &Value) -> Result<Self, Vec<ProfileViolation>> {
+        let Value::Object(fields) = patch else {
+            return Err(vec![ProfileViolation::NotAnObject]);
+        };
+        if fields.contains_key("appMetadata") {
+            return Err(vec![ProfileViolation::AppMetadataReadOnly]);
+        }
+
+        let mut document = serde_json::to_value(self)
+            .map_err(|e| vec![ProfileViolation::Malformed(e.to_string())])?;
+        merge_patch(&mut document, patch);
+        let profile: Self = serde_json::from_value(document)
+            .map_err(|e| vec![ProfileViolation::Malformed(e.to_string())])?;
+
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    // Applies a JSON merge patch from an admin to `app_metadata`.
+    pub fn with_app_metadata_patched(&self, patch: &Value) -> Result<Self, Vec<ProfileViolation>> {
+        if !patch.is_object() {
+            return Err(vec![ProfileViolation::NotAnObject]);
+        }
+
+        let mut document = Value::Object(self.app_metadata.clone());
+        merge_patch(&mut document, patch);
+        let Value::Object(app_metadata) = document else {
+            return Err(vec![ProfileViolation::NotAnObject]);
+        };
+
+        let profile = Self {
+            app_metadata,
+            ..self.clone()
+        };
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    pub fn validate(&self) -> Result<(), Vec<ProfileViolation>> {
+        let mut violations = Vec::new();
+
+        if let Some(display_name) = &self.display_name {
+            let length = display_name.chars().count();
+            if display_name.trim().is_empty()
+                || length > MAX_DISPLAY_NAME_LENGTH
+                || display_name.chars().any(char::is_control)
+            {
+                violations.push(ProfileViolation::InvalidDisplayName(
+                    MAX_DISPLAY_NAME_LENGTH,
+                ));
+            }
+        }
+        if let Some(locale) = &self.locale {
+            if !LOCALE.is_match(locale) {
+                violations.push(ProfileViolation::InvalidLocale);
+            }
+        }
+        if let Some(timezone) = &self.timezone {
+            if timezone.len() > 64 || !TIMEZONE.is_match(timezone) {
+                violations.push(ProfileViolation::InvalidTimezone);
+            }
+        }
+        if let Some(avatar_url) = &self.avatar_url {
+            let valid = avatar_url.len() <= MAX_AVATAR_URL_LENGTH
+                && Url::parse(avatar_url)
+                    .map(|url| matches!(url.scheme(), "http" | "https"))
+                    .unwrap_or(false);
+            if !valid {
+                violations.push(ProfileViolation::InvalidAvatarUrl);
+            }
+        }
+        for (field, metadata) in [
+            ("userMetadata", &self.user_metadata),
+            ("appMetadata", &self.app_metadata),
+        ] {
+            let size = serde_json::to_vec(metadata).map_or(usize::MAX, |bytes| bytes.len());
+            if size > MAX_METADATA_BYTES {
+                violations.push(ProfileViolation::MetadataTooLarge(
+                    field,
+                    MAX_METADATA_BYTES,
+                ));
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    // The fields to add to the user's tokens, leaving out unset ones. Fields that would take
+    // the claim over MAX_PROFILE_CLAIM_BYTES are left out too, so that the token still fits
+    // in its cookie; fields listed first in JWT_PROFILE_CLAIMS win.
+    pub fn claims(&self, fields: &[ProfileField]) -> Map<String, Value> {
+        let Ok(Value::Object(mut document)) = serde_json::to_value(self) else {
+            return Map::new();
+        };
+        let mut claims = Map::new();
+        // The braces of the serialized object.
+        let mut size = 2;
+        for field in fields {
+            let name = field.as_ref();
+            let Some(value) = document.remove(name).filter(|value| !value.is_null()) else {
+                continue;
+            };
+            let Ok(serialized) = serde_json::to_vec(&value) else {
+                continue;
+            };
+            // `"name":value`, plus a comma after the first field.
+            let field_size = name.len() + 3 + serialized.len() + usize::from(!claims.is_empty());
+            if size + field_size > MAX_PROFILE_CLAIM_BYTES {
+                tracing::warn!(field = name, "Left a profile field too large for the token out");
+                continue;
+            }
+            size += field_size;
+            claims.insert(name.to_owned(), value);
+        }
+        claims
+    }
+}
+
+// RFC 7396: objects are merged recursively, null removes a member, anything else replaces.
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ProfileViolation {
+    #[error("Profile must be a JSON object")]
+    NotAnObject,
+    #[error("Profile is malformed: {0}")]
+    Malformed(String),
+    #[error("appMetadata can only be changed by an admin")]
+    AppMetadataReadOnly,
+    #[error("displayName must be 1 to {0} characters without control characters")]
+    InvalidDisplayName(usize),
+    #[error("locale must be a language tag such as en-US")]
+    InvalidLocale,
+    #[error("timezone must be an IANA time zone name such as Europe/Paris")]
+    InvalidTimezone,
+    #[error("avatarUrl must be an http or https URL")]
+    InvalidAvatarUrl,
+    #[error("{0} must be at most {1} bytes of JSON")]
+    MetadataTooLarge(&'static str, usize),
+}
+
+// A profile field that can be projected into token claims.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileField {
+    DisplayName,
+    Locale,
+    Timezone,
+    AvatarUrl,
+    UserMetadata,
+    AppMetadata,
+}
+
+impl ProfileField {
+    pub fn parse(field: &str) -> Result<Self> {
+        match field {
+            "displayName" => Ok(Self::DisplayName),
+            "locale" => Ok(Self::Locale),
+            "timezone" => Ok(Self::Timezone),
+            "avatarUrl" => Ok(Self::AvatarUrl),
+            "userMetadata" => Ok(Self::UserMetadata),
+            "appMetadata" => Ok(Self::AppMetadata),
+            other => Err(eyre!("{} is not a profile field.", other)),
+        }
+    }
+
+    // Parses a comma-separated list such as "displayName,locale".
+    pub fn parse_list(fields: &str) -> Result<Vec<Self>> {
+        fields
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    // The fields from JWT_PROFILE_CLAIMS. Panics if they are invalid.
+    pub fn configured_claims() -> &'static [Self] {
+        &CONFIGURED_CLAIMS
+    }
+}
+
+impl AsRef<str> for ProfileField {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::DisplayName => "displayName",
+            Self::Locale => "locale",
+            Self::Timezone => "timezone",
+            Self::AvatarUrl => "avatarUrl",
+            Self::UserMetadata => "userMetadata",
+            Self::AppMetadata => "appMetadata",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn profile() -> UserProfile {
+        UserProfile {
+            display_name: Some("Ada".to_owned()),
+            locale: Some("en-GB".to_owned()),
+            user_metadata: json!({ "theme": "dark", "beta": true })
+                .as_object()
+                .unwrap()
+                .clone(),
+            app_metadata: json!({ "plan": "pro" }).as_object().unwrap().clone(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn patch_keeps_omitted_fields_and_clears_nulls() {
+        let patched = profile()
+            .patched(&json!({
+                "locale": null,
+                "timezone": "Europe/London",
+                "userMetadata": { "beta": null, "lang": "fr" }
+            }))
+            .unwrap();
+
+        assert_eq!(patched.display_name.as_deref(), Some("Ada"));
+        assert_eq!(patched.locale, None);
+        assert_eq!(patched.timezone.as_deref(), Some("Europe/London"));
+        assert_eq!(
+            Value::Object(patched.user_metadata),
+            json!({ "theme": "dark", "lang": "fr" })
+        );
+        assert_eq!(patched.app_metadata, profile().app_metadata);
+    }
+
+    #[test]
+    fn patch_rejects_app_metadata_and_unknown_fields() {
+        assert_eq!(
+            profile().patched(&json!({ "appMetadata": { "plan": "free" } })),
+            Err(vec![ProfileViolation::AppMetadataReadOnly])
+        );
+        assert!(matches!(
+            profile()
+                .patched(&json!({ "nickname": "Ada" }))
+                .unwrap_err()[..],
+            [ProfileViolation::Malformed(_)]
+        ));
+        assert_eq!(
+            profile().patched(&json!(["displayName"])),
+            Err(vec![ProfileViolation::NotAnObject])
+        );
+    }
+
+    #[test]
+    fn patch_reports_every_invalid_field() {
+        let violations = profile()
+            .patched(&json!({
+                "displayName": " ",
+                "locale": "english",
+                "timezone": "Mars Time",
+                "avatarUrl": "javascript:alert(1)",
+                "userMetadata": { "blob": "x".repeat(MAX_METADATA_BYTES) }
+            }))
+            .unwrap_err();
+
+        assert_eq!(
+            violations,
+            vec![
+                ProfileViolation::InvalidDisplayName(MAX_DISPLAY_NAME_LENGTH),
+                ProfileViolation::InvalidLocale,
+                ProfileViolation::InvalidTimezone,
+                ProfileViolation::InvalidAvatarUrl,
+                ProfileViolation::MetadataTooLarge("userMetadata", MAX_METADATA_BYTES),
+            ]
+        );
+    }
+
+    #[test]
+    fn admin_patch_only_changes_app_metadata() {
+        let patched = profile()
+            .with_app_metadata_patched(&json!({ "plan": null, "seats": 5 }))
+            .unwrap();
+
+        assert_eq!(
+            Value::Object(patched.app_metadata.clone()),
+            json!({ "seats": 5 })
+        );
+        assert_eq!(
+            UserProfile {
+                app_metadata: profile().app_metadata,
+                ..patched
+            },
+            profile()
+        );
+    }
+
+    #[test]
+    fn claims_include_only_requested_set_fields() {
+        let fields = ProfileField::parse_list("displayName, timezone,appMetadata").unwrap();
+
+        assert_eq!(
+            Value::Object(profile().claims(&fields)),
+            json!({ "displayName": "Ada", "appMetadata": { "plan": "pro" } })
+        );
+        assert!(ProfileField::parse_list("").unwrap().is_empty());
+        assert!(ProfileField::parse_list("displayName,password").is_err());
+    }
+
+    #[test]
+    fn claims_leave_out_fields_over_the_size_budget() {
+        let fields = ProfileField::parse_list("displayName,userMetadata,appMetadata").unwrap();
+        let blob = "x".repeat(MAX_PROFILE_CLAIM_BYTES / 2);
+        let profile = UserProfile {
+            user_metadata: json!({ "blob": blob }).as_object().unwrap().clone(),
+            app_metadata: json!({ "blob": blob }).as_object().unwrap().clone(),
+            ..profile()
+        };
+
+        let claims = profile.claims(&fields);
+        assert_eq!(
+            claims.keys().collect::<Vec<_>>(),
+            vec!["displayName", "userMetadata"]
+        );
+        assert!(serde_json::to_vec(&claims).unwrap().len() <= MAX_PROFILE_CLAIM_BYTES);
+
+        // A field that does not fit on its own does not keep later ones out.
+        let fields = ProfileField::parse_list("userMetadata,locale").unwrap();
+        let profile = UserProfile {
+            user_metadata: json!({ "blob": "x".repeat(MAX_PROFILE_CLAIM_BYTES) })
+                .as_object()
+                .unwrap()
+                .clone(),
+            ..profile
+        };
+        assert_eq!(
+            Value::Object(profile.claims(&fields)),
+            json!({ "locale": "en-GB" })
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use color_eyre::eyre::{eyre, Result};
+use serde::Serialize;
+
+pub const MAX_SCOPE_LENGTH: usize = 64;
+
+// An OAuth2 scope token (RFC 6749 section 3.3): printable ASCII without spaces, quotes or
+// backslashes, such as "reports:read".
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
+pub struct Scope(String);
+
+impl Scope {
+    pub fn parse(scope: &str) -> Result<Self> {
+        let valid = !scope.is_empty()
+            && scope.len() <= MAX_SCOPE_LENGTH
+            && scope
+                .chars()
+                .all(|c| matches!(c, '!' | '#'..='[' | ']'..='~'));
+        if valid {
+            Ok(Self(scope.to_owned()))
+        } else {
+            Err(eyre!("{} is not a valid scope.", scope))
+        }
+    }
+
+    // Parses the space-delimited list of the `scope` parameter and claim.
+    pub fn parse_list(scopes: &str) -> Result<Vec<Self>> {
+        scopes
+            .split(' ')
+            .filter(|s| !s.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    // Parses every scope and hands the valid ones to `build`. Fails with the reason `build`
+    // failed, if it did, followed by one for each invalid scope.
+    pub fn parse_all_then<T>(
+        scopes: &[String],
+        build: impl FnOnce(Vec<Self>) -> Result<T>,
+    ) -> Result<T, Vec<String>> {
+        let mut reasons = Vec::new();
+        let mut parsed = Vec::new();
+        for scope in scopes {
+            match Self::parse(scope) {
+                Ok(scope) => parsed.push(scope),
+                Err(e) => reasons.push(e.to_string()),
+            }
+        }
+        match build(parsed) {
+            Ok(built) if reasons.is_empty() => Ok(built),
+            Ok(_) => Err(reasons),
+            Err(e) => {
+                reasons.insert(0, e.to_string());
+                Err(reasons)
+            }
+        }
+    }
+
+    // Drops repeated scopes, keeping the first of each.
+    pub fn dedup(scopes: impl IntoIterator<Item = Self>) -> Vec<Self> {
+        let mut unique: Vec<Self> = Vec::new();
+        for scope in scopes {
+            if !unique.contains(&scope) {
+                unique.push(scope);
+            }
+        }
+        unique
+    }
+
+    pub fn join(scopes: &[Self]) -> String {
+        scopes
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl AsRef<str> for Scope {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(list: &str) -> Vec<Scope> {
+        Scope::parse_list(list).unwrap()
+    }
+
+    #[test]
+    fn scope_parse() {
+        for scope in [
+            "reports:read",
+            "admin",
+            "https://api.example.com/jobs.write",
+        ] {
+            assert_eq!(Scope::parse(scope).unwrap().as_ref(), scope);
+        }
+        for scope in [
+            "",
+            "a b",
+            "a\"b",
+            "a\\b",
+            "é",
+            &"a".repeat(MAX_SCOPE_LENGTH + 1),
+        ] {
+            assert!(
+                Scope::parse(scope).is_err(),
+                "{:?} should be invalid",
+                scope
+            );
+        }
+        assert_eq!(
+            scopes(" jobs:run  reports:read "),
+            scopes("jobs:run reports:read")
+        );
+        assert_eq!(
+            Scope::join(&scopes("jobs:run reports:read")),
+            "jobs:run reports:read"
+        );
+        assert_eq!(Scope::dedup(scopes("b a b a")), scopes("b a"));
+    }
+
+    #[test]
+    fn parse_all_then_collects_every_reason() {
+        let requested = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let invalid = "b c is not a valid scope.".to_owned();
+
+        assert_eq!(
+            Scope::parse_all_then(&requested(&["a", "b"]), Ok),
+            Ok(scopes("a b"))
+        );
+        assert_eq!(
+            Scope::parse_all_then(&requested(&["a", "b c"]), Ok),
+            Err(vec![invalid.clone()])
+        );
+        assert_eq!(
+            Scope::parse_all_then(&requested(&["a", "b c"]), |_| -> Result<()> {
+                Err(eyre!("bad name"))
+            }),
+            Err(vec!["bad name".to_owned(), invalid])
+        );
+    }
+}
@@ -0,0 +1,7 @@
+use crate::domain::phone_number::PhoneNumber;
+use color_eyre::eyre::Result;
+
+#[async_trait::async_trait]
+pub trait SmsClient: Send + Sync {
+    async fn send_sms(&self, recipient: &PhoneNumber, message: &str) -> Result<()>;
+}
@@ -0,0 +1,355 @@
+use std::{collections::HashSet, path::Path};
+
+use axum_extra::extract::cookie::SameSite;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use serde::Deserialize;
+
+use crate::{
+    domain::profile::ProfileField,
+    utils::constants::{JWT_COOKIE_NAME, JWT_SECRET, PUBLIC_URL},
+};
+
+pub const DEFAULT_TENANT_ID: &str = "default";
+
+// Identifies a tenant in URLs (`/realms/{id}`), Redis keys and the `users` table.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TenantId(String);
+
+impl TenantId {
+    // 1 to 32 lowercase letters, digits and dashes, not starting with a dash.
+    pub fn parse(id: &str) -> Result<Self> {
+        let valid = (1..=32).contains(&id.len())
+            && !id.starts_with('-')
+            && id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if valid {
+            Ok(Self(id.to_owned()))
+        } else {
+            Err(eyre!("{} is not a valid tenant id.", id))
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.0 == DEFAULT_TENANT_ID
+    }
+}
+
+impl Default for TenantId {
+    fn default() -> Self {
+        Self(DEFAULT_TENANT_ID.to_owned())
+    }
+}
+
+impl AsRef<str> for TenantId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for TenantId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// A product authenticated by this service. Tenants share the database and Redis, but each has
+// its own users, signing secret and Redis keys, so nothing issued for one is accepted by another.
+#[derive(Debug, Clone)]
+pub struct Tenant {
+    pub id: TenantId,
+    // Hosts whose requests this tenant serves, in addition to those under `/realms/{id}`.
+    pub hosts: Vec<String>,
+    // Serves requests that neither name a tenant in their path nor match one by host.
+    pub is_default: bool,
+    pub jwt_secret: String,
+    pub cookie: CookieSettings,
+    // Where users reach this tenant, for links in emails.
+    pub public_url: String,
+    pub signup_enabled: bool,
+    // Every login needs a 2FA code, whatever users chose at signup.
+    pub require_2fa: bool,
+    // Profile fields for the `profile` claim of user tokens, JWT_PROFILE_CLAIMS by default.
+    pub profile_claims: Vec<ProfileField>,
+    // Opens this tenant's admin API, and no other tenant's, in addition to ADMIN_API_KEY.
+    pub admin_api_key: Option<String>,
+}
+
+impl Tenant {
+    pub fn new(id: TenantId, jwt_secret: String) -> Self {
+        let public_url = format!("{}/realms/{}", PUBLIC_URL.as_str(), id);
+        Self {
+            id,
+            hosts: Vec::new(),
+            is_default: false,
+            jwt_secret,
+            cookie: CookieSettings::default(),
+            public_url,
+            signup_enabled: true,
+            require_2fa: false,
+            profile_claims: ProfileField::configured_claims().to_vec(),
+            admin_api_key: None,
+        }
+    }
+
+    // The only tenant when TENANTS_CONFIG_PATH is unset, serving every request the way the
+    // service did before it had tenants.
+    pub fn from_env() -> Self {
+        Self {
+            is_default: true,
+            public_url: PUBLIC_URL.to_owned(),
+            ..Self::new(TenantId::default(), JWT_SECRET.to_owned())
+        }
+    }
+
+    pub fn serves_host(&self, host: &str) -> bool {
+        self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+    }
+
+    // Reads a JSON array of tenants, see `TenantConfig` for the fields.
+    pub fn load_all(path: impl AsRef<Path>) -> Result<Vec<Self>> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read tenants {}", path.display()))?;
+        Self::parse_all(&contents).wrap_err_with(|| format!("Invalid tenants {}", path.display()))
+    }
+
+    pub fn parse_all(json: &str) -> Result<Vec<Self>> {
+        let configs: Vec<TenantConfig> =
+            serde_json::from_str(json).wrap_err("Tenants must be a JSON array")?;
+        if configs.is_empty() {
+            return Err(eyre!("At least one tenant must be configured."));
+        }
+
+        let mut ids = HashSet::new();
+        let mut hosts = HashSet::new();
+        let mut admin_api_keys = HashSet::new();
+        let mut tenants = Vec::new();
+        for config in configs {
+            let tenant = config.into_tenant()?;
+            if !ids.insert(tenant.id.clone()) {
+                return Err(eyre!("Tenant {} is configured twice.", tenant.id));
+            }
+            for host in &tenant.hosts {
+                if !hosts.insert(host.to_ascii_lowercase()) {
+                    return Err(eyre!("Host {} is assigned to more than one tenant.", host));
+                }
+            }
+            if let Some(key) = &tenant.admin_api_key {
+                if !admin_api_keys.insert(key.clone()) {
+                    return Err(eyre!("Tenant {} shares its adminApiKey.", tenant.id));
+                }
+            }
+            tenants.push(tenant);
+        }
+        if tenants.iter().filter(|tenant| tenant.is_default).count() > 1 {
+            return Err(eyre!("At most one tenant can be the default."));
+        }
+        Ok(tenants)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CookieSettings {
+    pub name: String,
+    pub path: String,
+    pub domain: Option<String>,
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+impl Default for CookieSettings {
+    fn default() -> Self {
+        Self {
+            name: JWT_COOKIE_NAME.to_owned(),
+            path: "/".to_owned(),
+            domain: None,
+            secure: false,
+            same_site: SameSite::Lax,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TenantConfig {
+    id: String,
+    jwt_secret: String,
+    #[serde(default)]
+    hosts: Vec<String>,
+    #[serde(default)]
+    default: bool,
+    public_url: Option<String>,
+    #[serde(default = "enabled")]
+    signup_enabled: bool,
+    #[serde(default, rename = "require2FA")]
+    require_2fa: bool,
+    // A comma-separated list, as in JWT_PROFILE_CLAIMS.
+    profile_claims: Option<String>,
+    admin_api_key: Option<String>,
+    #[serde(default)]
+    cookie: CookieConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct CookieConfig {
+    name: Option<String>,
+    path: Option<String>,
+    domain: Option<String>,
+    #[serde(default)]
+    secure: bool,
+    same_site: Option<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl TenantConfig {
+    fn into_tenant(self) -> Result<Tenant> {
+        let id = TenantId::parse(&self.id)?;
+        if self.jwt_secret.is_empty() {
+            return Err(eyre!("Tenant {} has an empty jwtSecret.", id));
+        }
+        if self.admin_api_key.as_deref() == Some("") {
+            return Err(eyre!("Tenant {} has an empty adminApiKey.", id));
+        }
+
+        let defaults = CookieSettings::default();
+        let same_site = match self.cookie.same_site.as_deref() {
+            None | Some("lax") => SameSite::Lax,
+            Some("strict") => SameSite::Strict,
+            Some("none") => SameSite::None,
+            Some(other) => return Err(eyre!("{} is not a valid cookie sameSite.", other)),
+        };
+        let cookie = CookieSettings {
+            name: self.cookie.name.unwrap_or(defaults.name),
+            path: self.cookie.path.unwrap_or(defaults.path),
+            domain: self.cookie.domain,
+            secure: self.cookie.secure,
+            same_site,
+        };
+        let valid_name = !cookie.name.is_empty()
+            && cookie
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid_name {
+            return Err(eyre!("{} is not a valid cookie name.", cookie.name));
+        }
+
+        let mut tenant = Tenant::new(id, self.jwt_secret);
+        if let Some(profile_claims) = self.profile_claims {
+            tenant.profile_claims = ProfileField::parse_list(&profile_claims)
+                .wrap_err_with(|| format!("Tenant {} has invalid profileClaims.", tenant.id))?;
+        }
+        if let Some(public_url) = self.public_url {
+            tenant.public_url = public_url.trim_end_matches('/').to_owned();
+        } else if self.default {
+            tenant.public_url = PUBLIC_URL.to_owned();
+        }
+        Ok(Tenant {
+            hosts: self.hosts,
+            is_default: self.default,
+            cookie,
+            signup_enabled: self.signup_enabled,
+            require_2fa: self.require_2fa,
+            admin_api_key: self.admin_api_key,
+            ..tenant
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tenant_id_parse() {
+        for id in ["default", "acme", "acme-eu-2"] {
+            assert_eq!(TenantId::parse(id).unwrap().as_ref(), id);
+        }
+        for id in ["", "-acme", "Acme", "acme corp", "acme/eu", &"a".repeat(33)] {
+            assert!(TenantId::parse(id).is_err(), "{:?} should be invalid", id);
+        }
+    }
+
+    #[test]
+    fn parse_all_applies_defaults() {
+        let tenants = Tenant::parse_all(
+            r#"[
+                { "id": "acme", "jwtSecret": "s1", "hosts": ["auth.acme.test"], "default": true },
+                {
+                    "id": "globex",
+                    "jwtSecret": "s2",
+                    "signupEnabled": false,
+                    "require2FA": true,
+                    "profileClaims": "displayName, locale",
+                    "adminApiKey": "globex-admin",
+                    "cookie": { "name": "globex_jwt", "secure": true, "sameSite": "strict" }
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        let (acme, globex) = (&tenants[0], &tenants[1]);
+        assert!(acme.is_default);
+        assert!(acme.serves_host("AUTH.acme.test"));
+        assert!(acme.signup_enabled);
+        assert!(!acme.require_2fa);
+        assert_eq!(acme.admin_api_key, None);
+        assert_eq!(acme.cookie.name, JWT_COOKIE_NAME);
+        assert_eq!(acme.public_url, PUBLIC_URL.as_str());
+
+        assert!(!globex.is_default);
+        assert!(!globex.signup_enabled);
+        assert!(globex.require_2fa);
+        assert_eq!(
+            globex.profile_claims,
+            vec![ProfileField::DisplayName, ProfileField::Locale]
+        );
+        assert_eq!(globex.admin_api_key.as_deref(), Some("globex-admin"));
+        assert_eq!(globex.cookie.name, "globex_jwt");
+        assert!(globex.cookie.secure);
+        assert_eq!(globex.cookie.same_site, SameSite::Strict);
+        assert_eq!(
+            globex.public_url,
+            format!("{}/realms/globex", PUBLIC_URL.as_str())
+        );
+    }
+
+    #[test]
+    fn parse_all_rejects_conflicts() {
+        let cases = [
+            "[]",
+            r#"[{ "id": "acme", "jwtSecret": "" }]"#,
+            r#"[{ "id": "acme", "jwtSecret": "s", "colour": "red" }]"#,
+            r#"[{ "id": "acme", "jwtSecret": "s" }, { "id": "acme", "jwtSecret": "t" }]"#,
+            r#"[
+                { "id": "acme", "jwtSecret": "s", "hosts": ["a.test"] },
+                { "id": "globex", "jwtSecret": "t", "hosts": ["A.test"] }
+            ]"#,
+            r#"[
+                { "id": "acme", "jwtSecret": "s", "default": true },
+                { "id": "globex", "jwtSecret": "t", "default": true }
+            ]"#,
+            r#"[{ "id": "acme", "jwtSecret": "s", "cookie": { "name": "a b" } }]"#,
+            r#"[{ "id": "acme", "jwtSecret": "s", "cookie": { "sameSite": "sometimes" } }]"#,
+            r#"[{ "id": "acme", "jwtSecret": "s", "profileClaims": "displayName,password" }]"#,
+            r#"[{ "id": "acme", "jwtSecret": "s", "adminApiKey": "" }]"#,
+            r#"[
+                { "id": "acme", "jwtSecret": "s", "adminApiKey": "k" },
+                { "id": "globex", "jwtSecret": "t", "adminApiKey": "k" }
+            ]"#,
+        ];
+
+        for case in cases {
+            assert!(
+                Tenant::parse_all(case).is_err(),
+                "{} should be invalid",
+                case
+            );
+        }
+    }
+}
@@ -1,19 +1,195 @@
-use crate::domain::{email::Email, password::Password};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use uuid::Uuid;
+
+use crate::domain::{email::Email, password::Password, phone_number::PhoneNumber};
+
+// A user's permanent identity. Unlike the email, it never changes, so it is what tokens and
+// other services should refer to the user by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UserId(Uuid);
+
+impl UserId {
+    pub fn parse(id: &str) -> Result<Self> {
+        let id = Uuid::parse_str(id).wrap_err("Invalid user id")?;
+        Ok(Self(id))
+    }
+}
+
+impl Default for UserId {
+    fn default() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl AsRef<Uuid> for UserId {
+    fn as_ref(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl From<Uuid> for UserId {
+    fn from(value: Uuid) -> Self {
+        Self(value)
+    }
+}
+
+impl std::fmt::Display for UserId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
 
 #[derive(Debug, Clone, sqlx::FromRow)]
 pub struct User {
+    pub id: UserId,
+
     pub email: Email,
 
     #[sqlx(rename = "password_hash")]
     pub password: Password,
 
     pub requires_2fa: bool,
+
+    pub status: UserStatus,
+
+    pub password_reset_required: bool,
+
+    pub password_changed_at: DateTime<Utc>,
+
+    pub phone_number: Option<PhoneNumber>,
+
+    pub phone_verified: bool,
+
+    pub two_fa_channel: TwoFAChannel,
 }
 
 impl User {
     pub fn new(email: String, password: String, requires_2fa: bool) -> Self {
         let email = Email::parse(&email).unwrap();
         let password = Password::parse(&password).unwrap();
-        Self { email, password, requires_2fa }
+        Self {
+            id: UserId::default(),
+            email,
+            password,
+            requires_2fa,
+            status: UserStatus::Active,
+            password_reset_required: false,
+            password_changed_at: Utc::now(),
+            phone_number: None,
+            phone_verified: false,
+            two_fa_channel: TwoFAChannel::Email,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == UserStatus::Active
+    }
+
+    // The number 2FA codes may be texted to, once the user has proven they own it.
+    pub fn verified_phone_number(&self) -> Option<&PhoneNumber> {
+        self.phone_number.as_ref().filter(|_| self.phone_verified)
+    }
+}
+
+// Only active accounts may log in, complete 2FA or use their tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserStatus {
+    Active,
+    Suspended,
+    Pending,
+}
+
+impl UserStatus {
+    pub fn parse(status: &str) -> Result<Self> {
+        match status {
+            "active" => Ok(Self::Active),
+            "suspended" => Ok(Self::Suspended),
+            "pending" => Ok(Self::Pending),
+            other => Err(eyre!("{} is not a valid user status.", other)),
+        }
+    }
+}
+
+impl AsRef<str> for UserStatus {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Active => "active",
+            Self::Suspended => "suspended",
+            Self::Pending => "pending",
+        }
+    }
+}
+
+// Where the user's 2FA codes are delivered. SMS requires a verified phone number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TwoFAChannel {
+    Email,
+    Sms,
+}
+
+impl TwoFAChannel {
+    pub fn parse(channel: &str) -> Result<Self> {
+        match channel {
+            "email" => Ok(Self::Email),
+            "sms" => Ok(Self::Sms),
+            other => Err(eyre!("{} is not a valid 2FA channel.", other)),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAChannel {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Email => "email",
+            Self::Sms => "sms",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_id_round_trips() {
+        let id = UserId::default();
+        assert_eq!(UserId::parse(&id.to_string()).unwrap(), id);
+        assert_ne!(UserId::default(), id);
+        assert!(UserId::parse("test@example.com").is_err());
+    }
+
+    #[test]
+    fn user_status_round_trips() {
+        for status in [UserStatus::Active, UserStatus::Suspended, UserStatus::Pending] {
+            assert_eq!(UserStatus::parse(status.as_ref()).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn user_status_parse_invalid() {
+        assert!(UserStatus::parse("deleted").is_err());
+        assert!(UserStatus::parse("").is_err());
+    }
+
+    #[test]
+    fn two_fa_channel_round_trips() {
+        for channel in [TwoFAChannel::Email, TwoFAChannel::Sms] {
+            assert_eq!(TwoFAChannel::parse(channel.as_ref()).unwrap(), channel);
+        }
+        assert!(TwoFAChannel::parse("pigeon").is_err());
+    }
+
+    #[test]
+    fn only_verified_phone_numbers_are_usable() {
+        let mut user = User::new("a@example.com".to_owned(), "password123".to_owned(), true);
+        user.phone_number = Some(PhoneNumber::parse("+14155550123").unwrap());
+        assert!(user.verified_phone_number().is_none());
+
+        user.phone_verified = true;
+        assert_eq!(
+            user.verified_phone_number().map(AsRef::as_ref),
+            Some("+14155550123")
+        );
     }
 }
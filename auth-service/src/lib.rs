@@ -4,97 +4,401 @@ pub mod routes;
 pub mod services;
 pub mod utils;
 
-use crate::app_state::AppState;
+use crate::app_state::{AppState, TenantType};
 use crate::domain::error::AuthAPIError;
-use crate::routes::{login, logout, signup, verify_2fa, verify_token};
-use crate::utils::tracing::{make_span_with_request_id, on_request, on_response};
-use axum::http::{Method, StatusCode};
+use crate::domain::user::UserStatus;
+use crate::routes::{
+    admin_disable_user, admin_enable_user, admin_force_password_reset, admin_get_user,
+    admin_list_users, admin_query_audit_log, admin_reset_2fa, admin_revoke_sessions,
+    admin_verify_audit_log, create_webhook_subscription, delete_webhook_subscription, health_live,
+    health_ready, list_failed_emails, list_webhook_dead_letters, list_webhook_subscriptions, login,
+    logout, metrics, require_admin, signup, verify_2fa, verify_token, add_phone, verify_phone,
+    remove_phone, set_two_fa_channel, change_password, reset_password, change_email, confirm_email_change,
+    get_me, update_me, admin_get_user_profile, admin_update_app_metadata, oauth_token,
+    create_service_account, list_service_accounts, get_service_account, delete_service_account,
+    rotate_service_account_secret, create_personal_access_token, list_personal_access_tokens,
+    revoke_personal_access_token, require_tenant_admin,
+};
+use crate::utils::constants::{REDIS_CONNECT_TIMEOUT, SHUTDOWN_DRAIN_TIMEOUT};
+use crate::utils::metrics::init_metrics;
+use crate::utils::shutdown::ShutdownHandle;
+use crate::utils::tracing::{
+    assign_request_id, expose_matched_route, make_span_with_request_id, on_request, on_response,
+    REQUEST_ID_HEADER,
+};
+use axum::extract::Request;
+use axum::http::{header::HOST, uri::PathAndQuery, Method, StatusCode, Uri};
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::Json;
-use axum::{routing::post, serve::Serve, Router};
-use redis::{Client, RedisResult};
+use axum::{
+    extract::connect_info::IntoMakeServiceWithConnectInfo,
+    middleware::{self, AddExtension},
+    routing::{delete, get, patch, post, put},
+    serve::Serve,
+    Router,
+};
+use redis::{aio::ConnectionManager, Client, RedisResult};
 use serde::{Deserialize, Serialize};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
+use tower::ServiceExt;
 use tower_http::cors::CorsLayer;
 use tower_http::services::ServeDir;
 use tower_http::trace::TraceLayer;
 use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Serialize, Deserialize)]
 pub struct ErrorResponse {
     pub error: String,
+    // Every individual reason, when the request failed for more than one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
         log_error_chain(&self); 
+        let reasons = match &self {
+            AuthAPIError::WeakPassword(violations) => {
+                violations.iter().map(ToString::to_string).collect()
+            }
+            AuthAPIError::InvalidProfile(violations) => {
+                violations.iter().map(ToString::to_string).collect()
+            }
+            AuthAPIError::InvalidServiceAccount(reasons) => reasons.clone(),
+            AuthAPIError::InvalidPersonalAccessToken(reasons) => reasons.clone(),
+            _ => Vec::new(),
+        };
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::WeakPassword(_) => {
+                (StatusCode::BAD_REQUEST, "Password does not meet the password policy")
+            }
             AuthAPIError::IncorrectCredentials => {
                 (StatusCode::UNAUTHORIZED, "Incorrect credentials")
             }
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing Token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid Token"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::WebhookSubscriptionNotFound => {
+                (StatusCode::NOT_FOUND, "Webhook subscription not found")
+            }
+            AuthAPIError::ServiceAccountNotFound => {
+                (StatusCode::NOT_FOUND, "Service account not found")
+            }
+            AuthAPIError::InvalidServiceAccount(_) => {
+                (StatusCode::BAD_REQUEST, "Invalid service account")
+            }
+            AuthAPIError::PersonalAccessTokenNotFound => {
+                (StatusCode::NOT_FOUND, "Personal access token not found")
+            }
+            AuthAPIError::InvalidPersonalAccessToken(_) => {
+                (StatusCode::BAD_REQUEST, "Invalid personal access token")
+            }
+            AuthAPIError::PasswordResetRequired => {
+                (StatusCode::FORBIDDEN, "Password reset required")
+            }
+            AuthAPIError::PasswordExpired => (StatusCode::FORBIDDEN, "Password expired"),
+            AuthAPIError::InvalidPhoneNumber => (StatusCode::BAD_REQUEST, "Invalid phone number"),
+            AuthAPIError::IncorrectVerificationCode => {
+                (StatusCode::BAD_REQUEST, "Incorrect verification code")
+            }
+            AuthAPIError::PhoneNumberNotVerified => {
+                (StatusCode::BAD_REQUEST, "Phone number not verified")
+            }
+            AuthAPIError::TooManyTexts => (StatusCode::TOO_MANY_REQUESTS, "Too many texts"),
+            AuthAPIError::InvalidProfile(_) => (StatusCode::BAD_REQUEST, "Invalid profile"),
+            AuthAPIError::SignupDisabled => (StatusCode::FORBIDDEN, "Signup is disabled"),
+            AuthAPIError::AccountNotActive(status) => match status {
+                UserStatus::Pending => (StatusCode::FORBIDDEN, "Account pending activation"),
+                _ => (StatusCode::FORBIDDEN, "Account suspended"),
+            },
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
         };
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
+            reasons,
         });
         (status, body).into_response()
     }
 }
 
+type AppServer = Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, axum::extract::ConnectInfo<SocketAddr>>,
+>;
+
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: AppServer,
     pub address: String,
+    shutdown_handle: ShutdownHandle,
+    drain_timeout: Duration,
 }
 
 impl Application {
     pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
+        Self::build_for_tenants(vec![app_state], address).await
+    }
+
+    // Serves every tenant from one listener, see `TenantRouters` for how requests find theirs.
+    // Metrics, health checks and the audit log verification are not per tenant and use the
+    // first state.
+    pub async fn build_for_tenants(
+        states: Vec<AppState>,
+        address: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        init_metrics();
+
+        let shared_state = states.first().cloned().ok_or("No tenant to serve")?;
+        let tenants = Arc::new(TenantRouters(
+            states
+                .into_iter()
+                .map(|state| (state.tenant.clone(), tenant_router(state)))
+                .collect(),
+        ));
+
         let allowed_origins = [
             "http://localhost:8000".parse()?,
             "http://134.209.78.82:8000".parse()?,
         ];
 
         let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
             .allow_credentials(true)
-            .allow_origin(allowed_origins);
+            .allow_origin(allowed_origins)
+            .expose_headers([REQUEST_ID_HEADER]);
 
         let router = Router::new()
-            .nest_service("/", ServeDir::new("assets"))
-            .route("/signup", post(signup))
-            .route("/login", post(login))
-            .route("/logout", post(logout))
-            .route("/verify-2fa", post(verify_2fa))
-            .route("/verify-token", post(verify_token))
-            .with_state(app_state)
+            .route(
+                "/metrics",
+                get(metrics).route_layer(middleware::from_fn(require_admin)),
+            )
+            // The hash chain links the records of every tenant, so only the global key may check it.
+            .route(
+                "/admin/audit-log/verify",
+                get(admin_verify_audit_log).route_layer(middleware::from_fn(require_admin)),
+            )
+            .route("/health/live", get(health_live))
+            .route("/health/ready", get(health_ready))
+            .with_state(shared_state)
+            .fallback(move |request: Request| {
+                let tenants = tenants.clone();
+                async move { tenants.dispatch(request).await }
+            })
             .layer(cors)
+            .layer(middleware::from_fn(expose_matched_route))
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(make_span_with_request_id)
                     .on_request(on_request)
                     .on_response(on_response),
-            );
+            )
+            .layer(middleware::from_fn(assign_request_id));
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
-        Ok(Application { server, address })
+        Ok(Application {
+            server,
+            address,
+            shutdown_handle: ShutdownHandle::new(),
+            drain_timeout: *SHUTDOWN_DRAIN_TIMEOUT,
+        })
+    }
+
+    // Triggering the handle makes `run` stop accepting connections and drain in-flight requests.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown_handle.clone()
+    }
+
+    // How long in-flight requests get to finish once shutdown is requested.
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
     }
 
     pub async fn run(self) -> Result<(), std::io::Error> {
         tracing::info!("listening on {}", &self.address);
-        self.server.await
+
+        let shutdown_handle = self.shutdown_handle;
+        let server = self.server.with_graceful_shutdown({
+            let shutdown_handle = shutdown_handle.clone();
+            async move {
+                shutdown_handle.requested().await;
+                tracing::info!("Shutting down, draining in-flight requests");
+            }
+        });
+        let drain_deadline = async {
+            shutdown_handle.requested().await;
+            tokio::time::sleep(self.drain_timeout).await;
+        };
+
+        tokio::select! {
+            result = server => result,
+            _ = drain_deadline => {
+                tracing::warn!(
+                    "In-flight requests did not finish within {:?}, dropping them",
+                    self.drain_timeout
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+// Every route of a tenant, served at the root of its hosts and under `/realms/{id}`.
+fn tenant_router(app_state: AppState) -> Router {
+    let admin_router = Router::new()
+        .route("/users", get(admin_list_users))
+        .route("/users/:email", get(admin_get_user))
+        .route("/users/:email/disable", post(admin_disable_user))
+        .route("/users/:email/enable", post(admin_enable_user))
+        .route(
+            "/users/:email/force-password-reset",
+            post(admin_force_password_reset),
+        )
+        .route("/users/:email/reset-2fa", post(admin_reset_2fa))
+        .route("/users/:email/revoke-sessions", post(admin_revoke_sessions))
+        .route("/users/:email/profile", get(admin_get_user_profile))
+        .route(
+            "/users/:email/app-metadata",
+            patch(admin_update_app_metadata),
+        )
+        .route("/audit-log", get(admin_query_audit_log))
+        .route(
+            "/webhooks",
+            get(list_webhook_subscriptions).post(create_webhook_subscription),
+        )
+        .route("/webhooks/:id", delete(delete_webhook_subscription))
+        .route("/webhooks/dead-letters", get(list_webhook_dead_letters))
+        .route("/emails/failed", get(list_failed_emails))
+        .route(
+            "/service-accounts",
+            get(list_service_accounts).post(create_service_account),
+        )
+        .route(
+            "/service-accounts/:client_id",
+            get(get_service_account).delete(delete_service_account),
+        )
+        .route(
+            "/service-accounts/:client_id/rotate-secret",
+            post(rotate_service_account_secret),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            require_tenant_admin,
+        ));
+
+    Router::new()
+        .nest_service("/", ServeDir::new("assets"))
+        .nest("/admin", admin_router)
+        .route("/signup", post(signup))
+        .route("/login", post(login))
+        .route("/logout", post(logout))
+        .route("/change-password", post(change_password))
+        .route("/reset-password", post(reset_password))
+        .route("/change-email", post(change_email))
+        .route("/change-email/confirm", get(confirm_email_change))
+        .route("/verify-2fa", post(verify_2fa))
+        .route("/verify-token", post(verify_token))
+        .route("/phone", post(add_phone).delete(remove_phone))
+        .route("/phone/verify", post(verify_phone))
+        .route("/2fa-channel", put(set_two_fa_channel))
+        .route("/me", get(get_me).patch(update_me))
+        .route("/oauth/token", post(oauth_token))
+        .route(
+            "/personal-access-tokens",
+            get(list_personal_access_tokens).post(create_personal_access_token),
+        )
+        .route(
+            "/personal-access-tokens/:id",
+            delete(revoke_personal_access_token),
+        )
+        .with_state(app_state)
+        .layer(middleware::from_fn(expose_matched_route))
+}
+
+// Finds the tenant of a request: the one named by a `/realms/{id}` path prefix, which is
+// stripped before routing, else the one serving the request's host, else the default tenant.
+struct TenantRouters(Vec<(TenantType, Router)>);
+
+impl TenantRouters {
+    async fn dispatch(&self, mut request: Request) -> Response {
+        let path = request.uri().path();
+        let router = if let Some(realm) = path.strip_prefix("/realms/") {
+            let (id, rest) = realm.split_once('/').unwrap_or((realm, ""));
+            let Some(router) = self.find(|tenant| tenant.id.as_ref() == id) else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            let Some(uri) = strip_realm(request.uri(), &format!("/{}", rest)) else {
+                return StatusCode::BAD_REQUEST.into_response();
+            };
+            *request.uri_mut() = uri;
+            router
+        } else {
+            let router = host_name(&request)
+                .and_then(|host| self.find(|tenant| tenant.serves_host(host)))
+                .or_else(|| self.find(|tenant| tenant.is_default));
+            let Some(router) = router else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            router
+        };
+
+        match router.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        }
+    }
+
+    fn find(&self, predicate: impl Fn(&TenantType) -> bool) -> Option<&Router> {
+        self.0
+            .iter()
+            .find(|(tenant, _)| predicate(tenant))
+            .map(|(_, router)| router)
     }
 }
 
+fn strip_realm(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_owned(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+// The request's host without its port.
+fn host_name(request: &Request) -> Option<&str> {
+    let host = match request.uri().host() {
+        Some(host) => host,
+        None => request.headers().get(HOST)?.to_str().ok()?,
+    };
+    let host = host
+        .rsplit_once(':')
+        .filter(|(_, port)| port.chars().all(|c| c.is_ascii_digit()))
+        .map_or(host, |(name, _)| name);
+    Some(host)
+}
+
 pub async fn get_postgres_pool(url: &str) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new().max_connections(5).connect(url).await
 }
@@ -104,6 +408,29 @@ pub fn get_redis_client(redis_hostname: String) -> RedisResult<Client> {
     redis::Client::open(redis_url)
 }
 
+// Retry delays between reconnection attempts: rand(0 .. factor * base^attempt) milliseconds.
+const REDIS_RECONNECT_BACKOFF_BASE: u64 = 2;
+const REDIS_RECONNECT_BACKOFF_FACTOR: u64 = 100;
+const REDIS_RECONNECT_ATTEMPTS: usize = 6;
+
+// A multiplexed connection that many tasks can use at once by cloning it. It reconnects in
+// the background when Redis drops it, and fails commands that take longer than
+// `command_timeout` instead of hanging the request.
+pub async fn get_redis_connection(
+    client: Client,
+    command_timeout: Duration,
+) -> RedisResult<ConnectionManager> {
+    ConnectionManager::new_with_backoff_and_timeouts(
+        client,
+        REDIS_RECONNECT_BACKOFF_BASE,
+        REDIS_RECONNECT_BACKOFF_FACTOR,
+        REDIS_RECONNECT_ATTEMPTS,
+        command_timeout,
+        REDIS_CONNECT_TIMEOUT,
+    )
+    .await
+}
+
 fn log_error_chain(e: &(dyn Error + 'static)) {
     let separator =
         "\n-----------------------------------------------------------------------------------\n";
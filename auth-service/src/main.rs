@@ -1,45 +1,156 @@
-use auth_service::get_redis_client;
-use auth_service::services::data_stores::postgres_user_store::PostgresUserStore;
+use auth_service::{get_redis_client, get_redis_connection};
+use auth_service::routes::HEALTH_CHECK_TIMEOUT;
+use auth_service::services::data_stores::postgres_audit_log::PostgresAuditLog;
+use auth_service::services::data_stores::postgres_email_outbox::PostgresEmailOutbox;
+use auth_service::services::data_stores::postgres_personal_access_token_store::PostgresPersonalAccessTokenStore;
+use auth_service::services::data_stores::postgres_service_account_store::PostgresServiceAccountStore;
+use auth_service::services::data_stores::postgres_user_store::{
+    canonicalize_stored_emails, PasswordHashingConfig, PostgresUserStore,
+};
+use auth_service::services::data_stores::postgres_webhook_store::PostgresWebhookStore;
 use auth_service::services::data_stores::redis_banned_token_store::RedisBannedTokenStore;
+use auth_service::services::data_stores::redis_sms_rate_limiter::RedisSmsRateLimiter;
 use auth_service::services::data_stores::redis_two_fa_code_store::RedisTwoFACodeStore;
+use auth_service::services::email_outbox_worker::{EmailOutboxWorker, EmailOutboxWorkerConfig};
+use auth_service::services::email_templates::{Branding, EmailTemplates};
+use auth_service::services::http_email_client::{HttpEmailClient, HttpEmailConfig};
+use auth_service::services::health_checks::{PostgresHealthCheck, RedisHealthCheck};
 use auth_service::services::mock_email_client::MockEmailClient;
-use auth_service::utils::constants::REDIS_HOST_NAME;
+use auth_service::services::mock_sms_client::MockSmsClient;
+use auth_service::services::http_sms_client::{HttpSmsClient, HttpSmsConfig};
+use auth_service::services::smtp_email_client::{SmtpConfig, SmtpEmailClient};
+use auth_service::services::webhook_dispatcher::{WebhookDispatcher, WebhookDispatcherConfig};
+use auth_service::utils::constants::{
+    EMAIL_CLIENT, PASSWORD_BREACH_CORPUS_PATH, PASSWORD_HISTORY_SIZE, PASSWORD_MAX_AGE_DAYS,
+    PASSWORD_MAX_LENGTH, PASSWORD_MIN_STRENGTH, REDIS_COMMAND_TIMEOUT, REDIS_HOST_NAME,
+    SMS_CLIENT, TENANTS_CONFIG_PATH,
+};
+use auth_service::utils::shutdown::shutdown_signal;
 use auth_service::utils::tracing::init_tracing;
+use auth_service::domain::email::EmailFolding;
+use auth_service::domain::password::{BreachCorpus, PasswordPolicy};
+use auth_service::domain::profile::ProfileField;
+use auth_service::domain::tenant::Tenant;
 use auth_service::{
-    app_state::AppState,
+    app_state::{AppState, EmailClientType, HealthCheckType, PasswordPolicyType, SmsClientType},
     get_postgres_pool,
     utils::constants::{prod, DATABASE_URL},
     Application,
 };
+use redis::aio::ConnectionManager;
 use sqlx::PgPool;
 use std::sync::Arc;
-use tokio::sync::RwLock;
 
 #[tokio::main]
 async fn main() {
     color_eyre::install().expect("Failed to install color_eyre");
     init_tracing().expect("Failed to initialize tracing");
+    // Fail at startup rather than on the first request that parses an email or issues a token.
+    EmailFolding::configured();
+    ProfileField::configured_claims();
 
-    let email_client = Arc::new(RwLock::new(MockEmailClient));
+    let email_client = configure_email_client();
+    let sms_client = configure_sms_client();
+    let password_policy = configure_password_policy();
+    let email_templates = Arc::new(
+        EmailTemplates::new(Branding::from_env()).expect("Failed to load email templates"),
+    );
 
-    let redis_client = Arc::new(RwLock::new(configure_redis()));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_client.clone())));
-    let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_client)));
+    let tenants = configure_tenants();
 
+    let redis_connection = configure_redis().await;
     let pg_pool = configure_postgresql().await;
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let password_hashing =
+        PasswordHashingConfig::from_env().expect("Invalid password hashing configuration");
+    let health_checks: Vec<HealthCheckType> = vec![
+        Arc::new(PostgresHealthCheck::new(pg_pool.clone())),
+        Arc::new(RedisHealthCheck::new(
+            get_redis_client(REDIS_HOST_NAME.to_owned()).expect("Failed to get Redis client"),
+            HEALTH_CHECK_TIMEOUT,
+        )),
+    ];
+    // The workers serve every tenant; the routes get stores of their own tenant below.
+    let webhook_store = Arc::new(PostgresWebhookStore::new(pg_pool.clone()));
+    let email_outbox = Arc::new(PostgresEmailOutbox::new(pg_pool.clone()));
 
-    let app_state = AppState::new(
-        user_store,
-        banned_token_store,
-        two_fa_code_store,
+    let webhook_dispatcher =
+        WebhookDispatcher::new(webhook_store.clone(), WebhookDispatcherConfig::default())
+            .expect("Failed to build the webhook dispatcher.");
+    let email_outbox_worker = EmailOutboxWorker::new(
+        email_outbox.clone(),
         email_client,
+        EmailOutboxWorkerConfig::default(),
     );
 
-    let app = Application::build(app_state, prod::APP_ADDRESS)
+    // Each tenant gets stores that only see its own rows and Redis keys.
+    let app_states = tenants
+        .into_iter()
+        .map(|tenant| {
+            let user_store = PostgresUserStore::new(pg_pool.clone())
+                .with_hashing_config(password_hashing)
+                .with_tenant(tenant.id.clone());
+            let two_fa_code_store =
+                RedisTwoFACodeStore::new(redis_connection.clone()).with_tenant(&tenant.id);
+            let banned_token_store =
+                RedisBannedTokenStore::new(redis_connection.clone()).with_tenant(&tenant.id);
+            let sms_rate_limiter =
+                RedisSmsRateLimiter::new(redis_connection.clone()).with_tenant(&tenant.id);
+            let service_account_store = PostgresServiceAccountStore::new(pg_pool.clone())
+                .with_hashing_config(password_hashing)
+                .with_tenant(tenant.id.clone());
+            let personal_access_token_store = PostgresPersonalAccessTokenStore::new(pg_pool.clone())
+                .with_tenant(tenant.id.clone());
+            let audit_log = PostgresAuditLog::new(pg_pool.clone()).with_tenant(tenant.id.clone());
+            let tenant_webhook_store =
+                PostgresWebhookStore::new(pg_pool.clone()).with_tenant(tenant.id.clone());
+            let tenant_email_outbox =
+                PostgresEmailOutbox::new(pg_pool.clone()).with_tenant(tenant.id.clone());
+            AppState::new(
+                Arc::new(user_store),
+                Arc::new(banned_token_store),
+                Arc::new(two_fa_code_store),
+                Arc::new(service_account_store),
+                Arc::new(personal_access_token_store),
+                Arc::new(tenant_email_outbox),
+                email_templates.clone(),
+                sms_client.clone(),
+                Arc::new(sms_rate_limiter),
+                Arc::new(audit_log),
+                Arc::new(tenant_webhook_store),
+                health_checks.clone(),
+                password_policy.clone(),
+            )
+            .with_tenant(tenant)
+        })
+        .collect();
+
+    let app = Application::build_for_tenants(app_states, prod::APP_ADDRESS)
         .await
         .expect("Failed to build the application.");
+
+    let shutdown_handle = app.shutdown_handle();
+    tokio::spawn({
+        let shutdown_handle = shutdown_handle.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown_handle.shutdown();
+        }
+    });
+    let dispatcher = tokio::spawn(webhook_dispatcher.run(shutdown_handle.clone()));
+    let email_worker = tokio::spawn(email_outbox_worker.run(shutdown_handle));
+
     app.run().await.expect("Failed to run the application.");
+
+    // The server has drained by now; let the workers finish, then release the connections.
+    if let Err(e) = dispatcher.await {
+        tracing::error!(error = ?e, "Webhook dispatcher task failed");
+    }
+    if let Err(e) = email_worker.await {
+        tracing::error!(error = ?e, "Email outbox worker task failed");
+    }
+    pg_pool.close().await;
+    drop(redis_connection);
+    tracing::info!("Shutdown complete");
 }
 
 async fn configure_postgresql() -> PgPool {
@@ -52,12 +163,80 @@ async fn configure_postgresql() -> PgPool {
         .await
         .expect("Failed to run migrations");
 
+    // Addresses stored before the current canonical form or EMAIL_FOLDING_DOMAINS would
+    // otherwise never match the ones users log in with.
+    let rewritten = canonicalize_stored_emails(&pg_pool, EmailFolding::configured())
+        .await
+        .expect("Failed to canonicalize stored emails");
+    if rewritten > 0 {
+        tracing::info!(count = rewritten, "Canonicalized stored emails");
+    }
+
     pg_pool
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
+fn configure_email_client() -> EmailClientType {
+    match EMAIL_CLIENT.as_str() {
+        "mock" => Arc::new(MockEmailClient),
+        "smtp" => {
+            let config = SmtpConfig::from_env().expect("Invalid SMTP configuration");
+            Arc::new(SmtpEmailClient::new(config).expect("Failed to build the SMTP email client"))
+        }
+        "http" => {
+            let config = HttpEmailConfig::from_env().expect("Invalid email API configuration");
+            Arc::new(HttpEmailClient::new(config).expect("Failed to build the HTTP email client"))
+        }
+        other => panic!(
+            "Unsupported EMAIL_CLIENT {:?}, expected mock, smtp or http.",
+            other
+        ),
+    }
+}
+
+fn configure_sms_client() -> SmsClientType {
+    match SMS_CLIENT.as_str() {
+        "mock" => Arc::new(MockSmsClient),
+        "http" => {
+            let config = HttpSmsConfig::from_env().expect("Invalid SMS gateway configuration");
+            Arc::new(HttpSmsClient::new(config).expect("Failed to build the HTTP SMS client"))
+        }
+        other => panic!("Unsupported SMS_CLIENT {:?}, expected mock or http.", other),
+    }
+}
+
+fn configure_password_policy() -> PasswordPolicyType {
+    let mut policy = PasswordPolicy::new(*PASSWORD_MAX_LENGTH, *PASSWORD_MIN_STRENGTH)
+        .with_history_size(*PASSWORD_HISTORY_SIZE);
+    if *PASSWORD_MAX_AGE_DAYS > 0 {
+        policy = policy.with_max_age(chrono::Duration::days(i64::from(*PASSWORD_MAX_AGE_DAYS)));
+    }
+    if PASSWORD_BREACH_CORPUS_PATH.is_empty() {
+        return Arc::new(policy);
+    }
+
+    let breach_corpus = BreachCorpus::load(PASSWORD_BREACH_CORPUS_PATH.as_str())
+        .expect("Failed to load the password breach corpus");
+    tracing::info!(
+        path = PASSWORD_BREACH_CORPUS_PATH.as_str(),
+        "Opened password breach corpus"
+    );
+    Arc::new(policy.with_breach_corpus(breach_corpus))
+}
+
+fn configure_tenants() -> Vec<Tenant> {
+    if TENANTS_CONFIG_PATH.is_empty() {
+        return vec![Tenant::from_env()];
+    }
+
+    let tenants =
+        Tenant::load_all(TENANTS_CONFIG_PATH.as_str()).expect("Failed to load the tenants");
+    tracing::info!(tenants = tenants.len(), "Loaded tenants");
+    tenants
+}
+
+async fn configure_redis() -> ConnectionManager {
+    let client = get_redis_client(REDIS_HOST_NAME.to_owned()).expect("Failed to get Redis client");
+    get_redis_connection(client, *REDIS_COMMAND_TIMEOUT)
+        .await
         .expect("Failed to get Redis connection")
 }
@@ -0,0 +1,400 @@
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        audit_log::{AuditAction, AuditQuery, AuditRecord},
+        data_stores::{UserQuery, UserStoreError},
+        email::Email,
+        error::AuthAPIError,
+        profile::UserProfile,
+        user::{User, UserId, UserStatus},
+        webhooks::{WebhookEvent, WebhookEventType},
+    },
+    routes::send_password_reset_link,
+    utils::{
+        constants::ADMIN_API_KEY, metrics::record_token_banned, webhooks::publish_webhook_event,
+    },
+};
+
+const MAX_PER_PAGE: u32 = 100;
+const MAX_AUDIT_EVENTS: u32 = 1000;
+
+// Rejects any admin request that does not carry `Authorization: Bearer <ADMIN_API_KEY>`.
+pub async fn require_admin(request: Request, next: Next) -> Response {
+    check_admin_key(request, next, None).await
+}
+
+// Like `require_admin`, but also accepts the tenant's own admin key.
+pub async fn require_tenant_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    check_admin_key(request, next, state.tenant.admin_api_key.as_deref()).await
+}
+
+async fn check_admin_key(request: Request, next: Next, tenant_key: Option<&str>) -> Response {
+    let provided = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    let authorized = match provided {
+        None => return AuthAPIError::MissingToken.into_response(),
+        Some(key) => {
+            let matches = |expected: &str| constant_time_eq(key.as_bytes(), expected.as_bytes());
+            matches(&ADMIN_API_KEY) || tenant_key.is_some_and(matches)
+        }
+    };
+    if !authorized {
+        return AuthAPIError::InvalidToken.into_response();
+    }
+    next.run(request).await
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[tracing::instrument(name = "Admin listing users", skip_all)]
+pub async fn admin_list_users(
+    State(state): State<AppState>,
+    Query(params): Query<ListUsersParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let query = UserQuery {
+        email_contains: params.email,
+        requires_2fa: params.requires_2fa,
+        status: params.status,
+        page: params.page.unwrap_or(1).max(1),
+        per_page: params.per_page.unwrap_or(20).clamp(1, MAX_PER_PAGE),
+    };
+    let (page, per_page) = (query.page, query.per_page);
+
+    let result = state
+        .user_store
+        .list_users(query)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(AdminUserListResponse {
+        users: result.users.iter().map(AdminUserResponse::from).collect(),
+        page,
+        per_page,
+        total: result.total,
+    }))
+}
+
+#[tracing::instrument(name = "Admin fetching user", skip_all)]
+pub async fn admin_get_user(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &email).await?;
+
+    Ok(Json(AdminUserResponse::from(&user)))
+}
+
+// Suspends the account; the user can no longer log in and existing tokens stop validating.
+#[tracing::instrument(name = "Admin disabling user", skip_all)]
+pub async fn admin_disable_user(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &email).await?;
+    state
+        .user_store
+        .set_status(&user.email, UserStatus::Suspended)
+        .await
+        .map_err(map_user_store_error)?;
+
+    revoke_all_sessions(&state, &user.id).await?;
+    publish_webhook_event(
+        &state.webhook_store,
+        WebhookEvent::new(
+            WebhookEventType::UserSuspended,
+            serde_json::json!({ "userId": user.id.to_string(), "email": user.email.as_ref() }),
+        ),
+    )
+    .await;
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Admin enabling user", skip_all)]
+pub async fn admin_enable_user(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = parse_email(&email)?;
+    state
+        .user_store
+        .set_status(&email, UserStatus::Active)
+        .await
+        .map_err(map_user_store_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Admin forcing password reset", skip_all)]
+pub async fn admin_force_password_reset(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &email).await?;
+    state
+        .user_store
+        .set_password_reset_required(&user.email, true)
+        .await
+        .map_err(map_user_store_error)?;
+
+    revoke_all_sessions(&state, &user.id).await?;
+    send_password_reset_link(&state, &user).await?;
+    Ok(StatusCode::OK)
+}
+
+// Turns 2FA off for the user and drops any pending code, so a user who lost access to
+// their 2FA channel can log in again and re-enable it.
+#[tracing::instrument(name = "Admin resetting 2FA", skip_all)]
+pub async fn admin_reset_2fa(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &email).await?;
+    state
+        .user_store
+        .set_requires_2fa(&user.email, false)
+        .await
+        .map_err(map_user_store_error)?;
+
+    state
+        .two_fa_code_store
+        .remove_code(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    publish_webhook_event(
+        &state.webhook_store,
+        WebhookEvent::new(
+            WebhookEventType::TwoFADisabled,
+            serde_json::json!({ "userId": user.id.to_string(), "email": user.email.as_ref() }),
+        ),
+    )
+    .await;
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Admin revoking sessions", skip_all)]
+pub async fn admin_revoke_sessions(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = find_user(&state, &email).await?;
+
+    revoke_all_sessions(&state, &user.id).await?;
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Admin fetching user profile", skip_all)]
+pub async fn admin_get_user_profile(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+) -> Result<Json<UserProfile>, AuthAPIError> {
+    let email = parse_email(&email)?;
+    state
+        .user_store
+        .get_profile(&email)
+        .await
+        .map(Json)
+        .map_err(map_user_store_error)
+}
+
+// Takes a JSON merge patch of the user's `appMetadata`, which the user can read but not edit.
+#[tracing::instrument(name = "Admin updating app metadata", skip_all)]
+pub async fn admin_update_app_metadata(
+    State(state): State<AppState>,
+    Path(email): Path<String>,
+    Json(patch): Json<Value>,
+) -> Result<Json<UserProfile>, AuthAPIError> {
+    let email = parse_email(&email)?;
+    let profile = state
+        .user_store
+        .get_profile(&email)
+        .await
+        .map_err(map_user_store_error)?
+        .with_app_metadata_patched(&patch)
+        .map_err(AuthAPIError::InvalidProfile)?;
+
+    state
+        .user_store
+        .set_app_metadata(&email, &profile.app_metadata)
+        .await
+        .map_err(map_user_store_error)?;
+
+    Ok(Json(profile))
+}
+
+#[tracing::instrument(name = "Admin querying audit log", skip_all)]
+pub async fn admin_query_audit_log(
+    State(state): State<AppState>,
+    Query(params): Query<AuditLogParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let query = AuditQuery {
+        actor: params.actor,
+        action: params.action,
+        from: params.from,
+        to: params.to,
+        limit: params.limit.unwrap_or(100).clamp(1, MAX_AUDIT_EVENTS),
+    };
+
+    let events = state
+        .audit_log
+        .query(query)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(AuditLogResponse { events }))
+}
+
+#[tracing::instrument(name = "Admin verifying audit log", skip_all)]
+pub async fn admin_verify_audit_log(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let first_invalid_id = state
+        .audit_log
+        .verify_chain()
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(AuditLogVerificationResponse {
+        valid: first_invalid_id.is_none(),
+        first_invalid_id,
+    }))
+}
+
+// Tokens carry the user's id as their subject. Personal access tokens go too, since they act
+// as the user just like a session does.
+pub(crate) async fn revoke_all_sessions(
+    state: &AppState,
+    user_id: &UserId,
+) -> Result<(), AuthAPIError> {
+    state
+        .banned_token_store
+        .revoke_all_tokens(&user_id.to_string(), Utc::now().timestamp_millis())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    record_token_banned();
+    state
+        .personal_access_token_store
+        .revoke_all_tokens(user_id)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+async fn find_user(state: &AppState, email: &str) -> Result<User, AuthAPIError> {
+    let email = parse_email(email)?;
+    state
+        .user_store
+        .get_user(email)
+        .await
+        .map_err(map_user_store_error)
+}
+
+fn parse_email(email: &str) -> Result<Email, AuthAPIError> {
+    Email::parse(email).map_err(|_| AuthAPIError::InvalidCredentials)
+}
+
+fn map_user_store_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListUsersParams {
+    pub email: Option<String>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_status")]
+    pub status: Option<UserStatus>,
+    pub page: Option<u32>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<u32>,
+}
+
+fn deserialize_status<'de, D>(deserializer: D) -> Result<Option<UserStatus>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let status: Option<String> = Option::deserialize(deserializer)?;
+    status
+        .map(|s| UserStatus::parse(&s).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AdminUserResponse {
+    pub id: String,
+    pub email: String,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    pub status: String,
+    #[serde(rename = "passwordResetRequired")]
+    pub password_reset_required: bool,
+    #[serde(rename = "passwordChangedAt")]
+    pub password_changed_at: DateTime<Utc>,
+}
+
+impl From<&User> for AdminUserResponse {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id.to_string(),
+            email: user.email.as_ref().to_owned(),
+            requires_2fa: user.requires_2fa,
+            status: user.status.as_ref().to_owned(),
+            password_reset_required: user.password_reset_required,
+            password_changed_at: user.password_changed_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdminUserListResponse {
+    pub users: Vec<AdminUserResponse>,
+    pub page: u32,
+    #[serde(rename = "perPage")]
+    pub per_page: u32,
+    pub total: u64,
+}
+
+#[derive(Deserialize)]
+pub struct AuditLogParams {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogResponse {
+    pub events: Vec<AuditRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogVerificationResponse {
+    pub valid: bool,
+    #[serde(rename = "firstInvalidId")]
+    pub first_invalid_id: Option<i64>,
+}
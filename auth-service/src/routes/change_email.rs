@@ -0,0 +1,228 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        audit_log::AuditAction,
+        data_stores::{
+            ChangedEmail, EmailChange, EmailChangeToken, UserStoreError, EMAIL_CHANGE_TTL_SECONDS,
+        },
+        email::Email,
+        email_outbox::OutboxEmail,
+        error::AuthAPIError,
+        password::Password,
+        webhooks::{WebhookEvent, WebhookEventType},
+    },
+    routes::revoke_all_sessions,
+    services::email_templates::EmailTemplate,
+    utils::{
+        audit::{audit_event_for, record_audit_event, AuditContext},
+        auth::AuthenticatedUser,
+        webhooks::publish_webhook_event,
+    },
+};
+
+// Mails a confirmation link to the new address and a notice to the current one. The address
+// only changes once the link is followed, so a typo cannot lock the user out. The password is
+// asked for again so that a stolen session is not enough to take over the account.
+#[tracing::instrument(name = "Changing email", skip_all)]
+pub async fn change_email(
+    State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let actor = Some(email.as_ref().to_owned());
+    let result = request_email_change(&state, &email, request).await;
+
+    let event = audit_event_for(actor, AuditAction::ChangeEmail, &result, metadata);
+    record_audit_event(&state.audit_log, event).await;
+
+    result?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+// Asks for the password again, for changes a stolen session should not be enough for.
+pub(crate) async fn confirm_password(
+    state: &AppState,
+    email: &Email,
+    password: &str,
+) -> Result<(), AuthAPIError> {
+    let password = Password::parse(password).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    if password.as_ref().chars().count() > state.password_policy.max_length() {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    state
+        .user_store
+        .validate_user(email.clone(), password)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)
+}
+
+async fn request_email_change(
+    state: &AppState,
+    email: &Email,
+    request: ChangeEmailRequest,
+) -> Result<(), AuthAPIError> {
+    let new_email =
+        Email::parse(&request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if new_email == *email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    confirm_password(state, email, &request.password).await?;
+
+    let change = EmailChange {
+        new_email: new_email.clone(),
+        token: EmailChangeToken::default(),
+        expires_at: Utc::now() + chrono::Duration::seconds(EMAIL_CHANGE_TTL_SECONDS as i64),
+    };
+    state
+        .user_store
+        .start_email_change(email, change.clone())
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    let link = format!(
+        "{}/change-email/confirm?token={}",
+        state.tenant.public_url,
+        change.token.as_ref()
+    );
+    let confirmation = state
+        .email_templates
+        .render(&EmailTemplate::EmailChange {
+            link: &link,
+            expires_in: Duration::from_secs(EMAIL_CHANGE_TTL_SECONDS),
+        })
+        .map_err(AuthAPIError::UnexpectedError)?;
+    let event = format!(
+        "A change of your email address to {} was requested",
+        new_email.as_ref()
+    );
+    let notice = state
+        .email_templates
+        .render(&EmailTemplate::SecurityNotice {
+            event: &event,
+            occurred_at: Utc::now(),
+        })
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    // The link is useless once it expires; the notice is worth sending however late.
+    let emails = [
+        OutboxEmail::new(new_email, confirmation).send_before(change.expires_at),
+        OutboxEmail::new(email.clone(), notice),
+    ];
+    for email in emails {
+        state
+            .email_outbox
+            .enqueue(email)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+
+    Ok(())
+}
+
+// The target of the link mailed by `change_email`, so it needs no auth cookie: holding the
+// token proves access to the new mailbox. Every session of the user is revoked, since their
+// tokens carry the old address.
+#[tracing::instrument(name = "Confirming email change", skip_all)]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
+    Query(params): Query<ConfirmEmailChangeParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let result = swap_email(&state, params).await;
+
+    let actor = result
+        .as_ref()
+        .ok()
+        .map(|changed| changed.old_email.as_ref().to_owned());
+    let event = audit_event_for(actor, AuditAction::ConfirmEmailChange, &result, metadata);
+    record_audit_event(&state.audit_log, event).await;
+
+    let changed = result?;
+    // Following the link proves the user owns the new address.
+    publish_webhook_event(
+        &state.webhook_store,
+        WebhookEvent::new(
+            WebhookEventType::EmailVerified,
+            serde_json::json!({
+                "userId": changed.user_id.to_string(),
+                "email": changed.new_email.as_ref(),
+            }),
+        ),
+    )
+    .await;
+    publish_webhook_event(
+        &state.webhook_store,
+        WebhookEvent::new(
+            WebhookEventType::EmailChanged,
+            serde_json::json!({
+                "userId": changed.user_id.to_string(),
+                "email": changed.new_email.as_ref(),
+                "previousEmail": changed.old_email.as_ref(),
+            }),
+        ),
+    )
+    .await;
+
+    Ok(Json(ConfirmEmailChangeResponse {
+        email: changed.new_email.as_ref().to_owned(),
+    }))
+}
+
+async fn swap_email(
+    state: &AppState,
+    params: ConfirmEmailChangeParams,
+) -> Result<ChangedEmail, AuthAPIError> {
+    let token = EmailChangeToken::parse(params.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let changed = state
+        .user_store
+        .confirm_email_change(&token)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::InvalidCredentials => AuthAPIError::InvalidToken,
+            UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    revoke_all_sessions(state, &changed.user_id).await?;
+    // A 2FA code sent during a login under the old address can no longer be completed.
+    let _ = state
+        .two_fa_code_store
+        .remove_code(&changed.old_email)
+        .await;
+
+    Ok(changed)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEmailRequest {
+    pub new_email: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmEmailChangeParams {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ConfirmEmailChangeResponse {
+    pub email: String,
+}
@@ -0,0 +1,255 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        audit_log::AuditAction,
+        data_stores::{
+            LoginAttemptId, PasswordReset, PasswordResetToken, TwoFACode, UserStoreError,
+            PASSWORD_RESET_TTL_SECONDS,
+        },
+        email::Email,
+        email_outbox::OutboxEmail,
+        error::AuthAPIError,
+        password::{Password, PasswordPolicyViolation},
+        user::User,
+    },
+    routes::{check_2fa_code, revoke_all_sessions, send_2fa_code, TwoFactorAuthResponse},
+    services::email_templates::EmailTemplate,
+    utils::{
+        audit::{audit_event_for, record_audit_event, AuditContext},
+        auth::AuthenticatedUser,
+    },
+};
+
+// Needs the current password on top of the session, and a 2FA code for users with 2FA, so
+// that neither a stolen session nor a stolen password is enough. Users who cannot log in
+// because their password expired or must be reset use `reset_password` instead. Every
+// session of the user is revoked afterwards, this one included.
+#[tracing::instrument(name = "Changing password", skip_all)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<Response, AuthAPIError> {
+    let actor = Some(email.as_ref().to_owned());
+    let result = replace_password(&state, &email, request).await;
+
+    let mut event = audit_event_for(actor, AuditAction::ChangePassword, &result, metadata);
+    if let Ok(Some(_)) = &result {
+        event = event.with_detail("2FA required");
+    }
+    record_audit_event(&state.audit_log, event).await;
+
+    // The code was sent; the client repeats the request with it.
+    if let Some(login_attempt_id) = result? {
+        let response = Json(TwoFactorAuthResponse {
+            message: "2FA required".to_owned(),
+            login_attempt_id: login_attempt_id.as_ref().to_owned(),
+        });
+        return Ok((StatusCode::PARTIAL_CONTENT, response).into_response());
+    }
+
+    Ok(password_changed())
+}
+
+// Takes the token of the link mailed by `send_password_reset_link` instead of a session.
+#[tracing::instrument(name = "Resetting password", skip_all)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<Response, AuthAPIError> {
+    let result = redeem_password_reset(&state, request).await;
+
+    let actor = result.as_ref().ok().map(|email| email.as_ref().to_owned());
+    let event = audit_event_for(actor, AuditAction::ResetPassword, &result, metadata);
+    record_audit_event(&state.audit_log, event).await;
+
+    result?;
+
+    Ok(password_changed())
+}
+
+// Mails `user` a link to choose a new password, replacing any link sent before.
+pub(crate) async fn send_password_reset_link(
+    state: &AppState,
+    user: &User,
+) -> Result<(), AuthAPIError> {
+    let reset = PasswordReset {
+        token: PasswordResetToken::default(),
+        expires_at: Utc::now() + chrono::Duration::seconds(PASSWORD_RESET_TTL_SECONDS as i64),
+    };
+    state
+        .user_store
+        .start_password_reset(&user.email, reset.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let link = format!(
+        "{}/reset-password?token={}",
+        state.tenant.public_url,
+        reset.token.as_ref()
+    );
+    let message = state
+        .email_templates
+        .render(&EmailTemplate::PasswordReset { link: &link })
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_outbox
+        .enqueue(OutboxEmail::new(user.email.clone(), message).send_before(reset.expires_at))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+// Returns the id of the login attempt if a 2FA code was sent rather than the password changed.
+async fn replace_password(
+    state: &AppState,
+    email: &Email,
+    request: ChangePasswordRequest,
+) -> Result<Option<LoginAttemptId>, AuthAPIError> {
+    let current_password =
+        Password::parse(&request.current_password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if current_password.as_ref().chars().count() > state.password_policy.max_length() {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    state
+        .user_store
+        .validate_user(email.clone(), current_password)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    let user = active_user(state, email).await?;
+    // The old password is not trusted any more; only the mailed link will do.
+    if user.password_reset_required {
+        return Err(AuthAPIError::PasswordResetRequired);
+    }
+
+    let new_password = state
+        .password_policy
+        .check(&request.new_password, email)
+        .map_err(AuthAPIError::WeakPassword)?;
+
+    if user.requires_2fa || state.tenant.require_2fa {
+        let (Some(login_attempt_id), Some(two_fa_code)) =
+            (request.login_attempt_id, request.two_fa_code)
+        else {
+            return send_2fa_code(state, &user).await.map(Some);
+        };
+        let login_attempt_id = LoginAttemptId::parse(login_attempt_id)
+            .map_err(|_| AuthAPIError::InvalidCredentials)?;
+        let two_fa_code =
+            TwoFACode::parse(two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+        check_2fa_code(state, email, &login_attempt_id, &two_fa_code).await?;
+        state
+            .two_fa_code_store
+            .remove_code(email)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+
+    store_new_password(state, &user, new_password).await?;
+    Ok(None)
+}
+
+async fn redeem_password_reset(
+    state: &AppState,
+    request: ResetPasswordRequest,
+) -> Result<Email, AuthAPIError> {
+    let token =
+        PasswordResetToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let email = state
+        .user_store
+        .find_password_reset(&token)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::InvalidCredentials => AuthAPIError::InvalidToken,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+    let user = active_user(state, &email).await?;
+
+    let new_password = state
+        .password_policy
+        .check(&request.new_password, &email)
+        .map_err(AuthAPIError::WeakPassword)?;
+
+    store_new_password(state, &user, new_password).await?;
+    Ok(email)
+}
+
+async fn active_user(state: &AppState, email: &Email) -> Result<User, AuthAPIError> {
+    let user = state
+        .user_store
+        .get_user(email.clone())
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    if !user.is_active() {
+        return Err(AuthAPIError::AccountNotActive(user.status));
+    }
+    Ok(user)
+}
+
+// Also drops the pending reset link and signs the user out everywhere, since whoever knew
+// the old password may have sessions of their own.
+async fn store_new_password(
+    state: &AppState,
+    user: &User,
+    new_password: Password,
+) -> Result<(), AuthAPIError> {
+    let history_size = state.password_policy.history_size();
+    state
+        .user_store
+        .change_password(&user.email, new_password, history_size)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::PasswordReused => {
+                AuthAPIError::WeakPassword(vec![PasswordPolicyViolation::RecentlyUsed(
+                    history_size,
+                )])
+            }
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    revoke_all_sessions(state, &user.id).await
+}
+
+fn password_changed() -> Response {
+    let response = Json(ChangePasswordResponse {
+        message: "Password changed successfully!".to_owned(),
+    });
+    (StatusCode::OK, response).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+    // Both sent on the retry after a 206, with the code the user received.
+    #[serde(rename = "loginAttemptId", default)]
+    pub login_attempt_id: Option<String>,
+    #[serde(rename = "2FACode", default)]
+    pub two_fa_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub struct ChangePasswordResponse {
+    pub message: String,
+}
@@ -0,0 +1,39 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{email_outbox::FailedEmail, error::AuthAPIError},
+};
+
+const MAX_FAILED_EMAILS: u32 = 1000;
+
+// Emails the outbox worker gave up on, newest first. Mounted under the admin API.
+#[tracing::instrument(name = "Listing failed emails", skip_all)]
+pub async fn list_failed_emails(
+    State(state): State<AppState>,
+    Query(params): Query<FailedEmailsParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let failed_emails = state
+        .email_outbox
+        .list_failed(params.limit.unwrap_or(100).clamp(1, MAX_FAILED_EMAILS))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(FailedEmailsResponse { failed_emails }))
+}
+
+#[derive(Deserialize)]
+pub struct FailedEmailsParams {
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FailedEmailsResponse {
+    #[serde(rename = "failedEmails")]
+    pub failed_emails: Vec<FailedEmail>,
+}
@@ -0,0 +1,185 @@
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::app_state::{AppState, HealthCheckType};
+
+pub const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The process is up and serving requests; dependencies are not probed.
+pub async fn health_live() -> impl IntoResponse {
+    Json(HealthResponse {
+        status: HealthStatus::Ok,
+        checks: vec![],
+    })
+}
+
+// Probes every dependency concurrently, answering 503 if a required one is unhealthy.
+#[tracing::instrument(name = "Checking readiness", skip_all)]
+pub async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
+    let checks = run_health_checks(&state.health_checks, HEALTH_CHECK_TIMEOUT).await;
+
+    let ready = checks
+        .iter()
+        .all(|check| !check.required || check.status == HealthStatus::Ok);
+    let (status_code, status) = match ready {
+        true => (StatusCode::OK, HealthStatus::Ok),
+        false => (StatusCode::SERVICE_UNAVAILABLE, HealthStatus::Unavailable),
+    };
+
+    (status_code, Json(HealthResponse { status, checks }))
+}
+
+pub async fn run_health_checks(
+    health_checks: &[HealthCheckType],
+    timeout: Duration,
+) -> Vec<DependencyHealth> {
+    // Spawn every probe first so they run concurrently, then collect them in order.
+    let probes: Vec<_> = health_checks
+        .iter()
+        .cloned()
+        .map(|health_check| {
+            tokio::spawn(async move {
+                let start = Instant::now();
+                let error = match tokio::time::timeout(timeout, health_check.check()).await {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e.to_string()),
+                    Err(_) => Some(format!("Timed out after {}ms", timeout.as_millis())),
+                };
+                (start.elapsed(), error)
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(probes.len());
+    for (health_check, probe) in health_checks.iter().zip(probes) {
+        let (latency, error) = match probe.await {
+            Ok(outcome) => outcome,
+            Err(e) => (timeout, Some(format!("Health check panicked: {}", e))),
+        };
+        if let Some(error) = &error {
+            tracing::warn!(
+                dependency = health_check.name(),
+                error,
+                "Health check failed"
+            );
+        }
+
+        results.push(DependencyHealth {
+            name: health_check.name().to_owned(),
+            status: match error {
+                None => HealthStatus::Ok,
+                Some(_) => HealthStatus::Unavailable,
+            },
+            required: health_check.required(),
+            latency_ms: latency.as_millis() as u64,
+            error,
+        });
+    }
+    results
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: HealthStatus,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub checks: Vec<DependencyHealth>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyHealth {
+    pub name: String,
+    pub status: HealthStatus,
+    pub required: bool,
+    pub latency_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use color_eyre::eyre::{eyre, Result};
+
+    use super::*;
+    use crate::domain::health::HealthCheck;
+
+    struct FakeCheck {
+        name: &'static str,
+        required: bool,
+        outcome: Option<&'static str>,
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl HealthCheck for FakeCheck {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn required(&self) -> bool {
+            self.required
+        }
+
+        async fn check(&self) -> Result<()> {
+            tokio::time::sleep(self.delay).await;
+            match self.outcome {
+                None => Ok(()),
+                Some(error) => Err(eyre!(error)),
+            }
+        }
+    }
+
+    fn fake(name: &'static str, outcome: Option<&'static str>, delay: Duration) -> HealthCheckType {
+        Arc::new(FakeCheck {
+            name,
+            required: true,
+            outcome,
+            delay,
+        })
+    }
+
+    #[tokio::test]
+    async fn reports_each_dependency_in_order() {
+        let checks = vec![
+            fake("postgres", None, Duration::ZERO),
+            fake("redis", Some("connection refused"), Duration::ZERO),
+        ];
+
+        let results = run_health_checks(&checks, Duration::from_secs(1)).await;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].name, "postgres");
+        assert_eq!(results[0].status, HealthStatus::Ok);
+        assert_eq!(results[1].name, "redis");
+        assert_eq!(results[1].status, HealthStatus::Unavailable);
+        assert_eq!(results[1].error.as_deref(), Some("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn times_out_hanging_dependencies_concurrently() {
+        let checks = vec![
+            fake("postgres", None, Duration::from_secs(60)),
+            fake("redis", None, Duration::from_secs(60)),
+        ];
+
+        let start = Instant::now();
+        let results = run_health_checks(&checks, Duration::from_millis(50)).await;
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(results
+            .iter()
+            .all(|result| result.status == HealthStatus::Unavailable));
+        assert_eq!(results[0].error.as_deref(), Some("Timed out after 50ms"));
+    }
+}
@@ -4,21 +4,60 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     domain::{
-        data_stores::{LoginAttemptId, TwoFACode},
+        audit_log::AuditAction,
+        data_stores::{LoginAttemptId, TwoFACode, TWO_FA_CODE_TTL_SECONDS},
         email::Email,
+        email_outbox::OutboxEmail,
         error::AuthAPIError,
         password::Password,
+        user::{TwoFAChannel, User},
     },
-    utils::auth::generate_auth_cookie,
+    utils::{
+        audit::{audit_actor, audit_event_for, record_audit_event, AuditContext},
+        auth::{generate_auth_cookie, profile_claims},
+        metrics::{record_2fa_code_issued, record_login, LoginOutcome},
+    },
+    routes::{send_code_by_sms, send_password_reset_link},
+    services::email_templates::EmailTemplate,
     AppState,
 };
+use chrono::Utc;
+use std::time::Duration;
 
 #[tracing::instrument(name = "Logging in", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let actor = audit_actor(&request.email);
+    let (jar, result) = authenticate(&state, jar, request).await;
+
+    let mut event = audit_event_for(actor, AuditAction::Login, &result, metadata);
+    let outcome = match &result {
+        Ok((_, Json(LoginResponse::TwoFactorAuth(_)))) => {
+            event = event.with_detail("2FA required");
+            record_2fa_code_issued();
+            LoginOutcome::TwoFARequired
+        }
+        Ok(_) => LoginOutcome::Success,
+        Err(_) => LoginOutcome::Failure,
+    };
+    record_audit_event(&state.audit_log, event).await;
+    record_login(outcome);
+
+    (jar, result)
+}
+
+async fn authenticate(
+    state: &AppState,
+    jar: CookieJar,
+    request: LoginRequest,
+) -> (
+    CookieJar,
+    Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
+) {
     let email = Email::parse(request.email.as_str());
     let password = Password::parse(request.password.as_str());
 
@@ -34,7 +73,12 @@ pub async fn login(
     let email = email.unwrap();
     let password = password.unwrap();
 
-    let user_store = state.user_store.read().await;
+    // No stored password can be this long, so don't spend a hash finding that out.
+    if password.as_ref().chars().count() > state.password_policy.max_length() {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    let user_store = &state.user_store;
     if user_store
         .validate_user(email.clone(), password)
         .await
@@ -48,61 +92,116 @@ pub async fn login(
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    match user.requires_2fa {
-        true => handle_2fa(&user.email, &state.clone(), jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+    if !user.is_active() {
+        return (jar, Err(AuthAPIError::AccountNotActive(user.status)));
+    }
+
+    // These users cannot log in to change their password, so they are mailed a reset link.
+    let blocked = if user.password_reset_required {
+        Some(AuthAPIError::PasswordResetRequired)
+    } else if state.password_policy.is_expired(user.password_changed_at) {
+        Some(AuthAPIError::PasswordExpired)
+    } else {
+        None
+    };
+    if let Some(e) = blocked {
+        let result = send_password_reset_link(state, &user).await;
+        return (jar, result.and(Err(e)));
+    }
+
+    // Users of tenants that make 2FA mandatory need a code even if they turned it off.
+    match user.requires_2fa || state.tenant.require_2fa {
+        true => handle_2fa(&user, state, jar).await,
+        false => handle_no_2fa(&user, state, jar).await,
     }
 }
 
 
 #[tracing::instrument(name = "login requires 2fa , we are handling it here", skip_all)]
 async fn handle_2fa(
-    email: &Email,
+    user: &User,
     state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    let email_client = state.email_client.read().await;
+    match send_2fa_code(state, user).await {
+        Ok(login_atempt_id) => {
+            let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
+                message: "2FA required".to_owned(),
+                login_attempt_id: login_atempt_id.as_ref().to_owned(),
+            }));
+
+            (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
+        }
+        Err(e) => (jar, Err(e)),
+    }
+}
+
+// Stores a new code for `user` and sends it over their 2FA channel. The code is checked
+// together with the returned id, see `check_2fa_code`.
+pub(crate) async fn send_2fa_code(
+    state: &AppState,
+    user: &User,
+) -> Result<LoginAttemptId, AuthAPIError> {
     let two_fa_code = TwoFACode::default();
     let login_atempt_id = LoginAttemptId::default();
 
-    let result = two_fa_code_store
-        .add_code(email.clone(), login_atempt_id.clone(), two_fa_code.clone())
-        .await;
-
-    match result {
-        Ok(_) => {
-            let email_response = email_client
-                .send_email(email, "Here is your auth code", two_fa_code.as_ref())
-                .await;
-            match email_response {
-                Ok(_) => {
-                    let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
-                        message: "2FA required".to_owned(),
-                        login_attempt_id: login_atempt_id.as_ref().to_owned(),
-                    }));
-
-                    (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
-                }
-                Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e))),
-            }
+    state
+        .two_fa_code_store
+        .add_code(user.email.clone(), login_atempt_id.clone(), two_fa_code.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    match (user.two_fa_channel, user.verified_phone_number()) {
+        (TwoFAChannel::Sms, Some(phone_number)) => {
+            send_code_by_sms(state, phone_number, &two_fa_code).await?
         }
-        Err(e) => (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
-    }
+        _ => send_code_by_email(state, &user.email, &two_fa_code).await?,
+    };
+
+    Ok(login_atempt_id)
+}
+
+// Sent by the outbox worker, so a slow or failing provider does not hold up the login.
+async fn send_code_by_email(
+    state: &AppState,
+    email: &Email,
+    two_fa_code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    let message = state
+        .email_templates
+        .render(&EmailTemplate::TwoFACode {
+            code: two_fa_code.as_ref(),
+            expires_in: Duration::from_secs(TWO_FA_CODE_TTL_SECONDS),
+        })
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let send_before = Utc::now() + chrono::Duration::seconds(TWO_FA_CODE_TTL_SECONDS as i64);
+    state
+        .email_outbox
+        .enqueue(OutboxEmail::new(email.clone(), message).send_before(send_before))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
 }
 
 #[tracing::instrument(name = "login DOES NOT require 2fa , we are handling it here", skip_all)]
 async fn handle_no_2fa(
-    email: &Email,
+    user: &User,
+    state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = generate_auth_cookie(email).unwrap();
+    let fields = &state.tenant.profile_claims;
+    let profile = match profile_claims(&state.user_store, fields, &user.email).await {
+        Ok(profile) => profile,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+    let auth_cookie =
+        generate_auth_cookie(&state.tenant, &user.id, &user.email, profile).unwrap();
     let updated_jar = jar.add(auth_cookie);
 
     (
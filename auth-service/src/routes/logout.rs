@@ -4,38 +4,65 @@ use color_eyre::eyre::Result;
 
 use crate::{
     app_state::AppState,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::audit_log::AuditAction,
+    utils::{
+        audit::{audit_event_for, record_audit_event, AuditContext},
+        auth::{removal_cookie, validate_token},
+        metrics::record_token_banned,
+    },
     AuthAPIError,
 };
 
 #[tracing::instrument(name = "Logging out", skip_all)]
 pub async fn logout(
     State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
     jar: CookieJar,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let cookie = jar.get(JWT_COOKIE_NAME);
+    let (jar, actor, result) = revoke_session(&state, jar).await;
+
+    let event = audit_event_for(actor, AuditAction::Logout, &result, metadata);
+    record_audit_event(&state.audit_log, event).await;
+
+    (jar, result)
+}
+
+// Returns the email the token was issued to alongside the result, when the token could be validated.
+async fn revoke_session(
+    state: &AppState,
+    jar: CookieJar,
+) -> (CookieJar, Option<String>, Result<StatusCode, AuthAPIError>) {
+    let cookie = jar.get(&state.tenant.cookie.name);
     if cookie.is_none() {
-        return (jar, Err(AuthAPIError::MissingToken));
+        return (jar, None, Err(AuthAPIError::MissingToken));
     }
     let cookie = cookie.unwrap();
     let token = cookie.value().to_owned();
 
-    let validation_result = validate_token(&token, state.banned_token_store.clone()).await;
-    if validation_result.is_err() {
-        return (jar, Err(AuthAPIError::InvalidToken));
-    }
+    let validation_result = validate_token(
+        &token,
+        &state.tenant,
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        state.service_account_store.clone(),
+    )
+    .await;
+    let claims = match validation_result {
+        Ok(claims) => claims,
+        Err(_) => return (jar, None, Err(AuthAPIError::InvalidToken)),
+    };
 
-    if let Err(e) = state
-        .banned_token_store
-        .write()
-        .await
-        .add_token(token.to_owned())
-        .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    if let Err(e) = state.banned_token_store.add_token(token.to_owned()).await {
+        return (
+            jar,
+            claims.email,
+            Err(AuthAPIError::UnexpectedError(e.into())),
+        );
     }
 
-    let jar = jar.remove(JWT_COOKIE_NAME);
+    record_token_banned();
+
+    let jar = jar.remove(removal_cookie(&state.tenant.cookie));
 
-    (jar, Ok(StatusCode::OK))
+    (jar, claims.email, Ok(StatusCode::OK))
 }
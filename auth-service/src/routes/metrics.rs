@@ -0,0 +1,11 @@
+use axum::{http::header::CONTENT_TYPE, response::IntoResponse};
+
+use crate::utils::metrics::render_metrics;
+
+// Prometheus scrape endpoint.
+pub async fn metrics() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        render_metrics(),
+    )
+}
@@ -1,12 +1,36 @@
+mod admin;
+mod change_email;
+mod change_password;
+mod emails;
+mod health;
 mod login;
 mod logout;
+mod metrics;
+mod oauth;
+mod personal_access_tokens;
+mod phone;
+mod profile;
+mod service_accounts;
 mod signup;
 mod verify_2fa;
 mod verify_token;
+mod webhooks;
 
 // re-export items from sub-modules
+pub use admin::*;
+pub use change_email::*;
+pub use change_password::*;
+pub use emails::*;
+pub use health::*;
 pub use login::*;
 pub use logout::*;
+pub use metrics::*;
+pub use oauth::*;
+pub use personal_access_tokens::*;
+pub use phone::*;
+pub use profile::*;
+pub use service_accounts::*;
 pub use signup::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
+pub use webhooks::*;
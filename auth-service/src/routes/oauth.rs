@@ -0,0 +1,238 @@
+use axum::{
+    extract::{rejection::FormRejection, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, PRAGMA, WWW_AUTHENTICATE},
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Form, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::Report;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        audit_log::{AuditAction, AuditEvent, AuditOutcome},
+        scope::Scope,
+        service_account::{ClientId, ClientSecret, ServiceAccountStoreError},
+    },
+    utils::{
+        audit::{record_audit_event, AuditContext},
+        auth::{generate_service_account_token, TOKEN_TTL_SECONDS},
+    },
+};
+
+// The token endpoint of RFC 6749, for the client credentials grant only (section 4.4). Service
+// accounts authenticate with HTTP Basic or with `client_id` and `client_secret` in the body.
+#[tracing::instrument(name = "Issuing OAuth2 token", skip_all)]
+pub async fn oauth_token(
+    State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
+    headers: HeaderMap,
+    form: Result<Form<TokenRequest>, FormRejection>,
+) -> Response {
+    let result = match form {
+        Ok(Form(request)) => client_credentials(&state, &headers, request).await,
+        Err(_) => Err((None, OAuthError::InvalidRequest("Malformed token request"))),
+    };
+
+    let (actor, result) = match result {
+        Ok((client_id, response)) => (Some(client_id), Ok(response)),
+        Err((client_id, error)) => (client_id, Err(error)),
+    };
+    let event = match &result {
+        Ok(_) => AuditEvent::new(
+            actor.map(|id| id.to_string()),
+            AuditAction::ClientCredentials,
+            AuditOutcome::Success,
+            metadata,
+        ),
+        Err(e) => AuditEvent::new(
+            actor.map(|id| id.to_string()),
+            AuditAction::ClientCredentials,
+            AuditOutcome::Failure,
+            metadata,
+        )
+        .with_detail(e.code()),
+    };
+    record_audit_event(&state.audit_log, event).await;
+
+    match result {
+        // Tokens must not be cached, see RFC 6749 section 5.1.
+        Ok(response) => (
+            [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+            Json(response),
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+// Returns the client id alongside the result, when the request named one.
+async fn client_credentials(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: TokenRequest,
+) -> Result<(ClientId, TokenResponse), (Option<ClientId>, OAuthError)> {
+    let (client_id, client_secret) =
+        client_authentication(headers, &request).map_err(|e| (None, e))?;
+    let client_id = ClientId::parse(&client_id).map_err(|_| (None, OAuthError::InvalidClient))?;
+    let fail = |e| (Some(client_id), e);
+
+    match request.grant_type.as_deref() {
+        Some("client_credentials") => {}
+        Some(_) => return Err(fail(OAuthError::UnsupportedGrantType)),
+        None => return Err(fail(OAuthError::InvalidRequest("Missing grant_type"))),
+    }
+    let requested = request
+        .scope
+        .as_deref()
+        .map(Scope::parse_list)
+        .transpose()
+        .map_err(|_| fail(OAuthError::InvalidScope))?;
+
+    let client_secret =
+        ClientSecret::parse(&client_secret).map_err(|_| fail(OAuthError::InvalidClient))?;
+    let account = state
+        .service_account_store
+        .authenticate(&client_id, &client_secret)
+        .await
+        .map_err(|e| match e {
+            ServiceAccountStoreError::NotFound | ServiceAccountStoreError::InvalidCredentials => {
+                fail(OAuthError::InvalidClient)
+            }
+            ServiceAccountStoreError::UnexpectedError(e) => fail(OAuthError::ServerError(e)),
+        })?;
+
+    let scopes = account
+        .grant(requested)
+        .map_err(|_| fail(OAuthError::InvalidScope))?;
+    let access_token = generate_service_account_token(&state.tenant, &account, &scopes)
+        .map_err(|e| fail(OAuthError::ServerError(Report::msg(format!("{:?}", e)))))?;
+
+    Ok((
+        client_id,
+        TokenResponse {
+            access_token,
+            token_type: "Bearer".to_owned(),
+            expires_in: TOKEN_TTL_SECONDS,
+            scope: Scope::join(&scopes),
+        },
+    ))
+}
+
+// The credentials from the Authorization header or the body; clients must use exactly one.
+fn client_authentication(
+    headers: &HeaderMap,
+    request: &TokenRequest,
+) -> Result<(String, String), OAuthError> {
+    let in_body = match (&request.client_id, &request.client_secret) {
+        (Some(id), Some(secret)) => Some((id.clone(), secret.clone())),
+        (None, None) => None,
+        _ => return Err(OAuthError::InvalidRequest("Incomplete client credentials")),
+    };
+    let Some(header) = headers.get(AUTHORIZATION) else {
+        return in_body.ok_or(OAuthError::InvalidClient);
+    };
+    if in_body.is_some() {
+        return Err(OAuthError::InvalidRequest(
+            "Client credentials given more than once",
+        ));
+    }
+
+    // Client ids and generated secrets never contain characters that would need form
+    // encoding, so the decoded pair is used as is.
+    header
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .and_then(|decoded| {
+            decoded
+                .split_once(':')
+                .map(|(id, secret)| (id.to_owned(), secret.to_owned()))
+        })
+        .ok_or(OAuthError::InvalidClient)
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub scope: String,
+}
+
+// The error response of RFC 6749 section 5.2, which clients expect instead of `ErrorResponse`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    pub error_description: String,
+}
+
+enum OAuthError {
+    InvalidRequest(&'static str),
+    InvalidClient,
+    UnsupportedGrantType,
+    InvalidScope,
+    ServerError(Report),
+}
+
+impl OAuthError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::InvalidClient => "invalid_client",
+            Self::UnsupportedGrantType => "unsupported_grant_type",
+            Self::InvalidScope => "invalid_scope",
+            Self::ServerError(_) => "server_error",
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let (status, description) = match &self {
+            Self::InvalidRequest(description) => (StatusCode::BAD_REQUEST, *description),
+            Self::InvalidClient => (StatusCode::UNAUTHORIZED, "Client authentication failed"),
+            Self::UnsupportedGrantType => (
+                StatusCode::BAD_REQUEST,
+                "Only the client_credentials grant is supported",
+            ),
+            Self::InvalidScope => (
+                StatusCode::BAD_REQUEST,
+                "The requested scope is invalid or not allowed",
+            ),
+            Self::ServerError(e) => {
+                tracing::error!(error = ?e, "Failed to issue OAuth2 token");
+                (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
+            }
+        };
+        let body = Json(OAuthErrorResponse {
+            error: self.code().to_owned(),
+            error_description: description.to_owned(),
+        });
+
+        match self {
+            Self::InvalidClient => (
+                status,
+                [(WWW_AUTHENTICATE, "Basic realm=\"oauth\"")],
+                [(CACHE_CONTROL, "no-store")],
+                body,
+            )
+                .into_response(),
+            _ => (status, [(CACHE_CONTROL, "no-store")], body).into_response(),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::UserStoreError,
+        email::Email,
+        error::AuthAPIError,
+        personal_access_token::{
+            PersonalAccessToken, PersonalAccessTokenId, PersonalAccessTokenSecret,
+            PersonalAccessTokenStoreError,
+        },
+        scope::Scope,
+        user::User,
+    },
+    routes::{confirm_password, parse_id},
+    utils::auth::AuthenticatedUser,
+};
+
+// Tokens are managed with the browser session only, so a leaked token cannot mint more. The
+// password is asked for again, as for `change_email`, since a token outlives the session.
+#[tracing::instrument(name = "Creating personal access token", skip_all)]
+pub async fn create_personal_access_token(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Json(request): Json<CreatePersonalAccessTokenRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    confirm_password(&state, &email, &request.password).await?;
+    let user = current_user(&state, email).await?;
+
+    let secret = PersonalAccessTokenSecret::default();
+    let token = Scope::parse_all_then(&request.scopes, |scopes| {
+        PersonalAccessToken::new(user.id, request.name, scopes, request.expires_at, &secret)
+    })
+    .map_err(AuthAPIError::InvalidPersonalAccessToken)?;
+
+    state
+        .personal_access_token_store
+        .add_token(token.clone(), &secret)
+        .await
+        .map_err(map_personal_access_token_store_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedPersonalAccessTokenResponse {
+            token: secret.as_ref().to_owned(),
+            details: token,
+        }),
+    ))
+}
+
+#[tracing::instrument(name = "Listing personal access tokens", skip_all)]
+pub async fn list_personal_access_tokens(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = current_user(&state, email).await?;
+    let personal_access_tokens = state
+        .personal_access_token_store
+        .list_tokens(&user.id)
+        .await
+        .map_err(map_personal_access_token_store_error)?;
+
+    Ok(Json(PersonalAccessTokensResponse {
+        personal_access_tokens,
+    }))
+}
+
+// The token stops working at once.
+#[tracing::instrument(name = "Revoking personal access token", skip_all)]
+pub async fn revoke_personal_access_token(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user = current_user(&state, email).await?;
+    let id = parse_id(
+        &id,
+        PersonalAccessTokenId::parse,
+        AuthAPIError::PersonalAccessTokenNotFound,
+    )?;
+    state
+        .personal_access_token_store
+        .revoke_token(&user.id, &id)
+        .await
+        .map_err(map_personal_access_token_store_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn current_user(state: &AppState, email: Email) -> Result<User, AuthAPIError> {
+    state.user_store.get_user(email).await.map_err(|e| match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    })
+}
+
+fn map_personal_access_token_store_error(e: PersonalAccessTokenStoreError) -> AuthAPIError {
+    match e {
+        PersonalAccessTokenStoreError::NotFound => AuthAPIError::PersonalAccessTokenNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePersonalAccessTokenRequest {
+    pub name: String,
+    pub password: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedPersonalAccessTokenResponse {
+    #[serde(flatten)]
+    pub details: PersonalAccessToken,
+    // Shown only here; the token cannot be retrieved later.
+    pub token: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalAccessTokensResponse {
+    pub personal_access_tokens: Vec<PersonalAccessToken>,
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::{PhoneVerification, TwoFACode, UserStoreError, TWO_FA_CODE_TTL_SECONDS},
+        error::AuthAPIError,
+        phone_number::PhoneNumber,
+        user::TwoFAChannel,
+    },
+    services::email_templates::EmailTemplate,
+    utils::auth::AuthenticatedUser,
+};
+
+// Stores the number as unverified and texts it a code, to be confirmed with `verify_phone`.
+// Rate limited per user and per number, since anyone can sign up and point this at a number.
+#[tracing::instrument(name = "Adding phone number", skip_all)]
+pub async fn add_phone(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Json(request): Json<AddPhoneRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let phone_number =
+        PhoneNumber::parse(&request.phone_number).map_err(|_| AuthAPIError::InvalidPhoneNumber)?;
+
+    let allowed = state
+        .sms_rate_limiter
+        .try_acquire(&email, &phone_number)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    if !allowed {
+        return Err(AuthAPIError::TooManyTexts);
+    }
+
+    let verification = PhoneVerification {
+        phone_number: phone_number.clone(),
+        code: TwoFACode::default(),
+        expires_at: Utc::now() + chrono::Duration::seconds(TWO_FA_CODE_TTL_SECONDS as i64),
+    };
+
+    state
+        .user_store
+        .start_phone_verification(&email, verification.clone())
+        .await
+        .map_err(map_user_store_error)?;
+
+    send_code_by_sms(&state, &phone_number, &verification.code).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[tracing::instrument(name = "Verifying phone number", skip_all)]
+pub async fn verify_phone(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Json(request): Json<VerifyPhoneRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let code =
+        TwoFACode::parse(request.code).map_err(|_| AuthAPIError::IncorrectVerificationCode)?;
+
+    let phone_number = state
+        .user_store
+        .confirm_phone_number(&email, &code)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::InvalidCredentials => AuthAPIError::IncorrectVerificationCode,
+            e => map_user_store_error(e),
+        })?;
+
+    Ok(Json(PhoneResponse {
+        phone_number: phone_number.as_ref().to_owned(),
+    }))
+}
+
+// Also moves 2FA back to email, since codes can no longer be texted.
+#[tracing::instrument(name = "Removing phone number", skip_all)]
+pub async fn remove_phone(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    state
+        .user_store
+        .remove_phone_number(&email)
+        .await
+        .map_err(map_user_store_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Setting 2FA channel", skip_all)]
+pub async fn set_two_fa_channel(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Json(request): Json<TwoFAChannelRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let channel =
+        TwoFAChannel::parse(&request.channel).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user_store = &state.user_store;
+    if channel == TwoFAChannel::Sms {
+        let user = user_store
+            .get_user(email.clone())
+            .await
+            .map_err(map_user_store_error)?;
+        if user.verified_phone_number().is_none() {
+            return Err(AuthAPIError::PhoneNumberNotVerified);
+        }
+    }
+
+    user_store
+        .set_two_fa_channel(&email, channel)
+        .await
+        .map_err(map_user_store_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+// Texts `code` right away rather than through a queue: unlike email, SMS codes go to users
+// who chose them because they want them within seconds.
+pub(crate) async fn send_code_by_sms(
+    state: &AppState,
+    recipient: &PhoneNumber,
+    code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    let text = state
+        .email_templates
+        .render_sms(&EmailTemplate::TwoFACode {
+            code: code.as_ref(),
+            expires_in: Duration::from_secs(TWO_FA_CODE_TTL_SECONDS),
+        })
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    state
+        .sms_client
+        .send_sms(recipient, &text)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)
+}
+
+fn map_user_store_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddPhoneRequest {
+    pub phone_number: String,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyPhoneRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhoneResponse {
+    pub phone_number: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFAChannelRequest {
+    pub channel: String,
+}
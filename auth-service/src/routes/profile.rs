@@ -0,0 +1,82 @@
+use axum::{extract::State, response::IntoResponse, Json};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        data_stores::UserStoreError, email::Email, error::AuthAPIError, profile::UserProfile,
+    },
+    utils::auth::AuthenticatedUser,
+};
+
+#[tracing::instrument(name = "Fetching own profile", skip_all)]
+pub async fn get_me(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let profile = state
+        .user_store
+        .get_profile(&email)
+        .await
+        .map_err(map_user_store_error)?;
+
+    me_response(&state, &email, profile).await
+}
+
+// Takes a JSON merge patch: omitted fields are kept and null clears a field or metadata key.
+// `appMetadata` is read-only here; admins set it through the admin API.
+#[tracing::instrument(name = "Updating own profile", skip_all)]
+pub async fn update_me(
+    State(state): State<AppState>,
+    AuthenticatedUser(email): AuthenticatedUser,
+    Json(patch): Json<Value>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let user_store = &state.user_store;
+    let profile = user_store
+        .get_profile(&email)
+        .await
+        .map_err(map_user_store_error)?
+        .patched(&patch)
+        .map_err(AuthAPIError::InvalidProfile)?;
+
+    user_store
+        .set_profile(&email, &profile)
+        .await
+        .map_err(map_user_store_error)?;
+
+    me_response(&state, &email, profile).await
+}
+
+async fn me_response(
+    state: &AppState,
+    email: &Email,
+    profile: UserProfile,
+) -> Result<Json<MeResponse>, AuthAPIError> {
+    let user = state
+        .user_store
+        .get_user(email.clone())
+        .await
+        .map_err(map_user_store_error)?;
+
+    Ok(Json(MeResponse {
+        id: user.id.to_string(),
+        email: user.email.as_ref().to_owned(),
+        profile,
+    }))
+}
+
+fn map_user_store_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MeResponse {
+    pub id: String,
+    pub email: String,
+    #[serde(flatten)]
+    pub profile: UserProfile,
+}
@@ -0,0 +1,167 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use color_eyre::eyre::Report;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::AuthAPIError,
+        scope::Scope,
+        service_account::{ClientId, ClientSecret, ServiceAccount, ServiceAccountStoreError},
+    },
+    utils::metrics::record_token_banned,
+};
+
+// Service accounts are managed through the admin API, see `require_admin`. The secret is only
+// ever returned by the request that generates it.
+#[tracing::instrument(name = "Creating service account", skip_all)]
+pub async fn create_service_account(
+    State(state): State<AppState>,
+    Json(request): Json<CreateServiceAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let account = Scope::parse_all_then(&request.scopes, |scopes| {
+        ServiceAccount::new(request.name, scopes)
+    })
+    .map_err(AuthAPIError::InvalidServiceAccount)?;
+
+    let client_secret = ClientSecret::default();
+    state
+        .service_account_store
+        .add_service_account(account.clone(), &client_secret)
+        .await
+        .map_err(map_service_account_store_error)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedServiceAccountResponse {
+            account,
+            client_secret: client_secret.as_ref().to_owned(),
+        }),
+    ))
+}
+
+#[tracing::instrument(name = "Listing service accounts", skip_all)]
+pub async fn list_service_accounts(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let service_accounts = state
+        .service_account_store
+        .list_service_accounts()
+        .await
+        .map_err(map_service_account_store_error)?;
+
+    Ok(Json(ServiceAccountsResponse { service_accounts }))
+}
+
+#[tracing::instrument(name = "Fetching service account", skip_all)]
+pub async fn get_service_account(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = parse_client_id(&client_id)?;
+    let account = state
+        .service_account_store
+        .get_service_account(&client_id)
+        .await
+        .map_err(map_service_account_store_error)?;
+
+    Ok(Json(account))
+}
+
+// Tokens of a deleted account stop validating at once, see `validate_token`.
+#[tracing::instrument(name = "Deleting service account", skip_all)]
+pub async fn delete_service_account(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = parse_client_id(&client_id)?;
+    state
+        .service_account_store
+        .delete_service_account(&client_id)
+        .await
+        .map_err(map_service_account_store_error)?;
+
+    Ok(StatusCode::OK)
+}
+
+// Replaces the secret and revokes the tokens issued with the old one, for when it leaked.
+#[tracing::instrument(name = "Rotating service account secret", skip_all)]
+pub async fn rotate_service_account_secret(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_id = parse_client_id(&client_id)?;
+    let client_secret = ClientSecret::default();
+    state
+        .service_account_store
+        .set_secret(&client_id, &client_secret)
+        .await
+        .map_err(map_service_account_store_error)?;
+
+    // Tokens carry the client id as their subject.
+    state
+        .banned_token_store
+        .revoke_all_tokens(&client_id.to_string(), Utc::now().timestamp_millis())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    record_token_banned();
+
+    Ok(Json(RotatedSecretResponse {
+        client_id,
+        client_secret: client_secret.as_ref().to_owned(),
+    }))
+}
+
+fn parse_client_id(client_id: &str) -> Result<ClientId, AuthAPIError> {
+    parse_id(client_id, ClientId::parse, AuthAPIError::ServiceAccountNotFound)
+}
+
+// Malformed ids cannot belong to anything, so they get the same error as unknown ones.
+pub(crate) fn parse_id<Id>(
+    id: &str,
+    parse: impl FnOnce(&str) -> Result<Id, Report>,
+    not_found: AuthAPIError,
+) -> Result<Id, AuthAPIError> {
+    parse(id).map_err(|_| not_found)
+}
+
+fn map_service_account_store_error(e: ServiceAccountStoreError) -> AuthAPIError {
+    match e {
+        ServiceAccountStoreError::NotFound => AuthAPIError::ServiceAccountNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateServiceAccountRequest {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedServiceAccountResponse {
+    #[serde(flatten)]
+    pub account: ServiceAccount,
+    pub client_secret: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceAccountsResponse {
+    pub service_accounts: Vec<ServiceAccount>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RotatedSecretResponse {
+    pub client_id: ClientId,
+    pub client_secret: String,
+}
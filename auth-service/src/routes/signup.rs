@@ -1,31 +1,52 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 
+use crate::domain::audit_log::AuditAction;
 use crate::domain::email::Email;
 use crate::domain::error::AuthAPIError;
-use crate::domain::password::Password;
-use crate::domain::user::User;
+use crate::domain::user::{User, UserId};
 use crate::domain::data_stores::UserStoreError;
+use crate::domain::webhooks::{WebhookEvent, WebhookEventType};
+use crate::utils::audit::{audit_actor, audit_event_for, record_audit_event, AuditContext};
+use crate::utils::metrics::record_signup;
+use crate::utils::webhooks::publish_webhook_event;
 use crate::AppState;
 
 #[tracing::instrument(name = "Signup", skip_all)] // New!
 pub async fn signup(
     State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    Email::parse(request.email.as_str()).map_err(|_|AuthAPIError::InvalidCredentials)?;
-    Password::parse(request.password.as_str()).map_err(|_|AuthAPIError::InvalidCredentials)?;
+    let actor = audit_actor(&request.email);
+    // Tenants can make 2FA mandatory whatever the user asks for.
+    let requires_2fa = request.requires_2fa || state.tenant.require_2fa;
+    let result = create_user(&state, request, requires_2fa).await;
 
-    let user = User::new(request.email, request.password, request.requires_2fa);
+    let event = audit_event_for(actor, AuditAction::Signup, &result, metadata);
+    record_audit_event(&state.audit_log, event).await;
+    record_signup(&result);
 
-    let mut user_store = state.user_store.write().await;
+    let (user_id, email) = result?;
 
-    if let Err(err) = user_store.add_user(user).await {
-        return match err {
-                UserStoreError::UserAlreadyExists => Err(AuthAPIError::UserAlreadyExists),
-                e => Err(AuthAPIError::UnexpectedError(e.into()))
-            };
+    let data = serde_json::json!({
+        "userId": user_id.to_string(),
+        "email": email.as_ref(),
+        "requires2FA": requires_2fa,
+    });
+    publish_webhook_event(
+        &state.webhook_store,
+        WebhookEvent::new(WebhookEventType::UserSignedUp, data.clone()),
+    )
+    .await;
+    if requires_2fa {
+        publish_webhook_event(
+            &state.webhook_store,
+            WebhookEvent::new(WebhookEventType::TwoFAEnabled, data),
+        )
+        .await;
     }
+
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
     });
@@ -33,6 +54,33 @@ pub async fn signup(
     Ok((StatusCode::CREATED, response))
 }
 
+async fn create_user(
+    state: &AppState,
+    request: SignupRequest,
+    requires_2fa: bool,
+) -> Result<(UserId, Email), AuthAPIError> {
+    if !state.tenant.signup_enabled {
+        return Err(AuthAPIError::SignupDisabled);
+    }
+    let email = Email::parse(request.email.as_str()).map_err(|_|AuthAPIError::InvalidCredentials)?;
+    let password = state
+        .password_policy
+        .check(&request.password, &email)
+        .map_err(AuthAPIError::WeakPassword)?;
+
+    let user = User::new(request.email, password.as_ref().to_owned(), requires_2fa);
+    let user_id = user.id;
+
+    if let Err(err) = state.user_store.add_user(user).await {
+        return match err {
+                UserStoreError::UserAlreadyExists => Err(AuthAPIError::UserAlreadyExists),
+                e => Err(AuthAPIError::UnexpectedError(e.into()))
+            };
+    }
+
+    Ok((user_id, email))
+}
+
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub email: String,
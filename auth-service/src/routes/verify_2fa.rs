@@ -5,18 +5,37 @@ use axum_extra::extract::CookieJar;
 use serde::{Deserialize, Serialize};
 
 use crate::app_state::AppState;
+use crate::domain::audit_log::AuditAction;
 use crate::domain::data_stores::{LoginAttemptId, TwoFACode};
 use crate::domain::email::Email;
 use crate::domain::error::AuthAPIError;
-use crate::utils::auth::generate_auth_cookie;
+use crate::utils::audit::{audit_actor, audit_event_for, record_audit_event, AuditContext};
+use crate::utils::auth::{generate_auth_cookie, profile_claims};
+use crate::utils::metrics::record_2fa_verification;
 use color_eyre::eyre::{eyre, Result};
 
 #[tracing::instrument(name = "Sending email", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    AuditContext(metadata): AuditContext,
     jar: CookieJar,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let actor = audit_actor(&request.email);
+    let (jar, result) = verify(&state, jar, request).await;
+
+    let event = audit_event_for(actor, AuditAction::Verify2FA, &result, metadata);
+    record_audit_event(&state.audit_log, event).await;
+    record_2fa_verification(&result);
+
+    (jar, result)
+}
+
+async fn verify(
+    state: &AppState,
+    jar: CookieJar,
+    request: Verify2FARequest,
+) -> (CookieJar, Result<(), AuthAPIError>) {
     let email = Email::parse(request.email.as_str());
     let two_fa_code = TwoFACode::parse(request.two_fa_code);
     let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id);
@@ -29,16 +48,19 @@ pub async fn verify_2fa(
     let two_fa_code = two_fa_code.unwrap();
     let login_attempt_id = login_attempt_id.unwrap();
 
-    let mut two_fa_code_store = state.two_fa_code_store.write().await;
-    let code_info = two_fa_code_store.get_code(&email).await;
+    let two_fa_code_store = &state.two_fa_code_store;
+    if let Err(e) = check_2fa_code(state, &email, &login_attempt_id, &two_fa_code).await {
+        return (jar, Err(e));
+    }
 
-    let (store_login_attempt_id, store_two_fa_code) = match code_info {
-        Ok(val) => val,
+    // The account may have been suspended between the login and the 2FA verification.
+    let user = match state.user_store.get_user(email.clone()).await {
+        Ok(user) => user,
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
     };
 
-    if two_fa_code != store_two_fa_code || login_attempt_id != store_login_attempt_id {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    if !user.is_active() {
+        return (jar, Err(AuthAPIError::AccountNotActive(user.status)));
     }
 
     let _ = two_fa_code_store
@@ -47,9 +69,15 @@ pub async fn verify_2fa(
 
     let result = two_fa_code_store.remove_code(&email).await;
 
+    let fields = &state.tenant.profile_claims;
+    let profile = match profile_claims(&state.user_store, fields, &email).await {
+        Ok(profile) => profile,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
     match result {
         Ok(_) => {
-            let cookie = match generate_auth_cookie(&email) {
+            let cookie = match generate_auth_cookie(&state.tenant, &user.id, &email, profile) {
                 Ok(cookie) => cookie,
                 Err(_) => {
                     return (
@@ -73,6 +101,26 @@ pub async fn verify_2fa(
     }
 }
 
+// Fails with `IncorrectCredentials` unless `two_fa_code` is the code last sent to `email`
+// for `login_attempt_id`. The code is left in place; callers remove it once it has been used.
+pub(crate) async fn check_2fa_code(
+    state: &AppState,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    two_fa_code: &TwoFACode,
+) -> Result<(), AuthAPIError> {
+    let (store_login_attempt_id, store_two_fa_code) = state
+        .two_fa_code_store
+        .get_code(email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if *two_fa_code != store_two_fa_code || *login_attempt_id != store_login_attempt_id {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    Ok(())
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct Verify2FARequest {
     email: String,
@@ -1,24 +1,86 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
-use serde::Deserialize;
+use axum::{extract::State, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
 
-use crate::{app_state::AppState, domain::error::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::{error::AuthAPIError, personal_access_token::PersonalAccessTokenSecret, scope::Scope},
+    utils::auth::{validate_personal_access_token, validate_token, Claims, SubjectType},
+};
 
+// Accepts JWTs and personal access tokens, telling them apart by the latter's prefix.
 #[tracing::instrument(name = "Verify token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, AuthAPIError> {
     let token = request.token;
-    let response = validate_token(&token, state.banned_token_store.clone()).await;
-    if response.is_err() {
-        return Err(AuthAPIError::InvalidToken);
+    if PersonalAccessTokenSecret::is_personal_access_token(&token) {
+        let (token, user) = validate_personal_access_token(
+            &token,
+            state.personal_access_token_store.clone(),
+            state.user_store.clone(),
+        )
+        .await?;
+        return Ok(Json(VerifyTokenResponse {
+            token_type: TokenType::PersonalAccessToken,
+            sub: user.id.to_string(),
+            sub_type: SubjectType::User,
+            email: Some(user.email.as_ref().to_owned()),
+            scope: Some(Scope::join(&token.scopes)),
+        }));
     }
 
-    tracing::debug!("token is verified and valid: {:?}", response);
-    Ok(StatusCode::OK)
+    let response = validate_token(
+        &token,
+        &state.tenant,
+        state.banned_token_store.clone(),
+        state.user_store.clone(),
+        state.service_account_store.clone(),
+    )
+    .await;
+    let claims = match response {
+        Ok(claims) => claims,
+        Err(_) => return Err(AuthAPIError::InvalidToken),
+    };
+
+    tracing::debug!("token is verified and valid: {:?}", claims);
+    Ok(Json(VerifyTokenResponse::from(claims)))
 }
 
 #[derive(Deserialize)]
 pub struct VerifyTokenRequest {
     pub token: String,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifyTokenResponse {
+    pub token_type: TokenType,
+    // The user's id or the service account's client id.
+    pub sub: String,
+    pub sub_type: SubjectType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    // The space-delimited scopes the token is limited to; unset for user JWTs, which are not.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl From<Claims> for VerifyTokenResponse {
+    fn from(claims: Claims) -> Self {
+        Self {
+            token_type: TokenType::Jwt,
+            sub: claims.sub,
+            sub_type: claims.sub_type,
+            email: claims.email,
+            scope: claims.scope,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    Jwt,
+    PersonalAccessToken,
+}
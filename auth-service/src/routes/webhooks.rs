@@ -0,0 +1,109 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::AuthAPIError,
+        webhooks::{DeadLetter, WebhookEventType, WebhookStoreError, WebhookSubscription},
+    },
+};
+
+const MAX_DEAD_LETTERS: u32 = 1000;
+
+// Webhook subscriptions are managed through the admin API, see `require_admin`.
+#[tracing::instrument(name = "Creating webhook subscription", skip_all)]
+pub async fn create_webhook_subscription(
+    State(state): State<AppState>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let url = reqwest::Url::parse(&request.url).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    if !matches!(url.scheme(), "http" | "https") || request.secret.is_empty() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let subscription =
+        WebhookSubscription::new(url.to_string(), request.secret, request.event_types);
+
+    state
+        .webhook_store
+        .add_subscription(subscription.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::CREATED, Json(subscription)))
+}
+
+#[tracing::instrument(name = "Listing webhook subscriptions", skip_all)]
+pub async fn list_webhook_subscriptions(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let subscriptions = state
+        .webhook_store
+        .list_subscriptions()
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(WebhookSubscriptionsResponse { subscriptions }))
+}
+
+#[tracing::instrument(name = "Deleting webhook subscription", skip_all)]
+pub async fn delete_webhook_subscription(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    state
+        .webhook_store
+        .delete_subscription(id)
+        .await
+        .map_err(|e| match e {
+            WebhookStoreError::SubscriptionNotFound => AuthAPIError::WebhookSubscriptionNotFound,
+            e => AuthAPIError::UnexpectedError(e.into()),
+        })?;
+
+    Ok(StatusCode::OK)
+}
+
+#[tracing::instrument(name = "Listing webhook dead letters", skip_all)]
+pub async fn list_webhook_dead_letters(
+    State(state): State<AppState>,
+    Query(params): Query<DeadLettersParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let dead_letters = state
+        .webhook_store
+        .list_dead_letters(params.limit.unwrap_or(100).clamp(1, MAX_DEAD_LETTERS))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(Json(DeadLettersResponse { dead_letters }))
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub secret: String,
+    #[serde(rename = "eventTypes", default)]
+    pub event_types: Vec<WebhookEventType>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WebhookSubscriptionsResponse {
+    pub subscriptions: Vec<WebhookSubscription>,
+}
+
+#[derive(Deserialize)]
+pub struct DeadLettersParams {
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeadLettersResponse {
+    #[serde(rename = "deadLetters")]
+    pub dead_letters: Vec<DeadLetter>,
+}
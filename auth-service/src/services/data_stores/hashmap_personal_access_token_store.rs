@@ -0,0 +1,128 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::domain::{
+    personal_access_token::{
+        PersonalAccessToken, PersonalAccessTokenId, PersonalAccessTokenSecret,
+        PersonalAccessTokenStore, PersonalAccessTokenStoreError,
+    },
+    user::UserId,
+};
+
+#[derive(Default)]
+pub struct HashmapPersonalAccessTokenStore {
+    tokens: RwLock<HashMap<PersonalAccessTokenSecret, PersonalAccessToken>>,
+}
+
+#[async_trait::async_trait]
+impl PersonalAccessTokenStore for HashmapPersonalAccessTokenStore {
+    async fn add_token(
+        &self,
+        token: PersonalAccessToken,
+        secret: &PersonalAccessTokenSecret,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        self.tokens.write().unwrap().insert(secret.clone(), token);
+        Ok(())
+    }
+
+    async fn list_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalAccessTokenStoreError> {
+        let mut tokens: Vec<PersonalAccessToken> = self
+            .tokens
+            .read()
+            .unwrap()
+            .values()
+            .filter(|token| token.user_id == *user_id)
+            .cloned()
+            .collect();
+        tokens.sort_by_key(|token| token.created_at);
+        Ok(tokens)
+    }
+
+    async fn revoke_token(
+        &self,
+        user_id: &UserId,
+        id: &PersonalAccessTokenId,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        let mut tokens = self.tokens.write().unwrap();
+        let before = tokens.len();
+        tokens.retain(|_, token| !(token.user_id == *user_id && token.id == *id));
+        if tokens.len() == before {
+            return Err(PersonalAccessTokenStoreError::NotFound);
+        }
+        Ok(())
+    }
+
+    async fn revoke_all_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        self.tokens
+            .write()
+            .unwrap()
+            .retain(|_, token| token.user_id != *user_id);
+        Ok(())
+    }
+
+    async fn find_token(
+        &self,
+        secret: &PersonalAccessTokenSecret,
+    ) -> Result<PersonalAccessToken, PersonalAccessTokenStoreError> {
+        self.tokens
+            .read()
+            .unwrap()
+            .get(secret)
+            .cloned()
+            .ok_or(PersonalAccessTokenStoreError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn users_only_see_and_revoke_their_own_tokens() {
+        let store = HashmapPersonalAccessTokenStore::default();
+        let (alice, bob) = (UserId::default(), UserId::default());
+        let secret = PersonalAccessTokenSecret::default();
+        let token = PersonalAccessToken::new(alice, "Laptop".to_owned(), Vec::new(), None, &secret)
+            .unwrap();
+        store.add_token(token.clone(), &secret).await.unwrap();
+
+        assert_eq!(
+            store.list_tokens(&alice).await.unwrap(),
+            vec![token.clone()]
+        );
+        assert!(store.list_tokens(&bob).await.unwrap().is_empty());
+        assert_eq!(store.find_token(&secret).await.unwrap(), token);
+
+        assert!(matches!(
+            store.revoke_token(&bob, &token.id).await,
+            Err(PersonalAccessTokenStoreError::NotFound)
+        ));
+        store.revoke_token(&alice, &token.id).await.unwrap();
+        assert!(matches!(
+            store.find_token(&secret).await,
+            Err(PersonalAccessTokenStoreError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn revoke_all_tokens_leaves_other_users_alone() {
+        let store = HashmapPersonalAccessTokenStore::default();
+        let (alice, bob) = (UserId::default(), UserId::default());
+        for user_id in [alice, alice, bob] {
+            let secret = PersonalAccessTokenSecret::default();
+            let token =
+                PersonalAccessToken::new(user_id, "Laptop".to_owned(), Vec::new(), None, &secret)
+                    .unwrap();
+            store.add_token(token, &secret).await.unwrap();
+        }
+
+        store.revoke_all_tokens(&alice).await.unwrap();
+        assert!(store.list_tokens(&alice).await.unwrap().is_empty());
+        assert_eq!(store.list_tokens(&bob).await.unwrap().len(), 1);
+    }
+}
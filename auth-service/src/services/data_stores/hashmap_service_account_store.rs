@@ -0,0 +1,122 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use crate::domain::service_account::{
+    ClientId, ClientSecret, ServiceAccount, ServiceAccountStore, ServiceAccountStoreError,
+};
+
+#[derive(Default)]
+pub struct HashmapServiceAccountStore {
+    accounts: RwLock<HashMap<ClientId, (ServiceAccount, ClientSecret)>>,
+}
+
+#[async_trait::async_trait]
+impl ServiceAccountStore for HashmapServiceAccountStore {
+    async fn add_service_account(
+        &self,
+        account: ServiceAccount,
+        secret: &ClientSecret,
+    ) -> Result<(), ServiceAccountStoreError> {
+        self.accounts
+            .write()
+            .unwrap()
+            .insert(account.client_id, (account, secret.clone()));
+        Ok(())
+    }
+
+    async fn get_service_account(
+        &self,
+        client_id: &ClientId,
+    ) -> Result<ServiceAccount, ServiceAccountStoreError> {
+        self.accounts
+            .read()
+            .unwrap()
+            .get(client_id)
+            .map(|(account, _)| account.clone())
+            .ok_or(ServiceAccountStoreError::NotFound)
+    }
+
+    async fn list_service_accounts(&self) -> Result<Vec<ServiceAccount>, ServiceAccountStoreError> {
+        let mut accounts: Vec<ServiceAccount> = self
+            .accounts
+            .read()
+            .unwrap()
+            .values()
+            .map(|(account, _)| account.clone())
+            .collect();
+        accounts.sort_by_key(|account| account.created_at);
+        Ok(accounts)
+    }
+
+    async fn delete_service_account(
+        &self,
+        client_id: &ClientId,
+    ) -> Result<(), ServiceAccountStoreError> {
+        self.accounts
+            .write()
+            .unwrap()
+            .remove(client_id)
+            .map(|_| ())
+            .ok_or(ServiceAccountStoreError::NotFound)
+    }
+
+    async fn set_secret(
+        &self,
+        client_id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<(), ServiceAccountStoreError> {
+        match self.accounts.write().unwrap().get_mut(client_id) {
+            Some((_, stored)) => {
+                *stored = secret.clone();
+                Ok(())
+            }
+            None => Err(ServiceAccountStoreError::NotFound),
+        }
+    }
+
+    async fn authenticate(
+        &self,
+        client_id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<ServiceAccount, ServiceAccountStoreError> {
+        match self.accounts.read().unwrap().get(client_id) {
+            Some((account, stored)) if stored == secret => Ok(account.clone()),
+            Some(_) => Err(ServiceAccountStoreError::InvalidCredentials),
+            None => Err(ServiceAccountStoreError::NotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn authenticates_with_the_current_secret_only() {
+        let store = HashmapServiceAccountStore::default();
+        let account = ServiceAccount::new("Nightly export".to_owned(), Vec::new()).unwrap();
+        let client_id = account.client_id;
+        let (old_secret, new_secret) = (ClientSecret::default(), ClientSecret::default());
+        store
+            .add_service_account(account.clone(), &old_secret)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.authenticate(&client_id, &old_secret).await.unwrap(),
+            account
+        );
+
+        store.set_secret(&client_id, &new_secret).await.unwrap();
+        assert!(matches!(
+            store.authenticate(&client_id, &old_secret).await,
+            Err(ServiceAccountStoreError::InvalidCredentials)
+        ));
+        assert!(store.authenticate(&client_id, &new_secret).await.is_ok());
+
+        store.delete_service_account(&client_id).await.unwrap();
+        assert!(matches!(
+            store.authenticate(&client_id, &new_secret).await,
+            Err(ServiceAccountStoreError::NotFound)
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::RwLock};
 
 use crate::domain::{
     data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
@@ -7,23 +7,23 @@ use crate::domain::{
 
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    pub codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    pub codes: RwLock<HashMap<Email, (LoginAttemptId, TwoFACode)>>,
 }
 
 #[async_trait::async_trait]
 impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        self.codes.write().unwrap().insert(email, (login_attempt_id, code));
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        self.codes.remove(email);
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        self.codes.write().unwrap().remove(email);
         Ok(())
     }
 
@@ -31,7 +31,7 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        match self.codes.get(email) {
+        match self.codes.read().unwrap().get(email) {
             Some((id, code)) => Ok((id.clone(), code.clone())),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
@@ -44,7 +44,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_code() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         store
             .add_code(
                 Email::parse("foo.bar@gmail.com").unwrap(),
@@ -53,12 +53,12 @@ mod tests {
             )
             .await
             .unwrap();
-        assert!(!store.codes.is_empty());
+        assert!(!store.codes.read().unwrap().is_empty());
     }
 
     #[tokio::test]
     async fn test_remove_code() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         store
             .add_code(
                 Email::parse("foo.bar@gmail.com").unwrap(),
@@ -71,12 +71,12 @@ mod tests {
             .remove_code(&Email::parse("foo.bar@gmail.com").unwrap())
             .await
             .unwrap();
-        assert!(store.codes.is_empty());
+        assert!(store.codes.read().unwrap().is_empty());
     }
 
     #[tokio::test]
     async fn test_get_code() {
-        let mut store = HashmapTwoFACodeStore::default();
+        let store = HashmapTwoFACodeStore::default();
         let email = Email::parse("foo.bar@gmail.com").unwrap();
         let la_id = LoginAttemptId::default();
         let two_fa = TwoFACode::default();
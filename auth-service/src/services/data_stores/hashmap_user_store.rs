@@ -1,34 +1,65 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::RwLock};
+
+use chrono::Utc;
 
 use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
+    data_stores::{
+        ChangedEmail, EmailChange, EmailChangeToken, PasswordReset, PasswordResetToken,
+        PhoneVerification, TwoFACode, UserPage, UserQuery, UserStore, UserStoreError,
+    },
     email::Email,
     password::Password,
-    user::User,
+    phone_number::PhoneNumber,
+    profile::{Metadata, UserProfile},
+    user::{TwoFAChannel, User, UserId, UserStatus},
 };
 
 #[derive(Default)]
 pub struct HashmapUserStore {
+    // One lock over both maps, so phone verification updates them together.
+    inner: RwLock<Users>,
+}
+
+#[derive(Default)]
+struct Users {
     users: HashMap<Email, User>,
+    phone_verifications: HashMap<Email, PhoneVerification>,
+    // Replaced passwords, oldest first.
+    password_history: HashMap<Email, Vec<Password>>,
+    email_changes: HashMap<UserId, EmailChange>,
+    password_resets: HashMap<UserId, PasswordReset>,
+    profiles: HashMap<UserId, UserProfile>,
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        if inner.users.contains_key(&user.email) {
             return Err(UserStoreError::UserAlreadyExists);
         };
-        self.users.insert(user.email.clone(), user);
+        inner.users.insert(user.email.clone(), user);
         Ok(())
     }
 
     async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
-        match self.users.get(&email) {
+        match self.inner.read().unwrap().users.get(&email) {
             Some(user) => Ok(user.clone()),
             None => Err(UserStoreError::UserNotFound),
         }
     }
 
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.inner
+            .read()
+            .unwrap()
+            .users
+            .values()
+            .find(|user| user.id == *id)
+            .cloned()
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
     async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
         let user = self.get_user(email).await?;
         if user.password == password {
@@ -37,6 +68,265 @@ impl UserStore for HashmapUserStore {
             Err(UserStoreError::InvalidCredentials)
         }
     }
+
+    async fn list_users(&self, query: UserQuery) -> Result<UserPage, UserStoreError> {
+        let mut users: Vec<User> = self
+            .inner
+            .read()
+            .unwrap()
+            .users
+            .values()
+            .filter(|user| query.matches(user))
+            .cloned()
+            .collect();
+        users.sort_by(|a, b| a.email.as_ref().cmp(b.email.as_ref()));
+
+        let total = users.len() as u64;
+        let users = users
+            .into_iter()
+            .skip(query.offset() as usize)
+            .take(query.per_page as usize)
+            .collect();
+
+        Ok(UserPage { users, total })
+    }
+
+    async fn set_status(
+        &self,
+        email: &Email,
+        status: UserStatus,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.status = status;
+        Ok(())
+    }
+
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.password_reset_required = required;
+        Ok(())
+    }
+
+    async fn change_password(
+        &self,
+        email: &Email,
+        password: Password,
+        reuse_limit: usize,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        let history = inner.password_history.entry(email.clone()).or_default();
+        let reused = std::iter::once(&user.password)
+            .chain(history.iter().rev())
+            .take(reuse_limit)
+            .any(|previous| *previous == password);
+        if reused {
+            return Err(UserStoreError::PasswordReused);
+        }
+
+        history.push(std::mem::replace(&mut user.password, password));
+        let excess = history.len().saturating_sub(reuse_limit.saturating_sub(1));
+        history.drain(..excess);
+        user.password_reset_required = false;
+        user.password_changed_at = Utc::now();
+        let user_id = user.id;
+        inner.password_resets.remove(&user_id);
+        Ok(())
+    }
+
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.requires_2fa = requires_2fa;
+        Ok(())
+    }
+
+    async fn start_phone_verification(
+        &self,
+        email: &Email,
+        verification: PhoneVerification,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.phone_number = Some(verification.phone_number.clone());
+        user.phone_verified = false;
+        user.two_fa_channel = TwoFAChannel::Email;
+        inner.phone_verifications.insert(email.clone(), verification);
+        Ok(())
+    }
+
+    async fn confirm_phone_number(
+        &self,
+        email: &Email,
+        code: &TwoFACode,
+    ) -> Result<PhoneNumber, UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        let verification = inner
+            .phone_verifications
+            .remove(email)
+            .ok_or(UserStoreError::InvalidCredentials)?;
+        if verification.code != *code || verification.expires_at <= Utc::now() {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+
+        user.phone_verified = true;
+        Ok(verification.phone_number)
+    }
+
+    async fn remove_phone_number(&self, email: &Email) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.phone_number = None;
+        user.phone_verified = false;
+        user.two_fa_channel = TwoFAChannel::Email;
+        inner.phone_verifications.remove(email);
+        Ok(())
+    }
+
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.two_fa_channel = channel;
+        Ok(())
+    }
+
+    async fn start_password_reset(
+        &self,
+        email: &Email,
+        reset: PasswordReset,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get(email).ok_or(UserStoreError::UserNotFound)?;
+        inner.password_resets.insert(user.id, reset);
+        Ok(())
+    }
+
+    async fn find_password_reset(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, UserStoreError> {
+        let inner = self.inner.read().unwrap();
+        let user_id = inner
+            .password_resets
+            .iter()
+            .find(|(_, reset)| reset.token == *token && reset.expires_at > Utc::now())
+            .map(|(user_id, _)| *user_id)
+            .ok_or(UserStoreError::InvalidCredentials)?;
+        inner
+            .users
+            .values()
+            .find(|user| user.id == user_id)
+            .map(|user| user.email.clone())
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    async fn start_email_change(
+        &self,
+        email: &Email,
+        change: EmailChange,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        if inner.users.contains_key(&change.new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+        let user = inner.users.get(email).ok_or(UserStoreError::UserNotFound)?;
+        inner.email_changes.insert(user.id, change);
+        Ok(())
+    }
+
+    async fn confirm_email_change(
+        &self,
+        token: &EmailChangeToken,
+    ) -> Result<ChangedEmail, UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user_id = inner
+            .email_changes
+            .iter()
+            .find(|(_, change)| change.token == *token)
+            .map(|(user_id, _)| *user_id)
+            .ok_or(UserStoreError::InvalidCredentials)?;
+        let change = inner.email_changes.remove(&user_id).unwrap();
+        if change.expires_at <= Utc::now() {
+            return Err(UserStoreError::InvalidCredentials);
+        }
+        if inner.users.contains_key(&change.new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        let old_email = inner
+            .users
+            .values()
+            .find(|user| user.id == user_id)
+            .map(|user| user.email.clone())
+            .ok_or(UserStoreError::UserNotFound)?;
+        let mut user = inner.users.remove(&old_email).unwrap();
+        user.email = change.new_email.clone();
+        inner.users.insert(change.new_email.clone(), user);
+        // Everything else kept per user moves along with the address.
+        if let Some(verification) = inner.phone_verifications.remove(&old_email) {
+            inner
+                .phone_verifications
+                .insert(change.new_email.clone(), verification);
+        }
+        if let Some(history) = inner.password_history.remove(&old_email) {
+            inner
+                .password_history
+                .insert(change.new_email.clone(), history);
+        }
+
+        Ok(ChangedEmail {
+            user_id,
+            old_email,
+            new_email: change.new_email,
+        })
+    }
+
+    async fn get_profile(&self, email: &Email) -> Result<UserProfile, UserStoreError> {
+        let inner = self.inner.read().unwrap();
+        let user = inner.users.get(email).ok_or(UserStoreError::UserNotFound)?;
+        Ok(inner.profiles.get(&user.id).cloned().unwrap_or_default())
+    }
+
+    async fn set_profile(
+        &self,
+        email: &Email,
+        profile: &UserProfile,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get(email).ok_or(UserStoreError::UserNotFound)?;
+        let stored = inner.profiles.entry(user.id).or_default();
+        *stored = UserProfile {
+            app_metadata: std::mem::take(&mut stored.app_metadata),
+            ..profile.clone()
+        };
+        Ok(())
+    }
+
+    async fn set_app_metadata(
+        &self,
+        email: &Email,
+        app_metadata: &Metadata,
+    ) -> Result<(), UserStoreError> {
+        let inner = &mut *self.inner.write().unwrap();
+        let user = inner.users.get(email).ok_or(UserStoreError::UserNotFound)?;
+        inner.profiles.entry(user.id).or_default().app_metadata = app_metadata.clone();
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -45,25 +335,25 @@ mod tests {
 
     #[tokio::test]
     async fn test_add_user() {
-        let mut store = HashmapUserStore::default();
+        let store = HashmapUserStore::default();
         let user = User::new(
             "foo.bar@gmail.com".to_owned(),
             "thePassword".to_owned(),
             false,
         );
         store.add_user(user).await.unwrap();
-        assert!(!store.users.is_empty());
+        assert!(!store.inner.read().unwrap().users.is_empty());
     }
 
     #[tokio::test]
     async fn test_get_user() {
-        let mut store = HashmapUserStore::default();
+        let store = HashmapUserStore::default();
         let user = User::new(
             "foo.bar@gmail.com".to_owned(),
             "thePassword".to_owned(),
             false,
         );
-        store.users.insert(user.email.clone(), user.clone());
+        store.inner.write().unwrap().users.insert(user.email.clone(), user.clone());
         let user = store
             .get_user(Email::parse("foo.bar@gmail.com").unwrap())
             .await
@@ -71,15 +361,32 @@ mod tests {
         assert_eq!(user.email.as_ref(), "foo.bar@gmail.com");
     }
 
+    #[tokio::test]
+    async fn test_get_user_by_id() {
+        let store = HashmapUserStore::default();
+        let user = User::new(
+            "foo.bar@gmail.com".to_owned(),
+            "thePassword".to_owned(),
+            false,
+        );
+        store.add_user(user.clone()).await.unwrap();
+        let found = store.get_user_by_id(&user.id).await.unwrap();
+        assert_eq!(found.email, user.email);
+        assert_eq!(
+            store.get_user_by_id(&UserId::default()).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
     #[tokio::test]
     async fn test_validate_user() {
-        let mut store = HashmapUserStore::default();
+        let store = HashmapUserStore::default();
         let user = User::new(
             "foo.bar@gmail.com".to_owned(),
             "thePassword".to_owned(),
             false,
         );
-        store.users.insert(user.email.clone(), user.clone());
+        store.inner.write().unwrap().users.insert(user.email.clone(), user.clone());
         let error = store
             .validate_user(
                 Email::parse("foo.bar@gmail.com").unwrap(),
@@ -88,4 +395,259 @@ mod tests {
             .await;
         assert_eq!(error, Err(UserStoreError::InvalidCredentials));
     }
+
+    #[tokio::test]
+    async fn test_list_users_filters_and_paginates() {
+        let store = HashmapUserStore::default();
+        for i in 0..5 {
+            let user = User::new(
+                format!("user{}@example.com", i),
+                "thePassword".to_owned(),
+                i % 2 == 0,
+            );
+            store.add_user(user).await.unwrap();
+        }
+
+        let page = store
+            .list_users(UserQuery {
+                requires_2fa: Some(true),
+                per_page: 2,
+                ..UserQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.users.len(), 2);
+        assert_eq!(page.users[0].email.as_ref(), "user0@example.com");
+
+        let page = store
+            .list_users(UserQuery {
+                email_contains: Some("USER4".to_owned()),
+                ..UserQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.users[0].email.as_ref(), "user4@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_set_status() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse("foo.bar@gmail.com").unwrap();
+        let user = User::new(email.as_ref().to_owned(), "thePassword".to_owned(), false);
+        store.add_user(user).await.unwrap();
+
+        store
+            .set_status(&email, UserStatus::Suspended)
+            .await
+            .unwrap();
+        let user = store.get_user(email.clone()).await.unwrap();
+        assert_eq!(user.status, UserStatus::Suspended);
+        assert!(!user.is_active());
+
+        let unknown = Email::parse("nobody@gmail.com").unwrap();
+        assert_eq!(
+            store.set_status(&unknown, UserStatus::Suspended).await,
+            Err(UserStoreError::UserNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_change_password_rejects_recent_passwords() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse("foo.bar@gmail.com").unwrap();
+        let user = User::new(email.as_ref().to_owned(), "password-0".to_owned(), false);
+        store.add_user(user).await.unwrap();
+        store.set_password_reset_required(&email, true).await.unwrap();
+
+        for i in 1..=3 {
+            let password = Password::parse(&format!("password-{}", i)).unwrap();
+            store.change_password(&email, password, 3).await.unwrap();
+        }
+        let user = store.get_user(email.clone()).await.unwrap();
+        assert_eq!(user.password.as_ref(), "password-3");
+        assert!(!user.password_reset_required);
+
+        // The current password and the two before it are off limits; older ones are forgotten.
+        for i in 1..=3 {
+            let password = Password::parse(&format!("password-{}", i)).unwrap();
+            assert_eq!(
+                store.change_password(&email, password, 3).await,
+                Err(UserStoreError::PasswordReused)
+            );
+        }
+        let password = Password::parse("password-0").unwrap();
+        assert!(store.change_password(&email, password, 3).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_confirm_phone_number() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse("foo.bar@gmail.com").unwrap();
+        let user = User::new(email.as_ref().to_owned(), "thePassword".to_owned(), true);
+        store.add_user(user).await.unwrap();
+
+        let phone_number = PhoneNumber::parse("+14155550123").unwrap();
+        let code = TwoFACode::parse("123456".to_owned()).unwrap();
+        let verification = PhoneVerification {
+            phone_number: phone_number.clone(),
+            code: code.clone(),
+            expires_at: Utc::now() + chrono::Duration::minutes(10),
+        };
+        store
+            .start_phone_verification(&email, verification.clone())
+            .await
+            .unwrap();
+        assert!(store
+            .get_user(email.clone())
+            .await
+            .unwrap()
+            .verified_phone_number()
+            .is_none());
+
+        // A wrong code uses up the pending verification.
+        let wrong_code = TwoFACode::parse("654321".to_owned()).unwrap();
+        assert_eq!(
+            store.confirm_phone_number(&email, &wrong_code).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            store.confirm_phone_number(&email, &code).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+
+        store
+            .start_phone_verification(&email, verification)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.confirm_phone_number(&email, &code).await.unwrap(),
+            phone_number
+        );
+        let user = store.get_user(email).await.unwrap();
+        assert_eq!(user.verified_phone_number(), Some(&phone_number));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_phone_number_rejects_expired_code() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse("foo.bar@gmail.com").unwrap();
+        let user = User::new(email.as_ref().to_owned(), "thePassword".to_owned(), true);
+        store.add_user(user).await.unwrap();
+
+        let code = TwoFACode::parse("123456".to_owned()).unwrap();
+        let verification = PhoneVerification {
+            phone_number: PhoneNumber::parse("+14155550123").unwrap(),
+            code: code.clone(),
+            expires_at: Utc::now() - chrono::Duration::seconds(1),
+        };
+        store
+            .start_phone_verification(&email, verification)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.confirm_phone_number(&email, &code).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_confirm_email_change() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse("foo.bar@gmail.com").unwrap();
+        let new_email = Email::parse("foo@example.com").unwrap();
+        let user = User::new(email.as_ref().to_owned(), "thePassword".to_owned(), false);
+        let user_id = user.id;
+        store.add_user(user).await.unwrap();
+
+        let change = EmailChange {
+            new_email: new_email.clone(),
+            token: EmailChangeToken::default(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        };
+        store
+            .start_email_change(&email, change.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .confirm_email_change(&EmailChangeToken::default())
+                .await
+                .unwrap_err(),
+            UserStoreError::InvalidCredentials
+        );
+
+        let changed = store.confirm_email_change(&change.token).await.unwrap();
+        assert_eq!(changed.user_id, user_id);
+        assert_eq!(changed.old_email, email);
+        assert_eq!(changed.new_email, new_email);
+        assert_eq!(store.get_user_by_id(&user_id).await.unwrap().email, new_email);
+        assert_eq!(
+            store.get_user(email).await.unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+
+        // The token is used up.
+        assert_eq!(
+            store.confirm_email_change(&change.token).await.unwrap_err(),
+            UserStoreError::InvalidCredentials
+        );
+    }
+
+    #[tokio::test]
+    async fn test_profile_sections_are_set_separately() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse("foo.bar@gmail.com").unwrap();
+        let user = User::new(email.as_ref().to_owned(), "thePassword".to_owned(), false);
+        store.add_user(user).await.unwrap();
+        assert_eq!(
+            store.get_profile(&email).await.unwrap(),
+            UserProfile::default()
+        );
+
+        let app_metadata = serde_json::json!({ "plan": "pro" })
+            .as_object()
+            .unwrap()
+            .clone();
+        store.set_app_metadata(&email, &app_metadata).await.unwrap();
+        let profile = UserProfile {
+            display_name: Some("Foo".to_owned()),
+            ..Default::default()
+        };
+        store.set_profile(&email, &profile).await.unwrap();
+
+        let stored = store.get_profile(&email).await.unwrap();
+        assert_eq!(stored.display_name.as_deref(), Some("Foo"));
+        assert_eq!(stored.app_metadata, app_metadata);
+        assert_eq!(
+            store
+                .get_profile(&Email::parse("nobody@example.com").unwrap())
+                .await
+                .unwrap_err(),
+            UserStoreError::UserNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn test_email_change_rejects_taken_address() {
+        let store = HashmapUserStore::default();
+        let email = Email::parse("foo.bar@gmail.com").unwrap();
+        let other_email = Email::parse("foo@example.com").unwrap();
+        for address in [&email, &other_email] {
+            let user = User::new(address.as_ref().to_owned(), "thePassword".to_owned(), false);
+            store.add_user(user).await.unwrap();
+        }
+
+        let change = EmailChange {
+            new_email: other_email,
+            token: EmailChangeToken::default(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        };
+        assert_eq!(
+            store.start_email_change(&email, change).await.unwrap_err(),
+            UserStoreError::UserAlreadyExists
+        );
+    }
 }
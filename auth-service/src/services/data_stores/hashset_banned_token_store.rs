@@ -1,21 +1,44 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+};
 
 use crate::domain::data_stores::{BannedTokenStore, BannedTokenStoreError};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
-    tokens: HashSet<String>,
+    tokens: RwLock<HashSet<String>>,
+    revoked_before: RwLock<HashMap<String, i64>>,
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for HashsetBannedTokenStore {
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        self.tokens.insert(token);
+    async fn add_token(&self, token: String) -> Result<(), BannedTokenStoreError> {
+        self.tokens.write().unwrap().insert(token);
         Ok(())
     }
 
     async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        Ok(self.tokens.contains(token))
+        Ok(self.tokens.read().unwrap().contains(token))
+    }
+
+    async fn revoke_all_tokens(
+        &self,
+        subject: &str,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        self.revoked_before
+            .write()
+            .unwrap()
+            .insert(subject.to_owned(), issued_before);
+        Ok(())
+    }
+
+    async fn tokens_revoked_before(
+        &self,
+        subject: &str,
+    ) -> Result<Option<i64>, BannedTokenStoreError> {
+        Ok(self.revoked_before.read().unwrap().get(subject).copied())
     }
 }
 
@@ -24,23 +47,36 @@ mod tests {
     use super::*;
     #[tokio::test]
     async fn test_add_token() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
         let token = "test_token".to_owned();
 
         let result = store.add_token(token.clone()).await;
 
         assert!(result.is_ok());
-        assert!(store.tokens.contains(&token));
+        assert!(store.tokens.read().unwrap().contains(&token));
     }
 
     #[tokio::test]
     async fn test_contains_token() {
-        let mut store = HashsetBannedTokenStore::default();
+        let store = HashsetBannedTokenStore::default();
         let token = "test_token".to_owned();
-        store.tokens.insert(token.clone());
+        store.tokens.write().unwrap().insert(token.clone());
 
         let result = store.contains_token(&token).await;
 
         assert!(result.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_revoke_all_tokens() {
+        let store = HashsetBannedTokenStore::default();
+        assert_eq!(store.tokens_revoked_before("foo@bar.com").await.unwrap(), None);
+
+        store.revoke_all_tokens("foo@bar.com", 42).await.unwrap();
+
+        assert_eq!(
+            store.tokens_revoked_before("foo@bar.com").await.unwrap(),
+            Some(42)
+        );
+    }
 }
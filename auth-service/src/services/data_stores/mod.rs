@@ -4,3 +4,20 @@ pub mod hashset_banned_token_store;
 pub mod hashmap_two_fa_code_store;
 pub mod redis_banned_token_store;
 pub mod redis_two_fa_code_store;
+pub mod redis_sms_rate_limiter;
+pub mod postgres_audit_log;
+pub mod postgres_webhook_store;
+pub mod postgres_email_outbox;
+pub mod postgres_service_account_store;
+pub mod hashmap_service_account_store;
+pub mod postgres_personal_access_token_store;
+pub mod hashmap_personal_access_token_store;
+
+use sha2::{Digest, Sha256};
+
+// For secrets of 256 random bits, which cannot be guessed from a dictionary like passwords
+// can, so an unsalted SHA-256 hash is enough to look them up by without storing anything
+// usable.
+pub(crate) fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,198 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::domain::{
+    audit_log::{
+        AuditAction, AuditEvent, AuditLog, AuditLogError, AuditOutcome, AuditQuery, AuditRecord,
+        RequestMetadata, GENESIS_HASH,
+    },
+    tenant::TenantId,
+};
+
+const VERIFY_BATCH_SIZE: i64 = 1000;
+
+pub struct PostgresAuditLog {
+    pool: PgPool,
+    tenant_id: TenantId,
+}
+
+impl PostgresAuditLog {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct AuditRow {
+    pub id: i64,
+    pub tenant_id: String,
+    pub occurred_at: DateTime<Utc>,
+    pub actor: Option<String>,
+    pub action: String,
+    pub outcome: String,
+    pub detail: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl TryFrom<AuditRow> for AuditRecord {
+    type Error = AuditLogError;
+
+    fn try_from(row: AuditRow) -> Result<Self, Self::Error> {
+        Ok(AuditRecord {
+            id: row.id,
+            event: AuditEvent {
+                occurred_at: row.occurred_at,
+                actor: row.actor,
+                action: AuditAction::parse(&row.action).map_err(AuditLogError::UnexpectedError)?,
+                outcome: AuditOutcome::parse(&row.outcome)
+                    .map_err(AuditLogError::UnexpectedError)?,
+                detail: row.detail,
+                metadata: RequestMetadata {
+                    ip: row.ip,
+                    user_agent: row.user_agent,
+                    request_id: row.request_id,
+                },
+            },
+            prev_hash: row.prev_hash,
+            hash: row.hash,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for PostgresAuditLog {
+    #[tracing::instrument(name = "Recording audit event in PostgreSQL", skip_all)]
+    async fn record(&self, event: AuditEvent) -> Result<(), AuditLogError> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        // Appends are serialized so that every record chains onto the latest hash.
+        // Readers are not blocked by this lock mode.
+        sqlx::query!("LOCK TABLE audit_log IN SHARE ROW EXCLUSIVE MODE")
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        let prev_hash = sqlx::query_scalar!("SELECT hash FROM audit_log ORDER BY id DESC LIMIT 1")
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| AuditLogError::UnexpectedError(e.into()))?
+            .unwrap_or_else(|| GENESIS_HASH.to_owned());
+
+        let hash = event.chain_hash(&self.tenant_id, &prev_hash);
+
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_log
+                (tenant_id, occurred_at, actor, action, outcome, detail, ip, user_agent,
+                 request_id, prev_hash, hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+            self.tenant_id.as_ref(),
+            event.occurred_at,
+            event.actor,
+            event.action.as_ref(),
+            event.outcome.as_ref(),
+            event.detail,
+            event.metadata.ip,
+            event.metadata.user_agent,
+            event.metadata.request_id,
+            prev_hash,
+            hash
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| AuditLogError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Querying audit log in PostgreSQL", skip_all)]
+    async fn query(&self, query: AuditQuery) -> Result<Vec<AuditRecord>, AuditLogError> {
+        let rows = sqlx::query_as!(
+            AuditRow,
+            r#"
+            SELECT id, tenant_id, occurred_at, actor, action, outcome, detail, ip, user_agent,
+                   request_id, prev_hash, hash
+            FROM audit_log
+            WHERE tenant_id = $1
+              AND ($2::TEXT IS NULL OR actor = $2)
+              AND ($3::TEXT IS NULL OR action = $3)
+              AND ($4::TIMESTAMPTZ IS NULL OR occurred_at >= $4)
+              AND ($5::TIMESTAMPTZ IS NULL OR occurred_at <= $5)
+            ORDER BY id DESC
+            LIMIT $6
+            "#,
+            self.tenant_id.as_ref(),
+            query.actor,
+            query.action.as_ref().map(|a| a.as_ref()),
+            query.from,
+            query.to,
+            i64::from(query.limit)
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+        rows.into_iter().map(AuditRecord::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Verifying audit log hash chain in PostgreSQL", skip_all)]
+    async fn verify_chain(&self) -> Result<Option<i64>, AuditLogError> {
+        let mut prev_hash = GENESIS_HASH.to_owned();
+        let mut last_id = 0;
+
+        loop {
+            let rows = sqlx::query_as!(
+                AuditRow,
+                r#"
+                SELECT id, tenant_id, occurred_at, actor, action, outcome, detail, ip,
+                       user_agent, request_id, prev_hash, hash
+                FROM audit_log
+                WHERE id > $1
+                ORDER BY id
+                LIMIT $2
+                "#,
+                last_id,
+                VERIFY_BATCH_SIZE
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| AuditLogError::UnexpectedError(e.into()))?;
+
+            if rows.is_empty() {
+                return Ok(None);
+            }
+
+            for row in rows {
+                let tenant_id =
+                    TenantId::parse(&row.tenant_id).map_err(AuditLogError::UnexpectedError)?;
+                let record = AuditRecord::try_from(row)?;
+                if record.prev_hash != prev_hash
+                    || record.event.chain_hash(&tenant_id, &prev_hash) != record.hash
+                {
+                    return Ok(Some(record.id));
+                }
+                last_id = record.id;
+                prev_hash = record.hash;
+            }
+        }
+    }
+}
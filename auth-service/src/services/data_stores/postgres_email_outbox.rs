@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    email::Email,
+    email_outbox::{EmailOutbox, EmailOutboxError, FailedEmail, OutboxEmail},
+    tenant::TenantId,
+    EmailMessage,
+};
+
+pub struct PostgresEmailOutbox {
+    pool: PgPool,
+    tenant_id: TenantId,
+}
+
+impl PostgresEmailOutbox {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailOutbox for PostgresEmailOutbox {
+    #[tracing::instrument(name = "Enqueuing email in PostgreSQL", skip_all)]
+    async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO email_outbox
+                (id, tenant_id, recipient, subject, html_body, text_body, send_before)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            email.id,
+            self.tenant_id.as_ref(),
+            email.recipient.as_ref(),
+            email.message.subject,
+            email.message.html_body,
+            email.message.text_body,
+            email.send_before
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Claiming due emails in PostgreSQL", skip_all)]
+    async fn claim_due(
+        &self,
+        limit: u32,
+        lease_until: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+        let rows = sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET next_attempt_at = $2, attempts = attempts + 1
+            WHERE id IN (
+                SELECT id FROM email_outbox
+                WHERE status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY next_attempt_at
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, recipient, subject, html_body, text_body, attempts, send_before
+            "#,
+            i64::from(limit),
+            lease_until
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| OutboxEmail {
+                id: row.id,
+                recipient: Email::from(row.recipient),
+                message: EmailMessage {
+                    subject: row.subject,
+                    html_body: row.html_body,
+                    text_body: row.text_body,
+                },
+                attempts: row.attempts,
+                send_before: row.send_before,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "Marking email as sent in PostgreSQL", skip_all)]
+    async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxError> {
+        sqlx::query!("DELETE FROM email_outbox WHERE id = $1", id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Scheduling email retry in PostgreSQL", skip_all)]
+    async fn schedule_retry(
+        &self,
+        id: Uuid,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), EmailOutboxError> {
+        sqlx::query!(
+            "UPDATE email_outbox SET next_attempt_at = $2, last_error = $3 WHERE id = $1",
+            id,
+            next_attempt_at,
+            error
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking email as failed in PostgreSQL", skip_all)]
+    async fn mark_failed(&self, id: Uuid, error: &str) -> Result<(), EmailOutboxError> {
+        sqlx::query!(
+            r#"
+            UPDATE email_outbox
+            SET status = 'failed', last_error = $2, failed_at = NOW(),
+                html_body = '', text_body = ''
+            WHERE id = $1
+            "#,
+            id,
+            error
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Listing failed emails from PostgreSQL", skip_all)]
+    async fn list_failed(&self, limit: u32) -> Result<Vec<FailedEmail>, EmailOutboxError> {
+        sqlx::query_as!(
+            FailedEmail,
+            r#"
+            SELECT id, recipient, subject, attempts,
+                   last_error AS "last_error!", created_at, failed_at AS "failed_at!"
+            FROM email_outbox
+            WHERE tenant_id = $1 AND status = 'failed'
+            ORDER BY failed_at DESC
+            LIMIT $2
+            "#,
+            self.tenant_id.as_ref(),
+            i64::from(limit)
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))
+    }
+}
@@ -0,0 +1,193 @@
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        personal_access_token::{
+            PersonalAccessToken, PersonalAccessTokenId, PersonalAccessTokenSecret,
+            PersonalAccessTokenStore, PersonalAccessTokenStoreError,
+        },
+        scope::Scope,
+        tenant::TenantId,
+        user::UserId,
+    },
+    services::data_stores::hash_token,
+};
+
+pub struct PostgresPersonalAccessTokenStore {
+    pool: PgPool,
+    tenant_id: TenantId,
+}
+
+impl PostgresPersonalAccessTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+}
+
+struct PersonalAccessTokenRow {
+    id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    name: String,
+    scopes: Vec<String>,
+    prefix: String,
+    created_at: chrono::DateTime<chrono::Utc>,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TryFrom<PersonalAccessTokenRow> for PersonalAccessToken {
+    type Error = PersonalAccessTokenStoreError;
+
+    fn try_from(row: PersonalAccessTokenRow) -> Result<Self, Self::Error> {
+        let scopes = row
+            .scopes
+            .iter()
+            .map(|scope| Scope::parse(scope))
+            .collect::<Result<_, _>>()
+            .map_err(PersonalAccessTokenStoreError::UnexpectedError)?;
+        Ok(PersonalAccessToken {
+            id: PersonalAccessTokenId::from(row.id),
+            user_id: UserId::from(row.user_id),
+            name: row.name,
+            scopes,
+            prefix: row.prefix,
+            created_at: row.created_at,
+            expires_at: row.expires_at,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl PersonalAccessTokenStore for PostgresPersonalAccessTokenStore {
+    #[tracing::instrument(name = "Adding personal access token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &self,
+        token: PersonalAccessToken,
+        secret: &PersonalAccessTokenSecret,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        let scopes: Vec<String> = token
+            .scopes
+            .iter()
+            .map(|scope| scope.as_ref().to_owned())
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO personal_access_tokens
+                (id, tenant_id, user_id, name, token_hash, prefix, scopes, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            token.id.as_ref(),
+            self.tenant_id.as_ref(),
+            token.user_id.as_ref(),
+            token.name,
+            hash_token(secret.as_ref()),
+            token.prefix,
+            &scopes,
+            token.created_at,
+            token.expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Listing personal access tokens from PostgreSQL", skip_all)]
+    async fn list_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<Vec<PersonalAccessToken>, PersonalAccessTokenStoreError> {
+        let rows = sqlx::query_as!(
+            PersonalAccessTokenRow,
+            r#"
+            SELECT id, user_id, name, scopes, prefix, created_at, expires_at
+            FROM personal_access_tokens
+            WHERE tenant_id = $1 AND user_id = $2
+            ORDER BY created_at
+            "#,
+            self.tenant_id.as_ref(),
+            user_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(PersonalAccessToken::try_from)
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Revoking personal access token in PostgreSQL", skip_all)]
+    async fn revoke_token(
+        &self,
+        user_id: &UserId,
+        id: &PersonalAccessTokenId,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM personal_access_tokens
+            WHERE tenant_id = $1 AND user_id = $2 AND id = $3
+            "#,
+            self.tenant_id.as_ref(),
+            user_id.as_ref(),
+            id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(PersonalAccessTokenStoreError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Revoking all personal access tokens in PostgreSQL", skip_all)]
+    async fn revoke_all_tokens(
+        &self,
+        user_id: &UserId,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        sqlx::query!(
+            "DELETE FROM personal_access_tokens WHERE tenant_id = $1 AND user_id = $2",
+            self.tenant_id.as_ref(),
+            user_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Finding personal access token in PostgreSQL", skip_all)]
+    async fn find_token(
+        &self,
+        secret: &PersonalAccessTokenSecret,
+    ) -> Result<PersonalAccessToken, PersonalAccessTokenStoreError> {
+        let row = sqlx::query_as!(
+            PersonalAccessTokenRow,
+            r#"
+            SELECT id, user_id, name, scopes, prefix, created_at, expires_at
+            FROM personal_access_tokens
+            WHERE tenant_id = $1 AND token_hash = $2
+            "#,
+            self.tenant_id.as_ref(),
+            hash_token(secret.as_ref())
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?
+        .ok_or(PersonalAccessTokenStoreError::NotFound)?;
+
+        row.try_into()
+    }
+}
@@ -0,0 +1,230 @@
+use color_eyre::eyre::eyre;
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        scope::Scope,
+        service_account::{
+            ClientId, ClientSecret, ServiceAccount, ServiceAccountStore, ServiceAccountStoreError,
+        },
+        tenant::TenantId,
+    },
+    services::data_stores::postgres_user_store::{
+        compute_password_hash, verify_password_hash, PasswordHashingConfig,
+    },
+};
+
+// Secrets are hashed like passwords, with the same Argon2id parameters.
+pub struct PostgresServiceAccountStore {
+    pool: PgPool,
+    hashing: PasswordHashingConfig,
+    tenant_id: TenantId,
+}
+
+impl PostgresServiceAccountStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            hashing: PasswordHashingConfig::default(),
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+
+    pub fn with_hashing_config(mut self, hashing: PasswordHashingConfig) -> Self {
+        self.hashing = hashing;
+        self
+    }
+
+    async fn hash_secret(&self, secret: &ClientSecret) -> Result<String, ServiceAccountStoreError> {
+        compute_password_hash(secret.as_ref().to_owned(), self.hashing)
+            .await
+            .map_err(|e| ServiceAccountStoreError::UnexpectedError(eyre!(e)))
+    }
+}
+
+struct ServiceAccountRow {
+    client_id: uuid::Uuid,
+    name: String,
+    scopes: Vec<String>,
+    created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<ServiceAccountRow> for ServiceAccount {
+    type Error = ServiceAccountStoreError;
+
+    fn try_from(row: ServiceAccountRow) -> Result<Self, Self::Error> {
+        let scopes = row
+            .scopes
+            .iter()
+            .map(|scope| Scope::parse(scope))
+            .collect::<Result<_, _>>()
+            .map_err(ServiceAccountStoreError::UnexpectedError)?;
+        Ok(ServiceAccount {
+            client_id: ClientId::from(row.client_id),
+            name: row.name,
+            scopes,
+            created_at: row.created_at,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ServiceAccountStore for PostgresServiceAccountStore {
+    #[tracing::instrument(name = "Adding service account to PostgreSQL", skip_all)]
+    async fn add_service_account(
+        &self,
+        account: ServiceAccount,
+        secret: &ClientSecret,
+    ) -> Result<(), ServiceAccountStoreError> {
+        let secret_hash = self.hash_secret(secret).await?;
+        let scopes: Vec<String> = account
+            .scopes
+            .iter()
+            .map(|scope| scope.as_ref().to_owned())
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO service_accounts (client_id, tenant_id, name, secret_hash, scopes, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            account.client_id.as_ref(),
+            self.tenant_id.as_ref(),
+            account.name,
+            secret_hash,
+            &scopes,
+            account.created_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving service account from PostgreSQL", skip_all)]
+    async fn get_service_account(
+        &self,
+        client_id: &ClientId,
+    ) -> Result<ServiceAccount, ServiceAccountStoreError> {
+        let row = sqlx::query_as!(
+            ServiceAccountRow,
+            r#"
+            SELECT client_id, name, scopes, created_at
+            FROM service_accounts
+            WHERE tenant_id = $1 AND client_id = $2
+            "#,
+            self.tenant_id.as_ref(),
+            client_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?
+        .ok_or(ServiceAccountStoreError::NotFound)?;
+
+        row.try_into()
+    }
+
+    #[tracing::instrument(name = "Listing service accounts from PostgreSQL", skip_all)]
+    async fn list_service_accounts(&self) -> Result<Vec<ServiceAccount>, ServiceAccountStoreError> {
+        let rows = sqlx::query_as!(
+            ServiceAccountRow,
+            r#"
+            SELECT client_id, name, scopes, created_at
+            FROM service_accounts
+            WHERE tenant_id = $1
+            ORDER BY created_at
+            "#,
+            self.tenant_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter().map(ServiceAccount::try_from).collect()
+    }
+
+    #[tracing::instrument(name = "Deleting service account from PostgreSQL", skip_all)]
+    async fn delete_service_account(
+        &self,
+        client_id: &ClientId,
+    ) -> Result<(), ServiceAccountStoreError> {
+        let result = sqlx::query!(
+            "DELETE FROM service_accounts WHERE tenant_id = $1 AND client_id = $2",
+            self.tenant_id.as_ref(),
+            client_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(ServiceAccountStoreError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Replacing service account secret in PostgreSQL", skip_all)]
+    async fn set_secret(
+        &self,
+        client_id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<(), ServiceAccountStoreError> {
+        let secret_hash = self.hash_secret(secret).await?;
+        let result = sqlx::query!(
+            r#"
+            UPDATE service_accounts SET secret_hash = $3
+            WHERE tenant_id = $1 AND client_id = $2
+            "#,
+            self.tenant_id.as_ref(),
+            client_id.as_ref(),
+            secret_hash
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(ServiceAccountStoreError::NotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Authenticating service account in PostgreSQL", skip_all)]
+    async fn authenticate(
+        &self,
+        client_id: &ClientId,
+        secret: &ClientSecret,
+    ) -> Result<ServiceAccount, ServiceAccountStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT client_id, name, scopes, created_at, secret_hash
+            FROM service_accounts
+            WHERE tenant_id = $1 AND client_id = $2
+            "#,
+            self.tenant_id.as_ref(),
+            client_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| ServiceAccountStoreError::UnexpectedError(e.into()))?
+        .ok_or(ServiceAccountStoreError::NotFound)?;
+
+        verify_password_hash(row.secret_hash, secret.as_ref().to_owned(), self.hashing)
+            .await
+            .map_err(|_| ServiceAccountStoreError::InvalidCredentials)?;
+
+        ServiceAccountRow {
+            client_id: row.client_id,
+            name: row.name,
+            scopes: row.scopes,
+            created_at: row.created_at,
+        }
+        .try_into()
+    }
+}
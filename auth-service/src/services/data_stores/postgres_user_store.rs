@@ -1,92 +1,294 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error};
 
 use argon2::{
     password_hash::{SaltString}, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
     PasswordVerifier, Version,
 };
-
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Result as EyreResult};
 use sqlx::PgPool;
+use uuid::Uuid;
 
-use crate::domain::{
-    data_stores::{UserStore, UserStoreError},
-    email::Email,
-    password::Password,
-    user::User,
+use crate::{
+    domain::{
+        data_stores::{
+            ChangedEmail, EmailChange, EmailChangeToken, PasswordReset,
+            PasswordResetToken, PhoneVerification, TwoFACode, UserPage, UserQuery, UserStore,
+            UserStoreError,
+        },
+        email::{Email, EmailFolding},
+        password::Password,
+        phone_number::PhoneNumber,
+        profile::{Metadata, UserProfile},
+        tenant::TenantId,
+        user::{TwoFAChannel, User, UserId, UserStatus},
+    },
+    services::data_stores::hash_token,
+    utils::constants::{
+        DEFAULT_PASSWORD_HASH_ITERATIONS, DEFAULT_PASSWORD_HASH_MEMORY_KIB,
+        DEFAULT_PASSWORD_HASH_PARALLELISM, PASSWORD_HASH_ITERATIONS, PASSWORD_HASH_MEMORY_KIB,
+        PASSWORD_HASH_PARALLELISM,
+    },
 };
 
+// Argon2id cost parameters for new password hashes. Stored hashes made with another
+// algorithm or other parameters are replaced the next time their owner logs in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PasswordHashingConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordHashingConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: DEFAULT_PASSWORD_HASH_MEMORY_KIB,
+            iterations: DEFAULT_PASSWORD_HASH_ITERATIONS,
+            parallelism: DEFAULT_PASSWORD_HASH_PARALLELISM,
+        }
+    }
+}
+
+impl PasswordHashingConfig {
+    pub fn from_env() -> EyreResult<Self> {
+        let config = Self {
+            memory_kib: *PASSWORD_HASH_MEMORY_KIB,
+            iterations: *PASSWORD_HASH_ITERATIONS,
+            parallelism: *PASSWORD_HASH_PARALLELISM,
+        };
+        config
+            .hasher()
+            .map_err(|e| eyre!("Invalid password hashing parameters: {}", e))?;
+        Ok(config)
+    }
+
+    fn hasher(&self) -> Result<Argon2<'static>, argon2::Error> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    // Whether `password_hash` was made with anything other than Argon2id and these parameters.
+    fn needs_rehash(&self, password_hash: &str) -> bool {
+        let Ok(hash) = PasswordHash::new(password_hash) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&hash) else {
+            return true;
+        };
+
+        hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+            || params.m_cost() != self.memory_kib
+            || params.t_cost() != self.iterations
+            || params.p_cost() != self.parallelism
+    }
+}
+
+// Only sees the users of one tenant; each tenant gets its own store over the shared pool.
 pub struct PostgresUserStore {
     pool: PgPool,
+    hashing: PasswordHashingConfig,
+    tenant_id: TenantId,
 }
 
 impl PostgresUserStore {
     pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            hashing: PasswordHashingConfig::default(),
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+
+    pub fn with_hashing_config(mut self, hashing: PasswordHashingConfig) -> Self {
+        self.hashing = hashing;
+        self
+    }
+
+    // Replaces `old_hash` with one made with the current parameters. A failure is only logged,
+    // since the login that verified the password has already succeeded.
+    #[tracing::instrument(name = "Rehashing password in PostgreSQL", skip_all)]
+    async fn rehash_password(&self, email: &Email, old_hash: &str, password: &Password) {
+        let new_hash = compute_password_hash(password.as_ref().to_owned(), self.hashing).await;
+        let new_hash = match new_hash {
+            Ok(new_hash) => new_hash,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to rehash password");
+                return;
+            }
+        };
+
+        // Matching on the old hash keeps a concurrent password change from being overwritten.
+        let result = sqlx::query!(
+            r#"
+            UPDATE users SET password_hash = $1
+            WHERE tenant_id = $4 AND email = $2 AND password_hash = $3
+            "#,
+            new_hash,
+            email.as_ref(),
+            old_hash,
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "Failed to store rehashed password");
+        }
     }
 }
 
 #[derive(sqlx::FromRow)]
 pub struct UserRow {
+    pub id: Uuid,
     pub email: String,
     pub password_hash: String,
     pub requires_2fa: bool,
+    pub status: String,
+    pub password_reset_required: bool,
+    pub password_changed_at: DateTime<Utc>,
+    pub phone_number: Option<String>,
+    pub phone_verified: bool,
+    pub two_fa_channel: String,
+}
+
+impl TryFrom<UserRow> for User {
+    type Error = UserStoreError;
+
+    fn try_from(row: UserRow) -> Result<Self, Self::Error> {
+        let mut user = User::new(row.email, row.password_hash, row.requires_2fa);
+        user.id = UserId::from(row.id);
+        user.status = UserStatus::parse(&row.status).map_err(UserStoreError::UnexpectedError)?;
+        user.password_reset_required = row.password_reset_required;
+        user.password_changed_at = row.password_changed_at;
+        user.phone_number = row.phone_number.map(PhoneNumber::from);
+        user.phone_verified = row.phone_verified;
+        user.two_fa_channel =
+            TwoFAChannel::parse(&row.two_fa_channel).map_err(UserStoreError::UnexpectedError)?;
+        Ok(user)
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
-        let row = sqlx::query!("SELECT * FROM users WHERE email = $1", email.as_ref())
-            .fetch_one(&self.pool)
-            .await;
+        let row = sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, requires_2fa, status, password_reset_required,
+                   password_changed_at, phone_number, phone_verified, two_fa_channel
+            FROM users
+            WHERE tenant_id = $1 AND email = $2
+            "#,
+            self.tenant_id.as_ref(),
+            email.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await;
 
         match row {
-            Ok(user_row) => {
-                let user = User::new(
-                    user_row.email,
-                    user_row.password_hash,
-                    user_row.requires_2fa,
-                );
-                Ok(user)
-            }
+            Ok(user_row) => user_row.try_into(),
             Err(_) => Err(UserStoreError::UserNotFound),
         }
     }
 
+    #[tracing::instrument(name = "Retrieving user by id from PostgreSQL", skip_all)]
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        let row = sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, requires_2fa, status, password_reset_required,
+                   password_changed_at, phone_number, phone_verified, two_fa_channel
+            FROM users
+            WHERE tenant_id = $1 AND id = $2
+            "#,
+            self.tenant_id.as_ref(),
+            id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        row.ok_or(UserStoreError::UserNotFound)?.try_into()
+    }
+
     #[tracing::instrument(name = "Validating user credentials in PostgreSQL", skip_all)]
     async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
-        let row = sqlx::query!("SELECT * FROM users WHERE email = $1", email.as_ref())
-            .fetch_one(&self.pool)
-            .await;
+        let row = sqlx::query!(
+            "SELECT * FROM users WHERE tenant_id = $1 AND email = $2",
+            self.tenant_id.as_ref(),
+            email.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await;
 
         match row {
             Ok(user_row) => {
-                let verify_password_result =
-                    verify_password_hash(user_row.password_hash, password.as_ref().to_owned())
+                let verify_password_result = verify_password_hash(
+                    user_row.password_hash.clone(),
+                    password.as_ref().to_owned(),
+                    self.hashing,
+                )
+                .await;
+                let mut needs_rehash = self.hashing.needs_rehash(&user_row.password_hash);
+                if verify_password_result.is_err() {
+                    // Hashes stored before passwords were normalized only match the password
+                    // as typed; they are replaced with a hash of the normalized form.
+                    let Some(unnormalized) = password.unnormalized() else {
+                        return Err(UserStoreError::InvalidCredentials);
+                    };
+                    verify_password_hash(
+                        user_row.password_hash.clone(),
+                        unnormalized.to_owned(),
+                        self.hashing,
+                    )
+                    .await
+                    .map_err(|_| UserStoreError::InvalidCredentials)?;
+                    needs_rehash = true;
+                }
+
+                if needs_rehash {
+                    self.rehash_password(&email, &user_row.password_hash, &password)
                         .await;
-                match verify_password_result {
-                    Ok(_) => Ok(()),
-                    Err(_) => Err(UserStoreError::InvalidCredentials),
                 }
+                Ok(())
             }
             Err(_) => Err(UserStoreError::UserNotFound),
         }
     }
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)] 
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        let password_hash = compute_password_hash(user.password.as_ref().to_owned()).await;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let password_hash =
+            compute_password_hash(user.password.as_ref().to_owned(), self.hashing).await;
         let password_hash = password_hash.unwrap();
 
-        let existing = sqlx::query!("SELECT * FROM users WHERE email = $1", user.email.as_ref())
-            .fetch_optional(&self.pool)
-            .await
-            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        let existing = sqlx::query!(
+            "SELECT * FROM users WHERE tenant_id = $1 AND email = $2",
+            self.tenant_id.as_ref(),
+            user.email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
         if existing.is_some() {
             return Err(UserStoreError::UserAlreadyExists);
         }
 
         let row = sqlx::query!(
-            "INSERT INTO users (email, password_hash, requires_2fa) VALUES ($1, $2, $3)",
+            r#"
+            INSERT INTO users (id, tenant_id, email, password_hash, requires_2fa)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            user.id.as_ref(),
+            self.tenant_id.as_ref(),
             user.email.as_ref(),
             password_hash,
             user.requires_2fa
@@ -96,15 +298,689 @@ impl UserStore for PostgresUserStore {
 
         match row {
             Ok(_) => Ok(()),
+            // Lost a race with a signup for the same address.
+            Err(e) if is_unique_violation(&e) => Err(UserStoreError::UserAlreadyExists),
             Err(e) => Err(UserStoreError::UnexpectedError(e.into())),
         }
     }
+
+    #[tracing::instrument(name = "Listing users from PostgreSQL", skip_all)]
+    async fn list_users(&self, query: UserQuery) -> Result<UserPage, UserStoreError> {
+        let limit = i64::from(query.per_page);
+        let offset = i64::try_from(query.offset())
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let rows = sqlx::query_as!(
+            UserRow,
+            r#"
+            SELECT id, email, password_hash, requires_2fa, status, password_reset_required,
+                   password_changed_at, phone_number, phone_verified, two_fa_channel
+            FROM users
+            WHERE tenant_id = $6
+              AND ($1::TEXT IS NULL OR strpos(lower(email), lower($1)) > 0)
+              AND ($2::BOOLEAN IS NULL OR requires_2fa = $2)
+              AND ($3::TEXT IS NULL OR status = $3)
+            ORDER BY email
+            LIMIT $4 OFFSET $5
+            "#,
+            query.email_contains,
+            query.requires_2fa,
+            query.status.as_ref().map(|s| s.as_ref()),
+            limit,
+            offset,
+            self.tenant_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) AS "total!"
+            FROM users
+            WHERE tenant_id = $4
+              AND ($1::TEXT IS NULL OR strpos(lower(email), lower($1)) > 0)
+              AND ($2::BOOLEAN IS NULL OR requires_2fa = $2)
+              AND ($3::TEXT IS NULL OR status = $3)
+            "#,
+            query.email_contains,
+            query.requires_2fa,
+            query.status.as_ref().map(|s| s.as_ref()),
+            self.tenant_id.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        Ok(UserPage {
+            users: rows
+                .into_iter()
+                .map(User::try_from)
+                .collect::<Result<_, _>>()?,
+            total: total as u64,
+        })
+    }
+
+    #[tracing::instrument(name = "Updating user status in PostgreSQL", skip_all)]
+    async fn set_status(
+        &self,
+        email: &Email,
+        status: UserStatus,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET status = $2 WHERE tenant_id = $3 AND email = $1",
+            email.as_ref(),
+            status.as_ref(),
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Updating user password reset flag in PostgreSQL", skip_all)]
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET password_reset_required = $2 WHERE tenant_id = $3 AND email = $1",
+            email.as_ref(),
+            required,
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Changing password in PostgreSQL", skip_all)]
+    async fn change_password(
+        &self,
+        email: &Email,
+        password: Password,
+        reuse_limit: usize,
+    ) -> Result<(), UserStoreError> {
+        let current = sqlx::query!(
+            "SELECT id, password_hash FROM users WHERE tenant_id = $1 AND email = $2",
+            self.tenant_id.as_ref(),
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+        let (user_id, current_hash) = (current.id, current.password_hash);
+
+        let kept = i64::try_from(reuse_limit.saturating_sub(1))
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        let previous_hashes = sqlx::query_scalar!(
+            r#"
+            SELECT password_hash
+            FROM password_history
+            WHERE user_id = $1
+            ORDER BY replaced_at DESC, id DESC
+            LIMIT $2
+            "#,
+            user_id,
+            kept
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let recent_hashes = std::iter::once(current_hash.clone())
+            .chain(previous_hashes)
+            .take(reuse_limit);
+        for hash in recent_hashes {
+            let reused =
+                verify_password_hash(hash, password.as_ref().to_owned(), self.hashing).await;
+            if reused.is_ok() {
+                return Err(UserStoreError::PasswordReused);
+            }
+        }
+
+        let new_hash = compute_password_hash(password.as_ref().to_owned(), self.hashing)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(eyre!(e)))?;
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Matching on the old hash keeps a concurrent change from slipping past the checks above.
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2, password_changed_at = NOW(), password_reset_required = FALSE
+            WHERE id = $1 AND password_hash = $3
+            "#,
+            user_id,
+            new_hash,
+            current_hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UnexpectedError(eyre!(
+                "Password was changed concurrently"
+            )));
+        }
+
+        sqlx::query!(
+            "INSERT INTO password_history (user_id, password_hash) VALUES ($1, $2)",
+            user_id,
+            current_hash
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!("DELETE FROM password_resets WHERE user_id = $1", user_id)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM password_history
+            WHERE user_id = $1 AND id NOT IN (
+                SELECT id
+                FROM password_history
+                WHERE user_id = $1
+                ORDER BY replaced_at DESC, id DESC
+                LIMIT $2
+            )
+            "#,
+            user_id,
+            kept
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA flag in PostgreSQL", skip_all)]
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET requires_2fa = $2 WHERE tenant_id = $3 AND email = $1",
+            email.as_ref(),
+            requires_2fa,
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Starting phone verification in PostgreSQL", skip_all)]
+    async fn start_phone_verification(
+        &self,
+        email: &Email,
+        verification: PhoneVerification,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET phone_number = $2, phone_verified = FALSE, two_fa_channel = 'email',
+                phone_verification_code = $3, phone_verification_expires_at = $4
+            WHERE tenant_id = $5 AND email = $1
+            "#,
+            email.as_ref(),
+            verification.phone_number.as_ref(),
+            verification.code.as_ref(),
+            verification.expires_at,
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Confirming phone number in PostgreSQL", skip_all)]
+    async fn confirm_phone_number(
+        &self,
+        email: &Email,
+        code: &TwoFACode,
+    ) -> Result<PhoneNumber, UserStoreError> {
+        // Every attempt consumes the pending code, so it cannot be guessed by trying them all.
+        let row = sqlx::query!(
+            r#"
+            UPDATE users
+            SET phone_verified = COALESCE(phone_verification_code = $2
+                                           AND phone_verification_expires_at > NOW(), FALSE),
+                phone_verification_code = NULL,
+                phone_verification_expires_at = NULL
+            WHERE tenant_id = $3 AND email = $1 AND phone_verification_code IS NOT NULL
+            RETURNING phone_number AS "phone_number!", phone_verified
+            "#,
+            email.as_ref(),
+            code.as_ref(),
+            self.tenant_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match row {
+            Some(row) if row.phone_verified => Ok(PhoneNumber::from(row.phone_number)),
+            Some(_) => Err(UserStoreError::InvalidCredentials),
+            None => {
+                self.get_user(email.clone()).await?;
+                Err(UserStoreError::InvalidCredentials)
+            }
+        }
+    }
+
+    #[tracing::instrument(name = "Removing phone number in PostgreSQL", skip_all)]
+    async fn remove_phone_number(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET phone_number = NULL, phone_verified = FALSE, two_fa_channel = 'email',
+                phone_verification_code = NULL, phone_verification_expires_at = NULL
+            WHERE tenant_id = $1 AND email = $2
+            "#,
+            self.tenant_id.as_ref(),
+            email.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Updating user 2FA channel in PostgreSQL", skip_all)]
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            "UPDATE users SET two_fa_channel = $2 WHERE tenant_id = $3 AND email = $1",
+            email.as_ref(),
+            channel.as_ref(),
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Starting password reset in PostgreSQL", skip_all)]
+    async fn start_password_reset(
+        &self,
+        email: &Email,
+        reset: PasswordReset,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO password_resets (user_id, token_hash, expires_at)
+            SELECT id, $2, $3 FROM users WHERE tenant_id = $4 AND email = $1
+            ON CONFLICT (user_id) DO UPDATE
+            SET token_hash = EXCLUDED.token_hash,
+                expires_at = EXCLUDED.expires_at
+            "#,
+            email.as_ref(),
+            hash_token(reset.token.as_ref()),
+            reset.expires_at,
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Finding password reset in PostgreSQL", skip_all)]
+    async fn find_password_reset(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, UserStoreError> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT users.email
+            FROM password_resets
+            JOIN users ON users.id = password_resets.user_id
+            WHERE password_resets.token_hash = $1
+              AND users.tenant_id = $2
+              AND password_resets.expires_at > NOW()
+            "#,
+            hash_token(token.as_ref()),
+            self.tenant_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .map(Email::from)
+        .ok_or(UserStoreError::InvalidCredentials)
+    }
+
+    #[tracing::instrument(name = "Starting email change in PostgreSQL", skip_all)]
+    async fn start_email_change(
+        &self,
+        email: &Email,
+        change: EmailChange,
+    ) -> Result<(), UserStoreError> {
+        let taken = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM users WHERE tenant_id = $1 AND email = $2) AS "taken!"
+            "#,
+            self.tenant_id.as_ref(),
+            change.new_email.as_ref()
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+        if taken {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO email_changes (user_id, new_email, token_hash, expires_at)
+            SELECT id, $2, $3, $4 FROM users WHERE tenant_id = $5 AND email = $1
+            ON CONFLICT (user_id) DO UPDATE
+            SET new_email = EXCLUDED.new_email,
+                token_hash = EXCLUDED.token_hash,
+                expires_at = EXCLUDED.expires_at
+            "#,
+            email.as_ref(),
+            change.new_email.as_ref(),
+            hash_token(change.token.as_ref()),
+            change.expires_at,
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Confirming email change in PostgreSQL", skip_all)]
+    async fn confirm_email_change(
+        &self,
+        token: &EmailChangeToken,
+    ) -> Result<ChangedEmail, UserStoreError> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        // Every attempt consumes the token, so an expired link cannot be retried either.
+        let change = sqlx::query!(
+            r#"
+            DELETE FROM email_changes
+            WHERE token_hash = $1
+              AND user_id IN (SELECT id FROM users WHERE tenant_id = $2)
+            RETURNING user_id, new_email, expires_at > NOW() AS "valid!"
+            "#,
+            hash_token(token.as_ref()),
+            self.tenant_id.as_ref()
+        )
+        .fetch_optional(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::InvalidCredentials)?;
+
+        let taken = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM users WHERE tenant_id = $1 AND email = $2) AS "taken!"
+            "#,
+            self.tenant_id.as_ref(),
+            change.new_email
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        let old_email = sqlx::query_scalar!(
+            "SELECT email FROM users WHERE id = $1 FOR UPDATE",
+            change.user_id
+        )
+        .fetch_one(&mut *transaction)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if change.valid && !taken {
+            sqlx::query!(
+                "UPDATE users SET email = $2 WHERE id = $1",
+                change.user_id,
+                change.new_email
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|e| match e {
+                // Lost a race with a signup for the same address.
+                e if is_unique_violation(&e) => UserStoreError::UserAlreadyExists,
+                e => UserStoreError::UnexpectedError(e.into()),
+            })?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        match (change.valid, taken) {
+            (false, _) => Err(UserStoreError::InvalidCredentials),
+            (true, true) => Err(UserStoreError::UserAlreadyExists),
+            (true, false) => Ok(ChangedEmail {
+                user_id: UserId::from(change.user_id),
+                old_email: Email::from(old_email),
+                new_email: Email::from(change.new_email),
+            }),
+        }
+    }
+
+    #[tracing::instrument(name = "Retrieving profile from PostgreSQL", skip_all)]
+    async fn get_profile(&self, email: &Email) -> Result<UserProfile, UserStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT p.display_name, p.locale, p.timezone, p.avatar_url,
+                   COALESCE(p.user_metadata, '{}') AS "user_metadata!",
+                   COALESCE(p.app_metadata, '{}') AS "app_metadata!"
+            FROM users u
+            LEFT JOIN user_profiles p ON p.user_id = u.id
+            WHERE u.tenant_id = $1 AND u.email = $2
+            "#,
+            self.tenant_id.as_ref(),
+            email.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        Ok(UserProfile {
+            display_name: row.display_name,
+            locale: row.locale,
+            timezone: row.timezone,
+            avatar_url: row.avatar_url,
+            user_metadata: parse_metadata(row.user_metadata)?,
+            app_metadata: parse_metadata(row.app_metadata)?,
+        })
+    }
+
+    #[tracing::instrument(name = "Storing profile in PostgreSQL", skip_all)]
+    async fn set_profile(
+        &self,
+        email: &Email,
+        profile: &UserProfile,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_profiles
+                (user_id, display_name, locale, timezone, avatar_url, user_metadata)
+            SELECT id, $2, $3, $4, $5, $6 FROM users WHERE tenant_id = $7 AND email = $1
+            ON CONFLICT (user_id) DO UPDATE
+            SET display_name = EXCLUDED.display_name,
+                locale = EXCLUDED.locale,
+                timezone = EXCLUDED.timezone,
+                avatar_url = EXCLUDED.avatar_url,
+                user_metadata = EXCLUDED.user_metadata
+            "#,
+            email.as_ref(),
+            profile.display_name,
+            profile.locale,
+            profile.timezone,
+            profile.avatar_url,
+            serde_json::Value::Object(profile.user_metadata.clone()),
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+
+    #[tracing::instrument(name = "Storing app metadata in PostgreSQL", skip_all)]
+    async fn set_app_metadata(
+        &self,
+        email: &Email,
+        app_metadata: &Metadata,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_profiles (user_id, app_metadata)
+            SELECT id, $2 FROM users WHERE tenant_id = $3 AND email = $1
+            ON CONFLICT (user_id) DO UPDATE SET app_metadata = EXCLUDED.app_metadata
+            "#,
+            email.as_ref(),
+            serde_json::Value::Object(app_metadata.clone()),
+            self.tenant_id.as_ref()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        ensure_user_updated(result.rows_affected())
+    }
+}
+
+// Rewrites the stored emails that `Email::parse` would now spell differently, such as ones
+// stored before internationalized domains were encoded or before folding was turned on for
+// their domain. Accounts are never merged: if two of them become the same address, nothing is
+// rewritten and they must be resolved by hand first. Returns how many emails were rewritten.
+pub async fn canonicalize_stored_emails(
+    pool: &PgPool,
+    folding: &EmailFolding,
+) -> Result<u64, UserStoreError> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+    // Keeps signups from adding an address the duplicate check below has not seen.
+    sqlx::query!("LOCK TABLE users IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+    let rows = sqlx::query!("SELECT id, tenant_id, email FROM users")
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+    let mut spellings: HashMap<(&str, String), Vec<&str>> = HashMap::new();
+    let mut rewrites = Vec::new();
+    for row in &rows {
+        // Addresses that no longer parse could not be logged in with before either.
+        let canonical = Email::parse_with_folding(&row.email, folding)
+            .map(|email| email.as_ref().to_owned())
+            .unwrap_or_else(|_| row.email.clone());
+        if canonical != row.email {
+            rewrites.push((row.id, canonical.clone()));
+        }
+        spellings
+            .entry((row.tenant_id.as_str(), canonical))
+            .or_default()
+            .push(row.email.as_str());
+    }
+
+    let mut duplicates: Vec<String> = spellings
+        .into_iter()
+        .filter(|(_, emails)| emails.len() > 1)
+        .map(|((tenant_id, canonical), emails)| {
+            format!("{} in {} ({})", canonical, tenant_id, emails.join(", "))
+        })
+        .collect();
+    if !duplicates.is_empty() {
+        duplicates.sort();
+        return Err(UserStoreError::UnexpectedError(eyre!(
+            "Users with emails that only differ before canonicalization: {}",
+            duplicates.join("; ")
+        )));
+    }
+
+    for (id, email) in &rewrites {
+        sqlx::query!("UPDATE users SET email = $1 WHERE id = $2", email, id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+    Ok(rewrites.len() as u64)
+}
+
+fn parse_metadata(value: serde_json::Value) -> Result<Metadata, UserStoreError> {
+    match value {
+        serde_json::Value::Object(metadata) => Ok(metadata),
+        _ => Err(UserStoreError::UnexpectedError(eyre!(
+            "Stored metadata is not a JSON object"
+        ))),
+    }
+}
+
+fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error()
+        .is_some_and(|e| e.is_unique_violation())
+}
+
+fn ensure_user_updated(rows_affected: u64) -> Result<(), UserStoreError> {
+    match rows_affected {
+        0 => Err(UserStoreError::UserNotFound),
+        _ => Ok(()),
+    }
 }
 
 #[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
+pub(crate) async fn verify_password_hash(
     expected_password_hash: String,
     password_candidate: String,
+    hashing: PasswordHashingConfig,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     let current_span: tracing::Span = tracing::Span::current();
     let result = tokio::task::spawn_blocking(move || {
@@ -112,7 +988,9 @@ async fn verify_password_hash(
             let expected_password_hash: PasswordHash<'_> =
                 PasswordHash::new(&expected_password_hash)?;
 
-            Argon2::default()
+            // The hash carries its own parameters, so this also verifies outdated hashes.
+            hashing
+                .hasher()?
                 .verify_password(password_candidate.as_bytes(), &expected_password_hash)
                 .map_err(|e| e.into())
         })
@@ -123,19 +1001,19 @@ async fn verify_password_hash(
 }
 
 #[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: String) -> Result<String, Box<dyn Error>> {
+pub(crate) async fn compute_password_hash(
+    password: String,
+    hashing: PasswordHashingConfig,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
     let current_span: tracing::Span = tracing::Span::current();
     let password_hash: Result<String, Box<dyn Error + Send + Sync>> =
         tokio::task::spawn_blocking(move || {
             current_span.in_scope(|| {
                 let salt: SaltString = SaltString::generate(&mut rand::thread_rng());
-                let  password_hash = Argon2::new(
-                    Algorithm::Argon2id,
-                    Version::V0x13,
-                    Params::new(15000, 2, 1, None)?,
-                )
-                .hash_password(password.as_bytes(), &salt)?
-                .to_string();
+                let  password_hash = hashing
+                    .hasher()?
+                    .hash_password(password.as_bytes(), &salt)?
+                    .to_string();
 
                 Ok(password_hash)
             })
@@ -150,35 +1028,93 @@ async fn compute_password_hash(password: String) -> Result<String, Box<dyn Error
 
 #[cfg(test)]
 mod tests {
-    use super::compute_password_hash;
+    use super::*;
+
+    fn config(memory_kib: u32, iterations: u32, parallelism: u32) -> PasswordHashingConfig {
+        PasswordHashingConfig {
+            memory_kib,
+            iterations,
+            parallelism,
+        }
+    }
 
     #[tokio::test]
     async fn test_compute_password_hash_returns_hash() {
         let password = "test_password".to_string();
-        let hash_result = compute_password_hash(password).await;
+        let hash_result = compute_password_hash(password, PasswordHashingConfig::default()).await;
         assert!(hash_result.is_ok());
         let hash = hash_result.unwrap();
-        // Argon2 hashes start with "$argon2"
-        assert!(hash.starts_with("$argon2"));
+        // The PHC string records the algorithm and parameters the hash was made with.
+        assert!(hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
     }
 
     #[tokio::test]
     async fn test_verify_password_hash_succeeds() {
         let password = "test_password".to_string();
-        let hash = super::compute_password_hash(password.clone())
+        let hash = compute_password_hash(password.clone(), PasswordHashingConfig::default())
             .await
             .unwrap();
-        let verify_result = super::verify_password_hash(hash, password).await;
+        let verify_result =
+            verify_password_hash(hash, password, PasswordHashingConfig::default()).await;
         assert!(verify_result.is_ok());
     }
 
     #[tokio::test]
     async fn test_verify_password_hash_fails() {
         let password = "test_password".to_string();
-        let hash = super::compute_password_hash(password.clone())
+        let hash = compute_password_hash(password.clone(), PasswordHashingConfig::default())
             .await
             .unwrap();
-        let verify_result = super::verify_password_hash(hash, "not_the_password".to_owned()).await;
+        let verify_result = verify_password_hash(
+            hash,
+            "not_the_password".to_owned(),
+            PasswordHashingConfig::default(),
+        )
+        .await;
         assert!(verify_result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_verify_password_hash_accepts_outdated_parameters() {
+        let password = "test_password".to_string();
+        let hash = compute_password_hash(password.clone(), config(8192, 1, 1))
+            .await
+            .unwrap();
+        let verify_result =
+            verify_password_hash(hash, password, PasswordHashingConfig::default()).await;
+        assert!(verify_result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_needs_rehash_detects_changed_parameters() {
+        let current = PasswordHashingConfig::default();
+        let hash = compute_password_hash("test_password".to_owned(), current)
+            .await
+            .unwrap();
+        assert!(!current.needs_rehash(&hash));
+
+        assert!(config(19456, 2, 1).needs_rehash(&hash));
+        assert!(config(15000, 3, 1).needs_rehash(&hash));
+        assert!(config(15000, 2, 2).needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_needs_rehash_detects_other_algorithms() {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let current = PasswordHashingConfig::default();
+        let params = Params::new(
+            current.memory_kib,
+            current.iterations,
+            current.parallelism,
+            None,
+        )
+        .unwrap();
+        let argon2i_hash = Argon2::new(Algorithm::Argon2i, Version::V0x13, params)
+            .hash_password(b"test_password", &salt)
+            .unwrap()
+            .to_string();
+
+        assert!(current.needs_rehash(&argon2i_hash));
+        assert!(current.needs_rehash("not a PHC string"));
+    }
 }
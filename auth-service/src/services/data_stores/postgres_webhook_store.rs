@@ -0,0 +1,266 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    tenant::TenantId,
+    webhooks::{
+        DeadLetter, WebhookDelivery, WebhookEvent, WebhookEventType, WebhookStore,
+        WebhookStoreError, WebhookSubscription,
+    },
+};
+
+pub struct PostgresWebhookStore {
+    pool: PgPool,
+    tenant_id: TenantId,
+}
+
+impl PostgresWebhookStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    pub fn with_tenant(mut self, tenant_id: TenantId) -> Self {
+        self.tenant_id = tenant_id;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl WebhookStore for PostgresWebhookStore {
+    #[tracing::instrument(name = "Adding webhook subscription to PostgreSQL", skip_all)]
+    async fn add_subscription(
+        &self,
+        subscription: WebhookSubscription,
+    ) -> Result<(), WebhookStoreError> {
+        let event_types: Vec<String> = subscription
+            .event_types
+            .iter()
+            .map(|t| t.as_ref().to_owned())
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_subscriptions (id, tenant_id, url, secret, event_types, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            subscription.id,
+            self.tenant_id.as_ref(),
+            subscription.url,
+            subscription.secret,
+            &event_types,
+            subscription.created_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Listing webhook subscriptions from PostgreSQL", skip_all)]
+    async fn list_subscriptions(&self) -> Result<Vec<WebhookSubscription>, WebhookStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, url, secret, event_types, created_at
+            FROM webhook_subscriptions
+            WHERE tenant_id = $1
+            ORDER BY created_at
+            "#,
+            self.tenant_id.as_ref()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                let event_types = row
+                    .event_types
+                    .iter()
+                    .map(|t| WebhookEventType::parse(t))
+                    .collect::<Result<_, _>>()
+                    .map_err(WebhookStoreError::UnexpectedError)?;
+                Ok(WebhookSubscription {
+                    id: row.id,
+                    url: row.url,
+                    secret: row.secret,
+                    event_types,
+                    created_at: row.created_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Deleting webhook subscription from PostgreSQL", skip_all)]
+    async fn delete_subscription(&self, id: Uuid) -> Result<(), WebhookStoreError> {
+        let result = sqlx::query!(
+            "DELETE FROM webhook_subscriptions WHERE tenant_id = $1 AND id = $2",
+            self.tenant_id.as_ref(),
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        match result.rows_affected() {
+            0 => Err(WebhookStoreError::SubscriptionNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(name = "Enqueuing webhook deliveries in PostgreSQL", skip_all)]
+    async fn enqueue(&self, event: WebhookEvent) -> Result<(), WebhookStoreError> {
+        let payload = serde_json::to_value(&event)
+            .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_deliveries (id, tenant_id, subscription_id, event_type, payload)
+            SELECT gen_random_uuid(), tenant_id, id, $2, $3
+            FROM webhook_subscriptions
+            WHERE tenant_id = $1 AND (cardinality(event_types) = 0 OR $2 = ANY(event_types))
+            "#,
+            self.tenant_id.as_ref(),
+            event.event_type.as_ref(),
+            payload
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Claiming due webhook deliveries in PostgreSQL", skip_all)]
+    async fn claim_due_deliveries(
+        &self,
+        limit: u32,
+        lease_until: DateTime<Utc>,
+    ) -> Result<Vec<WebhookDelivery>, WebhookStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries AS d
+            SET next_attempt_at = $2, attempts = d.attempts + 1
+            FROM webhook_subscriptions AS s
+            WHERE d.subscription_id = s.id
+              AND d.id IN (
+                  SELECT id FROM webhook_deliveries
+                  WHERE next_attempt_at <= NOW()
+                  ORDER BY next_attempt_at
+                  LIMIT $1
+                  FOR UPDATE SKIP LOCKED
+              )
+            RETURNING d.id, s.url, s.secret, d.payload::TEXT AS "payload!", d.attempts
+            "#,
+            i64::from(limit),
+            lease_until
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| WebhookDelivery {
+                id: row.id,
+                url: row.url,
+                secret: row.secret,
+                payload: row.payload,
+                attempts: row.attempts,
+            })
+            .collect())
+    }
+
+    #[tracing::instrument(name = "Marking webhook delivery as delivered in PostgreSQL", skip_all)]
+    async fn mark_delivered(&self, delivery_id: Uuid) -> Result<(), WebhookStoreError> {
+        sqlx::query!("DELETE FROM webhook_deliveries WHERE id = $1", delivery_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Scheduling webhook delivery retry in PostgreSQL", skip_all)]
+    async fn schedule_retry(
+        &self,
+        delivery_id: Uuid,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), WebhookStoreError> {
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET next_attempt_at = $2, last_error = $3 WHERE id = $1",
+            delivery_id,
+            next_attempt_at,
+            error
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "Moving webhook delivery to dead letters in PostgreSQL",
+        skip_all
+    )]
+    async fn move_to_dead_letter(
+        &self,
+        delivery_id: Uuid,
+        error: &str,
+    ) -> Result<(), WebhookStoreError> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_dead_letters
+                (id, tenant_id, subscription_id, event_type, payload, attempts, last_error)
+            SELECT id, tenant_id, subscription_id, event_type, payload, attempts, $2
+            FROM webhook_deliveries
+            WHERE id = $1
+            "#,
+            delivery_id,
+            error
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!("DELETE FROM webhook_deliveries WHERE id = $1", delivery_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))
+    }
+
+    #[tracing::instrument(name = "Listing webhook dead letters from PostgreSQL", skip_all)]
+    async fn list_dead_letters(&self, limit: u32) -> Result<Vec<DeadLetter>, WebhookStoreError> {
+        sqlx::query_as!(
+            DeadLetter,
+            r#"
+            SELECT id, subscription_id, event_type, payload, attempts, last_error, failed_at
+            FROM webhook_dead_letters
+            WHERE tenant_id = $1
+            ORDER BY failed_at DESC
+            LIMIT $2
+            "#,
+            self.tenant_id.as_ref(),
+            i64::from(limit)
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))
+    }
+}
@@ -1,21 +1,40 @@
-use std::sync::Arc;
-
 use color_eyre::eyre::{eyre, Result};
-use redis::{Commands, Connection, RedisResult};
-use tokio::sync::RwLock;
+use redis::{aio::ConnectionManager, AsyncCommands, RedisResult};
 
 use crate::{
-    domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
+    domain::{
+        data_stores::{BannedTokenStore, BannedTokenStoreError},
+        tenant::TenantId,
+    },
     utils::auth::TOKEN_TTL_SECONDS,
 };
 
+// Shares the multiplexed connection with `RedisTwoFACodeStore`, see there.
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
+    tenant_prefix: String,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            tenant_prefix: String::new(),
+        }
+    }
+
+    // Keeps the tenant's keys apart from other tenants' in the shared Redis.
+    pub fn with_tenant(mut self, tenant_id: &TenantId) -> Self {
+        self.tenant_prefix = tenant_key_prefix(tenant_id);
+        self
+    }
+
+    fn get_key(&self, token: &str) -> String {
+        format!("{}{}{}", self.tenant_prefix, BANNED_TOKEN_KEY_PREFIX, token)
+    }
+
+    fn get_revocation_key(&self, subject: &str) -> String {
+        format!("{}{}{}", self.tenant_prefix, TOKENS_REVOKED_KEY_PREFIX, subject)
     }
 }
 
@@ -23,10 +42,11 @@ impl RedisBannedTokenStore {
 impl BannedTokenStore for RedisBannedTokenStore {
 
     #[tracing::instrument(name = "Adding token to redis store", skip_all)]
-    async fn add_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
-        let mut redis_connection = self.conn.write().await;
-        let result: RedisResult<()> =
-            redis_connection.set_ex(get_key(token.as_str()), true, TOKEN_TTL_SECONDS as u64);
+    async fn add_token(&self, token: String) -> Result<(), BannedTokenStoreError> {
+        let mut redis_connection = self.conn.clone();
+        let result: RedisResult<()> = redis_connection
+            .set_ex(self.get_key(token.as_str()), true, TOKEN_TTL_SECONDS as u64)
+            .await;
         match result {
             Ok(_) => Ok(()),
             Err(_) => Err(BannedTokenStoreError::UnexpectedError(eyre!(
@@ -37,8 +57,8 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
     #[tracing::instrument(name = "Checking if redis store contains token", skip_all)]
     async fn contains_token(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        let mut redis_connection = self.conn.write().await;
-        let result: RedisResult<bool> = redis_connection.exists(get_key(token));
+        let mut redis_connection = self.conn.clone();
+        let result: RedisResult<bool> = redis_connection.exists(self.get_key(token)).await;
         match result {
             Ok(value) => Ok(value),
             Err(_) => Err(BannedTokenStoreError::UnexpectedError(eyre!(
@@ -46,11 +66,150 @@ impl BannedTokenStore for RedisBannedTokenStore {
             ))),
         }
     }
+
+    #[tracing::instrument(name = "Revoking all tokens of a subject in redis store", skip_all)]
+    async fn revoke_all_tokens(
+        &self,
+        subject: &str,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        let mut redis_connection = self.conn.clone();
+        // Tokens issued before the cutoff are expired once TOKEN_TTL_SECONDS have passed,
+        // so the marker does not need to outlive them.
+        let result: RedisResult<()> = redis_connection
+            .set_ex(
+                self.get_revocation_key(subject),
+                issued_before,
+                TOKEN_TTL_SECONDS as u64,
+            )
+            .await;
+        match result {
+            Ok(_) => Ok(()),
+            Err(_) => Err(BannedTokenStoreError::UnexpectedError(eyre!(
+                "Failed to revoke tokens in Redis"
+            ))),
+        }
+    }
+
+    #[tracing::instrument(name = "Getting token revocation cutoff from redis store", skip_all)]
+    async fn tokens_revoked_before(
+        &self,
+        subject: &str,
+    ) -> Result<Option<i64>, BannedTokenStoreError> {
+        let mut redis_connection = self.conn.clone();
+        let result: RedisResult<Option<i64>> =
+            redis_connection.get(self.get_revocation_key(subject)).await;
+        match result {
+            Ok(value) => Ok(value.map(cutoff_in_millis)),
+            Err(_) => Err(BannedTokenStoreError::UnexpectedError(eyre!(
+                "Failed to read token revocation cutoff from Redis",
+            ))),
+        }
+    }
 }
 
 // We are using a key prefix to prevent collisions and organize data!
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
+const TOKENS_REVOKED_KEY_PREFIX: &str = "tokens_revoked_before:";
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+// Cutoffs stored before they had millisecond precision are in seconds and covered the whole
+// second. They expire within TOKEN_TTL_SECONDS of an upgrade.
+fn cutoff_in_millis(cutoff: i64) -> i64 {
+    const FIRST_MILLIS_CUTOFF: i64 = 100_000_000_000;
+    if cutoff < FIRST_MILLIS_CUTOFF {
+        (cutoff + 1) * 1000
+    } else {
+        cutoff
+    }
+}
+
+// The default tenant's keys have no prefix, so keys written before there were tenants still count.
+pub(crate) fn tenant_key_prefix(tenant_id: &TenantId) -> String {
+    if tenant_id.is_default() {
+        String::new()
+    } else {
+        format!("{}:", tenant_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::*;
+    use crate::get_redis_connection;
+
+    // Answers the commands on the n-th accepted connection with `replies[n]` (cycling), and
+    // hangs up after `commands_per_connection` commands. An empty reply never answers.
+    async fn fake_redis(replies: Vec<&'static str>, commands_per_connection: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            for reply in replies.into_iter().cycle() {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut buffer = [0; 1024];
+                    let mut commands = 0;
+                    while commands < commands_per_connection {
+                        let read = match socket.read(&mut buffer).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => read,
+                        };
+                        // The two CLIENT SETINFO commands sent when connecting.
+                        if buffer[..read].windows(7).any(|w| w == b"SETINFO") {
+                            socket.write_all(b"+OK\r\n+OK\r\n").await.unwrap();
+                            continue;
+                        }
+                        if !reply.is_empty() {
+                            socket.write_all(reply.as_bytes()).await.unwrap();
+                        }
+                        commands += 1;
+                    }
+                });
+            }
+        });
+        address
+    }
+
+    async fn store(address: String, command_timeout: Duration) -> RedisBannedTokenStore {
+        let client = redis::Client::open(format!("redis://{}/", address)).unwrap();
+        let conn = get_redis_connection(client, command_timeout).await.unwrap();
+        RedisBannedTokenStore::new(conn)
+    }
+
+    #[tokio::test]
+    async fn times_out_commands_redis_does_not_answer() {
+        let address = fake_redis(vec![""], usize::MAX).await;
+        let store = store(address, Duration::from_millis(100)).await;
+
+        let started = Instant::now();
+        let result = store.contains_token("token").await;
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn reconnects_after_redis_drops_the_connection() {
+        // Every connection answers a single command and hangs up, the second one with `:1`.
+        let address = fake_redis(vec![":0\r\n", ":1\r\n"], 1).await;
+        let store = store(address, Duration::from_secs(1)).await;
+        assert!(!store.contains_token("token").await.unwrap());
+
+        let mut result = store.contains_token("token").await;
+        for _ in 0..20 {
+            if result.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            result = store.contains_token("token").await;
+        }
+
+        assert!(result.unwrap());
+    }
 }
@@ -0,0 +1,99 @@
+use color_eyre::eyre::Context;
+use redis::{aio::ConnectionManager, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+
+use crate::{
+    domain::{
+        data_stores::{
+            SmsRateLimiter, SmsRateLimiterError, SMS_COOLDOWN_SECONDS, SMS_MAX_PER_WINDOW,
+            SMS_WINDOW_SECONDS,
+        },
+        email::Email,
+        phone_number::PhoneNumber,
+        tenant::TenantId,
+    },
+    services::data_stores::redis_banned_token_store::tenant_key_prefix,
+};
+
+// Shares the multiplexed connection with `RedisTwoFACodeStore`, see there.
+pub struct RedisSmsRateLimiter {
+    conn: ConnectionManager,
+    tenant_prefix: String,
+}
+
+impl RedisSmsRateLimiter {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            tenant_prefix: String::new(),
+        }
+    }
+
+    // Keeps the tenant's keys apart from other tenants' in the shared Redis.
+    pub fn with_tenant(mut self, tenant_id: &TenantId) -> Self {
+        self.tenant_prefix = tenant_key_prefix(tenant_id);
+        self
+    }
+
+    fn get_user_key(&self, email: &Email) -> String {
+        format!(
+            "{}{}{}",
+            self.tenant_prefix,
+            SMS_SENT_BY_PREFIX,
+            email.as_ref()
+        )
+    }
+
+    fn get_number_key(&self, recipient: &PhoneNumber) -> String {
+        format!(
+            "{}{}{}",
+            self.tenant_prefix,
+            SMS_COOLDOWN_PREFIX,
+            recipient.as_ref()
+        )
+    }
+}
+
+#[async_trait::async_trait]
+impl SmsRateLimiter for RedisSmsRateLimiter {
+    #[tracing::instrument(name = "Acquiring SMS rate limit in Redis", skip_all)]
+    async fn try_acquire(
+        &self,
+        email: &Email,
+        recipient: &PhoneNumber,
+    ) -> Result<bool, SmsRateLimiterError> {
+        let mut conn = self.conn.clone();
+
+        // The window starts with the user's first text, as a fixed rather than sliding window.
+        let user_key = self.get_user_key(email);
+        let sent: u64 = conn
+            .incr(&user_key, 1)
+            .await
+            .wrap_err("failed to count texts in Redis")
+            .map_err(SmsRateLimiterError::UnexpectedError)?;
+        if sent == 1 {
+            let _: () = conn
+                .expire(&user_key, SMS_WINDOW_SECONDS as i64)
+                .await
+                .wrap_err("failed to expire the text count in Redis")
+                .map_err(SmsRateLimiterError::UnexpectedError)?;
+        }
+        if sent > SMS_MAX_PER_WINDOW {
+            return Ok(false);
+        }
+
+        // Only set if the number is not already cooling down.
+        let options = SetOptions::default()
+            .conditional_set(ExistenceCheck::NX)
+            .with_expiration(SetExpiry::EX(SMS_COOLDOWN_SECONDS as usize));
+        let started: Option<String> = conn
+            .set_options(self.get_number_key(recipient), 1, options)
+            .await
+            .wrap_err("failed to start the number's cooldown in Redis")
+            .map_err(SmsRateLimiterError::UnexpectedError)?;
+
+        Ok(started.is_some())
+    }
+}
+
+const SMS_SENT_BY_PREFIX: &str = "sms_sent_by:";
+const SMS_COOLDOWN_PREFIX: &str = "sms_cooldown:";
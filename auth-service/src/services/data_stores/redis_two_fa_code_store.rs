@@ -1,20 +1,37 @@
-use std::sync::Arc;
-
 use color_eyre::eyre::Context;
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
 
-use crate::domain::data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
+use crate::domain::data_stores::{
+    LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, TWO_FA_CODE_TTL_SECONDS,
+};
 use crate::domain::email::Email;
+use crate::domain::tenant::TenantId;
+use crate::services::data_stores::redis_banned_token_store::tenant_key_prefix;
 
+// Commands go through a clone of the shared multiplexed connection, so concurrent requests
+// are pipelined rather than queued behind a lock.
 pub struct RedisTwoFACodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
+    tenant_prefix: String,
 }
 
 impl RedisTwoFACodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
-        Self { conn }
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self {
+            conn,
+            tenant_prefix: String::new(),
+        }
+    }
+
+    // The same email can belong to users of several tenants, each with their own codes.
+    pub fn with_tenant(mut self, tenant_id: &TenantId) -> Self {
+        self.tenant_prefix = tenant_key_prefix(tenant_id);
+        self
+    }
+
+    fn get_key(&self, email: &Email) -> String {
+        format!("{}{}{}", self.tenant_prefix, TWO_FA_CODE_PREFIX, email.as_ref())
     }
 }
 
@@ -23,12 +40,12 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 
     #[tracing::instrument(name = "Adding code 2FA code to Redis", skip_all)]
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(&email);
+        let key = self.get_key(&email);
         let data = TwoFATuple(
             String::from(login_attempt_id.as_ref()),
             String::from(code.as_ref()),
@@ -39,9 +56,9 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 
         let _: () = self
             .conn
-            .write()
+            .clone()
+            .set_ex(&key, serialized_data, TWO_FA_CODE_TTL_SECONDS)
             .await
-            .set_ex(&key, serialized_data, TEN_MINUTES_IN_SECONDS)
             .wrap_err("failed to set 2FA code in Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
@@ -49,14 +66,14 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
     }
 
     #[tracing::instrument(name = "Removing 2FA code from Redis", skip_all)]
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        let key = get_key(email);
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let key = self.get_key(email);
 
         let _: () = self
             .conn
-            .write()
-            .await
+            .clone()
             .del(&key)
+            .await
             .wrap_err("failed to delete 2FA code from Redis")
             .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
@@ -69,10 +86,18 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let key = get_key(email);
+        let key = self.get_key(email);
 
-        match self.conn.write().await.get::<_, String>(&key) {
-            Ok(value) => {
+        let value: Option<String> = self
+            .conn
+            .clone()
+            .get(&key)
+            .await
+            .wrap_err("failed to get 2FA code from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        match value {
+            Some(value) => {
                 let data: TwoFATuple = serde_json::from_str(&value)
                     .wrap_err("failed to deserialize 2FA tuple") // New!
                     .map_err(TwoFACodeStoreError::UnexpectedError)?; // Updated!
@@ -85,7 +110,7 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 
                 Ok((login_attempt_id, email_code))
             }
-            Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 }
@@ -93,9 +118,4 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
 #[derive(Serialize, Deserialize)]
 struct TwoFATuple(pub String, pub String);
 
-const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
-
-fn get_key(email: &Email) -> String {
-    format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref())
-}
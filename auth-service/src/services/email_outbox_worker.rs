@@ -0,0 +1,470 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Report, Result};
+
+use crate::{
+    app_state::{EmailClientType, EmailOutboxType},
+    domain::email_outbox::{EmailOutboxError, OutboxEmail},
+    services::http_email_client::HttpEmailError,
+    utils::shutdown::ShutdownHandle,
+};
+
+#[derive(Debug, Clone)]
+pub struct EmailOutboxWorkerConfig {
+    pub poll_interval: Duration,
+    pub batch_size: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    // Emails that failed this many times are marked as failed and no longer retried.
+    pub max_attempts: i32,
+    // Upper bound for one `send_email` call.
+    pub send_timeout: Duration,
+}
+
+impl Default for EmailOutboxWorkerConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(500),
+            batch_size: 50,
+            base_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(30 * 60),
+            max_attempts: 6,
+            send_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl EmailOutboxWorkerConfig {
+    // Delay before the next attempt once `attempts` attempts have failed:
+    // base_backoff * 2^(attempts - 1), capped at max_backoff.
+    pub fn backoff(&self, attempts: i32) -> Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+        self.base_backoff
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_backoff)
+    }
+
+    // How long a claimed batch stays leased. Emails are sent one after the other, so every one
+    // of them may take the full send timeout; one more covers the outbox updates.
+    pub fn lease(&self) -> Duration {
+        self.send_timeout
+            .saturating_mul(self.batch_size.saturating_add(1))
+    }
+}
+
+// Background worker that sends the emails queued in the outbox.
+pub struct EmailOutboxWorker {
+    outbox: EmailOutboxType,
+    email_client: EmailClientType,
+    config: EmailOutboxWorkerConfig,
+}
+
+impl EmailOutboxWorker {
+    pub fn new(
+        outbox: EmailOutboxType,
+        email_client: EmailClientType,
+        config: EmailOutboxWorkerConfig,
+    ) -> Self {
+        Self {
+            outbox,
+            email_client,
+            config,
+        }
+    }
+
+    // Polls until shutdown is requested. A batch that is being sent at that point is
+    // finished first; anything still queued stays in the outbox for the next start.
+    pub async fn run(self, shutdown: ShutdownHandle) {
+        let mut interval = tokio::time::interval(self.config.poll_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.requested() => break,
+            }
+            if let Err(e) = self.send_due().await {
+                tracing::error!(error = ?e, "Failed to send queued emails");
+            }
+        }
+        tracing::info!("Email outbox worker stopped");
+    }
+
+    #[tracing::instrument(name = "Sending due emails", skip_all)]
+    pub async fn send_due(&self) -> Result<usize, EmailOutboxError> {
+        // No other worker may send an email again while this one still works through the batch.
+        let lease = chrono::Duration::from_std(self.config.lease())
+            .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+        let emails = self
+            .outbox
+            .claim_due(self.config.batch_size, Utc::now() + lease)
+            .await?;
+
+        let count = emails.len();
+        for email in emails {
+            let outcome = self.send(&email).await;
+            self.record_outcome(&email, outcome).await?;
+        }
+
+        Ok(count)
+    }
+
+    #[tracing::instrument(name = "Sending queued email", skip_all, fields(email_id = %email.id))]
+    async fn send(&self, email: &OutboxEmail) -> Result<(), SendError> {
+        if email
+            .send_before
+            .is_some_and(|deadline| deadline <= Utc::now())
+        {
+            return Err(SendError::Permanent(eyre!(
+                "Expired before it could be sent"
+            )));
+        }
+
+        let result = tokio::time::timeout(
+            self.config.send_timeout,
+            self.email_client
+                .send_email(&email.recipient, &email.message),
+        )
+        .await;
+
+        match result {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) if is_permanent(&e) => Err(SendError::Permanent(e)),
+            Ok(Err(e)) => Err(SendError::Transient(e)),
+            Err(_) => Err(SendError::Transient(eyre!("Timed out sending email"))),
+        }
+    }
+
+    async fn record_outcome(
+        &self,
+        email: &OutboxEmail,
+        outcome: Result<(), SendError>,
+    ) -> Result<(), EmailOutboxError> {
+        let outbox = &self.outbox;
+        match outcome {
+            Ok(()) => outbox.mark_sent(email.id).await,
+            Err(SendError::Transient(e)) if email.attempts < self.config.max_attempts => {
+                let delay = chrono::Duration::from_std(self.config.backoff(email.attempts))
+                    .map_err(|e| EmailOutboxError::UnexpectedError(e.into()))?;
+                outbox
+                    .schedule_retry(email.id, Utc::now() + delay, &format!("{:#}", e))
+                    .await
+            }
+            Err(SendError::Transient(e) | SendError::Permanent(e)) => {
+                let error = format!("{:#}", e);
+                tracing::warn!(email_id = %email.id, error = %error, "Giving up on email");
+                outbox.mark_failed(email.id, &error).await
+            }
+        }
+    }
+}
+
+enum SendError {
+    // Worth retrying later, e.g. the provider is down.
+    Transient(Report),
+    // Will fail the same way every time, e.g. the provider rejected the recipient.
+    Permanent(Report),
+}
+
+fn is_permanent(error: &Report) -> bool {
+    if let Some(e) = error.downcast_ref::<HttpEmailError>() {
+        return !e.is_retryable();
+    }
+    if let Some(e) = error.downcast_ref::<lettre::transport::smtp::Error>() {
+        return e.is_permanent();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    use chrono::DateTime;
+    use reqwest::StatusCode;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::domain::{
+        email::Email,
+        email_outbox::{EmailOutbox, FailedEmail},
+        EmailClient, EmailMessage,
+    };
+
+    struct QueuedEmail {
+        email: OutboxEmail,
+        next_attempt_at: DateTime<Utc>,
+        last_error: Option<String>,
+        failed: bool,
+    }
+
+    #[derive(Default)]
+    struct InMemoryOutbox {
+        emails: Mutex<Vec<QueuedEmail>>,
+    }
+
+    impl InMemoryOutbox {
+        fn update(&self, id: Uuid, f: impl FnOnce(&mut QueuedEmail)) {
+            if let Some(queued) = self
+                .emails
+                .lock()
+                .unwrap()
+                .iter_mut()
+                .find(|queued| queued.email.id == id)
+            {
+                f(queued);
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EmailOutbox for InMemoryOutbox {
+        async fn enqueue(&self, email: OutboxEmail) -> Result<(), EmailOutboxError> {
+            self.emails.lock().unwrap().push(QueuedEmail {
+                email,
+                next_attempt_at: Utc::now(),
+                last_error: None,
+                failed: false,
+            });
+            Ok(())
+        }
+
+        async fn claim_due(
+            &self,
+            limit: u32,
+            lease_until: DateTime<Utc>,
+        ) -> Result<Vec<OutboxEmail>, EmailOutboxError> {
+            let mut emails = self.emails.lock().unwrap();
+            Ok(emails
+                .iter_mut()
+                .filter(|queued| !queued.failed && queued.next_attempt_at <= Utc::now())
+                .take(limit as usize)
+                .map(|queued| {
+                    queued.email.attempts += 1;
+                    queued.next_attempt_at = lease_until;
+                    queued.email.clone()
+                })
+                .collect())
+        }
+
+        async fn mark_sent(&self, id: Uuid) -> Result<(), EmailOutboxError> {
+            self.emails
+                .lock()
+                .unwrap()
+                .retain(|queued| queued.email.id != id);
+            Ok(())
+        }
+
+        async fn schedule_retry(
+            &self,
+            id: Uuid,
+            next_attempt_at: DateTime<Utc>,
+            error: &str,
+        ) -> Result<(), EmailOutboxError> {
+            self.update(id, |queued| {
+                queued.next_attempt_at = next_attempt_at;
+                queued.last_error = Some(error.to_owned());
+            });
+            Ok(())
+        }
+
+        async fn mark_failed(&self, id: Uuid, error: &str) -> Result<(), EmailOutboxError> {
+            self.update(id, |queued| {
+                queued.last_error = Some(error.to_owned());
+                queued.failed = true;
+            });
+            Ok(())
+        }
+
+        async fn list_failed(&self, _limit: u32) -> Result<Vec<FailedEmail>, EmailOutboxError> {
+            Ok(self
+                .emails
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|queued| queued.failed)
+                .map(|queued| FailedEmail {
+                    id: queued.email.id,
+                    recipient: queued.email.recipient.as_ref().to_owned(),
+                    subject: queued.email.message.subject.clone(),
+                    attempts: queued.email.attempts,
+                    last_error: queued.last_error.clone().unwrap_or_default(),
+                    created_at: Utc::now(),
+                    failed_at: Utc::now(),
+                })
+                .collect())
+        }
+    }
+
+    // Fails the first `failures` sends with the error built by `error`.
+    struct ScriptedEmailClient {
+        failures: usize,
+        error: fn() -> Report,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl EmailClient for ScriptedEmailClient {
+        async fn send_email(&self, _recipient: &Email, _message: &EmailMessage) -> Result<()> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err((self.error)());
+            }
+            Ok(())
+        }
+    }
+
+    struct Setup {
+        outbox: Arc<InMemoryOutbox>,
+        client: Arc<ScriptedEmailClient>,
+        worker: EmailOutboxWorker,
+    }
+
+    fn setup(failures: usize, error: fn() -> Report) -> Setup {
+        let outbox = Arc::new(InMemoryOutbox::default());
+        let client = Arc::new(ScriptedEmailClient {
+            failures,
+            error,
+            calls: AtomicUsize::new(0),
+        });
+        let worker = EmailOutboxWorker::new(
+            outbox.clone(),
+            client.clone(),
+            EmailOutboxWorkerConfig {
+                base_backoff: Duration::ZERO,
+                max_attempts: 3,
+                ..EmailOutboxWorkerConfig::default()
+            },
+        );
+        Setup {
+            outbox,
+            client,
+            worker,
+        }
+    }
+
+    fn email() -> OutboxEmail {
+        OutboxEmail::new(
+            Email::parse("user@example.com").unwrap(),
+            EmailMessage {
+                subject: "Your sign-in code".to_owned(),
+                html_body: "<p>123456</p>".to_owned(),
+                text_body: "123456".to_owned(),
+            },
+        )
+    }
+
+    fn provider_down() -> Report {
+        HttpEmailError::Unavailable {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+        .into()
+    }
+
+    fn recipient_rejected() -> Report {
+        HttpEmailError::Rejected {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            body: "Invalid 'To' address".to_owned(),
+        }
+        .into()
+    }
+
+    async fn calls(setup: &Setup) -> usize {
+        setup.client.calls.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn sends_and_removes_queued_email() {
+        let setup = setup(0, provider_down);
+        setup.outbox.enqueue(email()).await.unwrap();
+
+        assert_eq!(setup.worker.send_due().await.unwrap(), 1);
+
+        assert_eq!(calls(&setup).await, 1);
+        assert!(setup.outbox.emails.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures() {
+        let setup = setup(2, provider_down);
+        setup.outbox.enqueue(email()).await.unwrap();
+
+        for _ in 0..3 {
+            setup.worker.send_due().await.unwrap();
+        }
+
+        assert_eq!(calls(&setup).await, 3);
+        assert!(setup.outbox.emails.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let setup = setup(usize::MAX, provider_down);
+        setup.outbox.enqueue(email()).await.unwrap();
+
+        for _ in 0..5 {
+            setup.worker.send_due().await.unwrap();
+        }
+
+        assert_eq!(calls(&setup).await, 3);
+        let failed = setup.outbox.list_failed(10).await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempts, 3);
+        assert_eq!(
+            failed[0].last_error,
+            "Email provider failed with 503 Service Unavailable"
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_permanent_failures() {
+        let setup = setup(usize::MAX, recipient_rejected);
+        setup.outbox.enqueue(email()).await.unwrap();
+
+        setup.worker.send_due().await.unwrap();
+        setup.worker.send_due().await.unwrap();
+
+        assert_eq!(calls(&setup).await, 1);
+        let failed = setup.outbox.list_failed(10).await.unwrap();
+        assert_eq!(failed[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn drops_expired_email_without_sending() {
+        let setup = setup(0, provider_down);
+        let expired = email().send_before(Utc::now() - chrono::Duration::seconds(1));
+        setup.outbox.enqueue(expired).await.unwrap();
+
+        setup.worker.send_due().await.unwrap();
+
+        assert_eq!(calls(&setup).await, 0);
+        let failed = setup.outbox.list_failed(10).await.unwrap();
+        assert_eq!(failed[0].last_error, "Expired before it could be sent");
+    }
+
+    #[test]
+    fn backoff_doubles_and_is_capped() {
+        let config = EmailOutboxWorkerConfig {
+            base_backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(30),
+            ..EmailOutboxWorkerConfig::default()
+        };
+
+        assert_eq!(config.backoff(1), Duration::from_secs(5));
+        assert_eq!(config.backoff(2), Duration::from_secs(10));
+        assert_eq!(config.backoff(3), Duration::from_secs(20));
+        assert_eq!(config.backoff(4), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn lease_outlasts_a_batch_of_timed_out_sends() {
+        let config = EmailOutboxWorkerConfig {
+            batch_size: 50,
+            send_timeout: Duration::from_secs(30),
+            ..EmailOutboxWorkerConfig::default()
+        };
+
+        assert!(config.lease() > config.send_timeout * config.batch_size);
+    }
+}
@@ -0,0 +1,313 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{Result, WrapErr};
+use minijinja::{context, Environment, Value};
+use serde::Serialize;
+
+use crate::{
+    domain::EmailMessage,
+    utils::constants::{
+        DEFAULT_EMAIL_BRAND_ACCENT_COLOR, DEFAULT_EMAIL_BRAND_NAME, EMAIL_BRAND_ACCENT_COLOR,
+        EMAIL_BRAND_NAME, EMAIL_BRAND_URL, EMAIL_SUPPORT_ADDRESS,
+    },
+};
+
+macro_rules! template {
+    ($name:literal) => {
+        (
+            $name,
+            include_str!(concat!("../../templates/email/", $name)),
+        )
+    };
+    (sms $name:literal) => {
+        (
+            concat!("sms/", $name),
+            include_str!(concat!("../../templates/sms/", $name)),
+        )
+    };
+}
+
+// Compiled into the binary so the service does not depend on files next to it at runtime.
+const TEMPLATES: &[(&str, &str)] = &[
+    template!("layout.html"),
+    template!("layout.txt"),
+    template!("two_fa_code.html"),
+    template!("two_fa_code.txt"),
+    template!("verification.html"),
+    template!("verification.txt"),
+    template!("password_reset.html"),
+    template!("password_reset.txt"),
+    template!("email_change.html"),
+    template!("email_change.txt"),
+    template!("security_notice.html"),
+    template!("security_notice.txt"),
+    template!(sms "two_fa_code.txt"),
+];
+
+// Variables available to every template as `brand.*`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Branding {
+    pub product_name: String,
+    pub support_email: Option<String>,
+    pub website_url: Option<String>,
+    pub accent_color: String,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Self {
+            product_name: DEFAULT_EMAIL_BRAND_NAME.to_owned(),
+            support_email: None,
+            website_url: None,
+            accent_color: DEFAULT_EMAIL_BRAND_ACCENT_COLOR.to_owned(),
+        }
+    }
+}
+
+impl Branding {
+    pub fn from_env() -> Self {
+        let optional = |value: &str| Some(value.to_owned()).filter(|value| !value.is_empty());
+
+        Self {
+            product_name: EMAIL_BRAND_NAME.to_owned(),
+            support_email: optional(&EMAIL_SUPPORT_ADDRESS),
+            website_url: optional(&EMAIL_BRAND_URL),
+            accent_color: EMAIL_BRAND_ACCENT_COLOR.to_owned(),
+        }
+    }
+}
+
+// The kinds of email the service sends, with the values each template needs.
+#[derive(Debug, Clone)]
+pub enum EmailTemplate<'a> {
+    TwoFACode {
+        code: &'a str,
+        expires_in: Duration,
+    },
+    Verification {
+        link: &'a str,
+    },
+    PasswordReset {
+        link: &'a str,
+    },
+    // Sent to the new address of an email change.
+    EmailChange {
+        link: &'a str,
+        expires_in: Duration,
+    },
+    SecurityNotice {
+        event: &'a str,
+        occurred_at: DateTime<Utc>,
+    },
+}
+
+impl EmailTemplate<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::TwoFACode { .. } => "two_fa_code",
+            Self::Verification { .. } => "verification",
+            Self::PasswordReset { .. } => "password_reset",
+            Self::EmailChange { .. } => "email_change",
+            Self::SecurityNotice { .. } => "security_notice",
+        }
+    }
+
+    fn subject(&self, branding: &Branding) -> String {
+        let product = &branding.product_name;
+        match self {
+            Self::TwoFACode { .. } => format!("Your {} sign-in code", product),
+            Self::Verification { .. } => format!("Verify your email for {}", product),
+            Self::PasswordReset { .. } => format!("Reset your {} password", product),
+            Self::EmailChange { .. } => format!("Confirm your new email for {}", product),
+            Self::SecurityNotice { .. } => format!("Security alert for your {} account", product),
+        }
+    }
+
+    fn context(&self) -> Value {
+        match self {
+            Self::TwoFACode { code, expires_in } => context! {
+                code => code,
+                expires_in_minutes => expires_in.as_secs().div_ceil(60),
+            },
+            Self::Verification { link } | Self::PasswordReset { link } => context! { link => link },
+            Self::EmailChange { link, expires_in } => context! {
+                link => link,
+                expires_in_hours => expires_in.as_secs().div_ceil(3600),
+            },
+            Self::SecurityNotice { event, occurred_at } => context! {
+                event => event,
+                occurred_at => occurred_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+            },
+        }
+    }
+}
+
+// Renders `EmailTemplate`s into HTML and plain-text bodies, and into SMS text for the
+// templates that have one. HTML templates escape their variables; the others do not.
+pub struct EmailTemplates {
+    environment: Environment<'static>,
+    branding: Branding,
+}
+
+impl EmailTemplates {
+    pub fn new(branding: Branding) -> Result<Self> {
+        let mut environment = Environment::new();
+        for (name, source) in TEMPLATES {
+            environment
+                .add_template(name, source)
+                .wrap_err_with(|| format!("Invalid email template {}", name))?;
+        }
+
+        Ok(Self {
+            environment,
+            branding,
+        })
+    }
+
+    pub fn render(&self, template: &EmailTemplate<'_>) -> Result<EmailMessage> {
+        let subject = template.subject(&self.branding);
+        let context = context! {
+            brand => &self.branding,
+            subject => &subject,
+            ..template.context()
+        };
+
+        let render = |extension: &str| {
+            let name = format!("{}.{}", template.name(), extension);
+            self.environment
+                .get_template(&name)
+                .and_then(|t| t.render(&context))
+                .wrap_err_with(|| format!("Failed to render email template {}", name))
+        };
+
+        Ok(EmailMessage {
+            html_body: render("html")?,
+            text_body: render("txt")?,
+            subject,
+        })
+    }
+
+    pub fn render_sms(&self, template: &EmailTemplate<'_>) -> Result<String> {
+        let name = format!("sms/{}.txt", template.name());
+        let context = context! {
+            brand => &self.branding,
+            ..template.context()
+        };
+
+        let text = self
+            .environment
+            .get_template(&name)
+            .and_then(|t| t.render(&context))
+            .wrap_err_with(|| format!("Failed to render SMS template {}", name))?;
+        Ok(text.trim_end().to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates() -> EmailTemplates {
+        EmailTemplates::new(Branding {
+            product_name: "Acme <ID>".to_owned(),
+            support_email: Some("help@acme.test".to_owned()),
+            website_url: None,
+            accent_color: "#ff0000".to_owned(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn renders_two_fa_code_in_both_parts() {
+        let message = templates()
+            .render(&EmailTemplate::TwoFACode {
+                code: "123456",
+                expires_in: Duration::from_secs(600),
+            })
+            .unwrap();
+
+        assert_eq!(message.subject, "Your Acme <ID> sign-in code");
+        assert!(message.html_body.contains(">123456</p>"));
+        assert!(message.html_body.contains("expires in 10 minutes"));
+        assert!(message.text_body.contains("\n    123456\n"));
+        assert!(message.text_body.contains("expires in 10 minutes"));
+        assert!(message.text_body.contains("Contact help@acme.test"));
+    }
+
+    #[test]
+    fn renders_two_fa_code_as_sms() {
+        let text = templates()
+            .render_sms(&EmailTemplate::TwoFACode {
+                code: "123456",
+                expires_in: Duration::from_secs(600),
+            })
+            .unwrap();
+
+        assert_eq!(
+            text,
+            "123456 is your Acme <ID> code. It expires in 10 minutes. Do not share it with anyone."
+        );
+        assert!(templates()
+            .render_sms(&EmailTemplate::Verification { link: "https://acme.test" })
+            .is_err());
+    }
+
+    #[test]
+    fn renders_email_change_link_and_expiry() {
+        let message = templates()
+            .render(&EmailTemplate::EmailChange {
+                link: "https://acme.test/change-email/confirm?token=abc",
+                expires_in: Duration::from_secs(24 * 3600),
+            })
+            .unwrap();
+
+        assert_eq!(message.subject, "Confirm your new email for Acme <ID>");
+        assert!(message.html_body.contains("expires in 24 hours"));
+        assert!(message
+            .text_body
+            .contains("\nhttps://acme.test/change-email/confirm?token=abc\n"));
+        assert!(message.text_body.contains("expires in 24 hours"));
+    }
+
+    #[test]
+    fn escapes_variables_in_html_only() {
+        let message = templates()
+            .render(&EmailTemplate::SecurityNotice {
+                event: "Password changed <script>",
+                occurred_at: DateTime::from_timestamp(0, 0).unwrap(),
+            })
+            .unwrap();
+
+        assert!(message.html_body.contains("Acme &lt;ID&gt;"));
+        assert!(message
+            .html_body
+            .contains("Password changed &lt;script&gt;"));
+        assert!(!message.html_body.contains("<script>"));
+        assert!(message.text_body.starts_with("Acme <ID>\n"));
+        assert!(message
+            .text_body
+            .contains("Password changed <script>\n1970-01-01 00:00 UTC"));
+    }
+
+    #[test]
+    fn renders_every_template() {
+        let templates = templates();
+        let link = "https://acme.test/confirm?token=abc&x=1";
+        for template in [
+            EmailTemplate::Verification { link },
+            EmailTemplate::PasswordReset { link },
+            EmailTemplate::EmailChange {
+                link,
+                expires_in: Duration::from_secs(3600),
+            },
+        ] {
+            let message = templates.render(&template).unwrap();
+            // HTML escaping also covers `/`, which browsers decode inside attributes.
+            assert!(message
+                .html_body
+                .contains("href=\"https:&#x2f;&#x2f;acme.test&#x2f;confirm?token=abc&amp;x=1\""));
+            assert!(message.text_body.contains(link));
+        }
+    }
+}
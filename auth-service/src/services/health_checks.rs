@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Result};
+use redis::Client;
+use sqlx::PgPool;
+
+use crate::domain::health::HealthCheck;
+
+pub struct PostgresHealthCheck {
+    pool: PgPool,
+}
+
+impl PostgresHealthCheck {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheck for PostgresHealthCheck {
+    fn name(&self) -> &'static str {
+        "postgres"
+    }
+
+    #[tracing::instrument(name = "Checking PostgreSQL health", skip_all)]
+    async fn check(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+}
+
+// Opens its own connection rather than borrowing the stores' one, so that a probe is
+// never queued behind (or blocks) regular requests.
+pub struct RedisHealthCheck {
+    client: Client,
+    timeout: Duration,
+}
+
+impl RedisHealthCheck {
+    pub fn new(client: Client, timeout: Duration) -> Self {
+        Self { client, timeout }
+    }
+}
+
+#[async_trait::async_trait]
+impl HealthCheck for RedisHealthCheck {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    #[tracing::instrument(name = "Checking Redis health", skip_all)]
+    async fn check(&self) -> Result<()> {
+        let client = self.client.clone();
+        let timeout = self.timeout;
+        let reply = tokio::task::spawn_blocking(move || -> Result<String> {
+            let mut conn = client.get_connection_with_timeout(timeout)?;
+            conn.set_read_timeout(Some(timeout))?;
+            conn.set_write_timeout(Some(timeout))?;
+            Ok(redis::cmd("PING").query(&mut conn)?)
+        })
+        .await??;
+
+        match reply.as_str() {
+            "PONG" => Ok(()),
+            other => Err(eyre!("Unexpected PING reply: {}", other)),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    domain::{email::Email, EmailClient, EmailMessage},
+    utils::constants::{EMAIL_API_BASE_URL, EMAIL_API_SENDER, EMAIL_API_TIMEOUT, EMAIL_API_TOKEN},
+};
+
+const SEND_EMAIL_PATH: &str = "email";
+// Postmark-style authentication header, also accepted by most compatible providers.
+pub const SERVER_TOKEN_HEADER: &str = "X-Postmark-Server-Token";
+
+#[derive(Clone)]
+pub struct HttpEmailConfig {
+    pub base_url: String,
+    pub api_token: String,
+    pub sender: String,
+    pub timeout: Duration,
+}
+
+impl HttpEmailConfig {
+    pub fn from_env() -> Result<Self> {
+        if EMAIL_API_BASE_URL.is_empty() {
+            return Err(eyre!("EMAIL_API_BASE_URL must be set."));
+        }
+        if EMAIL_API_TOKEN.is_empty() {
+            return Err(eyre!("EMAIL_API_TOKEN must be set."));
+        }
+        if EMAIL_API_SENDER.is_empty() {
+            return Err(eyre!("EMAIL_API_SENDER must be set."));
+        }
+
+        Ok(Self {
+            base_url: EMAIL_API_BASE_URL.to_owned(),
+            api_token: EMAIL_API_TOKEN.to_owned(),
+            sender: EMAIL_API_SENDER.to_owned(),
+            timeout: *EMAIL_API_TIMEOUT,
+        })
+    }
+}
+
+// Returned (wrapped in the eyre report) by `HttpEmailClient::send_email`, so callers can tell
+// a message the provider will never accept from one worth retrying.
+#[derive(Debug, Error)]
+pub enum HttpEmailError {
+    #[error("Email provider rejected the request with {status}: {body}")]
+    Rejected { status: StatusCode, body: String },
+    #[error("Email provider failed with {status}")]
+    Unavailable { status: StatusCode },
+    #[error("Failed to reach the email provider")]
+    Transport(#[from] reqwest::Error),
+}
+
+impl HttpEmailError {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Rejected { status, .. } => *status == StatusCode::TOO_MANY_REQUESTS,
+            Self::Unavailable { .. } | Self::Transport(_) => true,
+        }
+    }
+}
+
+pub struct HttpEmailClient {
+    http_client: reqwest::Client,
+    send_email_url: Url,
+    api_token: String,
+    sender: String,
+}
+
+impl HttpEmailClient {
+    pub fn new(config: HttpEmailConfig) -> Result<Self> {
+        let base_url = Url::parse(&config.base_url).wrap_err("EMAIL_API_BASE_URL is invalid")?;
+        let send_email_url = base_url
+            .join(SEND_EMAIL_PATH)
+            .wrap_err("EMAIL_API_BASE_URL is invalid")?;
+        let sender = Email::parse(&config.sender).wrap_err("EMAIL_API_SENDER is invalid")?;
+        let http_client = reqwest::Client::builder().timeout(config.timeout).build()?;
+
+        Ok(Self {
+            http_client,
+            send_email_url,
+            api_token: config.api_token,
+            sender: sender.as_ref().to_owned(),
+        })
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+}
+
+#[async_trait::async_trait]
+impl EmailClient for HttpEmailClient {
+    #[tracing::instrument(name = "Sending email over HTTP", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        let request = SendEmailRequest {
+            from: &self.sender,
+            to: recipient.as_ref(),
+            subject: &message.subject,
+            html_body: &message.html_body,
+            text_body: &message.text_body,
+        };
+
+        let response = self
+            .http_client
+            .post(self.send_email_url.clone())
+            .header(SERVER_TOKEN_HEADER, &self.api_token)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .json(&request)
+            .send()
+            .await
+            .map_err(HttpEmailError::from)?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        if status.is_client_error() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(HttpEmailError::Rejected { status, body }.into());
+        }
+        Err(HttpEmailError::Unavailable { status }.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn client(base_url: String, timeout: Duration) -> HttpEmailClient {
+        HttpEmailClient::new(HttpEmailConfig {
+            base_url,
+            api_token: "server-token".to_owned(),
+            sender: "no-reply@example.com".to_owned(),
+            timeout,
+        })
+        .unwrap()
+    }
+
+    fn recipient() -> Email {
+        Email::parse("user@example.com").unwrap()
+    }
+
+    async fn send(client: &HttpEmailClient) -> Result<()> {
+        client
+            .send_email(
+                &recipient(),
+                &EmailMessage {
+                    subject: "Your 2FA code".to_owned(),
+                    html_body: "<p>Your code is 123456</p>".to_owned(),
+                    text_body: "Your code is 123456".to_owned(),
+                },
+            )
+            .await
+    }
+
+    fn http_error(result: Result<()>) -> HttpEmailError {
+        result
+            .unwrap_err()
+            .downcast::<HttpEmailError>()
+            .expect("expected an HttpEmailError")
+    }
+
+    #[tokio::test]
+    async fn sends_expected_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/email"))
+            .and(header(SERVER_TOKEN_HEADER, "server-token"))
+            .and(header("Content-Type", "application/json"))
+            .and(header("Accept", "application/json"))
+            .and(body_json(json!({
+                "From": "no-reply@example.com",
+                "To": "user@example.com",
+                "Subject": "Your 2FA code",
+                "HtmlBody": "<p>Your code is 123456</p>",
+                "TextBody": "Your code is 123456"
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        send(&client(server.uri(), Duration::from_secs(2)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_base_url_path_prefix() {
+        let server = MockServer::start().await;
+        Mock::given(path("/v1/email"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        send(&client(
+            format!("{}/v1/", server.uri()),
+            Duration::from_secs(2),
+        ))
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn maps_client_errors_to_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(422).set_body_string("Invalid 'To' address"))
+            .mount(&server)
+            .await;
+
+        let error = http_error(send(&client(server.uri(), Duration::from_secs(2))).await);
+
+        assert!(matches!(
+            &error,
+            HttpEmailError::Rejected { status, body }
+                if *status == StatusCode::UNPROCESSABLE_ENTITY && body == "Invalid 'To' address"
+        ));
+        assert!(!error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn maps_server_errors_to_unavailable() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let error = http_error(send(&client(server.uri(), Duration::from_secs(2))).await);
+
+        assert!(matches!(
+            &error,
+            HttpEmailError::Unavailable { status } if *status == StatusCode::SERVICE_UNAVAILABLE
+        ));
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_provider() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let error = http_error(send(&client(server.uri(), Duration::from_millis(200))).await);
+
+        assert!(matches!(&error, HttpEmailError::Transport(e) if e.is_timeout()));
+        assert!(error.is_retryable());
+    }
+}
@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use reqwest::{StatusCode, Url};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    domain::{phone_number::PhoneNumber, SmsClient},
+    utils::constants::{SMS_GATEWAY_BASE_URL, SMS_GATEWAY_TIMEOUT, SMS_GATEWAY_TOKEN, SMS_SENDER},
+};
+
+const SEND_SMS_PATH: &str = "messages";
+
+#[derive(Clone)]
+pub struct HttpSmsConfig {
+    pub base_url: String,
+    pub api_token: String,
+    // A phone number or an alphanumeric sender id, depending on what the gateway allows.
+    pub sender: String,
+    pub timeout: Duration,
+}
+
+impl HttpSmsConfig {
+    pub fn from_env() -> Result<Self> {
+        if SMS_GATEWAY_BASE_URL.is_empty() {
+            return Err(eyre!("SMS_GATEWAY_BASE_URL must be set."));
+        }
+        if SMS_GATEWAY_TOKEN.is_empty() {
+            return Err(eyre!("SMS_GATEWAY_TOKEN must be set."));
+        }
+        if SMS_SENDER.is_empty() {
+            return Err(eyre!("SMS_SENDER must be set."));
+        }
+
+        Ok(Self {
+            base_url: SMS_GATEWAY_BASE_URL.to_owned(),
+            api_token: SMS_GATEWAY_TOKEN.to_owned(),
+            sender: SMS_SENDER.to_owned(),
+            timeout: *SMS_GATEWAY_TIMEOUT,
+        })
+    }
+}
+
+// Returned (wrapped in the eyre report) by `HttpSmsClient::send_sms`.
+#[derive(Debug, Error)]
+pub enum HttpSmsError {
+    #[error("SMS gateway rejected the request with {status}: {body}")]
+    Rejected { status: StatusCode, body: String },
+    #[error("SMS gateway failed with {status}")]
+    Unavailable { status: StatusCode },
+    #[error("Failed to reach the SMS gateway")]
+    Transport(#[from] reqwest::Error),
+}
+
+// Sends texts through a gateway that accepts `POST {base_url}/messages` with a bearer token,
+// as Twilio-compatible and most aggregator APIs do.
+pub struct HttpSmsClient {
+    http_client: reqwest::Client,
+    send_sms_url: Url,
+    api_token: String,
+    sender: String,
+}
+
+impl HttpSmsClient {
+    pub fn new(config: HttpSmsConfig) -> Result<Self> {
+        let base_url = Url::parse(&config.base_url).wrap_err("SMS_GATEWAY_BASE_URL is invalid")?;
+        let send_sms_url = base_url
+            .join(SEND_SMS_PATH)
+            .wrap_err("SMS_GATEWAY_BASE_URL is invalid")?;
+        let http_client = reqwest::Client::builder().timeout(config.timeout).build()?;
+
+        Ok(Self {
+            http_client,
+            send_sms_url,
+            api_token: config.api_token,
+            sender: config.sender,
+        })
+    }
+}
+
+#[derive(Serialize)]
+struct SendSmsRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    body: &'a str,
+}
+
+#[async_trait::async_trait]
+impl SmsClient for HttpSmsClient {
+    #[tracing::instrument(name = "Sending SMS over HTTP", skip_all)]
+    async fn send_sms(&self, recipient: &PhoneNumber, message: &str) -> Result<()> {
+        let request = SendSmsRequest {
+            from: &self.sender,
+            to: recipient.as_ref(),
+            body: message,
+        };
+
+        let response = self
+            .http_client
+            .post(self.send_sms_url.clone())
+            .bearer_auth(&self.api_token)
+            .header(reqwest::header::ACCEPT, "application/json")
+            .json(&request)
+            .send()
+            .await
+            .map_err(HttpSmsError::from)?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        if status.is_client_error() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(HttpSmsError::Rejected { status, body }.into());
+        }
+        Err(HttpSmsError::Unavailable { status }.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::{
+        matchers::{body_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn client(base_url: String, timeout: Duration) -> HttpSmsClient {
+        HttpSmsClient::new(HttpSmsConfig {
+            base_url,
+            api_token: "gateway-token".to_owned(),
+            sender: "AuthService".to_owned(),
+            timeout,
+        })
+        .unwrap()
+    }
+
+    async fn send(client: &HttpSmsClient) -> Result<()> {
+        client
+            .send_sms(
+                &PhoneNumber::parse("+14155550123").unwrap(),
+                "123456 is your sign-in code",
+            )
+            .await
+    }
+
+    fn http_error(result: Result<()>) -> HttpSmsError {
+        result
+            .unwrap_err()
+            .downcast::<HttpSmsError>()
+            .expect("expected an HttpSmsError")
+    }
+
+    #[tokio::test]
+    async fn sends_expected_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(header("Authorization", "Bearer gateway-token"))
+            .and(header("Content-Type", "application/json"))
+            .and(body_json(json!({
+                "from": "AuthService",
+                "to": "+14155550123",
+                "body": "123456 is your sign-in code"
+            })))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        send(&client(
+            format!("{}/v1/", server.uri()),
+            Duration::from_secs(2),
+        ))
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn maps_client_errors_to_rejected() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Unroutable number"))
+            .mount(&server)
+            .await;
+
+        let error = http_error(send(&client(server.uri(), Duration::from_secs(2))).await);
+
+        assert!(matches!(
+            &error,
+            HttpSmsError::Rejected { status, body }
+                if *status == StatusCode::BAD_REQUEST && body == "Unroutable number"
+        ));
+    }
+
+    #[tokio::test]
+    async fn maps_server_errors_to_unavailable() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(502))
+            .mount(&server)
+            .await;
+
+        let error = http_error(send(&client(server.uri(), Duration::from_secs(2))).await);
+
+        assert!(matches!(
+            &error,
+            HttpSmsError::Unavailable { status } if *status == StatusCode::BAD_GATEWAY
+        ));
+    }
+
+    #[tokio::test]
+    async fn times_out_slow_gateway() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .mount(&server)
+            .await;
+
+        let error = http_error(send(&client(server.uri(), Duration::from_millis(200))).await);
+
+        assert!(matches!(&error, HttpSmsError::Transport(e) if e.is_timeout()));
+    }
+}
@@ -1,4 +1,4 @@
-use crate::domain::{email::Email, EmailClient};
+use crate::domain::{email::Email, EmailClient, EmailMessage};
 use color_eyre::eyre::Result;
 
 pub struct MockEmailClient;
@@ -6,12 +6,12 @@ pub struct MockEmailClient;
 #[async_trait::async_trait]
 impl EmailClient for MockEmailClient {
     #[tracing::instrument(name = "Sending email", skip_all)]
-    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
         tracing::debug!(
             "Sending email to {} with subject: {} and content: {}",
             recipient.as_ref(),
-            subject,
-            content
+            message.subject,
+            message.text_body
         );
 
         Ok(())
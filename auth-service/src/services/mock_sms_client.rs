@@ -0,0 +1,18 @@
+use crate::domain::{phone_number::PhoneNumber, SmsClient};
+use color_eyre::eyre::Result;
+
+pub struct MockSmsClient;
+
+#[async_trait::async_trait]
+impl SmsClient for MockSmsClient {
+    #[tracing::instrument(name = "Sending SMS", skip_all)]
+    async fn send_sms(&self, recipient: &PhoneNumber, message: &str) -> Result<()> {
+        tracing::debug!(
+            "Sending SMS to {} with content: {}",
+            recipient.as_ref(),
+            message
+        );
+
+        Ok(())
+    }
+}
@@ -1,2 +1,10 @@
 pub mod mock_email_client;
 pub mod data_stores; 
+pub mod webhook_dispatcher;
+pub mod health_checks;
+pub mod smtp_email_client;
+pub mod http_email_client;
+pub mod email_templates;
+pub mod email_outbox_worker;
+pub mod mock_sms_client;
+pub mod http_sms_client;
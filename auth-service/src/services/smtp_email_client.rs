@@ -0,0 +1,382 @@
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::{authentication::Credentials, PoolConfig},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::{
+    domain::{email::Email, EmailClient, EmailMessage},
+    utils::constants::{
+        SMTP_HOST, SMTP_PASSWORD, SMTP_POOL_SIZE, SMTP_PORT, SMTP_SENDER, SMTP_TIMEOUT, SMTP_TLS,
+        SMTP_USERNAME,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    // Plain text connection, only meant for local relays and test sinks.
+    None,
+    // Upgrades the connection with STARTTLS and refuses to continue without it.
+    StartTls,
+    // TLS from the first byte (SMTPS).
+    Implicit,
+}
+
+impl SmtpTls {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "starttls" => Ok(Self::StartTls),
+            "tls" | "implicit" => Ok(Self::Implicit),
+            other => Err(eyre!(
+                "Unsupported SMTP TLS mode {:?}, expected none, starttls or tls",
+                other
+            )),
+        }
+    }
+
+    fn default_port(self) -> u16 {
+        match self {
+            Self::None => 25,
+            Self::StartTls => 587,
+            Self::Implicit => 465,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SmtpCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub credentials: Option<SmtpCredentials>,
+    pub sender: String,
+    // Upper bound for delivering one message, including connecting to the server.
+    pub timeout: Duration,
+    pub pool_size: u32,
+}
+
+impl SmtpConfig {
+    pub fn from_env() -> Result<Self> {
+        if SMTP_HOST.is_empty() {
+            return Err(eyre!("SMTP_HOST must be set."));
+        }
+        if SMTP_SENDER.is_empty() {
+            return Err(eyre!("SMTP_SENDER must be set."));
+        }
+        let tls = match SMTP_TLS.as_str() {
+            "" => SmtpTls::StartTls,
+            tls => SmtpTls::parse(tls)?,
+        };
+        let credentials = match (SMTP_USERNAME.as_str(), SMTP_PASSWORD.as_str()) {
+            ("", "") => None,
+            ("", _) | (_, "") => {
+                return Err(eyre!(
+                    "SMTP_USERNAME and SMTP_PASSWORD must be set together."
+                ))
+            }
+            (username, password) => Some(SmtpCredentials {
+                username: username.to_owned(),
+                password: password.to_owned(),
+            }),
+        };
+
+        Ok(Self {
+            host: SMTP_HOST.to_owned(),
+            port: SMTP_PORT.unwrap_or(tls.default_port()),
+            tls,
+            credentials,
+            sender: SMTP_SENDER.to_owned(),
+            timeout: *SMTP_TIMEOUT,
+            pool_size: *SMTP_POOL_SIZE,
+        })
+    }
+}
+
+pub struct SmtpEmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: Mailbox,
+    timeout: Duration,
+}
+
+impl SmtpEmailClient {
+    pub fn new(config: SmtpConfig) -> Result<Self> {
+        let sender = config
+            .sender
+            .parse()
+            .wrap_err("SMTP_SENDER is not a valid mailbox")?;
+
+        let builder = match config.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            SmtpTls::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        };
+        let mut builder = builder
+            .port(config.port)
+            .timeout(Some(config.timeout))
+            .pool_config(PoolConfig::new().max_size(config.pool_size));
+        if let Some(SmtpCredentials { username, password }) = config.credentials {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            sender,
+            timeout: config.timeout,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for SmtpEmailClient {
+    #[tracing::instrument(name = "Sending email over SMTP", skip_all)]
+    async fn send_email(&self, recipient: &Email, message: &EmailMessage) -> Result<()> {
+        let recipient: Mailbox = recipient
+            .as_ref()
+            .parse()
+            .wrap_err("Recipient is not a valid mailbox")?;
+        let message = Message::builder()
+            .from(self.sender.clone())
+            .to(recipient)
+            .subject(&message.subject)
+            .multipart(MultiPart::alternative_plain_html(
+                message.text_body.clone(),
+                message.html_body.clone(),
+            ))?;
+
+        // lettre's own timeout does not cover every read, e.g. a server that never greets.
+        tokio::time::timeout(self.timeout, self.transport.send(message))
+            .await
+            .map_err(|_| eyre!("Timed out sending email over SMTP"))?
+            .wrap_err("Failed to send email over SMTP")?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream},
+        sync::mpsc,
+    };
+
+    // What the sink saw during one SMTP session.
+    #[derive(Debug, Default)]
+    struct Session {
+        auth: Option<String>,
+        mail_from: Option<String>,
+        rcpt_to: Vec<String>,
+        messages: Vec<String>,
+    }
+
+    // Minimal in-process SMTP server: accepts every command, records the session and sends
+    // it over the channel once the client disconnects.
+    async fn spawn_smtp_sink() -> (u16, mpsc::UnboundedReceiver<Session>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let _ = sender.send(serve_session(stream).await);
+                });
+            }
+        });
+        (port, receiver)
+    }
+
+    async fn serve_session(stream: TcpStream) -> Session {
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let mut session = Session::default();
+        writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let command = line.to_ascii_uppercase();
+            let reply: &[u8] = if command.starts_with("EHLO") {
+                b"250-sink\r\n250 AUTH PLAIN LOGIN\r\n"
+            } else if command.starts_with("AUTH") {
+                session.auth = Some(line);
+                b"235 2.7.0 Authentication successful\r\n"
+            } else if command.starts_with("MAIL FROM") {
+                session.mail_from = Some(line);
+                b"250 OK\r\n"
+            } else if command.starts_with("RCPT TO") {
+                session.rcpt_to.push(line);
+                b"250 OK\r\n"
+            } else if command == "DATA" {
+                writer
+                    .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                    .await
+                    .unwrap();
+                let mut data = String::new();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "." {
+                        break;
+                    }
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                session.messages.push(data);
+                b"250 OK queued\r\n"
+            } else if command == "QUIT" {
+                writer.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                // RSET, NOOP and anything else.
+                b"250 OK\r\n"
+            };
+            writer.write_all(reply).await.unwrap();
+        }
+
+        session
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: "127.0.0.1".to_owned(),
+            port,
+            tls: SmtpTls::None,
+            credentials: None,
+            sender: "Auth Service <no-reply@example.com>".to_owned(),
+            timeout: Duration::from_secs(2),
+            pool_size: 1,
+        }
+    }
+
+    fn recipient() -> Email {
+        Email::parse("user@example.com").unwrap()
+    }
+
+    fn message(subject: &str) -> EmailMessage {
+        EmailMessage {
+            subject: subject.to_owned(),
+            html_body: "<p>Your code is 123456</p>".to_owned(),
+            text_body: "Your code is 123456".to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_html_and_text_alternatives() {
+        let (port, mut sessions) = spawn_smtp_sink().await;
+        let client = SmtpEmailClient::new(config(port)).unwrap();
+
+        client
+            .send_email(&recipient(), &message("Your 2FA code"))
+            .await
+            .unwrap();
+        drop(client);
+
+        let session = sessions.recv().await.unwrap();
+        assert_eq!(
+            session.mail_from.as_deref(),
+            Some("MAIL FROM:<no-reply@example.com>")
+        );
+        assert_eq!(session.rcpt_to, vec!["RCPT TO:<user@example.com>"]);
+        assert_eq!(session.auth, None);
+        let message = &session.messages[0];
+        assert!(message.contains("Subject: Your 2FA code\n"));
+        assert!(message.contains("To: user@example.com\n"));
+        assert!(message.contains("Content-Type: multipart/alternative"));
+        let text = message.find("Content-Type: text/plain").unwrap();
+        let html = message.find("Content-Type: text/html").unwrap();
+        assert!(text < html, "the preferred HTML part must come last");
+        assert!(message[text..html].contains("\nYour code is 123456\n"));
+        assert!(message[html..].contains("\n<p>Your code is 123456</p>\n"));
+    }
+
+    #[tokio::test]
+    async fn authenticates_with_configured_credentials() {
+        let (port, mut sessions) = spawn_smtp_sink().await;
+        let client = SmtpEmailClient::new(SmtpConfig {
+            credentials: Some(SmtpCredentials {
+                username: "mailer".to_owned(),
+                password: "s3cret".to_owned(),
+            }),
+            ..config(port)
+        })
+        .unwrap();
+
+        client
+            .send_email(&recipient(), &message("subject"))
+            .await
+            .unwrap();
+        drop(client);
+
+        let session = sessions.recv().await.unwrap();
+        // base64("\0mailer\0s3cret")
+        assert_eq!(
+            session.auth.as_deref(),
+            Some("AUTH PLAIN AG1haWxlcgBzM2NyZXQ=")
+        );
+    }
+
+    #[tokio::test]
+    async fn reuses_pooled_connection() {
+        let (port, mut sessions) = spawn_smtp_sink().await;
+        let client = SmtpEmailClient::new(config(port)).unwrap();
+
+        for subject in ["first", "second"] {
+            client
+                .send_email(&recipient(), &message(subject))
+                .await
+                .unwrap();
+            // Connections are handed back to the pool by a background task.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        drop(client);
+
+        let session = sessions.recv().await.unwrap();
+        assert_eq!(session.messages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn times_out_when_server_does_not_respond() {
+        // Accepts connections but never sends the greeting.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+        let client = SmtpEmailClient::new(SmtpConfig {
+            timeout: Duration::from_millis(200),
+            ..config(port)
+        })
+        .unwrap();
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.send_email(&recipient(), &message("subject")),
+        )
+        .await
+        .expect("send_email should give up on its own");
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn parses_tls_modes() {
+        assert_eq!(SmtpTls::parse("none").unwrap(), SmtpTls::None);
+        assert_eq!(SmtpTls::parse("STARTTLS").unwrap(), SmtpTls::StartTls);
+        assert_eq!(SmtpTls::parse("tls").unwrap(), SmtpTls::Implicit);
+        assert!(SmtpTls::parse("ssl3").is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result};
+
+use crate::{
+    app_state::WebhookStoreType,
+    domain::webhooks::{
+        sign_payload, WebhookDelivery, WebhookStoreError, DELIVERY_ID_HEADER, SIGNATURE_HEADER,
+        TIMESTAMP_HEADER,
+    },
+    utils::shutdown::ShutdownHandle,
+};
+
+#[derive(Debug, Clone)]
+pub struct WebhookDispatcherConfig {
+    pub poll_interval: Duration,
+    pub batch_size: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+    // Deliveries that failed this many times are moved to the dead-letter table.
+    pub max_attempts: i32,
+    pub request_timeout: Duration,
+}
+
+impl Default for WebhookDispatcherConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(1),
+            batch_size: 50,
+            base_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60 * 60),
+            max_attempts: 8,
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl WebhookDispatcherConfig {
+    // Delay before the next attempt once `attempts` attempts have failed:
+    // base_backoff * 2^(attempts - 1), capped at max_backoff.
+    pub fn backoff(&self, attempts: i32) -> Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+        self.base_backoff
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_backoff)
+    }
+
+    // How long a claimed batch stays leased. Deliveries are made one after the other, so every
+    // one of them may take the full request timeout; one more covers the store updates.
+    pub fn lease(&self) -> Duration {
+        self.request_timeout
+            .saturating_mul(self.batch_size.saturating_add(1))
+    }
+}
+
+// Background worker that POSTs queued webhook deliveries to their subscribers.
+pub struct WebhookDispatcher {
+    store: WebhookStoreType,
+    http_client: reqwest::Client,
+    config: WebhookDispatcherConfig,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: WebhookStoreType, config: WebhookDispatcherConfig) -> Result<Self> {
+        let http_client = reqwest::Client::builder()
+            .timeout(config.request_timeout)
+            .build()?;
+
+        Ok(Self {
+            store,
+            http_client,
+            config,
+        })
+    }
+
+    // Polls until shutdown is requested. A batch that is being delivered at that point is
+    // finished first, so no claimed delivery is left leased; anything still queued stays in
+    // the store for the next start.
+    pub async fn run(self, shutdown: ShutdownHandle) {
+        let mut interval = tokio::time::interval(self.config.poll_interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.requested() => break,
+            }
+            if let Err(e) = self.dispatch_due().await {
+                tracing::error!(error = ?e, "Failed to dispatch webhook deliveries");
+            }
+        }
+        tracing::info!("Webhook dispatcher stopped");
+    }
+
+    #[tracing::instrument(name = "Dispatching due webhook deliveries", skip_all)]
+    pub async fn dispatch_due(&self) -> Result<usize, WebhookStoreError> {
+        // No other worker may retry a delivery while this one still works through the batch.
+        let lease = chrono::Duration::from_std(self.config.lease())
+            .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+        let deliveries = self
+            .store
+            .claim_due_deliveries(self.config.batch_size, Utc::now() + lease)
+            .await?;
+
+        let count = deliveries.len();
+        for delivery in deliveries {
+            let outcome = self.deliver(&delivery).await;
+            self.record_outcome(&delivery, outcome).await?;
+        }
+
+        Ok(count)
+    }
+
+    #[tracing::instrument(name = "Delivering webhook", skip_all, fields(delivery_id = %delivery.id))]
+    async fn deliver(&self, delivery: &WebhookDelivery) -> Result<()> {
+        let timestamp = Utc::now().timestamp();
+        let signature = sign_payload(&delivery.secret, timestamp, &delivery.payload);
+
+        let response = self
+            .http_client
+            .post(&delivery.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(DELIVERY_ID_HEADER, delivery.id.to_string())
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+            .body(delivery.payload.clone())
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(eyre!("Subscriber responded with {}", status)),
+        }
+    }
+
+    async fn record_outcome(
+        &self,
+        delivery: &WebhookDelivery,
+        outcome: Result<()>,
+    ) -> Result<(), WebhookStoreError> {
+        let store = &self.store;
+        match outcome {
+            Ok(()) => store.mark_delivered(delivery.id).await,
+            Err(e) if delivery.attempts >= self.config.max_attempts => {
+                tracing::warn!(delivery_id = %delivery.id, error = %e, "Webhook delivery dead-lettered");
+                store.move_to_dead_letter(delivery.id, &e.to_string()).await
+            }
+            Err(e) => {
+                let delay = chrono::Duration::from_std(self.config.backoff(delivery.attempts))
+                    .map_err(|e| WebhookStoreError::UnexpectedError(e.into()))?;
+                store
+                    .schedule_retry(delivery.id, Utc::now() + delay, &e.to_string())
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_and_is_capped() {
+        let config = WebhookDispatcherConfig {
+            base_backoff: Duration::from_secs(10),
+            max_backoff: Duration::from_secs(60),
+            ..WebhookDispatcherConfig::default()
+        };
+
+        assert_eq!(config.backoff(1), Duration::from_secs(10));
+        assert_eq!(config.backoff(2), Duration::from_secs(20));
+        assert_eq!(config.backoff(3), Duration::from_secs(40));
+        assert_eq!(config.backoff(4), Duration::from_secs(60));
+        assert_eq!(config.backoff(100), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn lease_outlasts_a_batch_of_timed_out_deliveries() {
+        let config = WebhookDispatcherConfig {
+            batch_size: 50,
+            request_timeout: Duration::from_secs(10),
+            ..WebhookDispatcherConfig::default()
+        };
+
+        assert!(config.lease() > config.request_timeout * config.batch_size);
+    }
+}
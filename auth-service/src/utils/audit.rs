@@ -0,0 +1,79 @@
+use std::net::SocketAddr;
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts},
+};
+
+use crate::{
+    app_state::AuditLogType,
+    domain::{
+        audit_log::{AuditAction, AuditEvent, AuditOutcome, RequestMetadata},
+        email::Email,
+        error::AuthAPIError,
+    },
+    utils::tracing::RequestId,
+};
+
+// Extracts the client IP, user agent and request id of the current request for the audit log.
+pub struct AuditContext(pub RequestMetadata);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuditContext
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .map(|id| id.0.clone());
+
+        Ok(Self(RequestMetadata {
+            ip,
+            user_agent,
+            request_id,
+        }))
+    }
+}
+
+// Failing to write the audit trail must not fail the request itself, so errors are only logged.
+#[tracing::instrument(name = "Recording audit event", skip_all)]
+pub async fn record_audit_event(audit_log: &AuditLogType, event: AuditEvent) {
+    if let Err(e) = audit_log.record(event).await {
+        tracing::error!(error = ?e, "Failed to record audit event");
+    }
+}
+
+// Actors are recorded in canonical form, so that every spelling of an address is found under
+// the same one. Requests whose email does not parse have no actor.
+pub fn audit_actor(email: &str) -> Option<String> {
+    Email::parse(email)
+        .ok()
+        .map(|email| email.as_ref().to_owned())
+}
+
+pub fn audit_event_for<T>(
+    actor: Option<String>,
+    action: AuditAction,
+    result: &Result<T, AuthAPIError>,
+    metadata: RequestMetadata,
+) -> AuditEvent {
+    match result {
+        Ok(_) => AuditEvent::new(actor, action, AuditOutcome::Success, metadata),
+        Err(e) => AuditEvent::new(actor, action, AuditOutcome::Failure, metadata)
+            .with_detail(e.to_string()),
+    }
+}
@@ -1,26 +1,58 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
-use chrono::Utc;
+use axum::{async_trait, extract::FromRequestParts, http::request::Parts};
+use axum_extra::extract::cookie::{Cookie, CookieJar};
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::{
+        AppState, BannedTokenStoreType, PersonalAccessTokenStoreType, ServiceAccountStoreType,
+        UserStoreType,
+    },
+    domain::{
+        data_stores::UserStoreError,
+        email::Email,
+        error::AuthAPIError,
+        personal_access_token::{
+            PersonalAccessToken, PersonalAccessTokenSecret, PersonalAccessTokenStoreError,
+        },
+        profile::ProfileField,
+        scope::Scope,
+        service_account::{ClientId, ServiceAccount},
+        tenant::{CookieSettings, Tenant},
+        user::{User, UserId},
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
-
-#[tracing::instrument(name = "Generating auth cookie based on the email token ", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
-    Ok(create_auth_cookie(token))
+#[tracing::instrument(name = "Generating auth cookie for the user", skip_all)]
+pub fn generate_auth_cookie(
+    tenant: &Tenant,
+    user_id: &UserId,
+    email: &Email,
+    profile: Map<String, Value>,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(tenant, user_id, email, profile)?;
+    Ok(create_auth_cookie(token, &tenant.cookie))
 }
 
 #[tracing::instrument(name = "Creating auth cookie using Cookie::build", skip_all)]
-fn create_auth_cookie(token: String) -> Cookie<'static> {
-    let cookie = Cookie::build((JWT_COOKIE_NAME, token))
-        .path("/")
-        .http_only(true)
-        .same_site(SameSite::Lax)
-        .build();
+fn create_auth_cookie(token: String, settings: &CookieSettings) -> Cookie<'static> {
+    let mut cookie = removal_cookie(settings);
+    cookie.set_value(token);
+    cookie.set_http_only(true);
+    cookie.set_secure(settings.secure);
+    cookie.set_same_site(settings.same_site);
+    cookie
+}
 
+// Browsers only replace or remove a cookie given the same name, path and domain.
+pub fn removal_cookie(settings: &CookieSettings) -> Cookie<'static> {
+    let mut cookie = Cookie::new(settings.name.clone(), "");
+    cookie.set_path(settings.path.clone());
+    if let Some(domain) = &settings.domain {
+        cookie.set_domain(domain.clone());
+    }
     cookie
 }
 
@@ -33,11 +65,62 @@ pub enum GenerateTokenError {
 pub const TOKEN_TTL_SECONDS: i64 = 600;
 
 #[tracing::instrument(name = "Generate auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
+fn generate_auth_token(
+    tenant: &Tenant,
+    user_id: &UserId,
+    email: &Email,
+    profile: Map<String, Value>,
+) -> Result<String, GenerateTokenError> {
+    let now = Utc::now();
+    let (iat, exp) = issued_and_expiry(now)?;
+
+    let claims = Claims {
+        sub: user_id.to_string(),
+        sub_type: SubjectType::User,
+        email: Some(email.as_ref().to_owned()),
+        aud: tenant.id.to_string(),
+        exp,
+        iat,
+        iat_ms: Some(now.timestamp_millis()),
+        scope: None,
+        profile,
+    };
+
+    create_token(&claims, &tenant.jwt_secret).map_err(GenerateTokenError::TokenError)
+}
+
+// An access token for the client credentials grant. Its subject is the account's client id and
+// it carries the granted scopes instead of an email.
+#[tracing::instrument(name = "Generate service account token", skip_all)]
+pub fn generate_service_account_token(
+    tenant: &Tenant,
+    account: &ServiceAccount,
+    scopes: &[Scope],
+) -> Result<String, GenerateTokenError> {
+    let now = Utc::now();
+    let (iat, exp) = issued_and_expiry(now)?;
+
+    let claims = Claims {
+        sub: account.client_id.to_string(),
+        sub_type: SubjectType::ServiceAccount,
+        email: None,
+        aud: tenant.id.to_string(),
+        exp,
+        iat,
+        iat_ms: Some(now.timestamp_millis()),
+        scope: Some(Scope::join(scopes)),
+        profile: Map::new(),
+    };
+
+    create_token(&claims, &tenant.jwt_secret).map_err(GenerateTokenError::TokenError)
+}
+
+// `iat` and `exp` of a token issued at `now`.
+fn issued_and_expiry(now: DateTime<Utc>) -> Result<(usize, usize), GenerateTokenError> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
@@ -46,19 +129,106 @@ fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
-    let sub = email.as_ref().to_owned();
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
-    let claims = Claims { sub, exp };
+    Ok((iat, exp))
+}
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+// The profile fields the tenant projects into tokens, for the `profile` claim. Tokens carry
+// them as they were at login, so changes show up in the next token.
+pub async fn profile_claims(
+    user_store: &UserStoreType,
+    fields: &[ProfileField],
+    email: &Email,
+) -> Result<Map<String, Value>, UserStoreError> {
+    if fields.is_empty() {
+        return Ok(Map::new());
+    }
+    let profile = user_store.get_profile(email).await?;
+    Ok(profile.claims(fields))
 }
 
+// Accepts tokens of users and of service accounts; `sub_type` tells them apart.
 #[tracing::instrument(name = "Validating token", skip_all)]
 pub async fn validate_token(
     token: &str,
+    tenant: &Tenant,
     banned_token_store: BannedTokenStoreType,
+    user_store: UserStoreType,
+    service_account_store: ServiceAccountStoreType,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
-    match banned_token_store.read().await.contains_token(token).await {
+    let claims = decode_token(token, tenant, &banned_token_store).await?;
+    match claims.sub_type {
+        SubjectType::User => check_user(claims, user_store)
+            .await
+            .map(|(claims, _)| claims),
+        SubjectType::ServiceAccount => {
+            // The account may have been deleted after the token was issued.
+            let client_id = ClientId::parse(&claims.sub).map_err(|_| {
+                jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidSubject)
+            })?;
+            match service_account_store.get_service_account(&client_id).await {
+                Ok(_) => Ok(claims),
+                Err(_) => Err(jsonwebtoken::errors::Error::from(
+                    jsonwebtoken::errors::ErrorKind::InvalidSubject,
+                )),
+            }
+        }
+    }
+}
+
+// Returns the token and the user who created it, if the token is known, has not expired and
+// the user is still active. Revoking all sessions of a user revokes their personal access
+// tokens too.
+#[tracing::instrument(name = "Validating personal access token", skip_all)]
+pub async fn validate_personal_access_token(
+    token: &str,
+    personal_access_token_store: PersonalAccessTokenStoreType,
+    user_store: UserStoreType,
+) -> Result<(PersonalAccessToken, User), AuthAPIError> {
+    let secret = PersonalAccessTokenSecret::parse(token).map_err(|_| AuthAPIError::InvalidToken)?;
+    let token = match personal_access_token_store.find_token(&secret).await {
+        Ok(token) if !token.is_expired() => token,
+        Ok(_) | Err(PersonalAccessTokenStoreError::NotFound) => {
+            return Err(AuthAPIError::InvalidToken)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    match user_store.get_user_by_id(&token.user_id).await {
+        Ok(user) if user.is_active() => Ok((token, user)),
+        Ok(_) | Err(UserStoreError::UserNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+// Validates a user's token and returns the user it was issued to, as currently stored.
+async fn authenticate_token(
+    token: &str,
+    tenant: &Tenant,
+    banned_token_store: BannedTokenStoreType,
+    user_store: UserStoreType,
+) -> Result<(Claims, User), jsonwebtoken::errors::Error> {
+    let claims = decode_token(token, tenant, &banned_token_store).await?;
+    if claims.sub_type != SubjectType::User {
+        return Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidSubject,
+        ));
+    }
+    check_user(claims, user_store).await
+}
+
+// Checks what every token must pass, whoever its subject. Tokens of other tenants are rejected
+// by both their signature and their audience.
+async fn decode_token(
+    token: &str,
+    tenant: &Tenant,
+    banned_token_store: &BannedTokenStoreType,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    match banned_token_store.contains_token(token).await {
         Ok(value) => {
             if value {
                 return Err(jsonwebtoken::errors::Error::from(
@@ -73,45 +243,192 @@ pub async fn validate_token(
         }
     }
 
-    decode::<Claims>(
+    let mut validation = Validation::default();
+    validation.set_audience(&[tenant.id.as_ref()]);
+    let claims = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
+        &DecodingKey::from_secret(tenant.jwt_secret.as_bytes()),
+        &validation,
     )
-    .map(|data| data.claims)
+    .map(|data| data.claims)?;
+
+    // Tokens issued before an admin revoked every session of the subject are no longer valid.
+    match banned_token_store.tokens_revoked_before(&claims.sub).await {
+        Ok(Some(cutoff)) if claims.issued_at_millis() < cutoff => {
+            return Err(jsonwebtoken::errors::Error::from(
+                jsonwebtoken::errors::ErrorKind::InvalidToken,
+            ));
+        }
+        Ok(_) => {}
+        Err(_) => {
+            return Err(jsonwebtoken::errors::Error::from(
+                jsonwebtoken::errors::ErrorKind::InvalidToken,
+            ));
+        }
+    }
+
+    Ok(claims)
+}
+
+async fn check_user(
+    claims: Claims,
+    user_store: UserStoreType,
+) -> Result<(Claims, User), jsonwebtoken::errors::Error> {
+    // The subject may have been suspended after the token was issued.
+    let user_id = UserId::parse(&claims.sub).map_err(|_| {
+        jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidSubject)
+    })?;
+    match user_store.get_user_by_id(&user_id).await {
+        Ok(user) if user.is_active() => Ok((claims, user)),
+        _ => Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidSubject,
+        )),
+    }
+}
+
+// The signed-in user's current email, for routes that act on the caller's own account.
+// Rejects requests without a valid auth cookie.
+pub struct AuthenticatedUser(pub Email);
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let token = jar
+            .get(&state.tenant.cookie.name)
+            .ok_or(AuthAPIError::MissingToken)?
+            .value();
+
+        let (_, user) = authenticate_token(
+            token,
+            &state.tenant,
+            state.banned_token_store.clone(),
+            state.user_store.clone(),
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        Ok(Self(user.email))
+    }
 }
 
 #[tracing::instrument(name = "Creating token", skip_all)]
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
+fn create_token(claims: &Claims, secret: &str) -> Result<String, jsonwebtoken::errors::Error> {
     encode(
         &jsonwebtoken::Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &EncodingKey::from_secret(secret.as_bytes()),
     )
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    // The user's id, which stays the same when their email changes, or the service account's
+    // client id.
     pub sub: String,
+    #[serde(default)]
+    pub sub_type: SubjectType,
+    // Only in tokens of users.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    // The id of the tenant that issued the token.
+    pub aud: String,
     pub exp: usize,
+    pub iat: usize,
+    // `iat` in milliseconds, so that a token issued in the same second as a revocation but after
+    // it stays valid. Missing from tokens issued before it was added.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat_ms: Option<i64>,
+    // The space-delimited scopes granted to a service account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub profile: Map<String, Value>,
+}
+
+impl Claims {
+    pub fn issued_at_millis(&self) -> i64 {
+        self.iat_ms.unwrap_or(self.iat as i64 * 1000)
+    }
+}
+
+// Whether a token was issued to a person or to a service account. Tokens without the claim
+// predate service accounts and belong to users.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubjectType {
+    #[default]
+    User,
+    ServiceAccount,
 }
 
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
-    use tokio::sync::RwLock;
 
     use crate::{
-        domain::data_stores::BannedTokenStore,
-        services::data_stores::hashset_banned_token_store::HashsetBannedTokenStore,
+        domain::{
+            data_stores::{BannedTokenStore, UserStore},
+            personal_access_token::PersonalAccessTokenStore,
+            service_account::{ClientSecret, ServiceAccountStore},
+            user::UserStatus,
+        },
+        services::data_stores::{
+            hashmap_personal_access_token_store::HashmapPersonalAccessTokenStore,
+            hashmap_service_account_store::HashmapServiceAccountStore,
+            hashmap_user_store::HashmapUserStore,
+            hashset_banned_token_store::HashsetBannedTokenStore,
+        },
     };
 
+    use axum_extra::extract::cookie::SameSite;
+
     use super::*;
+    use crate::domain::tenant::TenantId;
+    use crate::utils::constants::JWT_COOKIE_NAME;
+
+    fn tenant() -> Tenant {
+        Tenant::from_env()
+    }
+
+    fn service_accounts() -> ServiceAccountStoreType {
+        Arc::new(HashmapServiceAccountStore::default())
+    }
+
+    async fn personal_access_token(
+        user_id: UserId,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> (PersonalAccessTokenStoreType, PersonalAccessTokenSecret) {
+        let store = HashmapPersonalAccessTokenStore::default();
+        let secret = PersonalAccessTokenSecret::default();
+        let scopes = Scope::parse_list("repo:read").unwrap();
+        let mut token =
+            PersonalAccessToken::new(user_id, "CI".to_owned(), scopes, None, &secret).unwrap();
+        // Set afterwards, since tokens cannot be created already expired.
+        token.expires_at = expires_at;
+        store.add_token(token, &secret).await.unwrap();
+        (Arc::new(store), secret)
+    }
+
+    async fn user_store_with(email: &Email, status: UserStatus) -> (UserStoreType, UserId) {
+        let store = HashmapUserStore::default();
+        let user = User::new(email.as_ref().to_owned(), "password123".to_owned(), false);
+        let user_id = user.id;
+        store.add_user(user).await.unwrap();
+        store.set_status(email, status).await.unwrap();
+        (Arc::new(store), user_id)
+    }
 
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com").unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie =
+            generate_auth_cookie(&tenant(), &UserId::default(), &email, Map::new()).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -122,7 +439,7 @@ mod tests {
     #[tokio::test]
     async fn test_create_auth_cookie() {
         let token = "test_token".to_owned();
-        let cookie = create_auth_cookie(token.clone());
+        let cookie = create_auth_cookie(token.clone(), &CookieSettings::default());
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value(), token);
         assert_eq!(cookie.path(), Some("/"));
@@ -133,17 +450,29 @@ mod tests {
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result =
+            generate_auth_token(&tenant(), &UserId::default(), &email, Map::new()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
-        assert_eq!(result.sub, "test@example.com");
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let token = generate_auth_token(&tenant(), &user_id, &email, Map::new()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.sub, user_id.to_string());
+        assert_eq!(result.email.as_deref(), Some("test@example.com"));
+        assert_eq!(result.sub_type, SubjectType::User);
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -153,22 +482,322 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_validate_token_with_profile_claims() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let profile = serde_json::json!({ "displayName": "Test" })
+            .as_object()
+            .unwrap()
+            .clone();
+        let token = generate_auth_token(&tenant(), &user_id, &email, profile.clone()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.profile, profile);
+    }
+
+    #[tokio::test]
+    async fn test_auth_cookie_uses_tenant_cookie_settings() {
+        let email = Email::parse("test@example.com").unwrap();
+        let mut tenant = Tenant::new(TenantId::parse("acme").unwrap(), "secret".to_owned());
+        tenant.cookie = CookieSettings {
+            name: "acme_jwt".to_owned(),
+            path: "/realms/acme".to_owned(),
+            domain: Some("acme.test".to_owned()),
+            secure: true,
+            same_site: SameSite::Strict,
+        };
+        let cookie = generate_auth_cookie(&tenant, &UserId::default(), &email, Map::new()).unwrap();
+        assert_eq!(cookie.name(), "acme_jwt");
+        assert_eq!(cookie.path(), Some("/realms/acme"));
+        assert_eq!(cookie.domain(), Some("acme.test"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_of_another_tenant() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let acme = Tenant::new(TenantId::parse("acme").unwrap(), "secret".to_owned());
+        let token = generate_auth_token(&acme, &user_id, &email, Map::new()).unwrap();
+
+        // Same secret, other audience.
+        let globex = Tenant::new(TenantId::parse("globex").unwrap(), "secret".to_owned());
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            &globex,
+            banned_token_store.clone(),
+            user_store.clone(),
+            service_accounts(),
+        )
+        .await;
+        assert!(result.is_err());
+
+        // Same audience, other secret.
+        let impostor = Tenant::new(TenantId::parse("acme").unwrap(), "other".to_owned());
+        let result = validate_token(
+            &token,
+            &impostor,
+            banned_token_store.clone(),
+            user_store.clone(),
+            service_accounts(),
+        )
+        .await;
+        assert!(result.is_err());
+
+        let result = validate_token(
+            &token,
+            &acme,
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await;
+        assert_eq!(result.unwrap().aud, "acme");
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let user_store = Arc::new(HashmapUserStore::default());
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email = Email::parse("test@example.com").unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let mut hs = HashsetBannedTokenStore::default();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let token = generate_auth_token(&tenant(), &user_id, &email, Map::new()).unwrap();
+        let hs = HashsetBannedTokenStore::default();
         hs.add_token(token.clone()).await.unwrap();
-        let banned_token_store = Arc::new(RwLock::new(hs));
-        let result = validate_token(&token, banned_token_store).await;
+        let banned_token_store = Arc::new(hs);
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_sessions() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let token = generate_auth_token(&tenant(), &user_id, &email, Map::new()).unwrap();
+        let hs = HashsetBannedTokenStore::default();
+        // Later than the token was issued, even if that was within the same millisecond.
+        hs.revoke_all_tokens(&user_id.to_string(), Utc::now().timestamp_millis() + 1)
+            .await
+            .unwrap();
+        let banned_token_store = Arc::new(hs);
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_issued_after_revocation_in_the_same_second() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let token = generate_auth_token(&tenant(), &user_id, &email, Map::new()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let tenant = tenant();
+        let validate = || {
+            validate_token(
+                &token,
+                &tenant,
+                banned_token_store.clone(),
+                user_store.clone(),
+                service_accounts(),
+            )
+        };
+        let issued_at = validate().await.unwrap().issued_at_millis();
+        assert_eq!(issued_at / 1000, validate().await.unwrap().iat as i64);
+
+        banned_token_store
+            .revoke_all_tokens(&user_id.to_string(), issued_at)
+            .await
+            .unwrap();
+        assert!(validate().await.is_ok());
+        banned_token_store
+            .revoke_all_tokens(&user_id.to_string(), issued_at + 1)
+            .await
+            .unwrap();
+        assert!(validate().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_unknown_subject() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, _) = user_store_with(&email, UserStatus::Active).await;
+        // Another user's id, e.g. one whose account was deleted.
+        let token = generate_auth_token(&tenant(), &UserId::default(), &email, Map::new()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_token_with_suspended_subject() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Suspended).await;
+        let token = generate_auth_token(&tenant(), &user_id, &email, Map::new()).unwrap();
+        let banned_token_store = Arc::new(HashsetBannedTokenStore::default());
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_service_account_token() {
+        let store = HashmapServiceAccountStore::default();
+        let account = ServiceAccount::new(
+            "Nightly export".to_owned(),
+            Scope::parse_list("jobs:run reports:read").unwrap(),
+        )
+        .unwrap();
+        store
+            .add_service_account(account.clone(), &ClientSecret::default())
+            .await
+            .unwrap();
+        let service_accounts: ServiceAccountStoreType = Arc::new(store);
+        let scopes = Scope::parse_list("reports:read").unwrap();
+        let token = generate_service_account_token(&tenant(), &account, &scopes).unwrap();
+        let banned_token_store: BannedTokenStoreType = Arc::new(HashsetBannedTokenStore::default());
+        let user_store: UserStoreType = Arc::new(HashmapUserStore::default());
+
+        let claims = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store.clone(),
+            user_store.clone(),
+            service_accounts.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(claims.sub, account.client_id.to_string());
+        assert_eq!(claims.sub_type, SubjectType::ServiceAccount);
+        assert_eq!(claims.scope.as_deref(), Some("reports:read"));
+        assert_eq!(claims.email, None);
+
+        // Service accounts cannot act as a signed-in user.
+        let result = authenticate_token(
+            &token,
+            &tenant(),
+            banned_token_store.clone(),
+            user_store.clone(),
+        )
+        .await;
+        assert!(result.is_err());
+
+        service_accounts
+            .delete_service_account(&account.client_id)
+            .await
+            .unwrap();
+        let result = validate_token(
+            &token,
+            &tenant(),
+            banned_token_store,
+            user_store,
+            service_accounts,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn claims_without_subject_type_belong_to_users() {
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "sub": UserId::default().to_string(),
+            "email": "test@example.com",
+            "aud": "default",
+            "exp": 2,
+            "iat": 1
+        }))
+        .unwrap();
+        assert_eq!(claims.sub_type, SubjectType::User);
+    }
+
+    #[tokio::test]
+    async fn test_validate_personal_access_token() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let (store, secret) = personal_access_token(user_id, None).await;
+
+        let (token, user) =
+            validate_personal_access_token(secret.as_ref(), store.clone(), user_store.clone())
+                .await
+                .unwrap();
+        assert_eq!(user.id, user_id);
+        assert_eq!(token.scopes, Scope::parse_list("repo:read").unwrap());
+
+        let unknown = PersonalAccessTokenSecret::default();
+        for token in [unknown.as_ref(), "pat_", "invalid_token"] {
+            let result =
+                validate_personal_access_token(token, store.clone(), user_store.clone()).await;
+            assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_validate_expired_personal_access_token() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Active).await;
+        let expired = Utc::now() - chrono::Duration::try_seconds(1).unwrap();
+        let (store, secret) = personal_access_token(user_id, Some(expired)).await;
+
+        let result = validate_personal_access_token(secret.as_ref(), store, user_store).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_personal_access_token_of_suspended_user() {
+        let email = Email::parse("test@example.com").unwrap();
+        let (user_store, user_id) = user_store_with(&email, UserStatus::Suspended).await;
+        let (store, secret) = personal_access_token(user_id, None).await;
+
+        let result = validate_personal_access_token(secret.as_ref(), store, user_store).await;
+        assert!(matches!(result, Err(AuthAPIError::InvalidToken)));
+    }
 }
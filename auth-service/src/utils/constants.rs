@@ -1,12 +1,66 @@
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
 use std::env as std_env;
+use std::time::Duration;
+
+use crate::domain::password::{
+    DEFAULT_MAX_PASSWORD_LENGTH, DEFAULT_MIN_PASSWORD_STRENGTH, DEFAULT_PASSWORD_HISTORY_SIZE,
+};
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
     pub static ref JWT_SECRET: String = set_token();
     pub static ref DATABASE_URL: String = set_db_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host(); // New!
+    pub static ref ADMIN_API_KEY: String = set_admin_api_key();
+    pub static ref LOG_FORMAT: String = set_log_format();
+    pub static ref SHUTDOWN_DRAIN_TIMEOUT: Duration = set_shutdown_drain_timeout();
+    pub static ref EMAIL_CLIENT: String = set_email_client();
+    pub static ref SMS_CLIENT: String = set_sms_client();
+    pub static ref REDIS_COMMAND_TIMEOUT: Duration = set_redis_command_timeout();
+    pub static ref PASSWORD_MAX_LENGTH: usize = set_password_max_length();
+    pub static ref PASSWORD_MIN_STRENGTH: u8 = set_password_min_strength();
+    pub static ref PASSWORD_BREACH_CORPUS_PATH: String = set_password_breach_corpus_path();
+    pub static ref PASSWORD_HISTORY_SIZE: usize = set_password_history_size();
+    pub static ref PASSWORD_MAX_AGE_DAYS: u32 = set_password_max_age_days();
+    pub static ref EMAIL_FOLDING_DOMAINS: String = set_email_folding_domains();
+    pub static ref PUBLIC_URL: String = set_public_url();
+    pub static ref JWT_PROFILE_CLAIMS: String = set_jwt_profile_claims();
+    pub static ref TENANTS_CONFIG_PATH: String = set_tenants_config_path();
+    pub static ref SMTP_HOST: String = set_optional_var(env::SMTP_HOST_ENV_VAR);
+    pub static ref SMTP_PORT: Option<u16> = set_smtp_port();
+    pub static ref SMTP_TLS: String = set_optional_var(env::SMTP_TLS_ENV_VAR);
+    pub static ref SMTP_USERNAME: String = set_optional_var(env::SMTP_USERNAME_ENV_VAR);
+    pub static ref SMTP_PASSWORD: String = set_optional_var(env::SMTP_PASSWORD_ENV_VAR);
+    pub static ref SMTP_SENDER: String = set_optional_var(env::SMTP_SENDER_ENV_VAR);
+    pub static ref SMTP_TIMEOUT: Duration = set_smtp_timeout();
+    pub static ref SMTP_POOL_SIZE: u32 = set_smtp_pool_size();
+    pub static ref EMAIL_API_BASE_URL: String = set_optional_var(env::EMAIL_API_BASE_URL_ENV_VAR);
+    pub static ref EMAIL_API_TOKEN: String = set_optional_var(env::EMAIL_API_TOKEN_ENV_VAR);
+    pub static ref EMAIL_API_SENDER: String = set_optional_var(env::EMAIL_API_SENDER_ENV_VAR);
+    pub static ref EMAIL_API_TIMEOUT: Duration = set_email_api_timeout();
+    pub static ref EMAIL_BRAND_NAME: String = set_email_brand_name();
+    pub static ref EMAIL_SUPPORT_ADDRESS: String =
+        set_optional_var(env::EMAIL_SUPPORT_ADDRESS_ENV_VAR);
+    pub static ref EMAIL_BRAND_URL: String = set_optional_var(env::EMAIL_BRAND_URL_ENV_VAR);
+    pub static ref EMAIL_BRAND_ACCENT_COLOR: String = set_email_brand_accent_color();
+    pub static ref SMS_GATEWAY_BASE_URL: String =
+        set_optional_var(env::SMS_GATEWAY_BASE_URL_ENV_VAR);
+    pub static ref SMS_GATEWAY_TOKEN: String = set_optional_var(env::SMS_GATEWAY_TOKEN_ENV_VAR);
+    pub static ref SMS_SENDER: String = set_optional_var(env::SMS_SENDER_ENV_VAR);
+    pub static ref SMS_GATEWAY_TIMEOUT: Duration = set_sms_gateway_timeout();
+    pub static ref PASSWORD_HASH_MEMORY_KIB: u32 = set_password_hash_param(
+        env::PASSWORD_HASH_MEMORY_KIB_ENV_VAR,
+        DEFAULT_PASSWORD_HASH_MEMORY_KIB
+    );
+    pub static ref PASSWORD_HASH_ITERATIONS: u32 = set_password_hash_param(
+        env::PASSWORD_HASH_ITERATIONS_ENV_VAR,
+        DEFAULT_PASSWORD_HASH_ITERATIONS
+    );
+    pub static ref PASSWORD_HASH_PARALLELISM: u32 = set_password_hash_param(
+        env::PASSWORD_HASH_PARALLELISM_ENV_VAR,
+        DEFAULT_PASSWORD_HASH_PARALLELISM
+    );
 }
 
 fn set_token() -> String {
@@ -27,6 +81,214 @@ fn set_db_url() -> String {
     secret
 }
 
+fn set_admin_api_key() -> String {
+    dotenv().ok();
+    let key = std_env::var(env::ADMIN_API_KEY_ENV_VAR).expect("ADMIN_API_KEY must be set.");
+    if key.is_empty() {
+        panic!("ADMIN_API_KEY must not be empty.");
+    }
+    key
+}
+
+fn set_log_format() -> String {
+    dotenv().ok();
+    std_env::var(env::LOG_FORMAT_ENV_VAR).unwrap_or(DEFAULT_LOG_FORMAT.to_owned())
+}
+
+fn set_shutdown_drain_timeout() -> Duration {
+    dotenv().ok();
+    let seconds = match std_env::var(env::SHUTDOWN_DRAIN_TIMEOUT_SECS_ENV_VAR) {
+        Ok(seconds) => seconds
+            .parse()
+            .expect("SHUTDOWN_DRAIN_TIMEOUT_SECS must be a number of seconds."),
+        Err(_) => DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_SECS,
+    };
+    Duration::from_secs(seconds)
+}
+
+fn set_email_client() -> String {
+    dotenv().ok();
+    std_env::var(env::EMAIL_CLIENT_ENV_VAR).unwrap_or(DEFAULT_EMAIL_CLIENT.to_owned())
+}
+
+fn set_sms_client() -> String {
+    dotenv().ok();
+    std_env::var(env::SMS_CLIENT_ENV_VAR).unwrap_or(DEFAULT_SMS_CLIENT.to_owned())
+}
+
+fn set_redis_command_timeout() -> Duration {
+    dotenv().ok();
+    let millis = match std_env::var(env::REDIS_COMMAND_TIMEOUT_MS_ENV_VAR) {
+        Ok(millis) => millis
+            .parse()
+            .expect("REDIS_COMMAND_TIMEOUT_MS must be a number of milliseconds."),
+        Err(_) => DEFAULT_REDIS_COMMAND_TIMEOUT_MS,
+    };
+    Duration::from_millis(millis)
+}
+
+fn set_password_max_length() -> usize {
+    dotenv().ok();
+    match std_env::var(env::PASSWORD_MAX_LENGTH_ENV_VAR) {
+        Ok(length) => length
+            .parse()
+            .expect("PASSWORD_MAX_LENGTH must be a number of characters."),
+        Err(_) => DEFAULT_MAX_PASSWORD_LENGTH,
+    }
+}
+
+fn set_password_min_strength() -> u8 {
+    dotenv().ok();
+    let strength = match std_env::var(env::PASSWORD_MIN_STRENGTH_ENV_VAR) {
+        Ok(strength) => strength
+            .parse()
+            .expect("PASSWORD_MIN_STRENGTH must be a score from 0 to 4."),
+        Err(_) => DEFAULT_MIN_PASSWORD_STRENGTH,
+    };
+    if strength > 4 {
+        panic!("PASSWORD_MIN_STRENGTH must be a score from 0 to 4.");
+    }
+    strength
+}
+
+// Empty when passwords aren't checked against a breach corpus.
+fn set_password_breach_corpus_path() -> String {
+    dotenv().ok();
+    std_env::var(env::PASSWORD_BREACH_CORPUS_PATH_ENV_VAR).unwrap_or_default()
+}
+
+fn set_password_history_size() -> usize {
+    dotenv().ok();
+    match std_env::var(env::PASSWORD_HISTORY_SIZE_ENV_VAR) {
+        Ok(size) => size
+            .parse()
+            .expect("PASSWORD_HISTORY_SIZE must be a number of passwords."),
+        Err(_) => DEFAULT_PASSWORD_HISTORY_SIZE,
+    }
+}
+
+// 0 when passwords never expire.
+fn set_password_max_age_days() -> u32 {
+    dotenv().ok();
+    match std_env::var(env::PASSWORD_MAX_AGE_DAYS_ENV_VAR) {
+        Ok(days) => days
+            .parse()
+            .expect("PASSWORD_MAX_AGE_DAYS must be a number of days."),
+        Err(_) => 0,
+    }
+}
+
+// Empty when no domain folds the local part of its addresses.
+fn set_email_folding_domains() -> String {
+    dotenv().ok();
+    std_env::var(env::EMAIL_FOLDING_DOMAINS_ENV_VAR).unwrap_or_default()
+}
+
+// Where users reach the service, for links in emails.
+fn set_public_url() -> String {
+    dotenv().ok();
+    let url = std_env::var(env::PUBLIC_URL_ENV_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_PUBLIC_URL.to_owned());
+    url.trim_end_matches('/').to_owned()
+}
+
+// Empty when tokens carry no profile fields.
+fn set_jwt_profile_claims() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_PROFILE_CLAIMS_ENV_VAR).unwrap_or_default()
+}
+
+// Empty when the service has a single tenant, configured by the other variables.
+fn set_tenants_config_path() -> String {
+    dotenv().ok();
+    std_env::var(env::TENANTS_CONFIG_PATH_ENV_VAR).unwrap_or_default()
+}
+
+// Empty when unset. For settings that are only required by some configurations, such as
+// those of the email client selected with EMAIL_CLIENT.
+fn set_optional_var(name: &str) -> String {
+    dotenv().ok();
+    std_env::var(name).unwrap_or_default()
+}
+
+// None to use the default port of SMTP_TLS.
+fn set_smtp_port() -> Option<u16> {
+    dotenv().ok();
+    std_env::var(env::SMTP_PORT_ENV_VAR)
+        .ok()
+        .filter(|port| !port.is_empty())
+        .map(|port| port.parse().expect("SMTP_PORT must be a port number."))
+}
+
+fn set_smtp_timeout() -> Duration {
+    dotenv().ok();
+    let seconds = match std_env::var(env::SMTP_TIMEOUT_SECS_ENV_VAR) {
+        Ok(seconds) if !seconds.is_empty() => seconds
+            .parse()
+            .expect("SMTP_TIMEOUT_SECS must be a number of seconds."),
+        _ => DEFAULT_SMTP_TIMEOUT_SECS,
+    };
+    Duration::from_secs(seconds)
+}
+
+fn set_smtp_pool_size() -> u32 {
+    dotenv().ok();
+    match std_env::var(env::SMTP_POOL_SIZE_ENV_VAR) {
+        Ok(size) if !size.is_empty() => size.parse().expect("SMTP_POOL_SIZE must be a number."),
+        _ => DEFAULT_SMTP_POOL_SIZE,
+    }
+}
+
+fn set_email_api_timeout() -> Duration {
+    dotenv().ok();
+    let seconds = match std_env::var(env::EMAIL_API_TIMEOUT_SECS_ENV_VAR) {
+        Ok(seconds) if !seconds.is_empty() => seconds
+            .parse()
+            .expect("EMAIL_API_TIMEOUT_SECS must be a number of seconds."),
+        _ => DEFAULT_EMAIL_API_TIMEOUT_SECS,
+    };
+    Duration::from_secs(seconds)
+}
+
+fn set_email_brand_name() -> String {
+    dotenv().ok();
+    std_env::var(env::EMAIL_BRAND_NAME_ENV_VAR)
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_EMAIL_BRAND_NAME.to_owned())
+}
+
+fn set_email_brand_accent_color() -> String {
+    dotenv().ok();
+    std_env::var(env::EMAIL_BRAND_ACCENT_COLOR_ENV_VAR)
+        .ok()
+        .filter(|color| !color.is_empty())
+        .unwrap_or(DEFAULT_EMAIL_BRAND_ACCENT_COLOR.to_owned())
+}
+
+fn set_sms_gateway_timeout() -> Duration {
+    dotenv().ok();
+    let seconds = match std_env::var(env::SMS_GATEWAY_TIMEOUT_SECS_ENV_VAR) {
+        Ok(seconds) if !seconds.is_empty() => seconds
+            .parse()
+            .expect("SMS_GATEWAY_TIMEOUT_SECS must be a number of seconds."),
+        _ => DEFAULT_SMS_GATEWAY_TIMEOUT_SECS,
+    };
+    Duration::from_secs(seconds)
+}
+
+fn set_password_hash_param(name: &str, default: u32) -> u32 {
+    dotenv().ok();
+    match std_env::var(name) {
+        Ok(value) if !value.is_empty() => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} must be a positive number.", name)),
+        _ => default,
+    }
+}
+
 fn set_redis_host() -> String {
     dotenv().ok();
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
@@ -36,10 +298,65 @@ pub mod env {
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const REDIS_COMMAND_TIMEOUT_MS_ENV_VAR: &str = "REDIS_COMMAND_TIMEOUT_MS";
+    pub const ADMIN_API_KEY_ENV_VAR: &str = "ADMIN_API_KEY";
+    pub const LOG_FORMAT_ENV_VAR: &str = "LOG_FORMAT";
+    pub const SHUTDOWN_DRAIN_TIMEOUT_SECS_ENV_VAR: &str = "SHUTDOWN_DRAIN_TIMEOUT_SECS";
+    pub const EMAIL_CLIENT_ENV_VAR: &str = "EMAIL_CLIENT";
+    pub const SMTP_HOST_ENV_VAR: &str = "SMTP_HOST";
+    pub const SMTP_PORT_ENV_VAR: &str = "SMTP_PORT";
+    pub const SMTP_TLS_ENV_VAR: &str = "SMTP_TLS";
+    pub const SMTP_USERNAME_ENV_VAR: &str = "SMTP_USERNAME";
+    pub const SMTP_PASSWORD_ENV_VAR: &str = "SMTP_PASSWORD";
+    pub const SMTP_SENDER_ENV_VAR: &str = "SMTP_SENDER";
+    pub const SMTP_TIMEOUT_SECS_ENV_VAR: &str = "SMTP_TIMEOUT_SECS";
+    pub const SMTP_POOL_SIZE_ENV_VAR: &str = "SMTP_POOL_SIZE";
+    pub const EMAIL_API_BASE_URL_ENV_VAR: &str = "EMAIL_API_BASE_URL";
+    pub const EMAIL_API_TOKEN_ENV_VAR: &str = "EMAIL_API_TOKEN";
+    pub const EMAIL_API_SENDER_ENV_VAR: &str = "EMAIL_API_SENDER";
+    pub const EMAIL_API_TIMEOUT_SECS_ENV_VAR: &str = "EMAIL_API_TIMEOUT_SECS";
+    pub const EMAIL_BRAND_NAME_ENV_VAR: &str = "EMAIL_BRAND_NAME";
+    pub const EMAIL_SUPPORT_ADDRESS_ENV_VAR: &str = "EMAIL_SUPPORT_ADDRESS";
+    pub const EMAIL_BRAND_URL_ENV_VAR: &str = "EMAIL_BRAND_URL";
+    pub const EMAIL_BRAND_ACCENT_COLOR_ENV_VAR: &str = "EMAIL_BRAND_ACCENT_COLOR";
+    pub const SMS_CLIENT_ENV_VAR: &str = "SMS_CLIENT";
+    pub const SMS_GATEWAY_BASE_URL_ENV_VAR: &str = "SMS_GATEWAY_BASE_URL";
+    pub const SMS_GATEWAY_TOKEN_ENV_VAR: &str = "SMS_GATEWAY_TOKEN";
+    pub const SMS_SENDER_ENV_VAR: &str = "SMS_SENDER";
+    pub const SMS_GATEWAY_TIMEOUT_SECS_ENV_VAR: &str = "SMS_GATEWAY_TIMEOUT_SECS";
+    pub const PASSWORD_HASH_MEMORY_KIB_ENV_VAR: &str = "PASSWORD_HASH_MEMORY_KIB";
+    pub const PASSWORD_HASH_ITERATIONS_ENV_VAR: &str = "PASSWORD_HASH_ITERATIONS";
+    pub const PASSWORD_HASH_PARALLELISM_ENV_VAR: &str = "PASSWORD_HASH_PARALLELISM";
+    pub const PASSWORD_MAX_LENGTH_ENV_VAR: &str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_MIN_STRENGTH_ENV_VAR: &str = "PASSWORD_MIN_STRENGTH";
+    pub const PASSWORD_BREACH_CORPUS_PATH_ENV_VAR: &str = "PASSWORD_BREACH_CORPUS_PATH";
+    pub const PASSWORD_HISTORY_SIZE_ENV_VAR: &str = "PASSWORD_HISTORY_SIZE";
+    pub const PASSWORD_MAX_AGE_DAYS_ENV_VAR: &str = "PASSWORD_MAX_AGE_DAYS";
+    pub const EMAIL_FOLDING_DOMAINS_ENV_VAR: &str = "EMAIL_FOLDING_DOMAINS";
+    pub const PUBLIC_URL_ENV_VAR: &str = "AUTH_SERVICE_PUBLIC_URL";
+    pub const JWT_PROFILE_CLAIMS_ENV_VAR: &str = "JWT_PROFILE_CLAIMS";
+    pub const TENANTS_CONFIG_PATH_ENV_VAR: &str = "TENANTS_CONFIG_PATH";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1"; 
+pub const DEFAULT_REDIS_COMMAND_TIMEOUT_MS: u64 = 500;
+// Each attempt to (re)connect to Redis gives up after this long.
+pub const REDIS_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+pub const DEFAULT_LOG_FORMAT: &str = "compact";
+pub const DEFAULT_SHUTDOWN_DRAIN_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_EMAIL_CLIENT: &str = "mock";
+pub const DEFAULT_SMS_CLIENT: &str = "mock";
+pub const DEFAULT_PUBLIC_URL: &str = "http://localhost:3000";
+pub const DEFAULT_SMTP_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_SMTP_POOL_SIZE: u32 = 4;
+pub const DEFAULT_EMAIL_API_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_EMAIL_BRAND_NAME: &str = "Auth Service";
+pub const DEFAULT_EMAIL_BRAND_ACCENT_COLOR: &str = "#2563eb";
+pub const DEFAULT_SMS_GATEWAY_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_PASSWORD_HASH_MEMORY_KIB: u32 = 15000;
+pub const DEFAULT_PASSWORD_HASH_ITERATIONS: u32 = 2;
+pub const DEFAULT_PASSWORD_HASH_PARALLELISM: u32 = 1;
 
 pub mod prod {
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
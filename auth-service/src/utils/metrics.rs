@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+use axum::http::{Method, StatusCode};
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::domain::error::AuthAPIError;
+
+const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Spans created by these modules are timed as store operations.
+const STORE_MODULE_PREFIX: &str = "auth_service::services::data_stores::";
+
+lazy_static::lazy_static! {
+    static ref PROMETHEUS_HANDLE: PrometheusHandle = PrometheusBuilder::new()
+        .set_buckets(LATENCY_BUCKETS)
+        .expect("Latency buckets must not be empty")
+        .install_recorder()
+        .expect("Failed to install the Prometheus recorder");
+}
+
+// Installs the process wide metrics recorder. Safe to call more than once.
+pub fn init_metrics() {
+    lazy_static::initialize(&PROMETHEUS_HANDLE);
+}
+
+// Everything recorded so far, in the Prometheus text exposition format.
+pub fn render_metrics() -> String {
+    PROMETHEUS_HANDLE.render()
+}
+
+pub fn record_http_request(method: &Method, route: &str, status: StatusCode, latency: Duration) {
+    let labels = [
+        ("method", method.to_string()),
+        ("route", route.to_owned()),
+        ("status", status.as_u16().to_string()),
+    ];
+    counter!("http_requests_total", &labels).increment(1);
+    histogram!("http_request_duration_seconds", &labels).record(latency);
+}
+
+pub fn record_signup<T>(result: &Result<T, AuthAPIError>) {
+    counter!("auth_signups_total", "outcome" => outcome(result)).increment(1);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum LoginOutcome {
+    Success,
+    TwoFARequired,
+    Failure,
+}
+
+impl AsRef<str> for LoginOutcome {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Success => "success",
+            Self::TwoFARequired => "2fa_required",
+            Self::Failure => "failure",
+        }
+    }
+}
+
+pub fn record_login(outcome: LoginOutcome) {
+    counter!("auth_logins_total", "outcome" => outcome.as_ref().to_owned()).increment(1);
+}
+
+pub fn record_2fa_code_issued() {
+    counter!("auth_2fa_codes_issued_total").increment(1);
+}
+
+pub fn record_2fa_verification<T>(result: &Result<T, AuthAPIError>) {
+    match result {
+        Ok(_) => counter!("auth_2fa_codes_verified_total").increment(1),
+        Err(_) => counter!("auth_2fa_codes_failed_total").increment(1),
+    }
+}
+
+// Counts logouts and revocations of every token of a subject, once each whatever the number of
+// tokens revoked.
+pub fn record_token_banned() {
+    counter!("auth_tokens_banned_total").increment(1);
+}
+
+fn outcome<T>(result: &Result<T, AuthAPIError>) -> &'static str {
+    match result {
+        Ok(_) => "success",
+        Err(_) => "failure",
+    }
+}
+
+// Records the duration of every span opened by a data store (including the Argon2 hashing
+// done by the user store) as `store_operation_duration_seconds`, labelled by store module
+// and span name.
+pub struct StoreMetricsLayer;
+
+struct SpanStart(Instant);
+
+impl<S> Layer<S> for StoreMetricsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if span.metadata().target().starts_with(STORE_MODULE_PREFIX) {
+                span.extensions_mut().insert(SpanStart(Instant::now()));
+            }
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(SpanStart(start)) = span.extensions_mut().remove::<SpanStart>() else {
+            return;
+        };
+
+        let metadata = span.metadata();
+        let store = metadata
+            .target()
+            .strip_prefix(STORE_MODULE_PREFIX)
+            .unwrap_or(metadata.target());
+        histogram!(
+            "store_operation_duration_seconds",
+            "store" => store,
+            "operation" => metadata.name()
+        )
+        .record(start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::{layer::SubscriberExt, registry};
+
+    #[test]
+    fn renders_http_and_domain_metrics() {
+        init_metrics();
+        record_http_request(
+            &Method::POST,
+            "/login",
+            StatusCode::OK,
+            Duration::from_millis(3),
+        );
+        record_login(LoginOutcome::TwoFARequired);
+
+        let rendered = render_metrics();
+        assert!(
+            rendered.contains(r#"http_requests_total{method="POST",route="/login",status="200"}"#)
+        );
+        assert!(rendered.contains(
+            r#"http_request_duration_seconds_bucket{method="POST",route="/login",status="200",le="0.005"}"#
+        ));
+        assert!(rendered.contains(r#"auth_logins_total{outcome="2fa_required"}"#));
+    }
+
+    #[test]
+    fn times_spans_from_store_modules_only() {
+        init_metrics();
+        let subscriber = registry().with(StoreMetricsLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!(
+                target: "auth_service::services::data_stores::postgres_user_store",
+                "Retrieving user"
+            )
+            .in_scope(|| {});
+            tracing::info_span!(target: "auth_service::routes::login", "Logging in")
+                .in_scope(|| {});
+        });
+
+        let rendered = render_metrics();
+        assert!(rendered.contains(
+            r#"store_operation_duration_seconds_count{store="postgres_user_store",operation="Retrieving user"} 1"#
+        ));
+        assert!(!rendered.contains(r#"operation="Logging in""#));
+    }
+}
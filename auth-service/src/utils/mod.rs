@@ -1,3 +1,8 @@
 pub mod constants;
 pub mod auth;
 pub mod tracing;
+pub mod audit;
+pub mod metrics;
+pub mod redaction;
+pub mod shutdown;
+pub mod webhooks;
@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+// Cloneable trigger shared by the server and the background workers. Once requested,
+// shutdown cannot be cancelled.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownHandle {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    // Resolves once shutdown has been requested, immediately if it already was.
+    pub async fn requested(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so this cannot fail.
+        let _ = receiver.wait_for(|requested| *requested).await;
+    }
+}
+
+// Resolves on Ctrl+C or, on Unix, SIGTERM (what orchestrators send before killing us).
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install the Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received Ctrl+C"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn requested_resolves_for_every_clone() {
+        let handle = ShutdownHandle::new();
+        let waiter = tokio::spawn({
+            let handle = handle.clone();
+            async move { handle.requested().await }
+        });
+        assert!(!handle.is_requested());
+
+        handle.shutdown();
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("Waiter was not woken up")
+            .unwrap();
+        assert!(handle.clone().is_requested());
+    }
+
+    #[tokio::test]
+    async fn requested_resolves_immediately_after_shutdown() {
+        let handle = ShutdownHandle::new();
+        handle.shutdown();
+
+        tokio::time::timeout(Duration::from_millis(100), handle.requested())
+            .await
+            .expect("Shutdown was already requested");
+    }
+}
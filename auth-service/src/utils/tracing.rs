@@ -1,14 +1,31 @@
-use axum::{body::Body, extract::Request, response::Response};
-use color_eyre::eyre::Result;
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request},
+    http::{HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use color_eyre::eyre::{eyre, Result};
 use std::time::Duration;
-use tracing::{Level, Span};
+use tracing::{Level, Span, Subscriber};
 use tracing_error::ErrorLayer;
-use tracing_subscriber::{fmt, EnvFilter};
+use tracing_subscriber::{fmt, fmt::MakeWriter, registry::LookupSpan, EnvFilter, Layer};
 use tracing_subscriber::{layer::SubscriberExt, registry, util::SubscriberInitExt};
 
+use crate::utils::{
+    constants::LOG_FORMAT,
+    metrics::{record_http_request, StoreMetricsLayer},
+    redaction::RedactingMakeWriter,
+};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// Longest incoming X-Request-Id that is reused instead of replaced.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
 pub fn init_tracing() -> Result<()> {
-    // Create a formatting layer for tracing output with a compact format
-    let fmt_layer = fmt::layer().compact();
+    // Create a formatting layer for tracing output, in the format selected with LOG_FORMAT.
+    let fmt_layer = fmt_layer(LOG_FORMAT.as_str(), std::io::stdout)?;
 
     // Create a filter layer to control the verbosity of logs
     // Try to get the filter configuration from the environment variables
@@ -19,19 +36,101 @@ pub fn init_tracing() -> Result<()> {
     // the filter layer, and the error layer for enhanced error reporting
     registry()
         .with(filter_layer) // Add the filter layer to control log verbosity
-        .with(fmt_layer) // Add the formatting layer for compact log output
+        .with(fmt_layer) // Add the formatting layer for log output
         .with(ErrorLayer::default()) // Add the error layer to capture error contexts
+        .with(StoreMetricsLayer) // Add the layer timing data store operations
         .init(); // Initialize the tracing subscriber
 
     Ok(())
 }
 
+// Writes through RedactingMakeWriter so that secrets and emails never reach the logs. Colors
+// are off, since the escape codes around field names would keep secrets from being recognized.
+fn fmt_layer<S, W>(format: &str, make_writer: W) -> Result<Box<dyn Layer<S> + Send + Sync>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = fmt::layer()
+        .with_ansi(false)
+        .with_writer(RedactingMakeWriter(make_writer));
+    match format {
+        "json" => Ok(layer.json().boxed()),
+        "compact" => Ok(layer.compact().boxed()),
+        other => Err(eyre!("{} is not a supported log format.", other)),
+    }
+}
+
 //...
 
+// Unique id of the request being served, stored in the request extensions so that
+// handlers (e.g. the audit log) can refer to the same id as the tracing span.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+// Assigns a RequestId to every incoming request, reusing the caller's X-Request-Id when it
+// is sensible, and echoes it in the response. Must wrap the TraceLayer so the id already
+// exists when the span is created.
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_request_id(id))
+        .map(str::to_owned)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    request
+        .extensions_mut()
+        .insert(RequestId(request_id.clone()));
+    let mut response = next.run(request).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+// Only short, printable ids are propagated, so callers cannot inject into the logs.
+fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_graphic())
+}
+
+// Method and route template of the request, copied onto the response so that `on_response`
+// can label metrics without creating one series per concrete path.
+#[derive(Debug, Clone)]
+pub struct MatchedRoute {
+    pub method: Method,
+    pub route: String,
+}
+
+// Must sit inside the TraceLayer, where the router has already set the MatchedPath.
+pub async fn expose_matched_route(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned());
+
+    let mut response = next.run(request).await;
+    if let Some(route) = route {
+        response
+            .extensions_mut()
+            .insert(MatchedRoute { method, route });
+    }
+    response
+}
+
 // Creates a new tracing span with a unique request ID for each incoming request.
 // This helps in tracking and correlating logs for individual requests.
 pub fn make_span_with_request_id(request: &Request<Body>) -> Span {
-    let request_id = uuid::Uuid::new_v4();
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.clone())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
     tracing::span!(
         Level::INFO,
         "[REQUEST]",
@@ -49,11 +148,16 @@ pub fn on_request(_request: &Request<Body>, _span: &Span) {
 
 // Logs an event indicating the end of a request, including its latency and status code.
 // If the status code indicates an error (4xx or 5xx), it logs at the ERROR level.
+// Also records the request count and latency metrics for the matched route.
 pub fn on_response(response: &Response, latency: Duration, _span: &Span) {
     let status = response.status();
     let status_code = status.as_u16();
     let status_code_class = status_code / 100;
 
+    if let Some(matched) = response.extensions().get::<MatchedRoute>() {
+        record_http_request(&matched.method, &matched.route, status, latency);
+    }
+
     match status_code_class {
         4..=5 => {
             tracing::event!(
@@ -73,3 +177,51 @@ pub fn on_response(response: &Response, latency: Duration, _span: &Span) {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn fmt_layers_redact_sensitive_fields() {
+        for format in ["compact", "json"] {
+            let buffer = Buffer::default();
+            let subscriber = registry().with(fmt_layer(format, buffer.clone()).unwrap());
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info!(password = "hunter22", email = "foo@example.com", "Logging in");
+            });
+
+            let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+            assert!(output.contains("Logging in"), "{}", output);
+            assert!(output.contains("[REDACTED]"), "{}", output);
+            assert!(!output.contains("hunter22"), "{}", output);
+            assert!(!output.contains("foo@"), "{}", output);
+        }
+    }
+}
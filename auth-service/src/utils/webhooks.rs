@@ -0,0 +1,10 @@
+use crate::{app_state::WebhookStoreType, domain::webhooks::WebhookEvent};
+
+// Queues the event for every matching subscription. Delivery itself happens in the
+// background `WebhookDispatcher`, so a failure here is only logged.
+#[tracing::instrument(name = "Publishing webhook event", skip_all)]
+pub async fn publish_webhook_event(webhook_store: &WebhookStoreType, event: WebhookEvent) {
+    if let Err(e) = webhook_store.enqueue(event).await {
+        tracing::error!(error = ?e, "Failed to enqueue webhook event");
+    }
+}
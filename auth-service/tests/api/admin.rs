@@ -0,0 +1,258 @@
+use auth_service::{
+    routes::{AdminUserListResponse, AdminUserResponse},
+    utils::constants::{ADMIN_API_KEY, JWT_COOKIE_NAME},
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": requires_2fa
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn should_return_400_if_admin_key_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/admin/users", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_admin_key_invalid() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_admin("/users", "not-the-admin-key").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_users_with_filters_and_pagination() {
+    let mut app = TestApp::new().await;
+
+    for requires_2fa in [true, true, false] {
+        signup(&app, &get_random_email(), requires_2fa).await;
+    }
+
+    let response = app
+        .get_admin("/users?requires2FA=true&perPage=1", &ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<AdminUserListResponse>()
+        .await
+        .expect("Could not deserialize response body to AdminUserListResponse");
+    assert_eq!(body.total, 2);
+    assert_eq!(body.per_page, 1);
+    assert_eq!(body.users.len(), 1);
+    assert!(body.users[0].requires_2fa);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn email_filter_matches_a_literal_substring() {
+    let mut app = TestApp::new().await;
+    for email in ["ada@example.com", "bob@example.com"] {
+        signup(&app, email, false).await;
+    }
+
+    // `%` (sent as `%25`) and `_` are not wildcards.
+    for (filter, expected) in [
+        ("ADA", vec!["ada@example.com"]),
+        ("%25", vec![]),
+        ("_", vec![]),
+    ] {
+        let response = app
+            .get_admin(&format!("/users?email={}", filter), &ADMIN_API_KEY)
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        let body = response
+            .json::<AdminUserListResponse>()
+            .await
+            .expect("Could not deserialize response body to AdminUserListResponse");
+        let emails: Vec<&str> = body.users.iter().map(|user| user.email.as_str()).collect();
+        assert_eq!(emails, expected, "Failed for filter {:?}", filter);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_user_by_email() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let response = app
+        .get_admin(&format!("/users/{}", email), &ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<AdminUserResponse>()
+        .await
+        .expect("Could not deserialize response body to AdminUserResponse");
+    assert_eq!(body.email, email);
+    assert_eq!(body.status, "active");
+    assert!(uuid::Uuid::parse_str(&body.id).is_ok());
+
+    let response = app
+        .get_admin(&format!("/users/{}", get_random_email()), &ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_block_login_while_user_disabled() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    let login_body = serde_json::json!({ "email": email, "password": "vivid-Otter-chimney-17" });
+
+    let response = app.post_admin(&format!("/users/{}/disable", email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 403);
+
+    let response = app
+        .get_admin("/users?status=suspended", &ADMIN_API_KEY)
+        .await;
+    let body = response
+        .json::<AdminUserListResponse>()
+        .await
+        .expect("Could not deserialize response body to AdminUserListResponse");
+    assert_eq!(body.total, 1);
+    assert_eq!(body.users[0].email, email);
+
+    let response = app.post_admin(&format!("/users/{}/enable", email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_403_on_login_after_forced_password_reset() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let response = app
+        .post_admin(&format!("/users/{}/force-password-reset", email))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "vivid-Otter-chimney-17" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_login_without_2fa_after_reset_2fa() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, true).await;
+
+    let response = app.post_admin(&format!("/users/{}/reset-2fa", email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "vivid-Otter-chimney-17" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_invalidate_existing_tokens_after_revoke_sessions() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "vivid-Otter-chimney-17" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_admin(&format!("/users/{}/revoke-sessions", email))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Sessions started right after the revocation, even within the same second, are valid.
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "vivid-Otter-chimney-17" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_existing_token_after_user_suspended() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "vivid-Otter-chimney-17" }))
+        .await;
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app.post_admin(&format!("/users/{}/disable", email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
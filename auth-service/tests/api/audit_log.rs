@@ -0,0 +1,215 @@
+use auth_service::{
+    routes::{AuditLogResponse, AuditLogVerificationResponse},
+    utils::constants::ADMIN_API_KEY,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_record_signup_and_login_attempts() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "wrongPassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "vivid-Otter-chimney-17" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .get_admin(&format!("/audit-log?actor={}", email), &ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+
+    // Most recent first.
+    let summary: Vec<(&str, &str)> = body
+        .events
+        .iter()
+        .map(|record| (record.event.action.as_ref(), record.event.outcome.as_ref()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("login", "success"),
+            ("login", "failure"),
+            ("signup", "success")
+        ]
+    );
+    assert!(body.events[0].event.metadata.ip.is_some());
+    assert!(body.events[0].event.metadata.request_id.is_some());
+
+    let response = app
+        .get_admin(
+            &format!("/audit-log?actor={}&action=signup", email),
+            &ADMIN_API_KEY,
+        )
+        .await;
+    let body = response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+    assert_eq!(body.events.len(), 1);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_the_canonical_email_as_actor() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let spelling = format!("  {} ", email.to_uppercase());
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": spelling,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": spelling, "password": "vivid-Otter-chimney-17" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": "not-an-email", "password": "wrongPassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = app
+        .get_admin(&format!("/audit-log?actor={}", email), &ADMIN_API_KEY)
+        .await
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+    let actions: Vec<&str> = body
+        .events
+        .iter()
+        .map(|record| record.event.action.as_ref())
+        .collect();
+    assert_eq!(actions, vec!["login", "signup"]);
+
+    let body = app
+        .get_admin("/audit-log?action=login", &ADMIN_API_KEY)
+        .await
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+    assert_eq!(body.events[0].event.actor, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_filter_audit_log_by_time_range() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .get_admin(
+            &format!("/audit-log?actor={}&to=2000-01-01T00:00:00Z", email),
+            &ADMIN_API_KEY,
+        )
+        .await;
+    let body = response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+    assert!(body.events.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_report_intact_hash_chain() {
+    let mut app = TestApp::new().await;
+
+    for _ in 0..3 {
+        app.post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    }
+
+    let response = app.get_admin("/audit-log/verify", &ADMIN_API_KEY).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<AuditLogVerificationResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogVerificationResponse");
+    assert!(body.valid);
+    assert_eq!(body.first_invalid_id, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_report_the_first_tampered_row() {
+    let mut app = TestApp::new().await;
+    let emails: Vec<String> = (0..3).map(|_| get_random_email()).collect();
+    for email in &emails {
+        app.post_signup(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    }
+
+    let response = app
+        .get_admin(&format!("/audit-log?actor={}", emails[1]), &ADMIN_API_KEY)
+        .await;
+    let body = response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+    let tampered_id = body.events[0].id;
+
+    // Someone with database access covers up that the signup happened.
+    sqlx::query("UPDATE audit_log SET outcome = 'failure' WHERE id = $1")
+        .bind(tampered_id)
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let response = app.get_admin("/audit-log/verify", &ADMIN_API_KEY).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<AuditLogVerificationResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogVerificationResponse");
+    assert!(!body.valid);
+    assert_eq!(body.first_invalid_id, Some(tampered_id));
+
+    app.clean_up().await;
+}
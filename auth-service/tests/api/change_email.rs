@@ -0,0 +1,285 @@
+use std::{sync::Arc, time::Duration};
+
+use auth_service::{
+    domain::{
+        email::Email,
+        webhooks::{WebhookEvent, WebhookEventType},
+        EmailMessage,
+    },
+    routes::ConfirmEmailChangeResponse,
+    ErrorResponse,
+};
+use axum::http::StatusCode;
+use tokio::sync::mpsc;
+
+use crate::{
+    helpers::{get_random_email, RecordingEmailClient, TestApp, PASSWORD},
+    webhooks::{next_webhook, spawn_receiver},
+};
+
+async fn login_status(app: &TestApp, email: &str) -> u16 {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+// Waits for the confirmation and the notice, in whatever order the outbox sends them.
+async fn receive_emails(
+    emails: &mut mpsc::UnboundedReceiver<(Email, EmailMessage)>,
+) -> Vec<(Email, EmailMessage)> {
+    let mut received = Vec::new();
+    for _ in 0..2 {
+        let email = tokio::time::timeout(Duration::from_secs(5), emails.recv())
+            .await
+            .expect("Timed out waiting for an email")
+            .unwrap();
+        received.push(email);
+    }
+    received
+}
+
+fn token_from(message: &EmailMessage) -> String {
+    let (_, rest) = message
+        .text_body
+        .split_once("/change-email/confirm?token=")
+        .expect("No confirmation link in the email");
+    rest.chars().take_while(char::is_ascii_hexdigit).collect()
+}
+
+#[tokio::test]
+async fn should_change_email_once_the_new_address_is_confirmed() {
+    let (sender, mut emails) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_email_client(Arc::new(RecordingEmailClient(sender))).await;
+    let old_email = get_random_email();
+    let new_email = get_random_email();
+    let token = app.sign_up_and_log_in(&old_email).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let received = receive_emails(&mut emails).await;
+    let confirmation = received
+        .iter()
+        .find(|(recipient, _)| recipient.as_ref() == new_email)
+        .map(|(_, message)| message)
+        .expect("No confirmation sent to the new address");
+    let notice = received
+        .iter()
+        .find(|(recipient, _)| recipient.as_ref() == old_email)
+        .map(|(_, message)| message)
+        .expect("No notice sent to the old address");
+    assert!(notice.text_body.contains(&new_email));
+
+    // Nothing changes until the link is followed.
+    assert_eq!(login_status(&app, &old_email).await, 200);
+    assert_eq!(login_status(&app, &new_email).await, 401);
+
+    let response = app
+        .get_confirm_email_change(&token_from(confirmation))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<ConfirmEmailChangeResponse>()
+            .await
+            .expect("Could not deserialize response body to ConfirmEmailChangeResponse"),
+        ConfirmEmailChangeResponse {
+            email: new_email.clone(),
+        }
+    );
+
+    // Tokens issued under the old address are revoked.
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(login_status(&app, &old_email).await, 401);
+    assert_eq!(login_status(&app, &new_email).await, 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirming_publishes_email_verified_and_changed_events() {
+    let (sender, mut emails) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_email_client(Arc::new(RecordingEmailClient(sender))).await;
+    let old_email = get_random_email();
+    let new_email = get_random_email();
+    app.sign_up_and_log_in(&old_email).await;
+
+    let (url, mut receiver) = spawn_receiver(StatusCode::OK).await;
+    let subscription = serde_json::json!({
+        "url": url,
+        "secret": "webhook-secret",
+        "eventTypes": ["user.email_verified", "user.email_changed"],
+    });
+    let response = app.post_admin_json("/webhooks", &subscription).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let received = receive_emails(&mut emails).await;
+    let (_, confirmation) = received
+        .iter()
+        .find(|(recipient, _)| recipient.as_ref() == new_email)
+        .expect("No confirmation sent to the new address");
+    let response = app
+        .get_confirm_email_change(&token_from(confirmation))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Both are queued at once, so they may arrive in either order.
+    let mut events = Vec::new();
+    for _ in 0..2 {
+        let webhook = next_webhook(&mut receiver).await;
+        events.push(serde_json::from_str::<WebhookEvent>(&webhook.body).unwrap());
+    }
+    events.sort_by_key(|event| event.event_type.as_ref().to_owned());
+    assert_eq!(events[0].event_type, WebhookEventType::EmailChanged);
+    assert_eq!(events[0].data["previousEmail"], old_email);
+    assert_eq!(events[1].event_type, WebhookEventType::EmailVerified);
+    for event in &events {
+        assert_eq!(event.data["email"], new_email);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_link_is_invalid_or_used() {
+    let (sender, mut emails) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_email_client(Arc::new(RecordingEmailClient(sender))).await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+    let new_email = get_random_email();
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let received = receive_emails(&mut emails).await;
+    let (_, confirmation) = received
+        .iter()
+        .find(|(recipient, _)| recipient.as_ref() == new_email)
+        .expect("No confirmation sent to the new address");
+    let token = token_from(confirmation);
+
+    for invalid in ["not-a-token", &"0".repeat(64)] {
+        let response = app.get_confirm_email_change(invalid).await;
+        assert_eq!(response.status().as_u16(), 401, "Failed for: {}", invalid);
+    }
+
+    assert_eq!(
+        app.get_confirm_email_change(&token).await.status().as_u16(),
+        200
+    );
+    assert_eq!(
+        app.get_confirm_email_change(&token).await.status().as_u16(),
+        401
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_link_expired() {
+    let (sender, mut emails) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_email_client(Arc::new(RecordingEmailClient(sender))).await;
+    let old_email = get_random_email();
+    app.sign_up_and_log_in(&old_email).await;
+    let new_email = get_random_email();
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": new_email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let received = receive_emails(&mut emails).await;
+    let (_, confirmation) = received
+        .iter()
+        .find(|(recipient, _)| recipient.as_ref() == new_email)
+        .expect("No confirmation sent to the new address");
+
+    sqlx::query("UPDATE email_changes SET expires_at = NOW() - INTERVAL '1 second'")
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to expire the email change");
+
+    let response = app
+        .get_confirm_email_change(&token_from(confirmation))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(login_status(&app, &old_email).await, 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_is_taken() {
+    let mut app = TestApp::new().await;
+    let taken_email = get_random_email();
+    app.sign_up_and_log_in(&taken_email).await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": taken_email.to_uppercase(),
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "User already exists"
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": get_random_email(),
+            "password": "wrong-Otter-chimney-17",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "newEmail": get_random_email(),
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    app.clean_up().await;
+}
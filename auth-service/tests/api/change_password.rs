@@ -0,0 +1,431 @@
+use std::{sync::Arc, time::Duration};
+
+use auth_service::{
+    domain::{email::Email, EmailMessage},
+    routes::{ChangePasswordResponse, TwoFactorAuthResponse},
+    ErrorResponse,
+};
+use tokio::sync::mpsc;
+
+use crate::helpers::{get_random_email, RecordingEmailClient, TestApp, PASSWORD_MAX_AGE_DAYS};
+
+async fn sign_up(app: &TestApp, email: &str, password: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": password,
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+// Logs in so that the cookie jar of the app holds a session for `email`.
+async fn log_in(app: &TestApp, email: &str, password: &str) {
+    assert_eq!(login_status(app, email, password).await, 200);
+}
+
+async fn change_password(
+    app: &TestApp,
+    current_password: &str,
+    new_password: &str,
+) -> reqwest::Response {
+    app.post_change_password(&serde_json::json!({
+        "currentPassword": current_password,
+        "newPassword": new_password,
+    }))
+    .await
+}
+
+async fn login_status(app: &TestApp, email: &str, password: &str) -> u16 {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+    .status()
+    .as_u16()
+}
+
+async fn receive_reset_token(
+    emails: &mut mpsc::UnboundedReceiver<(Email, EmailMessage)>,
+) -> String {
+    let (_, message) = tokio::time::timeout(Duration::from_secs(5), emails.recv())
+        .await
+        .expect("Timed out waiting for an email")
+        .unwrap();
+    let (_, rest) = message
+        .text_body
+        .split_once("/reset-password?token=")
+        .expect("No reset link in the email");
+    rest.chars().take_while(char::is_ascii_hexdigit).collect()
+}
+
+#[tokio::test]
+async fn should_return_200_and_replace_password() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+    log_in(&app, &email, "vivid-Otter-chimney-17").await;
+
+    let response = change_password(&app, "vivid-Otter-chimney-17", "brisk-Heron-lantern-42").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<ChangePasswordResponse>()
+            .await
+            .expect("Could not deserialize response body to ChangePasswordResponse"),
+        ChangePasswordResponse {
+            message: "Password changed successfully!".to_owned(),
+        }
+    );
+
+    assert_eq!(
+        login_status(&app, &email, "vivid-Otter-chimney-17").await,
+        401
+    );
+    assert_eq!(
+        login_status(&app, &email, "brisk-Heron-lantern-42").await,
+        200
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_without_a_session() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+
+    let response = change_password(&app, "vivid-Otter-chimney-17", "brisk-Heron-lantern-42").await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        login_status(&app, &email, "vivid-Otter-chimney-17").await,
+        200
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_is_incorrect() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+    log_in(&app, &email, "vivid-Otter-chimney-17").await;
+
+    let response = change_password(&app, "wrong-Otter-chimney-17", "brisk-Heron-lantern-42").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        login_status(&app, &email, "vivid-Otter-chimney-17").await,
+        200
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_every_session_after_a_change() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+    log_in(&app, &email, "vivid-Otter-chimney-17").await;
+    assert_eq!(app.get_me().await.status().as_u16(), 200);
+
+    let response = change_password(&app, "vivid-Otter-chimney-17", "brisk-Heron-lantern-42").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(app.get_me().await.status().as_u16(), 401);
+    let response = change_password(&app, "brisk-Heron-lantern-42", "quiet-Badger-teapot-85").await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_a_2fa_code_from_users_with_2fa() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&email).unwrap())
+        .await
+        .unwrap();
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = change_password(&app, "vivid-Otter-chimney-17", "brisk-Heron-lantern-42").await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&email).unwrap())
+        .await
+        .unwrap();
+    let wrong_code = if code.as_ref() == "123456" {
+        "654321"
+    } else {
+        "123456"
+    };
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "vivid-Otter-chimney-17",
+            "newPassword": "brisk-Heron-lantern-42",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": wrong_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "vivid-Otter-chimney-17",
+            "newPassword": "brisk-Heron-lantern-42",
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        login_status(&app, &email, "brisk-Heron-lantern-42").await,
+        206
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_is_weak() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+    log_in(&app, &email, "vivid-Otter-chimney-17").await;
+
+    let response = change_password(&app, "vivid-Otter-chimney-17", "password").await;
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.error, "Password does not meet the password policy");
+    assert_eq!(
+        body.reasons,
+        vec!["Password is too easy to guess: avoid common passwords and words"]
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_was_used_recently() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    // The test policy remembers the default 5 most recent passwords.
+    let passwords = [
+        "vivid-Otter-chimney-17",
+        "brisk-Heron-lantern-42",
+        "quiet-Badger-teapot-85",
+        "amber-Walrus-saddle-09",
+        "lunar-Ferret-bucket-63",
+        "sunny-Magpie-anchor-31",
+    ];
+    sign_up(&app, &email, passwords[0]).await;
+    for pair in passwords.windows(2) {
+        // Each change signs the user out.
+        log_in(&app, &email, pair[0]).await;
+        let response = change_password(&app, pair[0], pair[1]).await;
+        assert_eq!(
+            response.status().as_u16(),
+            200,
+            "Failed for password: {}",
+            pair[1]
+        );
+    }
+
+    let current = passwords[5];
+    log_in(&app, &email, current).await;
+    for reused in &passwords[1..] {
+        let response = change_password(&app, current, reused).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for password: {}",
+            reused
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .reasons,
+            vec!["Password must differ from the last 5 passwords"]
+        );
+    }
+
+    // Only the 4 passwords before the current one are kept.
+    let history: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM password_history JOIN users ON users.id = user_id WHERE email = $1",
+    )
+    .bind(&email)
+    .fetch_one(&app.pg_pool)
+    .await
+    .expect("Failed to count password history");
+    assert_eq!(history, 4);
+
+    let response = change_password(&app, current, passwords[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_the_mailed_link_after_a_forced_reset() {
+    let (sender, mut emails) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_email_client(Arc::new(RecordingEmailClient(sender))).await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+    log_in(&app, &email, "vivid-Otter-chimney-17").await;
+
+    let response = app
+        .post_admin(&format!("/users/{}/force-password-reset", email))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let first_token = receive_reset_token(&mut emails).await;
+
+    // The reset revoked the session, and the old password no longer changes anything.
+    assert_eq!(app.get_me().await.status().as_u16(), 401);
+    assert_eq!(
+        login_status(&app, &email, "vivid-Otter-chimney-17").await,
+        403
+    );
+
+    // Logging in mailed a new link, which replaces the first.
+    let token = receive_reset_token(&mut emails).await;
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": first_token,
+            "newPassword": "brisk-Heron-lantern-42",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token,
+            "newPassword": "brisk-Heron-lantern-42",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        login_status(&app, &email, "brisk-Heron-lantern-42").await,
+        200
+    );
+
+    // Each link works once.
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token,
+            "newPassword": "quiet-Badger-teapot-85",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_change_a_password_that_must_be_reset() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+    log_in(&app, &email, "vivid-Otter-chimney-17").await;
+
+    // Flagged directly, so that the session survives.
+    sqlx::query("UPDATE users SET password_reset_required = TRUE WHERE email = $1")
+        .bind(&email)
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to require a password reset");
+
+    let response = change_password(&app, "vivid-Otter-chimney-17", "brisk-Heron-lantern-42").await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        login_status(&app, &email, "brisk-Heron-lantern-42").await,
+        401
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_let_users_with_an_expired_password_reset_it() {
+    let (sender, mut emails) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_email_client(Arc::new(RecordingEmailClient(sender))).await;
+    let email = get_random_email();
+    sign_up(&app, &email, "vivid-Otter-chimney-17").await;
+
+    sqlx::query(
+        "UPDATE users SET password_changed_at = NOW() - make_interval(days => $2) WHERE email = $1",
+    )
+    .bind(&email)
+    .bind(PASSWORD_MAX_AGE_DAYS as i32 + 1)
+    .execute(&app.pg_pool)
+    .await
+    .expect("Failed to age the password");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Password expired"
+    );
+
+    let token = receive_reset_token(&mut emails).await;
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": token,
+            "newPassword": "brisk-Heron-lantern-42",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        login_status(&app, &email, "brisk-Heron-lantern-42").await,
+        200
+    );
+    app.clean_up().await;
+}
@@ -0,0 +1,115 @@
+use auth_service::{
+    domain::{email::Email, EmailClient, EmailMessage},
+    routes::FailedEmailsResponse,
+    services::http_email_client::HttpEmailError,
+    utils::constants::ADMIN_API_KEY,
+};
+use color_eyre::eyre::Result;
+use reqwest::StatusCode;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+use crate::helpers::{get_random_email, RecordingEmailClient, TestApp};
+
+struct UnavailableEmailClient;
+
+#[async_trait::async_trait]
+impl EmailClient for UnavailableEmailClient {
+    async fn send_email(&self, _recipient: &Email, _message: &EmailMessage) -> Result<()> {
+        Err(HttpEmailError::Unavailable {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+        .into())
+    }
+}
+
+async fn login_with_2fa(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "vivid-Otter-chimney-17",
+        "requires2FA": true
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "vivid-Otter-chimney-17",
+    });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 206);
+}
+
+async fn failed_emails(app: &TestApp) -> FailedEmailsResponse {
+    let response = app
+        .get_admin("/emails/failed", ADMIN_API_KEY.as_str())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn should_send_2fa_code_through_the_outbox() {
+    let (sender, mut emails) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_email_client(Arc::new(RecordingEmailClient(sender))).await;
+    let random_email = get_random_email();
+
+    login_with_2fa(&app, &random_email).await;
+
+    let (recipient, message) = tokio::time::timeout(Duration::from_secs(5), emails.recv())
+        .await
+        .expect("Timed out waiting for the 2FA email")
+        .unwrap();
+    let (_, code) = app.two_fa_code_store.get_code(&recipient).await.unwrap();
+    assert_eq!(recipient.as_ref(), random_email);
+    assert!(message.text_body.contains(code.as_ref()));
+    assert!(message.html_body.contains(code.as_ref()));
+    assert!(failed_emails(&app).await.failed_emails.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_emails_that_could_not_be_sent() {
+    let mut app = TestApp::with_email_client(Arc::new(UnavailableEmailClient)).await;
+    let random_email = get_random_email();
+
+    // The login does not wait for the provider, so it succeeds while the provider is down.
+    login_with_2fa(&app, &random_email).await;
+
+    let mut failed = Vec::new();
+    for _ in 0..50 {
+        failed = failed_emails(&app).await.failed_emails;
+        if !failed.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(failed.len(), 1, "the email was never marked as failed");
+    assert_eq!(failed[0].recipient, random_email);
+    assert_eq!(failed[0].attempts, 3);
+    assert_eq!(
+        failed[0].last_error,
+        "Email provider failed with 503 Service Unavailable"
+    );
+
+    let (html_body, text_body): (String, String) =
+        sqlx::query_as("SELECT html_body, text_body FROM email_outbox WHERE id = $1")
+            .bind(failed[0].id)
+            .fetch_one(&app.pg_pool)
+            .await
+            .unwrap();
+    assert!(html_body.is_empty(), "the dead-lettered email kept its HTML body");
+    assert!(text_body.is_empty(), "the dead-lettered email kept its text body");
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_failed_emails_without_admin_key() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_admin("/emails/failed", "wrong-key").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
@@ -0,0 +1,76 @@
+use auth_service::routes::{HealthResponse, HealthStatus};
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn liveness_returns_200() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_health("live").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<HealthResponse>()
+        .await
+        .expect("Could not deserialize response body to HealthResponse");
+    assert_eq!(body.status, HealthStatus::Ok);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn readiness_reports_each_dependency() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_health("ready").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<HealthResponse>()
+        .await
+        .expect("Could not deserialize response body to HealthResponse");
+    assert_eq!(body.status, HealthStatus::Ok);
+
+    let dependencies: Vec<(&str, HealthStatus)> = body
+        .checks
+        .iter()
+        .map(|check| (check.name.as_str(), check.status))
+        .collect();
+    assert_eq!(
+        dependencies,
+        vec![("postgres", HealthStatus::Ok), ("redis", HealthStatus::Ok)]
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn readiness_returns_503_when_a_dependency_is_down() {
+    // Nothing listens on port 1.
+    let mut app = TestApp::with_redis_health_check_at("127.0.0.1:1").await;
+
+    let response = app.get_health("ready").await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body = response
+        .json::<HealthResponse>()
+        .await
+        .expect("Could not deserialize response body to HealthResponse");
+    assert_eq!(body.status, HealthStatus::Unavailable);
+
+    let redis = body
+        .checks
+        .iter()
+        .find(|check| check.name == "redis")
+        .expect("No redis check");
+    assert_eq!(redis.status, HealthStatus::Unavailable);
+    assert!(redis.error.is_some());
+    let postgres = body
+        .checks
+        .iter()
+        .find(|check| check.name == "postgres")
+        .expect("No postgres check");
+    assert_eq!(postgres.status, HealthStatus::Ok);
+
+    // Liveness does not depend on Redis.
+    assert_eq!(app.get_health("live").await.status().as_u16(), 200);
+    app.clean_up().await;
+}
@@ -1,24 +1,51 @@
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
-    get_postgres_pool, get_redis_client,
+    app_state::{
+        AppState, BannedTokenStoreType, EmailClientType, HealthCheckType, SmsClientType,
+        TwoFACodeStoreType, UserStoreType, WebhookStoreType,
+    },
+    domain::{
+        email::Email,
+        password::{BreachCorpus, PasswordPolicy},
+        tenant::Tenant,
+        EmailClient, EmailMessage,
+    },
+    get_postgres_pool, get_redis_client, get_redis_connection,
     services::{
+        email_templates::{Branding, EmailTemplates},
         data_stores::{
+            postgres_audit_log::PostgresAuditLog, postgres_email_outbox::PostgresEmailOutbox,
+            postgres_personal_access_token_store::PostgresPersonalAccessTokenStore,
+            postgres_service_account_store::PostgresServiceAccountStore,
             postgres_user_store::PostgresUserStore,
+            postgres_webhook_store::PostgresWebhookStore,
             redis_banned_token_store::RedisBannedTokenStore,
+            redis_sms_rate_limiter::RedisSmsRateLimiter,
             redis_two_fa_code_store::RedisTwoFACodeStore,
         },
+        email_outbox_worker::{EmailOutboxWorker, EmailOutboxWorkerConfig},
+        health_checks::{PostgresHealthCheck, RedisHealthCheck},
         mock_email_client::MockEmailClient,
+        mock_sms_client::MockSmsClient,
+        webhook_dispatcher::{WebhookDispatcher, WebhookDispatcherConfig},
+    },
+    utils::{
+        constants::{
+            test, ADMIN_API_KEY, DATABASE_URL, JWT_COOKIE_NAME, REDIS_COMMAND_TIMEOUT,
+            REDIS_HOST_NAME,
+        },
+        shutdown::ShutdownHandle,
     },
-    utils::constants::{test, DATABASE_URL, REDIS_HOST_NAME},
     Application,
 };
+use rand::Rng;
+use redis::aio::ConnectionManager;
 use reqwest::cookie::Jar;
 use sqlx::{
     postgres::{PgConnectOptions, PgPoolOptions},
     Connection, Executor, PgConnection, PgPool,
 };
-use std::{str::FromStr, sync::Arc};
-use tokio::sync::RwLock;
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tokio::{sync::mpsc, task::JoinHandle};
 use uuid::Uuid;
 
 pub struct TestApp {
@@ -26,40 +53,200 @@ pub struct TestApp {
     pub cookie_jar: Arc<Jar>,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub pg_pool: PgPool,
     pub http_client: reqwest::Client,
+    pub shutdown_handle: ShutdownHandle,
+    pub server: JoinHandle<Result<(), std::io::Error>>,
+    // The webhook dispatcher and the email outbox worker.
+    pub workers: Vec<JoinHandle<()>>,
     pub db_name: String,
     pub cleanup_called: bool,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
-        let email_client = Arc::new(RwLock::new(MockEmailClient));
+        Self::with_email_client(Arc::new(MockEmailClient)).await
+    }
+
+    pub async fn with_email_client(email_client: EmailClientType) -> Self {
+        Self::with_clients(email_client, Arc::new(MockSmsClient)).await
+    }
+
+    pub async fn with_sms_client(sms_client: SmsClientType) -> Self {
+        Self::with_clients(Arc::new(MockEmailClient), sms_client).await
+    }
+
+    // Emails queued by the app are sent through `email_client` by the outbox worker.
+    pub async fn with_clients(email_client: EmailClientType, sms_client: SmsClientType) -> Self {
+        Self::build(
+            email_client,
+            sms_client,
+            vec![Tenant::from_env()],
+            |user_store| user_store,
+            &REDIS_HOST_NAME,
+        )
+        .await
+    }
 
-        let redis_client = Arc::new(RwLock::new(configure_redis()));
-        let two_fa_code_store =
-            Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_client.clone())));
-        let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_client)));
+    // Serves each tenant with its own stores, like the service does given TENANTS_CONFIG_PATH.
+    // The store fields of the TestApp are those of the first tenant.
+    pub async fn with_tenants(tenants: Vec<Tenant>) -> Self {
+        Self::build(
+            Arc::new(MockEmailClient),
+            Arc::new(MockSmsClient),
+            tenants,
+            |user_store| user_store,
+            &REDIS_HOST_NAME,
+        )
+        .await
+    }
+
+    // The app uses whatever `wrap_user_store` returns in place of the Postgres user store.
+    pub async fn with_user_store_wrapper(
+        wrap_user_store: impl FnOnce(UserStoreType) -> UserStoreType,
+    ) -> Self {
+        Self::build(
+            Arc::new(MockEmailClient),
+            Arc::new(MockSmsClient),
+            vec![Tenant::from_env()],
+            wrap_user_store,
+            &REDIS_HOST_NAME,
+        )
+        .await
+    }
 
+    // The readiness probe checks Redis at `redis_host`, while the stores keep using the real one.
+    pub async fn with_redis_health_check_at(redis_host: &str) -> Self {
+        Self::build(
+            Arc::new(MockEmailClient),
+            Arc::new(MockSmsClient),
+            vec![Tenant::from_env()],
+            |user_store| user_store,
+            redis_host,
+        )
+        .await
+    }
+
+    // `wrap_user_store` is applied to the first tenant's user store.
+    async fn build(
+        email_client: EmailClientType,
+        sms_client: SmsClientType,
+        tenants: Vec<Tenant>,
+        wrap_user_store: impl FnOnce(UserStoreType) -> UserStoreType,
+        redis_health_host: &str,
+    ) -> Self {
+        let email_templates =
+            Arc::new(EmailTemplates::new(Branding::default()).expect("Failed to load templates"));
+
+        let redis_connection = configure_redis().await;
         let (db_name, pg_pool) = configure_postgresql().await;
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let health_checks: Vec<HealthCheckType> = vec![
+            Arc::new(PostgresHealthCheck::new(pg_pool.clone())),
+            Arc::new(RedisHealthCheck::new(
+                get_redis_client(redis_health_host.to_owned()).expect("Failed to get Redis client"),
+                Duration::from_secs(2),
+            )),
+        ];
+        let webhook_store: WebhookStoreType = Arc::new(PostgresWebhookStore::new(pg_pool.clone()));
+        let email_outbox = Arc::new(PostgresEmailOutbox::new(pg_pool.clone()));
+        let breach_corpus =
+            BreachCorpus::parse(BREACHED_PASSWORD_SHA1).expect("Failed to parse breach corpus");
+        let password_policy = Arc::new(
+            PasswordPolicy::default()
+                .with_breach_corpus(breach_corpus)
+                .with_max_age(chrono::Duration::days(PASSWORD_MAX_AGE_DAYS)),
+        );
 
-        let app_state = AppState::new(
-            user_store,
-            banned_token_store.clone(),
-            two_fa_code_store.clone(),
-            email_client.clone(),
+        // Short intervals so that delivery and dead-lettering happen within a test.
+        let webhook_dispatcher = WebhookDispatcher::new(
+            webhook_store.clone(),
+            WebhookDispatcherConfig {
+                poll_interval: Duration::from_millis(50),
+                base_backoff: Duration::from_millis(50),
+                max_backoff: Duration::from_millis(200),
+                max_attempts: 3,
+                request_timeout: Duration::from_secs(2),
+                ..WebhookDispatcherConfig::default()
+            },
+        )
+        .expect("Failed to build webhook dispatcher");
+        let email_outbox_worker = EmailOutboxWorker::new(
+            email_outbox.clone(),
+            email_client,
+            EmailOutboxWorkerConfig {
+                poll_interval: Duration::from_millis(50),
+                base_backoff: Duration::from_millis(50),
+                max_backoff: Duration::from_millis(200),
+                max_attempts: 3,
+                send_timeout: Duration::from_secs(2),
+                ..EmailOutboxWorkerConfig::default()
+            },
         );
 
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let mut wrap_user_store = Some(wrap_user_store);
+        let app_states: Vec<AppState> = tenants
+            .into_iter()
+            .map(|tenant| {
+                let mut user_store: UserStoreType = Arc::new(
+                    PostgresUserStore::new(pg_pool.clone()).with_tenant(tenant.id.clone()),
+                );
+                if let Some(wrap_user_store) = wrap_user_store.take() {
+                    user_store = wrap_user_store(user_store);
+                }
+                AppState::new(
+                    user_store,
+                    Arc::new(
+                        RedisBannedTokenStore::new(redis_connection.clone())
+                            .with_tenant(&tenant.id),
+                    ),
+                    Arc::new(
+                        RedisTwoFACodeStore::new(redis_connection.clone()).with_tenant(&tenant.id),
+                    ),
+                    Arc::new(
+                        PostgresServiceAccountStore::new(pg_pool.clone())
+                            .with_tenant(tenant.id.clone()),
+                    ),
+                    Arc::new(
+                        PostgresPersonalAccessTokenStore::new(pg_pool.clone())
+                            .with_tenant(tenant.id.clone()),
+                    ),
+                    Arc::new(
+                        PostgresEmailOutbox::new(pg_pool.clone()).with_tenant(tenant.id.clone()),
+                    ),
+                    email_templates.clone(),
+                    sms_client.clone(),
+                    Arc::new(
+                        RedisSmsRateLimiter::new(redis_connection.clone()).with_tenant(&tenant.id),
+                    ),
+                    Arc::new(PostgresAuditLog::new(pg_pool.clone()).with_tenant(tenant.id.clone())),
+                    Arc::new(
+                        PostgresWebhookStore::new(pg_pool.clone()).with_tenant(tenant.id.clone()),
+                    ),
+                    health_checks.clone(),
+                    password_policy.clone(),
+                )
+                .with_tenant(tenant)
+            })
+            .collect();
+        let banned_token_store = app_states[0].banned_token_store.clone();
+        let two_fa_code_store = app_states[0].two_fa_code_store.clone();
+
+        let app = Application::build_for_tenants(app_states, test::APP_ADDRESS)
             .await
-            .expect("Failed to build app");
+            .expect("Failed to build app")
+            .with_drain_timeout(Duration::from_secs(1));
 
         let address = format!("http://{}", app.address.clone());
+        let shutdown_handle = app.shutdown_handle();
+
+        let workers = vec![
+            tokio::spawn(webhook_dispatcher.run(shutdown_handle.clone())),
+            tokio::spawn(email_outbox_worker.run(shutdown_handle.clone())),
+        ];
 
         // Run the auth service in a separate async task
         // to avoid blocking the main test thread.
-        #[allow(clippy::let_underscore_future)]
-        let _ = tokio::spawn(app.run());
+        let server = tokio::spawn(app.run());
 
         let cookie_jar = Arc::new(Jar::default());
         let http_client = reqwest::Client::builder()
@@ -72,7 +259,11 @@ impl TestApp {
             cookie_jar,
             banned_token_store,
             two_fa_code_store,
+            pg_pool,
             http_client,
+            shutdown_handle,
+            server,
+            workers,
             db_name,
             cleanup_called: false,
         }
@@ -86,6 +277,23 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_health(&self, probe: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/health/{}", &self.address, probe))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_metrics(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/metrics", &self.address))
+            .bearer_auth(ADMIN_API_KEY.as_str())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -110,6 +318,83 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    // Signs up `email` with PASSWORD, without 2FA, and logs in.
+    pub async fn sign_up_and_log_in(&self, email: &str) -> String {
+        let response = self
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": PASSWORD,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+        self.log_in(email).await
+    }
+
+    // Logs `email` in with PASSWORD. Returns the auth token, which the cookie jar also keeps
+    // for later requests.
+    pub async fn log_in(&self, email: &str) -> String {
+        let response = self
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": PASSWORD,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        let token = response
+            .cookies()
+            .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+            .expect("No auth cookie found")
+            .value()
+            .to_owned();
+        token
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/reset-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_confirm_email_change(&self, token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/change-email/confirm", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
             .post(format!("{}/logout", &self.address))
@@ -142,7 +427,181 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_phone<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/phone", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_phone<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/phone/verify", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_phone(&self) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/phone", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn put_2fa_channel<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/2fa-channel", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_me(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/me", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn patch_me<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .patch(format!("{}/me", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_personal_access_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/personal-access-tokens", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_personal_access_tokens(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/personal-access-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_personal_access_token(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/personal-access-tokens/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Sends `body` form encoded, authenticating the client with HTTP Basic if `basic_auth` is set.
+    pub async fn post_oauth_token<Body>(
+        &self,
+        body: &Body,
+        basic_auth: Option<(&str, &str)>,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/oauth/token", &self.address))
+            .form(body);
+        if let Some((client_id, client_secret)) = basic_auth {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_admin(&self, path: &str, api_key: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(api_key)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin(&self, path: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(ADMIN_API_KEY.as_str())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_json<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(ADMIN_API_KEY.as_str())
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn patch_admin_json<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .patch(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(ADMIN_API_KEY.as_str())
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_admin(&self, path: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/admin{}", &self.address, path))
+            .bearer_auth(ADMIN_API_KEY.as_str())
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn clean_up(&mut self) {
+        self.shutdown_handle.shutdown();
+        // Anything still running would reconnect while the database is dropped. Tests may
+        // have awaited the server themselves already.
+        if !self.server.is_finished() {
+            let _ = (&mut self.server).await;
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.await;
+        }
+        self.pg_pool.close().await;
         delete_database(&self.db_name).await;
         self.cleanup_called = true;
     }
@@ -156,10 +615,36 @@ impl Drop for TestApp {
     }
 }
 
+// Forwards every email to the test instead of sending it.
+pub struct RecordingEmailClient(pub mpsc::UnboundedSender<(Email, EmailMessage)>);
+
+#[async_trait::async_trait]
+impl EmailClient for RecordingEmailClient {
+    async fn send_email(
+        &self,
+        recipient: &Email,
+        message: &EmailMessage,
+    ) -> color_eyre::Result<()> {
+        let _ = self.0.send((recipient.clone(), message.clone()));
+        Ok(())
+    }
+}
+
+pub const PASSWORD: &str = "vivid-Otter-chimney-17";
+pub const PASSWORD_MAX_AGE_DAYS: i64 = 90;
+
+// SHA-1 of "correct horse battery staple", the one password in the test breach corpus.
+const BREACHED_PASSWORD_SHA1: &str = "ABF7AAD6438836DBE526AA231ABDE2D0EEF74D42";
+
 pub fn get_random_email() -> String {
     format!("{}@example.com", Uuid::new_v4())
 }
 
+// Texts to a number are rate limited in the shared Redis, so tests each need their own.
+pub fn get_random_phone_number() -> String {
+    format!("+1415{:07}", rand::thread_rng().gen_range(0..10_000_000))
+}
+
 async fn configure_postgresql() -> (String, PgPool) {
     let postgresql_conn_url = DATABASE_URL.to_owned();
 
@@ -241,9 +726,9 @@ async fn delete_database(db_name: &str) {
         .expect("Failed to drop the database.");
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
+async fn configure_redis() -> ConnectionManager {
+    let client = get_redis_client(REDIS_HOST_NAME.to_owned()).expect("Failed to get Redis client");
+    get_redis_connection(client, *REDIS_COMMAND_TIMEOUT)
+        .await
         .expect("Failed to get Redis connection")
 }
@@ -1,6 +1,10 @@
 use crate::helpers::{get_random_email, TestApp};
+use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
 use auth_service::{
-    domain::email::Email, routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME,
+    domain::{data_stores::UserStore, email::Email, user::User},
+    routes::TwoFactorAuthResponse,
+    services::data_stores::postgres_user_store::{PasswordHashingConfig, PostgresUserStore},
+    utils::constants::JWT_COOKIE_NAME,
 };
 
 #[tokio::test]
@@ -11,7 +15,7 @@ async fn should_return_422_if_malformed_credentials() {
 
     let test_cases = [
         serde_json::json!({
-            "password": "password123",
+            "password": "vivid-Otter-chimney-17",
         }),
         serde_json::json!({
             "email": random_email,
@@ -41,7 +45,7 @@ async fn should_return_400_if_invalid_input() {
     let test_cases = [
         serde_json::json!({
             "email": "foo.foo",
-            "password": "password123",
+            "password": "vivid-Otter-chimney-17",
         }),
         serde_json::json!({
             "email": random_email,
@@ -70,16 +74,23 @@ async fn should_return_401_if_incorrect_credentials() {
     let response = app
         .post_signup(&serde_json::json!({ // create a user
             "email": random_email,
-            "password": "password123",
+            "password": "vivid-Otter-chimney-17",
             "requires2FA": false
         }))
         .await;
     assert_eq!(response.status().as_u16(), 201);
 
-    let test_cases = [serde_json::json!({
-        "email": random_email,
-        "password": "wrongEmail",
-    })];
+    let test_cases = [
+        serde_json::json!({
+            "email": random_email,
+            "password": "wrongEmail",
+        }),
+        // Longer than any password the policy allows, so it is refused without hashing.
+        serde_json::json!({
+            "email": random_email,
+            "password": "x".repeat(100_000),
+        }),
+    ];
 
     for test_case in test_cases.iter() {
         let response = app.post_login(test_case).await;
@@ -102,7 +113,7 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": false
     });
 
@@ -112,7 +123,7 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
     });
 
     let response = app.post_login(&login_body).await;
@@ -128,6 +139,36 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     app.clean_up().await;
 }
 
+#[tokio::test]
+async fn should_return_200_for_any_spelling_of_the_email() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "Jane.Roe@Example.com",
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    for email in ["jane.roe@example.com", " JANE.ROE@EXAMPLE.COM"] {
+        let response = app
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": "vivid-Otter-chimney-17",
+            }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            200,
+            "Failed for email: {:?}",
+            email
+        );
+    }
+    app.clean_up().await;
+}
+
 #[tokio::test]
 async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let mut app = TestApp::new().await;
@@ -136,7 +177,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": true
     });
     let response = app.post_signup(&signup_body).await;
@@ -144,7 +185,7 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
     });
     let response = app.post_login(&login_body).await;
     assert_eq!(response.status().as_u16(), 206);
@@ -156,9 +197,8 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     assert_eq!(json_body.message, "2FA required".to_owned());
 
-    let binding = app.two_fa_code_store.clone();
-    let store = binding.read().await;
-    let (login_attempt_id, _) = store
+    let (login_attempt_id, _) = app
+        .two_fa_code_store
         .get_code(&Email::parse(random_email.as_str()).unwrap())
         .await
         .unwrap();
@@ -166,3 +206,94 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     assert_eq!(json_body.login_attempt_id, login_attempt_id.as_ref());
     app.clean_up().await;
 }
+
+async fn stored_password_hash(app: &TestApp, email: &str) -> String {
+    sqlx::query_scalar("SELECT password_hash FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to read the password hash")
+}
+
+#[tokio::test]
+async fn should_rehash_outdated_password_hash_on_login() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    // Stored as if the user had signed up while weaker parameters were configured.
+    let outdated = PasswordHashingConfig {
+        memory_kib: 8192,
+        iterations: 1,
+        parallelism: 1,
+    };
+    PostgresUserStore::new(app.pg_pool.clone())
+        .with_hashing_config(outdated)
+        .add_user(User::new(
+            random_email.clone(),
+            "vivid-Otter-chimney-17".to_owned(),
+            false,
+        ))
+        .await
+        .unwrap();
+    let outdated_hash = stored_password_hash(&app, &random_email).await;
+    assert!(outdated_hash.starts_with("$argon2id$v=19$m=8192,t=1,p=1$"));
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "vivid-Otter-chimney-17",
+    });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    let current_hash = stored_password_hash(&app, &random_email).await;
+    assert!(current_hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
+
+    // The upgraded hash keeps working and is left alone from now on.
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+    assert_eq!(
+        stored_password_hash(&app, &random_email).await,
+        current_hash
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_accept_and_rehash_password_hashed_before_normalization() {
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    // "ﬁ" is a single ligature character that NFKC turns into "fi".
+    let typed = "vivid-Otter-\u{FB01}sh-17";
+    let normalized = "vivid-Otter-fish-17";
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": normalized,
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    // Stored as if the user had signed up before passwords were normalized.
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let legacy_hash = Argon2::default()
+        .hash_password(typed.as_bytes(), &salt)
+        .unwrap()
+        .to_string();
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE email = $2")
+        .bind(&legacy_hash)
+        .bind(&random_email)
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+
+    let login_body = serde_json::json!({ "email": random_email, "password": typed });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+    assert_ne!(stored_password_hash(&app, &random_email).await, legacy_hash);
+
+    // The new hash is of the normalized form, so either way of typing it works.
+    let login_body = serde_json::json!({ "email": random_email, "password": normalized });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+    let login_body = serde_json::json!({ "email": random_email, "password": typed });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
@@ -43,7 +43,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": false
     });
 
@@ -53,7 +53,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
     });
 
     let response = app.post_login(&login_body).await;
@@ -80,9 +80,8 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert!(auth_cookie.value().is_empty());
 
-    let binding = app.banned_token_store.clone();
-    let banned_token_store = binding.read().await;
-    let contains_token = banned_token_store
+    let contains_token = app
+        .banned_token_store
         .contains_token(token)
         .await
         .expect("Failed to check if token is banned");
@@ -95,12 +94,12 @@ async fn should_return_200_if_valid_jwt_cookie() {
 async fn should_return_400_if_logout_called_twice_in_a_row() {
     let signup_body = serde_json::json!({
         "email": "foo.bar@gmail.com",
-        "password": "password1234",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": false
     });
     let login_body = serde_json::json!({
         "email": "foo.bar@gmail.com",
-        "password": "password1234",
+        "password": "vivid-Otter-chimney-17",
     });
 
     let mut app = TestApp::new().await;
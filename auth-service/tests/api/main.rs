@@ -1,7 +1,23 @@
+mod admin;
+mod audit_log;
+mod change_email;
+mod change_password;
+mod emails;
+mod health;
 mod helpers;
 mod login;
 mod logout;
+mod metrics;
+mod oauth;
+mod personal_access_tokens;
+mod phone;
+mod profile;
+mod request_id;
 mod root;
+mod service_accounts;
+mod shutdown;
 mod signup;
+mod tenants;
 mod verify_2fa;
 mod verify_token;
+mod webhooks;
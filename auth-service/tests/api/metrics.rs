@@ -0,0 +1,110 @@
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_expose_request_and_domain_metrics() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "vivid-Otter-chimney-17",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrong-password",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_metrics().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+
+    let body = response.text().await.unwrap();
+    for expected in [
+        r#"http_requests_total{method="POST",route="/signup",status="201"}"#,
+        r#"http_request_duration_seconds_bucket{method="POST",route="/login",status="401""#,
+        r#"auth_signups_total{outcome="success"}"#,
+        r#"auth_logins_total{outcome="failure"}"#,
+    ] {
+        assert!(body.contains(expected), "Missing {} in:\n{}", expected, body);
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_label_requests_by_route_template() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_admin(&format!("/users/{}/disable", get_random_email()))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let body = app.get_metrics().await.text().await.unwrap();
+    assert!(body.contains(
+        r#"http_requests_total{method="POST",route="/admin/users/:email/disable",status="404"}"#
+    ));
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_the_admin_api_key() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/metrics", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .http_client
+        .get(format!("{}/metrics", &app.address))
+        .bearer_auth("wrong-key")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_count_revoked_sessions_as_banned_tokens() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "vivid-Otter-chimney-17",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let before = banned_tokens(&app).await;
+
+    let response = app
+        .post_admin(&format!("/users/{}/revoke-sessions", random_email))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Other tests share the counter, so it may have grown by more than one.
+    assert!(banned_tokens(&app).await > before);
+    app.clean_up().await;
+}
+
+async fn banned_tokens(app: &TestApp) -> f64 {
+    let body = app.get_metrics().await.text().await.unwrap();
+    body.lines()
+        .find_map(|line| line.strip_prefix("auth_tokens_banned_total "))
+        .map_or(0.0, |value| value.parse().unwrap())
+}
@@ -0,0 +1,234 @@
+use auth_service::{
+    routes::{AuditLogResponse, OAuthErrorResponse, TokenResponse, VerifyTokenResponse},
+    utils::{
+        auth::{SubjectType, TOKEN_TTL_SECONDS},
+        constants::{ADMIN_API_KEY, JWT_COOKIE_NAME},
+    },
+};
+use reqwest::header::{CACHE_CONTROL, COOKIE, WWW_AUTHENTICATE};
+use serde_json::{json, Value};
+
+use crate::helpers::TestApp;
+
+// Creates a service account allowed `scopes` and returns its client id and secret.
+async fn service_account(app: &TestApp, scopes: &[&str]) -> (String, String) {
+    let response = app
+        .post_admin_json(
+            "/service-accounts",
+            &json!({ "name": "Nightly export", "scopes": scopes }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let body: Value = response.json().await.unwrap();
+    (
+        body["clientId"].as_str().unwrap().to_owned(),
+        body["clientSecret"].as_str().unwrap().to_owned(),
+    )
+}
+
+async fn assert_oauth_error(response: reqwest::Response, status: u16, error: &str) {
+    assert_eq!(response.status().as_u16(), status);
+    let body = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(body.error, error);
+}
+
+#[tokio::test]
+async fn should_issue_token_to_client_using_basic_auth() {
+    let mut app = TestApp::new().await;
+    let (client_id, secret) = service_account(&app, &["reports:read", "jobs:run"]).await;
+
+    let response = app
+        .post_oauth_token(
+            &json!({ "grant_type": "client_credentials" }),
+            Some((&client_id, &secret)),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+    let body = response.json::<TokenResponse>().await.unwrap();
+    assert_eq!(body.token_type, "Bearer");
+    assert_eq!(body.expires_in, TOKEN_TTL_SECONDS);
+    assert_eq!(body.scope, "reports:read jobs:run");
+
+    let response = app
+        .post_verify_token(&json!({ "token": body.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let claims = response.json::<VerifyTokenResponse>().await.unwrap();
+    assert_eq!(claims.sub, client_id);
+    assert_eq!(claims.sub_type, SubjectType::ServiceAccount);
+    assert_eq!(claims.scope.as_deref(), Some("reports:read jobs:run"));
+    assert_eq!(claims.email, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_issue_token_to_client_using_credentials_in_body() {
+    let mut app = TestApp::new().await;
+    let (client_id, secret) = service_account(&app, &["reports:read"]).await;
+
+    let response = app
+        .post_oauth_token(
+            &json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": secret
+            }),
+            None,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_grant_only_allowed_scopes() {
+    let mut app = TestApp::new().await;
+    let (client_id, secret) = service_account(&app, &["reports:read", "jobs:run"]).await;
+    let credentials = Some((client_id.as_str(), secret.as_str()));
+
+    let response = app
+        .post_oauth_token(
+            &json!({ "grant_type": "client_credentials", "scope": "jobs:run" }),
+            credentials,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.json::<TokenResponse>().await.unwrap();
+    assert_eq!(body.scope, "jobs:run");
+
+    let response = app
+        .post_oauth_token(
+            &json!({ "grant_type": "client_credentials", "scope": "jobs:run users:write" }),
+            credentials,
+        )
+        .await;
+    assert_oauth_error(response, 400, "invalid_scope").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_invalid_client_credentials() {
+    let mut app = TestApp::new().await;
+    let (client_id, _) = service_account(&app, &[]).await;
+    let body = json!({ "grant_type": "client_credentials" });
+
+    let response = app
+        .post_oauth_token(&body, Some((&client_id, "wrong-secret")))
+        .await;
+    assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    let unknown_client = "4c0a4b0e-5f3a-4e0a-9d0b-1f2e3d4c5b6a";
+    let response = app
+        .post_oauth_token(&body, Some((unknown_client, "secret")))
+        .await;
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    let response = app.post_oauth_token(&body, None).await;
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_malformed_requests() {
+    let mut app = TestApp::new().await;
+    let (client_id, secret) = service_account(&app, &[]).await;
+    let credentials = Some((client_id.as_str(), secret.as_str()));
+
+    let response = app
+        .post_oauth_token(&json!({ "grant_type": "password" }), credentials)
+        .await;
+    assert_oauth_error(response, 400, "unsupported_grant_type").await;
+
+    let response = app.post_oauth_token(&json!({}), credentials).await;
+    assert_oauth_error(response, 400, "invalid_request").await;
+
+    // Credentials in both the header and the body.
+    let response = app
+        .post_oauth_token(
+            &json!({
+                "grant_type": "client_credentials",
+                "client_id": client_id,
+                "client_secret": secret
+            }),
+            credentials,
+        )
+        .await;
+    assert_oauth_error(response, 400, "invalid_request").await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/oauth/token", &app.address))
+        .json(&json!({ "grant_type": "client_credentials" }))
+        .send()
+        .await
+        .unwrap();
+    assert_oauth_error(response, 400, "invalid_request").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn service_account_tokens_do_not_sign_in_a_user() {
+    let mut app = TestApp::new().await;
+    let (client_id, secret) = service_account(&app, &[]).await;
+    let response = app
+        .post_oauth_token(
+            &json!({ "grant_type": "client_credentials" }),
+            Some((&client_id, &secret)),
+        )
+        .await;
+    let token = response.json::<TokenResponse>().await.unwrap().access_token;
+
+    let response = app
+        .http_client
+        .get(format!("{}/me", &app.address))
+        .header(COOKIE, format!("{}={}", JWT_COOKIE_NAME, token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_audit_token_requests() {
+    let mut app = TestApp::new().await;
+    let (client_id, secret) = service_account(&app, &[]).await;
+    let body = json!({ "grant_type": "client_credentials" });
+
+    app.post_oauth_token(&body, Some((&client_id, "wrong-secret")))
+        .await;
+    app.post_oauth_token(&body, Some((&client_id, &secret)))
+        .await;
+
+    let response = app
+        .get_admin(
+            &format!("/audit-log?actor={}&action=client_credentials", client_id),
+            &ADMIN_API_KEY,
+        )
+        .await;
+    let body = response.json::<AuditLogResponse>().await.unwrap();
+    let summary: Vec<(&str, Option<&str>)> = body
+        .events
+        .iter()
+        .map(|record| {
+            (
+                record.event.outcome.as_ref(),
+                record.event.detail.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![("success", None), ("failure", Some("invalid_client"))]
+    );
+
+    app.clean_up().await;
+}
@@ -0,0 +1,251 @@
+use auth_service::{routes::VerifyTokenResponse, ErrorResponse};
+use serde_json::{json, Value};
+
+use crate::helpers::{get_random_email, TestApp, PASSWORD};
+
+// Adds the password every request needs.
+async fn create(app: &TestApp, body: &Value) -> Value {
+    let mut body = body.clone();
+    body["password"] = json!(PASSWORD);
+    let response = app.post_personal_access_token(&body).await;
+    assert_eq!(response.status().as_u16(), 201);
+    response.json().await.unwrap()
+}
+
+async fn verify(app: &TestApp, token: &str) -> reqwest::Response {
+    app.post_verify_token(&json!({ "token": token })).await
+}
+
+#[tokio::test]
+async fn should_create_list_and_revoke_tokens() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+
+    let created = create(
+        &app,
+        &json!({
+            "name": "CI deploys",
+            "scopes": ["repo:read", "deploy"],
+            "expiresAt": "2999-01-01T00:00:00Z"
+        }),
+    )
+    .await;
+    let token = created["token"].as_str().unwrap();
+    let id = created["id"].as_str().unwrap();
+    assert!(token.starts_with("pat_"));
+    assert_eq!(created["prefix"].as_str().unwrap(), &token[..12]);
+    assert_eq!(created["scopes"], json!(["repo:read", "deploy"]));
+    assert_eq!(created["expiresAt"], "2999-01-01T00:00:00Z");
+
+    let response = app.get_personal_access_tokens().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let list: Value = response.json().await.unwrap();
+    let listed = &list["personalAccessTokens"];
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_eq!(listed[0]["id"], id);
+    assert_eq!(listed[0]["name"], "CI deploys");
+    assert_eq!(listed[0]["prefix"], created["prefix"]);
+    // The token itself is only shown when it is created.
+    assert!(listed[0].get("token").is_none());
+
+    assert_eq!(verify(&app, token).await.status().as_u16(), 200);
+
+    let response = app.delete_personal_access_token(id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify(&app, token).await.status().as_u16(), 401);
+    let response = app.delete_personal_access_token(id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_token_returns_the_scopes_and_user() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+    let user_id = app.get_me().await.json::<Value>().await.unwrap()["id"].clone();
+
+    let created = create(
+        &app,
+        &json!({ "name": "Laptop", "scopes": ["repo:read", "repo:write"] }),
+    )
+    .await;
+
+    let response = verify(&app, created["token"].as_str().unwrap()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "tokenType": "personal_access_token",
+            "sub": user_id,
+            "subType": "user",
+            "email": email,
+            "scope": "repo:read repo:write"
+        })
+    );
+    let body: VerifyTokenResponse = serde_json::from_value(body).unwrap();
+    assert_eq!(body.scope.as_deref(), Some("repo:read repo:write"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_store_a_hash_of_the_token() {
+    let mut app = TestApp::new().await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+    let created = create(&app, &json!({ "name": "Laptop" })).await;
+
+    let (token_hash, prefix): (String, String) =
+        sqlx::query_as("SELECT token_hash, prefix FROM personal_access_tokens")
+            .fetch_one(&app.pg_pool)
+            .await
+            .unwrap();
+    let token = created["token"].as_str().unwrap();
+    assert_ne!(token_hash, token);
+    assert!(!token_hash.contains(&token[4..]));
+    assert_eq!(prefix, &token[..12]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn expired_tokens_are_rejected() {
+    let mut app = TestApp::new().await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+    let created = create(
+        &app,
+        &json!({ "name": "Laptop", "expiresAt": "2999-01-01T00:00:00Z" }),
+    )
+    .await;
+    let token = created["token"].as_str().unwrap();
+    assert_eq!(verify(&app, token).await.status().as_u16(), 200);
+
+    sqlx::query("UPDATE personal_access_tokens SET expires_at = NOW() - INTERVAL '1 second'")
+        .execute(&app.pg_pool)
+        .await
+        .unwrap();
+    assert_eq!(verify(&app, token).await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_tokens() {
+    let mut app = TestApp::new().await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+
+    let response = app
+        .post_personal_access_token(&json!({
+            "name": "",
+            "password": PASSWORD,
+            "scopes": ["has space"],
+            "expiresAt": "2000-01-01T00:00:00Z"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "Invalid personal access token");
+    assert_eq!(error.reasons.len(), 2);
+
+    let response = app
+        .post_personal_access_token(&json!({
+            "name": "Laptop",
+            "password": PASSWORD,
+            "expiresAt": "2000-01-01T00:00:00Z"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_a_session() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_personal_access_token(&json!({ "name": "Laptop", "password": PASSWORD }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        app.get_personal_access_tokens().await.status().as_u16(),
+        400
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn users_cannot_see_or_revoke_tokens_of_others() {
+    let mut app = TestApp::new().await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+    let created = create(&app, &json!({ "name": "Laptop" })).await;
+    let id = created["id"].as_str().unwrap();
+
+    // The cookie jar now holds the second user's session.
+    app.sign_up_and_log_in(&get_random_email()).await;
+    let list: Value = app.get_personal_access_tokens().await.json().await.unwrap();
+    assert_eq!(list["personalAccessTokens"], json!([]));
+    let response = app.delete_personal_access_token(id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let token = created["token"].as_str().unwrap();
+    assert_eq!(verify(&app, token).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_the_current_password() {
+    let mut app = TestApp::new().await;
+    app.sign_up_and_log_in(&get_random_email()).await;
+
+    for password in ["wrong-Otter-chimney-17", ""] {
+        let response = app
+            .post_personal_access_token(&json!({ "name": "Laptop", "password": password }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401, "Failed for {:?}", password);
+    }
+    let response = app
+        .post_personal_access_token(&json!({ "name": "Laptop" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+
+    let list: Value = app.get_personal_access_tokens().await.json().await.unwrap();
+    assert_eq!(list["personalAccessTokens"], json!([]));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn tokens_are_revoked_with_the_sessions() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+    let created = create(&app, &json!({ "name": "Laptop" })).await;
+    let token = created["token"].as_str().unwrap();
+
+    let response = app
+        .post_admin(&format!("/users/{}/revoke-sessions", email))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify(&app, token).await.status().as_u16(), 401);
+
+    // Changing the password signs the user out everywhere, tokens included.
+    app.log_in(&email).await;
+    let created = create(&app, &json!({ "name": "Laptop" })).await;
+    let token = created["token"].as_str().unwrap();
+    let response = app
+        .post_change_password(&json!({
+            "currentPassword": PASSWORD,
+            "newPassword": "brisk-Heron-lantern-42"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(verify(&app, token).await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,260 @@
+use auth_service::{
+    domain::{
+        data_stores::SMS_MAX_PER_WINDOW, email::Email, phone_number::PhoneNumber, SmsClient,
+    },
+    routes::{PhoneResponse, TwoFactorAuthResponse},
+    ErrorResponse,
+};
+use color_eyre::eyre::Result;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::mpsc;
+
+use crate::helpers::{get_random_email, get_random_phone_number, TestApp};
+
+// Forwards every text to the test instead of sending it.
+struct RecordingSmsClient(mpsc::UnboundedSender<(PhoneNumber, String)>);
+
+#[async_trait::async_trait]
+impl SmsClient for RecordingSmsClient {
+    async fn send_sms(&self, recipient: &PhoneNumber, message: &str) -> Result<()> {
+        let _ = self.0.send((recipient.clone(), message.to_owned()));
+        Ok(())
+    }
+}
+
+async fn next_sms(texts: &mut mpsc::UnboundedReceiver<(PhoneNumber, String)>) -> (String, String) {
+    let (recipient, message) = tokio::time::timeout(Duration::from_secs(5), texts.recv())
+        .await
+        .expect("Timed out waiting for the SMS")
+        .unwrap();
+    let code = message.split_whitespace().next().unwrap().to_owned();
+    (recipient.as_ref().to_owned(), code)
+}
+
+async fn login(app: &TestApp, email: &str) -> reqwest::Response {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "vivid-Otter-chimney-17",
+    });
+    app.post_login(&login_body).await
+}
+
+// Signs up a user with 2FA and completes the login with the emailed code.
+async fn signup_and_login(app: &TestApp, email: &str) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "vivid-Otter-chimney-17",
+        "requires2FA": true
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let response = login(app, email).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap()
+        .login_attempt_id;
+
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    let verify_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code.as_ref(),
+    });
+    assert_eq!(
+        app.post_verify_2fa(&verify_body).await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_text_2fa_code_to_verified_phone() {
+    let (sender, mut texts) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_sms_client(Arc::new(RecordingSmsClient(sender))).await;
+    let random_email = get_random_email();
+    let phone_number = get_random_phone_number();
+    signup_and_login(&app, &random_email).await;
+
+    // Written the way people type it, e.g. "+1 (415) 555-0123".
+    let formatted = format!(
+        "+1 ({}) {}-{}",
+        &phone_number[2..5],
+        &phone_number[5..8],
+        &phone_number[8..]
+    );
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": formatted }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let (recipient, code) = next_sms(&mut texts).await;
+    assert_eq!(recipient, phone_number);
+
+    let response = app
+        .post_verify_phone(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: PhoneResponse = response.json().await.unwrap();
+    assert_eq!(body.phone_number, phone_number);
+
+    let response = app
+        .put_2fa_channel(&serde_json::json!({ "channel": "sms" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(login(&app, &random_email).await.status().as_u16(), 206);
+    let (recipient, code) = next_sms(&mut texts).await;
+    let (_, stored_code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(recipient, phone_number);
+    assert_eq!(code, stored_code.as_ref());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_sms_channel_without_verified_phone() {
+    let (sender, mut texts) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_sms_client(Arc::new(RecordingSmsClient(sender))).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let channel_body = serde_json::json!({ "channel": "sms" });
+    let response = app.put_2fa_channel(&channel_body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Phone number not verified"
+    );
+
+    // An unconfirmed number is not enough either.
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": get_random_phone_number() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    next_sms(&mut texts).await;
+    assert_eq!(
+        app.put_2fa_channel(&channel_body).await.status().as_u16(),
+        400
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_incorrect_verification_code() {
+    let (sender, mut texts) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_sms_client(Arc::new(RecordingSmsClient(sender))).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": get_random_phone_number() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let (_, code) = next_sms(&mut texts).await;
+    let wrong_code = if code == "123456" { "654321" } else { "123456" };
+
+    let response = app
+        .post_verify_phone(&serde_json::json!({ "code": wrong_code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Incorrect verification code"
+    );
+
+    // The pending code is used up by the failed attempt.
+    let response = app
+        .post_verify_phone(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_phone_number() {
+    let mut app = TestApp::new().await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": "555-0123" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Invalid phone number"
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": get_random_phone_number() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(app.delete_phone().await.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_when_the_number_was_just_texted() {
+    let (sender, mut texts) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_sms_client(Arc::new(RecordingSmsClient(sender))).await;
+    let phone_number = get_random_phone_number();
+
+    signup_and_login(&app, &get_random_email()).await;
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": phone_number }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    next_sms(&mut texts).await;
+
+    // Another account cannot have the number texted again either.
+    signup_and_login(&app, &get_random_email()).await;
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": phone_number }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(
+        response.json::<ErrorResponse>().await.unwrap().error,
+        "Too many texts"
+    );
+    assert!(texts.try_recv().is_err());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_when_the_user_sent_too_many_texts() {
+    let (sender, mut texts) = mpsc::unbounded_channel();
+    let mut app = TestApp::with_sms_client(Arc::new(RecordingSmsClient(sender))).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    for _ in 0..SMS_MAX_PER_WINDOW {
+        let response = app
+            .post_phone(&serde_json::json!({ "phoneNumber": get_random_phone_number() }))
+            .await;
+        assert_eq!(response.status().as_u16(), 202);
+        next_sms(&mut texts).await;
+    }
+
+    let response = app
+        .post_phone(&serde_json::json!({ "phoneNumber": get_random_phone_number() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(texts.try_recv().is_err());
+
+    app.clean_up().await;
+}
@@ -0,0 +1,226 @@
+use auth_service::{
+    domain::{
+        profile::{ProfileField, UserProfile},
+        tenant::Tenant,
+    },
+    utils::{auth::Claims, constants::ADMIN_API_KEY},
+    ErrorResponse,
+};
+use jsonwebtoken::{DecodingKey, Validation};
+use serde_json::{json, Value};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_empty_profile_for_new_user() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+
+    let response = app.get_me().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let me: Value = response.json().await.unwrap();
+    assert_eq!(me["email"], email.as_str());
+    assert!(uuid::Uuid::parse_str(me["id"].as_str().unwrap()).is_ok());
+    assert_eq!(me["displayName"], Value::Null);
+    assert_eq!(me["userMetadata"], json!({}));
+    assert_eq!(me["appMetadata"], json!({}));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_merge_profile_updates() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+
+    let response = app
+        .patch_me(&json!({
+            "displayName": "Ada Lovelace",
+            "locale": "en-GB",
+            "timezone": "Europe/London",
+            "avatarUrl": "https://example.com/ada.png",
+            "userMetadata": { "theme": "dark", "beta": true }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .patch_me(&json!({
+            "locale": null,
+            "userMetadata": { "beta": null, "lang": "en" }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let me: Value = app.get_me().await.json().await.unwrap();
+    assert_eq!(me["displayName"], "Ada Lovelace");
+    assert_eq!(me["locale"], Value::Null);
+    assert_eq!(me["timezone"], "Europe/London");
+    assert_eq!(me["avatarUrl"], "https://example.com/ada.png");
+    assert_eq!(me["userMetadata"], json!({ "theme": "dark", "lang": "en" }));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_profile() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+
+    let test_cases = [
+        json!({ "locale": "not a locale" }),
+        json!({ "avatarUrl": "ftp://example.com/a.png" }),
+        json!({ "nickname": "Ada" }),
+        json!({ "appMetadata": { "plan": "enterprise" } }),
+        json!({ "userMetadata": { "blob": "x".repeat(4096) } }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.patch_me(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+        let error = response.json::<ErrorResponse>().await.unwrap();
+        assert_eq!(error.error, "Invalid profile");
+        assert!(!error.reasons.is_empty());
+    }
+
+    // Nothing was stored.
+    let me: Value = app.get_me().await.json().await.unwrap();
+    assert_eq!(me["locale"], Value::Null);
+    assert_eq!(me["appMetadata"], json!({}));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_login() {
+    let mut app = TestApp::new().await;
+
+    assert_eq!(app.get_me().await.status().as_u16(), 400);
+    assert_eq!(
+        app.patch_me(&json!({ "displayName": "Ada" }))
+            .await
+            .status()
+            .as_u16(),
+        400
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn admin_app_metadata_is_kept_apart_from_user_edits() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+
+    let response = app
+        .patch_admin_json(
+            &format!("/users/{}/app-metadata", email),
+            &json!({ "plan": "pro", "seats": 3 }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .patch_me(&json!({ "displayName": "Ada", "userMetadata": { "plan": "free" } }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .patch_admin_json(
+            &format!("/users/{}/app-metadata", email),
+            &json!({ "seats": null }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .get_admin(&format!("/users/{}/profile", email), &ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let profile = response.json::<UserProfile>().await.unwrap();
+    assert_eq!(profile.display_name.as_deref(), Some("Ada"));
+    assert_eq!(
+        Value::Object(profile.user_metadata),
+        json!({ "plan": "free" })
+    );
+    assert_eq!(
+        Value::Object(profile.app_metadata),
+        json!({ "plan": "pro" })
+    );
+
+    let me: Value = app.get_me().await.json().await.unwrap();
+    assert_eq!(me["appMetadata"], json!({ "plan": "pro" }));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn admin_profile_routes_return_404_for_unknown_user() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .get_admin(&format!("/users/{}/profile", email), &ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app
+        .patch_admin_json(
+            &format!("/users/{}/app-metadata", email),
+            &json!({ "plan": "pro" }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_tokens_carry_the_projected_profile_claims() {
+    let tenant = Tenant {
+        profile_claims: vec![ProfileField::DisplayName, ProfileField::AppMetadata],
+        ..Tenant::from_env()
+    };
+    let mut app = TestApp::with_tenants(vec![tenant.clone()]).await;
+    let email = get_random_email();
+    app.sign_up_and_log_in(&email).await;
+
+    let response = app
+        .patch_me(&json!({ "displayName": "Ada", "locale": "en-GB" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .patch_admin_json(
+            &format!("/users/{}/app-metadata", email),
+            &json!({ "plan": "pro" }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The profile is read at login, so only the next token has it.
+    let token = app.log_in(&email).await;
+    let mut validation = Validation::default();
+    validation.set_audience(&[tenant.id.as_ref()]);
+    let claims = jsonwebtoken::decode::<Claims>(
+        &token,
+        &DecodingKey::from_secret(tenant.jwt_secret.as_bytes()),
+        &validation,
+    )
+    .expect("Invalid login token")
+    .claims;
+    assert_eq!(
+        Value::Object(claims.profile),
+        json!({ "displayName": "Ada", "appMetadata": { "plan": "pro" } })
+    );
+
+    app.clean_up().await;
+}
@@ -0,0 +1,92 @@
+use auth_service::{
+    routes::AuditLogResponse,
+    utils::{constants::ADMIN_API_KEY, tracing::REQUEST_ID_HEADER},
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_generate_request_id_when_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_root().await;
+
+    let request_id = response.headers()[REQUEST_ID_HEADER.as_str()]
+        .to_str()
+        .unwrap();
+    assert!(uuid::Uuid::parse_str(request_id).is_ok());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_echo_incoming_request_id() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/", &app.address))
+        .header(REQUEST_ID_HEADER.as_str(), "upstream-id-123")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(
+        response.headers()[REQUEST_ID_HEADER.as_str()],
+        "upstream-id-123"
+    );
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_replace_unusable_request_id() {
+    let mut app = TestApp::new().await;
+
+    for request_id in ["x".repeat(129), "has spaces".to_owned()] {
+        let response = app
+            .http_client
+            .get(format!("{}/", &app.address))
+            .header(REQUEST_ID_HEADER.as_str(), &request_id)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+
+        let echoed = response.headers()[REQUEST_ID_HEADER.as_str()]
+            .to_str()
+            .unwrap();
+        assert_ne!(echoed, request_id);
+        assert!(uuid::Uuid::parse_str(echoed).is_ok());
+    }
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_incoming_request_id_in_audit_log() {
+    let mut app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .http_client
+        .post(format!("{}/signup", &app.address))
+        .header(REQUEST_ID_HEADER.as_str(), "signup-request-1")
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    let body = app
+        .get_admin(&format!("/audit-log?actor={}", email), &ADMIN_API_KEY)
+        .await
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse");
+    assert_eq!(
+        body.events[0].event.metadata.request_id.as_deref(),
+        Some("signup-request-1")
+    );
+    app.clean_up().await;
+}
@@ -0,0 +1,208 @@
+use auth_service::{domain::tenant::Tenant, utils::constants::ADMIN_API_KEY, ErrorResponse};
+use serde_json::{json, Value};
+
+use crate::helpers::TestApp;
+
+async fn create(app: &TestApp, name: &str, scopes: &[&str]) -> Value {
+    let response = app
+        .post_admin_json(
+            "/service-accounts",
+            &json!({ "name": name, "scopes": scopes }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    response.json().await.unwrap()
+}
+
+async fn token(app: &TestApp, client_id: &str, client_secret: &str) -> reqwest::Response {
+    let body = json!({ "grant_type": "client_credentials" });
+    app.post_oauth_token(&body, Some((client_id, client_secret)))
+        .await
+}
+
+async fn access_token(app: &TestApp, client_id: &str, client_secret: &str) -> String {
+    let response = token(app, client_id, client_secret).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Value = response.json().await.unwrap();
+    body["access_token"].as_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn should_create_list_get_and_delete_service_accounts() {
+    let mut app = TestApp::new().await;
+
+    let created = create(&app, "Nightly export", &["reports:read", "jobs:run"]).await;
+    let client_id = created["clientId"].as_str().unwrap();
+    assert_eq!(created["name"], "Nightly export");
+    assert_eq!(created["scopes"], json!(["reports:read", "jobs:run"]));
+    assert_eq!(created["clientSecret"].as_str().unwrap().len(), 64);
+
+    let path = format!("/service-accounts/{}", client_id);
+    let response = app.get_admin(&path, &ADMIN_API_KEY).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let account: Value = response.json().await.unwrap();
+    assert_eq!(account["clientId"], client_id);
+    // The secret is only shown when it is generated.
+    assert!(account.get("clientSecret").is_none());
+
+    let response = app.get_admin("/service-accounts", &ADMIN_API_KEY).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let list: Value = response.json().await.unwrap();
+    assert_eq!(list["serviceAccounts"], json!([account]));
+
+    assert_eq!(app.delete_admin(&path).await.status().as_u16(), 200);
+    assert_eq!(
+        app.get_admin(&path, &ADMIN_API_KEY).await.status().as_u16(),
+        404
+    );
+    assert_eq!(app.delete_admin(&path).await.status().as_u16(), 404);
+    let response = app
+        .get_admin("/service-accounts/not-a-client-id", &ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_service_accounts() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_admin_json(
+            "/service-accounts",
+            &json!({ "name": " ", "scopes": ["reports:read", "has space"] }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "Invalid service account");
+    assert_eq!(error.reasons.len(), 2);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_the_admin_api_key() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_admin("/service-accounts", "wrong-key").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn rotating_the_secret_revokes_the_old_secret_and_its_tokens() {
+    let mut app = TestApp::new().await;
+    let created = create(&app, "Billing sync", &[]).await;
+    let client_id = created["clientId"].as_str().unwrap();
+    let old_secret = created["clientSecret"].as_str().unwrap();
+    let old_token = access_token(&app, client_id, old_secret).await;
+
+    let response = app
+        .post_admin(&format!("/service-accounts/{}/rotate-secret", client_id))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let rotated: Value = response.json().await.unwrap();
+    assert_eq!(rotated["clientId"], client_id);
+    let new_secret = rotated["clientSecret"].as_str().unwrap();
+    assert_ne!(new_secret, old_secret);
+
+    assert_eq!(
+        token(&app, client_id, old_secret).await.status().as_u16(),
+        401
+    );
+    assert_eq!(
+        token(&app, client_id, new_secret).await.status().as_u16(),
+        200
+    );
+    let response = app.post_verify_token(&json!({ "token": old_token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // Most likely issued in the same second as the rotation.
+    let new_token = access_token(&app, client_id, new_secret).await;
+    let response = app.post_verify_token(&json!({ "token": new_token })).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_admin("/service-accounts/4c0a4b0e-5f3a-4e0a-9d0b-1f2e3d4c5b6a/rotate-secret")
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn deleting_a_service_account_invalidates_its_tokens() {
+    let mut app = TestApp::new().await;
+    let created = create(&app, "Billing sync", &[]).await;
+    let client_id = created["clientId"].as_str().unwrap();
+    let secret = created["clientSecret"].as_str().unwrap();
+    let access_token = access_token(&app, client_id, secret).await;
+
+    let response = app
+        .post_verify_token(&json!({ "token": access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let path = format!("/service-accounts/{}", client_id);
+    assert_eq!(app.delete_admin(&path).await.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&json!({ "token": access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(token(&app, client_id, secret).await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn service_accounts_belong_to_one_tenant() {
+    let tenants = Tenant::parse_all(
+        r#"[
+            { "id": "acme", "jwtSecret": "acme-secret", "default": true },
+            { "id": "globex", "jwtSecret": "globex-secret" }
+        ]"#,
+    )
+    .expect("Invalid tenants");
+    let mut app = TestApp::with_tenants(tenants).await;
+    let created = create(&app, "Nightly export", &[]).await;
+    let client_id = created["clientId"].as_str().unwrap();
+    let secret = created["clientSecret"].as_str().unwrap();
+
+    let response = app
+        .http_client
+        .get(format!(
+            "{}/realms/globex/admin/service-accounts",
+            &app.address
+        ))
+        .bearer_auth(ADMIN_API_KEY.as_str())
+        .send()
+        .await
+        .unwrap();
+    let list: Value = response.json().await.unwrap();
+    assert_eq!(list["serviceAccounts"], json!([]));
+
+    let response = app
+        .http_client
+        .post(format!("{}/realms/globex/oauth/token", &app.address))
+        .basic_auth(client_id, Some(secret))
+        .form(&json!({ "grant_type": "client_credentials" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let access_token = access_token(&app, client_id, secret).await;
+    let response = app
+        .http_client
+        .post(format!("{}/realms/globex/verify-token", &app.address))
+        .json(&json!({ "token": access_token }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+use crate::helpers::TestApp;
+
+// Sends everything but the blank line ending the headers, so the request stays in flight.
+async fn start_request(app: &TestApp) -> TcpStream {
+    let host = app.address.trim_start_matches("http://");
+    let mut stream = TcpStream::connect(host).await.unwrap();
+    stream
+        .write_all(format!("GET / HTTP/1.1\r\nHost: {}\r\n", host).as_bytes())
+        .await
+        .unwrap();
+    // Give the server time to start reading the request.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    stream
+}
+
+#[tokio::test]
+async fn should_stop_accepting_connections_after_shutdown() {
+    let mut app = TestApp::new().await;
+    assert_eq!(app.get_root().await.status().as_u16(), 200);
+
+    app.shutdown_handle.shutdown();
+    tokio::time::timeout(Duration::from_secs(5), &mut app.server)
+        .await
+        .expect("Server did not stop")
+        .unwrap()
+        .unwrap();
+
+    let result = reqwest::Client::new()
+        .get(format!("{}/", &app.address))
+        .send()
+        .await;
+    assert!(result.is_err());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_drain_in_flight_requests() {
+    let mut app = TestApp::new().await;
+    let mut stream = start_request(&app).await;
+
+    app.shutdown_handle.shutdown();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!app.server.is_finished());
+
+    stream.write_all(b"\r\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    tokio::time::timeout(Duration::from_secs(5), &mut app.server)
+        .await
+        .expect("Server did not stop")
+        .unwrap()
+        .unwrap();
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_give_up_on_requests_after_drain_deadline() {
+    let mut app = TestApp::new().await;
+    let _stream = start_request(&app).await;
+
+    app.shutdown_handle.shutdown();
+
+    // TestApp drains for at most one second.
+    tokio::time::timeout(Duration::from_secs(5), &mut app.server)
+        .await
+        .expect("Server did not stop at the drain deadline")
+        .unwrap()
+        .unwrap();
+    app.clean_up().await;
+}
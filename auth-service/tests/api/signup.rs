@@ -1,5 +1,24 @@
-use crate::helpers::{get_random_email, TestApp};
-use auth_service::{routes::SignupResponse, ErrorResponse};
+use std::{sync::Arc, time::Duration};
+
+use crate::helpers::{get_random_email, TestApp, PASSWORD};
+use auth_service::{
+    app_state::UserStoreType,
+    domain::{
+        data_stores::{
+            ChangedEmail, EmailChange, EmailChangeToken, PasswordReset, PasswordResetToken,
+            PhoneVerification, TwoFACode, UserPage, UserQuery, UserStore, UserStoreError,
+        },
+        email::{Email, EmailFolding},
+        password::Password,
+        phone_number::PhoneNumber,
+        profile::{Metadata, UserProfile},
+        user::{TwoFAChannel, User, UserId, UserStatus},
+    },
+    routes::SignupResponse,
+    services::data_stores::postgres_user_store::canonicalize_stored_emails,
+    ErrorResponse,
+};
+use tokio::sync::{mpsc, Semaphore};
 
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
@@ -9,11 +28,11 @@ async fn should_return_422_if_malformed_input() {
 
     let test_cases = [
         serde_json::json!({
-            "password": "password123",
+            "password": "vivid-Otter-chimney-17",
             "requires2FA": true
         }),
         serde_json::json!({
-            "password": "password123",
+            "password": "vivid-Otter-chimney-17",
             "email": random_email
         }),
         serde_json::json!({
@@ -40,7 +59,7 @@ async fn should_return_201_if_valid_input() {
     let response = app
         .post_signup(&serde_json::json!({
             "email": "foo.bar@gmail.com",
-            "password": "thepassword",
+            "password": "vivid-Otter-chimney-17",
             "requires2FA": false
         }))
         .await;
@@ -67,17 +86,12 @@ async fn should_return_400_if_invalid_input() {
     let test_cases = [
         serde_json::json!({
             "email": "",
-            "password": "password123",
+            "password": "vivid-Otter-chimney-17",
             "requires2FA": true
         }),
         serde_json::json!({
             "email": "foo.foo",
-            "password": "password123",
-            "requires2FA": true
-        }),
-        serde_json::json!({
-            "email": "foo.bar@gmail.com",
-            "password": "short",
+            "password": "vivid-Otter-chimney-17",
             "requires2FA": true
         }),
     ];
@@ -98,13 +112,64 @@ async fn should_return_400_if_invalid_input() {
     app.clean_up().await;
 }
 
+#[tokio::test]
+async fn should_return_400_with_every_reason_if_password_is_weak() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        (
+            "short",
+            vec![
+                "Password must be at least 8 characters long",
+                "Password is too easy to guess",
+            ],
+        ),
+        (
+            "password",
+            vec!["Password is too easy to guess: avoid common passwords and words"],
+        ),
+        (
+            "Foo.Bar-vault-93",
+            vec!["Password must not contain the email address"],
+        ),
+        (
+            "correct horse battery staple",
+            vec!["Password has appeared in a data breach"],
+        ),
+    ];
+
+    for (password, reasons) in test_cases {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": "foo.bar@gmail.com",
+                "password": password,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for password: {}",
+            password
+        );
+
+        let body = response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse");
+        assert_eq!(body.error, "Password does not meet the password policy");
+        assert_eq!(body.reasons, reasons, "Failed for password: {}", password);
+    }
+    app.clean_up().await;
+}
+
 #[tokio::test]
 async fn should_return_409_if_email_already_exists() {
     // Call the signup route twice. The second request should fail with a 409 HTTP status code
     let mut app = TestApp::new().await;
     let test_case = serde_json::json!({
         "email": "foo.foo@gmail.com",
-        "password": "password123asdf",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": false
     });
 
@@ -122,3 +187,331 @@ async fn should_return_409_if_email_already_exists() {
     );
     app.clean_up().await;
 }
+
+#[tokio::test]
+async fn should_return_409_if_email_differs_only_in_case() {
+    let mut app = TestApp::new().await;
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "Foo.Foo@Gmail.com",
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    for email in ["foo.foo@gmail.com", "  FOO.FOO@GMAIL.COM "] {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": "vivid-Otter-chimney-17",
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            409,
+            "Failed for email: {:?}",
+            email
+        );
+    }
+
+    let stored: Vec<String> = sqlx::query_scalar("SELECT email FROM users")
+        .fetch_all(&app.pg_pool)
+        .await
+        .expect("Failed to read users");
+    assert_eq!(stored, vec!["foo.foo@gmail.com".to_owned()]);
+    app.clean_up().await;
+}
+
+async fn stored_emails(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar("SELECT email FROM users ORDER BY email")
+        .fetch_all(&app.pg_pool)
+        .await
+        .expect("Failed to read users")
+}
+
+// Stands in for rows written before the current canonical form.
+async fn store_email(app: &TestApp, canonical: &str, legacy: &str) {
+    sqlx::query("UPDATE users SET email = $2 WHERE email = $1")
+        .bind(canonical)
+        .bind(legacy)
+        .execute(&app.pg_pool)
+        .await
+        .expect("Failed to update user");
+}
+
+#[tokio::test]
+async fn stored_emails_are_rewritten_in_canonical_form() {
+    let mut app = TestApp::new().await;
+    for email in ["bob@bücher.example", "b.o.b+news@gmail.com"] {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": PASSWORD,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+    store_email(&app, "bob@xn--bcher-kva.example", "Bob@Bücher.example").await;
+
+    // Folding for gmail.com was turned on after the second signup.
+    let folding = EmailFolding::parse("gmail.com:plus:dots").unwrap();
+    let rewritten = canonicalize_stored_emails(&app.pg_pool, &folding)
+        .await
+        .unwrap();
+    assert_eq!(rewritten, 2);
+    assert_eq!(
+        stored_emails(&app).await,
+        vec!["bob@gmail.com", "bob@xn--bcher-kva.example"]
+    );
+    let rewritten = canonicalize_stored_emails(&app.pg_pool, &folding)
+        .await
+        .unwrap();
+    assert_eq!(rewritten, 0);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": "bob@bücher.example", "password": PASSWORD }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn stored_emails_are_not_merged_when_canonicalized() {
+    let mut app = TestApp::new().await;
+    for email in ["anna@bücher.example", "anna@example.com"] {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": PASSWORD,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+    store_email(&app, "anna@example.com", "ANNA@Bücher.example").await;
+
+    let result = canonicalize_stored_emails(&app.pg_pool, &EmailFolding::default()).await;
+    assert!(result.is_err());
+    assert_eq!(
+        stored_emails(&app).await,
+        vec!["ANNA@Bücher.example", "anna@xn--bcher-kva.example"]
+    );
+    app.clean_up().await;
+}
+
+// Holds every `add_user` call until the test hands out a permit, reporting each one that
+// arrives, so that the test can see how many signups are inside the store at once.
+struct GatedUserStore {
+    inner: UserStoreType,
+    entered: mpsc::UnboundedSender<()>,
+    permits: Arc<Semaphore>,
+}
+
+#[async_trait::async_trait]
+impl UserStore for GatedUserStore {
+    async fn get_user(&self, email: Email) -> Result<User, UserStoreError> {
+        self.inner.get_user(email).await
+    }
+
+    async fn get_user_by_id(&self, id: &UserId) -> Result<User, UserStoreError> {
+        self.inner.get_user_by_id(id).await
+    }
+
+    async fn validate_user(&self, email: Email, password: Password) -> Result<(), UserStoreError> {
+        self.inner.validate_user(email, password).await
+    }
+
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        let _ = self.entered.send(());
+        self.permits.acquire().await.unwrap().forget();
+        self.inner.add_user(user).await
+    }
+
+    async fn list_users(&self, query: UserQuery) -> Result<UserPage, UserStoreError> {
+        self.inner.list_users(query).await
+    }
+
+    async fn set_status(&self, email: &Email, status: UserStatus) -> Result<(), UserStoreError> {
+        self.inner.set_status(email, status).await
+    }
+
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        self.inner
+            .set_password_reset_required(email, required)
+            .await
+    }
+
+    async fn change_password(
+        &self,
+        email: &Email,
+        password: Password,
+        reuse_limit: usize,
+    ) -> Result<(), UserStoreError> {
+        self.inner
+            .change_password(email, password, reuse_limit)
+            .await
+    }
+
+    async fn set_requires_2fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_requires_2fa(email, requires_2fa).await
+    }
+
+    async fn start_phone_verification(
+        &self,
+        email: &Email,
+        verification: PhoneVerification,
+    ) -> Result<(), UserStoreError> {
+        self.inner
+            .start_phone_verification(email, verification)
+            .await
+    }
+
+    async fn confirm_phone_number(
+        &self,
+        email: &Email,
+        code: &TwoFACode,
+    ) -> Result<PhoneNumber, UserStoreError> {
+        self.inner.confirm_phone_number(email, code).await
+    }
+
+    async fn remove_phone_number(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.inner.remove_phone_number(email).await
+    }
+
+    async fn set_two_fa_channel(
+        &self,
+        email: &Email,
+        channel: TwoFAChannel,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_two_fa_channel(email, channel).await
+    }
+
+    async fn start_password_reset(
+        &self,
+        email: &Email,
+        reset: PasswordReset,
+    ) -> Result<(), UserStoreError> {
+        self.inner.start_password_reset(email, reset).await
+    }
+
+    async fn find_password_reset(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, UserStoreError> {
+        self.inner.find_password_reset(token).await
+    }
+
+    async fn start_email_change(
+        &self,
+        email: &Email,
+        change: EmailChange,
+    ) -> Result<(), UserStoreError> {
+        self.inner.start_email_change(email, change).await
+    }
+
+    async fn confirm_email_change(
+        &self,
+        token: &EmailChangeToken,
+    ) -> Result<ChangedEmail, UserStoreError> {
+        self.inner.confirm_email_change(token).await
+    }
+
+    async fn get_profile(&self, email: &Email) -> Result<UserProfile, UserStoreError> {
+        self.inner.get_profile(email).await
+    }
+
+    async fn set_profile(
+        &self,
+        email: &Email,
+        profile: &UserProfile,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_profile(email, profile).await
+    }
+
+    async fn set_app_metadata(
+        &self,
+        email: &Email,
+        app_metadata: &Metadata,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_app_metadata(email, app_metadata).await
+    }
+}
+
+// Parks a batch of signups inside the user store at the same time and logs in while they are
+// still there. With a lock around the store, the first parked signup would keep all the
+// others (and the login) out.
+#[tokio::test]
+async fn concurrent_signups_do_not_serialize() {
+    const SIGNUPS: usize = 16;
+
+    let (entered_sender, mut entered) = mpsc::unbounded_channel();
+    // One permit for the user that logs in below.
+    let permits = Arc::new(Semaphore::new(1));
+    let store_permits = permits.clone();
+    let mut app = TestApp::with_user_store_wrapper(move |inner| {
+        Arc::new(GatedUserStore {
+            inner,
+            entered: entered_sender,
+            permits: store_permits,
+        })
+    })
+    .await;
+
+    let login_body = serde_json::json!({
+        "email": get_random_email(),
+        "password": "vivid-Otter-chimney-17",
+    });
+    let mut signup_body = login_body.clone();
+    signup_body["requires2FA"] = false.into();
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+    entered.recv().await.unwrap();
+
+    let mut signups = tokio::task::JoinSet::new();
+    for _ in 0..SIGNUPS {
+        let http_client = app.http_client.clone();
+        let url = format!("{}/signup", app.address);
+        let signup_body = serde_json::json!({
+            "email": get_random_email(),
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        });
+        signups.spawn(async move {
+            http_client
+                .post(url)
+                .json(&signup_body)
+                .send()
+                .await
+                .expect("Failed to execute request.")
+                .status()
+                .as_u16()
+        });
+    }
+
+    for parked in 0..SIGNUPS {
+        tokio::time::timeout(Duration::from_secs(10), entered.recv())
+            .await
+            .unwrap_or_else(|_| panic!("Only {} of {} signups reached the store", parked, SIGNUPS));
+    }
+
+    let login = tokio::time::timeout(Duration::from_secs(10), app.post_login(&login_body))
+        .await
+        .expect("Login waited for the pending signups");
+    assert_eq!(login.status().as_u16(), 200);
+
+    permits.add_permits(SIGNUPS);
+    while let Some(status) = signups.join_next().await {
+        assert_eq!(status.unwrap(), 201);
+    }
+    app.clean_up().await;
+}
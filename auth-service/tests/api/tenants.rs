@@ -0,0 +1,365 @@
+use auth_service::{
+    domain::{
+        tenant::Tenant,
+        webhooks::{WebhookEvent, WebhookEventType},
+    },
+    routes::{AuditLogResponse, AuditLogVerificationResponse, WebhookSubscriptionsResponse},
+    utils::constants::{ADMIN_API_KEY, JWT_COOKIE_NAME},
+    ErrorResponse,
+};
+use axum::http::StatusCode;
+use reqwest::{header::HOST, Method};
+use serde_json::{json, Value};
+
+use crate::{
+    helpers::{get_random_email, TestApp, PASSWORD},
+    webhooks::{next_webhook, spawn_receiver},
+};
+
+// `acme` serves requests that name no tenant; every tenant can be reached under `/realms/{id}`.
+async fn app_with_tenants() -> TestApp {
+    let tenants = Tenant::parse_all(
+        r#"[
+            { "id": "acme", "jwtSecret": "acme-secret", "hosts": ["auth.acme.test"], "default": true },
+            {
+                "id": "globex",
+                "jwtSecret": "globex-secret",
+                "hosts": ["auth.globex.test"],
+                "adminApiKey": "globex-admin"
+            },
+            { "id": "initech", "jwtSecret": "initech-secret", "signupEnabled": false },
+            { "id": "umbrella", "jwtSecret": "umbrella-secret", "require2FA": true }
+        ]"#,
+    )
+    .expect("Invalid tenants");
+    TestApp::with_tenants(tenants).await
+}
+
+async fn post(app: &TestApp, path: &str, host: Option<&str>, body: &Value) -> reqwest::Response {
+    let mut request = app
+        .http_client
+        .post(format!("{}{}", &app.address, path))
+        .json(body);
+    if let Some(host) = host {
+        request = request.header(HOST, host);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+async fn sign_up(app: &TestApp, realm: &str, email: &str) -> reqwest::Response {
+    let body = json!({ "email": email, "password": PASSWORD, "requires2FA": false });
+    post(app, &format!("/realms/{}/signup", realm), None, &body).await
+}
+
+async fn log_in(app: &TestApp, realm: &str, email: &str) -> reqwest::Response {
+    let body = json!({ "email": email, "password": PASSWORD });
+    post(app, &format!("/realms/{}/login", realm), None, &body).await
+}
+
+async fn admin(
+    app: &TestApp,
+    method: Method,
+    realm: &str,
+    path: &str,
+    api_key: &str,
+    body: Option<&Value>,
+) -> reqwest::Response {
+    let mut request = app
+        .http_client
+        .request(
+            method,
+            format!("{}/realms/{}/admin{}", &app.address, realm, path),
+        )
+        .bearer_auth(api_key);
+    if let Some(body) = body {
+        request = request.json(body);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+fn auth_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn users_of_one_tenant_cannot_log_in_to_another() {
+    let mut app = app_with_tenants().await;
+    let email = get_random_email();
+
+    assert_eq!(sign_up(&app, "acme", &email).await.status().as_u16(), 201);
+
+    assert_eq!(log_in(&app, "acme", &email).await.status().as_u16(), 200);
+    assert_eq!(log_in(&app, "globex", &email).await.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn same_email_can_sign_up_in_each_tenant() {
+    let mut app = app_with_tenants().await;
+    let email = get_random_email();
+
+    assert_eq!(sign_up(&app, "acme", &email).await.status().as_u16(), 201);
+    assert_eq!(sign_up(&app, "globex", &email).await.status().as_u16(), 201);
+    assert_eq!(sign_up(&app, "globex", &email).await.status().as_u16(), 409);
+
+    // Each tenant has its own copy of the user.
+    let password = json!({
+        "currentPassword": PASSWORD,
+        "newPassword": "brisk-Heron-lantern-42"
+    });
+    assert_eq!(log_in(&app, "globex", &email).await.status().as_u16(), 200);
+    let response = post(&app, "/realms/globex/change-password", None, &password).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(log_in(&app, "acme", &email).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn tokens_of_one_tenant_are_rejected_by_another() {
+    let mut app = app_with_tenants().await;
+    let email = get_random_email();
+    assert_eq!(sign_up(&app, "acme", &email).await.status().as_u16(), 201);
+    // The user exists in both, so only the token itself can be what globex rejects.
+    assert_eq!(sign_up(&app, "globex", &email).await.status().as_u16(), 201);
+
+    let response = log_in(&app, "acme", &email).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = json!({ "token": auth_token(&response) });
+
+    let response = post(&app, "/realms/globex/verify-token", None, &token).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = post(&app, "/realms/acme/verify-token", None, &token).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The acme cookie is sent to globex too, but does not sign the user in there.
+    let me = |realm: &str| {
+        app.http_client
+            .get(format!("{}/realms/{}/me", &app.address, realm))
+            .send()
+    };
+    assert_eq!(me("globex").await.unwrap().status().as_u16(), 401);
+    assert_eq!(me("acme").await.unwrap().status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn resolves_tenant_from_host_then_path_then_default() {
+    let mut app = app_with_tenants().await;
+    let email = get_random_email();
+    let signup = json!({ "email": email, "password": PASSWORD, "requires2FA": false });
+    let login = json!({ "email": email, "password": PASSWORD });
+
+    let response = post(&app, "/signup", Some("auth.globex.test:3000"), &signup).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    assert_eq!(log_in(&app, "globex", &email).await.status().as_u16(), 200);
+    // The path wins over the host.
+    let response = post(&app, "/realms/globex/login", Some("auth.acme.test"), &login).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = post(&app, "/login", Some("auth.acme.test"), &login).await;
+    assert_eq!(response.status().as_u16(), 401);
+    // Unknown hosts go to the default tenant.
+    let response = post(&app, "/login", Some("auth.unknown.test"), &login).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(app.post_login(&login).await.status().as_u16(), 401);
+
+    let response = post(&app, "/realms/hooli/login", None, &login).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn tenants_can_disable_signup() {
+    let mut app = app_with_tenants().await;
+    let email = get_random_email();
+
+    let response = sign_up(&app, "initech", &email).await;
+    assert_eq!(response.status().as_u16(), 403);
+    let error = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "Signup is disabled");
+
+    assert_eq!(sign_up(&app, "acme", &email).await.status().as_u16(), 201);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn tenants_can_make_2fa_mandatory() {
+    let mut app = app_with_tenants().await;
+    let email = get_random_email();
+
+    assert_eq!(
+        sign_up(&app, "umbrella", &email).await.status().as_u16(),
+        201
+    );
+    assert_eq!(sign_up(&app, "acme", &email).await.status().as_u16(), 201);
+
+    assert_eq!(
+        log_in(&app, "umbrella", &email).await.status().as_u16(),
+        206
+    );
+    assert_eq!(log_in(&app, "acme", &email).await.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn tenant_admin_keys_only_open_their_own_tenant() {
+    let mut app = app_with_tenants().await;
+
+    let response = admin(&app, Method::GET, "globex", "/users", "globex-admin", None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = admin(&app, Method::GET, "acme", "/users", "globex-admin", None).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = admin(&app, Method::GET, "initech", "/users", "globex-admin", None).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The deployment-wide key still opens every tenant.
+    for realm in ["acme", "globex"] {
+        let response = admin(&app, Method::GET, realm, "/users", &ADMIN_API_KEY, None).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn webhooks_only_see_events_of_their_tenant() {
+    let mut app = app_with_tenants().await;
+    let (acme_url, mut acme_receiver) = spawn_receiver(StatusCode::OK).await;
+    let (globex_url, mut globex_receiver) = spawn_receiver(StatusCode::OK).await;
+    for (realm, url) in [("acme", &acme_url), ("globex", &globex_url)] {
+        let body =
+            json!({ "url": url, "secret": "webhook-secret", "eventTypes": ["user.signed_up"] });
+        let response = admin(
+            &app,
+            Method::POST,
+            realm,
+            "/webhooks",
+            &ADMIN_API_KEY,
+            Some(&body),
+        )
+        .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+
+    // The globex signup goes out first, so a leak would be the first thing acme receives.
+    let globex_email = get_random_email();
+    assert_eq!(
+        sign_up(&app, "globex", &globex_email)
+            .await
+            .status()
+            .as_u16(),
+        201
+    );
+    let event: WebhookEvent =
+        serde_json::from_str(&next_webhook(&mut globex_receiver).await.body).unwrap();
+    assert_eq!(event.event_type, WebhookEventType::UserSignedUp);
+    assert_eq!(event.data["email"], globex_email);
+
+    let acme_email = get_random_email();
+    assert_eq!(
+        sign_up(&app, "acme", &acme_email).await.status().as_u16(),
+        201
+    );
+    let event: WebhookEvent =
+        serde_json::from_str(&next_webhook(&mut acme_receiver).await.body).unwrap();
+    assert_eq!(event.data["email"], acme_email);
+
+    // Each tenant lists and deletes only its own subscriptions.
+    let response = admin(
+        &app,
+        Method::GET,
+        "globex",
+        "/webhooks",
+        "globex-admin",
+        None,
+    )
+    .await;
+    let subscriptions = response
+        .json::<WebhookSubscriptionsResponse>()
+        .await
+        .unwrap()
+        .subscriptions;
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].url, globex_url);
+
+    let path = format!("/webhooks/{}", subscriptions[0].id);
+    let response = admin(&app, Method::DELETE, "acme", &path, &ADMIN_API_KEY, None).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let response = admin(&app, Method::DELETE, "globex", &path, "globex-admin", None).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = admin(&app, Method::GET, "acme", "/webhooks", &ADMIN_API_KEY, None).await;
+    let subscriptions = response
+        .json::<WebhookSubscriptionsResponse>()
+        .await
+        .unwrap()
+        .subscriptions;
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(subscriptions[0].url, acme_url);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn audit_log_only_shows_events_of_its_tenant() {
+    let mut app = app_with_tenants().await;
+    let email = get_random_email();
+    assert_eq!(sign_up(&app, "acme", &email).await.status().as_u16(), 201);
+    assert_eq!(sign_up(&app, "globex", &email).await.status().as_u16(), 201);
+    assert_eq!(log_in(&app, "globex", &email).await.status().as_u16(), 200);
+
+    let actions = |body: AuditLogResponse| -> Vec<String> {
+        body.events
+            .iter()
+            .map(|record| record.event.action.as_ref().to_owned())
+            .collect()
+    };
+    let path = format!("/audit-log?actor={}", email);
+
+    let response = admin(&app, Method::GET, "acme", &path, &ADMIN_API_KEY, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(actions(response.json().await.unwrap()), vec!["signup"]);
+
+    let response = admin(&app, Method::GET, "globex", &path, "globex-admin", None).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        actions(response.json().await.unwrap()),
+        vec!["login", "signup"]
+    );
+
+    // Records of every tenant share one hash chain, which only the global key may verify.
+    let response = admin(
+        &app,
+        Method::GET,
+        "acme",
+        "/audit-log/verify",
+        &ADMIN_API_KEY,
+        None,
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.get_admin("/audit-log/verify", "globex-admin").await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_admin("/audit-log/verify", &ADMIN_API_KEY).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let verification = response
+        .json::<AuditLogVerificationResponse>()
+        .await
+        .unwrap();
+    assert!(verification.valid);
+
+    app.clean_up().await;
+}
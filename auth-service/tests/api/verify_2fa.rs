@@ -77,7 +77,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": true
     });
     let response = app.post_signup(&signup_body).await;
@@ -85,7 +85,7 @@ async fn should_return_401_if_incorrect_credentials() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
     });
     let response = app.post_login(&login_body).await;
     assert_eq!(response.status().as_u16(), 206);
@@ -121,7 +121,7 @@ async fn should_return_401_if_old_code() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": true
     });
 
@@ -133,7 +133,7 @@ async fn should_return_401_if_old_code() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "vivid-Otter-chimney-17"
     });
 
     let response = app.post_login(&login_body).await;
@@ -152,8 +152,6 @@ async fn should_return_401_if_old_code() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&Email::parse(random_email.as_ref()).unwrap())
         .await
         .unwrap();
@@ -188,7 +186,7 @@ async fn should_return_200_if_correct_code() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": true
     });
     let response = app.post_signup(&signup_body).await;
@@ -196,7 +194,7 @@ async fn should_return_200_if_correct_code() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
     });
     let response = app.post_login(&login_body).await;
     assert_eq!(response.status().as_u16(), 206);
@@ -210,8 +208,6 @@ async fn should_return_200_if_correct_code() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&Email::parse(random_email.as_ref()).unwrap())
         .await
         .unwrap();
@@ -241,7 +237,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let signup_body = serde_json::json!({
         "email": random_email,
-        "password": "password123",
+        "password": "vivid-Otter-chimney-17",
         "requires2FA": true
     });
 
@@ -251,7 +247,7 @@ async fn should_return_401_if_same_code_twice() {
 
     let login_body = serde_json::json!({
         "email": random_email,
-        "password": "password123"
+        "password": "vivid-Otter-chimney-17"
     });
 
     let response = app.post_login(&login_body).await;
@@ -270,8 +266,6 @@ async fn should_return_401_if_same_code_twice() {
 
     let code_tuple = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&Email::parse(random_email.as_str()).unwrap())
         .await
         .unwrap();
@@ -300,3 +294,51 @@ async fn should_return_401_if_same_code_twice() {
     assert_eq!(response.status().as_u16(), 401);
     app.clean_up().await;
 }
+
+#[tokio::test]
+async fn should_return_403_if_account_suspended_after_login() {
+    let mut app = TestApp::new().await;
+
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "vivid-Otter-chimney-17",
+        "requires2FA": true
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "vivid-Otter-chimney-17",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    let code_tuple = app
+        .two_fa_code_store
+        .get_code(&Email::parse(random_email.as_ref()).unwrap())
+        .await
+        .unwrap();
+
+    let response = app
+        .post_admin(&format!("/users/{}/disable", random_email))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let post_verify_request_body = serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": json_body.login_attempt_id,
+            "2FACode": code_tuple.1.as_ref(),
+    });
+    let response = app.post_verify_2fa(&post_verify_request_body).await;
+
+    assert_eq!(response.status().as_u16(), 403);
+    app.clean_up().await;
+}
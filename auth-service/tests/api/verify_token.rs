@@ -1,6 +1,15 @@
-use auth_service::{domain::email::Email, utils::{auth::generate_auth_cookie }};
+use auth_service::{domain::{email::Email, tenant::Tenant, user::UserId}, utils::{auth::generate_auth_cookie }};
 use crate::helpers::{get_random_email, TestApp};
 
+async fn user_id(app: &TestApp, email: &str) -> UserId {
+    let id: uuid::Uuid = sqlx::query_scalar("SELECT id FROM users WHERE email = $1")
+        .bind(email)
+        .fetch_one(&app.pg_pool)
+        .await
+        .expect("Failed to fetch the user id");
+    UserId::from(id)
+}
+
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
     let mut app = TestApp::new().await;
@@ -16,10 +25,19 @@ async fn should_return_422_if_malformed_input() {
 
 #[tokio::test]
 async fn should_return_200_valid_token() {
-    let email = Email::parse(&get_random_email()).unwrap();
-    let token = generate_auth_cookie(&email).unwrap();
+    let random_email = get_random_email();
+    let email = Email::parse(&random_email).unwrap();
 
     let mut app = TestApp::new().await;
+    // Tokens are only valid for existing, active users.
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "vivid-Otter-chimney-17",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+    let token = generate_auth_cookie(&Tenant::from_env(), &user_id(&app, email.as_ref()).await, &email, serde_json::Map::new()).unwrap();
+
     let token_request = serde_json::json!({
         "token": token.value(),
     });
@@ -43,3 +61,58 @@ async fn should_return_401_if_invalid_token() {
     assert_eq!(response.status().as_u16(), 401);
     app.clean_up().await;
 }
+
+// Throughput benchmark rather than a check, run with
+// `cargo test --test api verify_token_throughput -- --ignored --nocapture`.
+#[tokio::test(flavor = "multi_thread")]
+#[ignore]
+async fn verify_token_throughput() {
+    const REQUESTS: usize = 4000;
+    const CONCURRENCY: usize = 64;
+
+    let mut app = TestApp::new().await;
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "vivid-Otter-chimney-17",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+    let email = Email::parse(&random_email).unwrap();
+    let token = generate_auth_cookie(&Tenant::from_env(), &user_id(&app, email.as_ref()).await, &email, serde_json::Map::new())
+        .unwrap()
+        .value()
+        .to_owned();
+
+    let started = std::time::Instant::now();
+    let mut workers = tokio::task::JoinSet::new();
+    for _ in 0..CONCURRENCY {
+        let http_client = app.http_client.clone();
+        let url = format!("{}/verify-token", app.address);
+        let token_request = serde_json::json!({ "token": token });
+        workers.spawn(async move {
+            for _ in 0..REQUESTS / CONCURRENCY {
+                let response = http_client
+                    .post(&url)
+                    .json(&token_request)
+                    .send()
+                    .await
+                    .expect("Failed to execute request.");
+                assert_eq!(response.status().as_u16(), 200);
+            }
+        });
+    }
+    while let Some(result) = workers.join_next().await {
+        result.unwrap();
+    }
+    let elapsed = started.elapsed();
+
+    println!(
+        "{} /verify-token requests with {} concurrent clients in {:.2?}: {:.0} requests/s",
+        REQUESTS,
+        CONCURRENCY,
+        elapsed,
+        REQUESTS as f64 / elapsed.as_secs_f64()
+    );
+    app.clean_up().await;
+}
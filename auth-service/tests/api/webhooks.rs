@@ -0,0 +1,242 @@
+use auth_service::{
+    domain::webhooks::{
+        sign_payload, WebhookEvent, WebhookEventType, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+    },
+    routes::{DeadLettersResponse, WebhookSubscriptionsResponse},
+    utils::constants::ADMIN_API_KEY,
+};
+use axum::{body::Bytes, extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::helpers::{get_random_email, TestApp};
+
+const SECRET: &str = "webhook-secret";
+
+pub struct ReceivedWebhook {
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+// Starts a subscriber endpoint that answers with `status` and forwards every delivery.
+pub async fn spawn_receiver(
+    status: StatusCode,
+) -> (String, mpsc::UnboundedReceiver<ReceivedWebhook>) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let router = Router::new()
+        .route(
+            "/hook",
+            post(
+                move |State(sender): State<mpsc::UnboundedSender<ReceivedWebhook>>,
+                      headers: HeaderMap,
+                      body: Bytes| async move {
+                    let body = String::from_utf8(body.to_vec()).unwrap();
+                    let _ = sender.send(ReceivedWebhook { headers, body });
+                    status
+                },
+            ),
+        )
+        .with_state(sender);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    (url, receiver)
+}
+
+pub async fn next_webhook(
+    receiver: &mut mpsc::UnboundedReceiver<ReceivedWebhook>,
+) -> ReceivedWebhook {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("Timed out waiting for webhook")
+        .expect("Receiver closed")
+}
+
+async fn subscribe(app: &TestApp, url: &str, event_types: &[&str]) {
+    let response = app
+        .post_admin_json(
+            "/webhooks",
+            &serde_json::json!({ "url": url, "secret": SECRET, "eventTypes": event_types }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn signup(app: &TestApp, requires_2fa: bool) -> String {
+    let email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": requires_2fa
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    email
+}
+
+#[tokio::test]
+async fn should_deliver_signed_event_on_signup() {
+    let mut app = TestApp::new().await;
+    let (url, mut receiver) = spawn_receiver(StatusCode::OK).await;
+    subscribe(&app, &url, &[]).await;
+
+    let email = signup(&app, false).await;
+
+    let webhook = next_webhook(&mut receiver).await;
+    let timestamp: i64 = webhook.headers[TIMESTAMP_HEADER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(
+        webhook.headers[SIGNATURE_HEADER].to_str().unwrap(),
+        format!("sha256={}", sign_payload(SECRET, timestamp, &webhook.body))
+    );
+
+    let event: WebhookEvent = serde_json::from_str(&webhook.body).unwrap();
+    assert_eq!(event.event_type, WebhookEventType::UserSignedUp);
+    assert_eq!(event.data["email"], email);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_deliver_subscribed_event_types() {
+    let mut app = TestApp::new().await;
+    let (url, mut receiver) = spawn_receiver(StatusCode::OK).await;
+    subscribe(&app, &url, &["user.2fa_enabled"]).await;
+
+    signup(&app, false).await;
+    let email = signup(&app, true).await;
+
+    let event: WebhookEvent =
+        serde_json::from_str(&next_webhook(&mut receiver).await.body).unwrap();
+    assert_eq!(event.event_type, WebhookEventType::TwoFAEnabled);
+    assert_eq!(event.data["email"], email);
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(receiver.try_recv().is_err());
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_publish_the_canonical_email_on_signup() {
+    let mut app = TestApp::new().await;
+    let (url, mut receiver) = spawn_receiver(StatusCode::OK).await;
+    subscribe(&app, &url, &["user.signed_up"]).await;
+
+    let email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": format!(" {} ", email.to_uppercase()),
+            "password": "vivid-Otter-chimney-17",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let event: WebhookEvent =
+        serde_json::from_str(&next_webhook(&mut receiver).await.body).unwrap();
+    assert_eq!(event.data["email"], email);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_emit_suspended_event_when_admin_disables_user() {
+    let mut app = TestApp::new().await;
+    let (url, mut receiver) = spawn_receiver(StatusCode::OK).await;
+    subscribe(&app, &url, &["user.suspended"]).await;
+
+    let email = signup(&app, false).await;
+    let response = app.post_admin(&format!("/users/{}/disable", email)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let event: WebhookEvent =
+        serde_json::from_str(&next_webhook(&mut receiver).await.body).unwrap();
+    assert_eq!(event.event_type, WebhookEventType::UserSuspended);
+    assert_eq!(event.data["email"], email);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_dead_letter_after_repeated_failures() {
+    let mut app = TestApp::new().await;
+    let (url, mut receiver) = spawn_receiver(StatusCode::INTERNAL_SERVER_ERROR).await;
+    subscribe(&app, &url, &["user.signed_up"]).await;
+
+    signup(&app, false).await;
+
+    // The test dispatcher gives up after three attempts.
+    for _ in 0..3 {
+        next_webhook(&mut receiver).await;
+    }
+
+    let mut dead_letters = vec![];
+    for _ in 0..50 {
+        dead_letters = app
+            .get_admin("/webhooks/dead-letters", &ADMIN_API_KEY)
+            .await
+            .json::<DeadLettersResponse>()
+            .await
+            .unwrap()
+            .dead_letters;
+        if !dead_letters.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].event_type, "user.signed_up");
+    assert_eq!(dead_letters[0].attempts, 3);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_list_and_delete_subscriptions() {
+    let mut app = TestApp::new().await;
+    subscribe(&app, "https://example.com/hook", &["user.signed_up"]).await;
+
+    let response = app.get_admin("/webhooks", &ADMIN_API_KEY).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(!body.contains(SECRET));
+
+    let subscriptions = serde_json::from_str::<WebhookSubscriptionsResponse>(&body)
+        .unwrap()
+        .subscriptions;
+    assert_eq!(subscriptions.len(), 1);
+    assert_eq!(
+        subscriptions[0].event_types,
+        vec![WebhookEventType::UserSignedUp]
+    );
+
+    let path = format!("/webhooks/{}", subscriptions[0].id);
+    assert_eq!(app.delete_admin(&path).await.status().as_u16(), 200);
+    assert_eq!(app.delete_admin(&path).await.status().as_u16(), 404);
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_subscription() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({ "url": "not a url", "secret": SECRET }),
+        serde_json::json!({ "url": "ftp://example.com/hook", "secret": SECRET }),
+        serde_json::json!({ "url": "https://example.com/hook", "secret": "" }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_admin_json("/webhooks", test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+    app.clean_up().await;
+}